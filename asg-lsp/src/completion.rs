@@ -27,6 +27,7 @@ pub fn get_completions(
         ("export", "Export from module"),
         ("module", "Module definition"),
         ("lambda", "Anonymous function"),
+        ("rec-lambda", "Anonymous recursive function"),
         ("for", "For loop"),
         ("try", "Try-catch block"),
         ("throw", "Throw error"),
@@ -4,6 +4,8 @@ use tower_lsp::lsp_types::*;
 use asg_lang::asg::ASG;
 use asg_lang::nodecodes::NodeType;
 
+use crate::syntax::offset_to_position;
+
 /// Информация о определении символа.
 #[derive(Debug, Clone)]
 pub struct DefinitionInfo {
@@ -158,26 +160,6 @@ pub fn find_references(
     }
 }
 
-/// Конвертировать offset в Position.
-fn offset_to_position(content: &str, offset: usize) -> Position {
-    let mut line = 0u32;
-    let mut col = 0u32;
-
-    for (i, ch) in content.chars().enumerate() {
-        if i >= offset {
-            break;
-        }
-        if ch == '\n' {
-            line += 1;
-            col = 0;
-        } else {
-            col += 1;
-        }
-    }
-
-    Position { line, character: col }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
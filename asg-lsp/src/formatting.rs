@@ -0,0 +1,53 @@
+//! Formatting implementation.
+
+use tower_lsp::lsp_types::*;
+use asg_lang::parser::format_source;
+
+/// Отформатировать документ каноничным принтером (как `asg-pkg fmt`).
+///
+/// Возвращает одну правку на весь документ; пусто, если документ не парсится,
+/// содержит комментарии или уже отформатирован.
+pub fn format_document(content: &str) -> Vec<TextEdit> {
+    let formatted = match format_source(content) {
+        Ok(formatted) if formatted != content => formatted,
+        _ => return Vec::new(),
+    };
+
+    vec![TextEdit {
+        range: Range {
+            start: Position { line: 0, character: 0 },
+            end: end_position(content),
+        },
+        new_text: formatted,
+    }]
+}
+
+/// Позиция конца документа.
+fn end_position(content: &str) -> Position {
+    let line = content.matches('\n').count() as u32;
+    let line_start = content.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line,
+        character: content[line_start..].chars().count() as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_poorly_indented_document() {
+        let content = "(fn   add (a b)\n        (+ a   b))\n   (let x (add 1\n 2))";
+        let edits = format_document(content);
+
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range.start, Position { line: 0, character: 0 });
+        assert_eq!(edits[0].range.end, Position { line: 3, character: 4 });
+        assert_eq!(edits[0].new_text, "(fn add (a b) (+ a b))\n(let x (add 1 2))\n");
+
+        // Уже отформатированный и непарсящийся документы — без правок
+        assert!(format_document(&edits[0].new_text).is_empty());
+        assert!(format_document("(fn add (a b)").is_empty());
+    }
+}
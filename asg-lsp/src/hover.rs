@@ -67,6 +67,7 @@ fn get_keyword_info(word: &str) -> Option<String> {
         "for" => "**for** - For loop\n\n```asg\n(for var iterable body)\n```",
         "match" => "**match** - Pattern matching\n\n```asg\n(match value\n  (pattern1 result1)\n  (pattern2 result2))\n```",
         "lambda" => "**lambda** - Anonymous function\n\n```asg\n(lambda (args) body)\n```",
+        "rec-lambda" => "**rec-lambda** - Anonymous recursive function (`self` names the lambda)\n\n```asg\n(rec-lambda self (args) body)\n```",
         "import" => "**import** - Import module\n\n```asg\n(import \"module-name\")\n(import \"module\" :as alias)\n(import \"module\" :only (name1 name2))\n```",
         "export" => "**export** - Export from module\n\n```asg\n(export name1 name2 ...)\n```",
         "module" => "**module** - Module definition\n\n```asg\n(module name\n  (export ...)\n  definitions...)\n```",
@@ -0,0 +1,275 @@
+//! Инкрементальный парсинг документа.
+//!
+//! Документ хранится как последовательность top-level форм. При правке
+//! перепарсиваются только формы, задетые диапазоном правки (вместе с промежутком
+//! между соседними незатронутыми формами); остальные формы сохраняют свои
+//! узлы и ID, а их span сдвигаются на разницу длины.
+
+use asg_lang::asg::{Node, NodeID, ASG};
+use asg_lang::parser::{AsgBuilder, Parser};
+
+/// Одна top-level форма документа.
+#[derive(Debug, Clone)]
+pub struct ParsedForm {
+    /// Начало формы в документе (байт)
+    pub start: usize,
+    /// Конец формы в документе (байт)
+    pub end: usize,
+    /// ID корневого узла формы
+    pub root: NodeID,
+    /// Узлы формы (ID уникальны в пределах документа)
+    pub nodes: Vec<Node>,
+}
+
+/// Распарсенный документ, поддерживающий инкрементальные правки.
+#[derive(Debug, Clone)]
+pub struct IncrementalParse {
+    /// Текущее содержимое
+    content: String,
+    /// Формы в порядке следования; пусто, если последний парсинг не удался
+    forms: Vec<ParsedForm>,
+    /// Ошибка последнего парсинга
+    error: Option<String>,
+    /// Следующий свободный ID узла (ID не переиспользуются)
+    next_id: NodeID,
+}
+
+impl IncrementalParse {
+    /// Распарсить документ целиком.
+    pub fn new(content: String) -> Self {
+        let mut parse = Self {
+            content,
+            forms: Vec::new(),
+            error: None,
+            next_id: 1,
+        };
+        parse.reparse_all();
+        parse
+    }
+
+    /// Текущее содержимое документа.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Формы документа.
+    pub fn forms(&self) -> &[ParsedForm] {
+        &self.forms
+    }
+
+    /// Ошибка последнего парсинга.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Собрать ASG документа (None, если последний парсинг не удался).
+    pub fn asg(&self) -> Option<ASG> {
+        if self.error.is_some() {
+            return None;
+        }
+        let mut asg = ASG::new();
+        for form in &self.forms {
+            for node in &form.nodes {
+                asg.add_node(node.clone());
+            }
+        }
+        Some(asg)
+    }
+
+    /// Заменить байты `start..end` на `text` и перепарсить затронутые формы.
+    pub fn apply_edit(&mut self, start: usize, end: usize, text: &str) {
+        let start = start.min(self.content.len());
+        let end = end.clamp(start, self.content.len());
+        let old_len = self.content.len();
+        // Кавычка или `;` меняют границы строк и комментариев и в удалённом тексте
+        let touches_literals = text.contains(['"', ';'])
+            || self.content[start..end].contains(['"', ';']);
+        self.content.replace_range(start..end, text);
+
+        // Строки и комментарии могут поглотить соседние формы — надёжнее перепарсить всё
+        if self.error.is_some() || touches_literals {
+            self.reparse_all();
+            return;
+        }
+
+        // Формы, касающиеся правки, перепарсиваются вместе с промежутком до соседей
+        let first = self.forms.partition_point(|f| f.end < start);
+        let last = self.forms.partition_point(|f| f.start <= end);
+        let delta = text.len() as isize - (end - start) as isize;
+        let region_start = first.checked_sub(1).map_or(0, |i| self.forms[i].end);
+        let old_region_end = self.forms.get(last).map_or(old_len, |f| f.start);
+        let region_end = (old_region_end as isize + delta) as usize;
+
+        match self.parse_region(region_start, region_end) {
+            Ok(new_forms) => {
+                let tail: Vec<ParsedForm> = self
+                    .forms
+                    .drain(first..)
+                    .skip(last - first)
+                    .map(|form| shift_form(form, delta))
+                    .collect();
+                self.forms.extend(new_forms);
+                self.forms.extend(tail);
+            }
+            Err(message) => {
+                self.forms.clear();
+                self.error = Some(message);
+            }
+        }
+    }
+
+    /// Полный перепарсинг (ID продолжают расти, чтобы не совпадать со старыми).
+    fn reparse_all(&mut self) {
+        self.error = None;
+        match self.parse_region(0, self.content.len()) {
+            Ok(forms) => self.forms = forms,
+            Err(message) => {
+                self.forms.clear();
+                self.error = Some(message);
+            }
+        }
+    }
+
+    /// Распарсить участок документа в формы с абсолютными span и свежими ID.
+    fn parse_region(&mut self, start: usize, end: usize) -> Result<Vec<ParsedForm>, String> {
+        let source = &self.content[start..end];
+        let exprs = Parser::new(source)
+            .parse_all()
+            .map_err(|e| e.to_string())?;
+
+        let mut forms = Vec::with_capacity(exprs.len());
+        for expr in &exprs {
+            let (asg, root) = AsgBuilder::new()
+                .build_single(expr)
+                .map_err(|e| e.to_string())?;
+
+            // Перенумеровываем ID в пространство документа и сдвигаем span
+            let base = self.next_id - 1;
+            let max_id = asg.nodes.iter().map(|n| n.id).max().unwrap_or(0);
+            self.next_id += max_id;
+            let nodes = asg
+                .nodes
+                .into_iter()
+                .map(|mut node| {
+                    node.id += base;
+                    for edge in &mut node.edges {
+                        edge.target_node_id += base;
+                    }
+                    if let Some(span) = &mut node.span {
+                        span.start += start;
+                        span.end += start;
+                    }
+                    node
+                })
+                .collect();
+
+            let span = expr.span();
+            forms.push(ParsedForm {
+                start: span.start + start,
+                end: span.end + start,
+                root: root + base,
+                nodes,
+            });
+        }
+        Ok(forms)
+    }
+}
+
+/// Сдвинуть форму после правки на `delta` байт (узлы и ID не меняются).
+fn shift_form(mut form: ParsedForm, delta: isize) -> ParsedForm {
+    let shift = |offset: usize| (offset as isize + delta) as usize;
+    form.start = shift(form.start);
+    form.end = shift(form.end);
+    for node in &mut form.nodes {
+        if let Some(span) = &mut node.span {
+            span.start = shift(span.start);
+            span.end = shift(span.end);
+        }
+    }
+    form
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(form: &ParsedForm) -> Vec<NodeID> {
+        form.nodes.iter().map(|n| n.id).collect()
+    }
+
+    #[test]
+    fn test_edit_inside_form_reuses_other_forms() {
+        let content = "(fn f (x) (+ x 1))\n(let y 2)\n(let z 3)".to_string();
+        let mut doc = IncrementalParse::new(content);
+        assert_eq!(doc.forms().len(), 3);
+        let before: Vec<ParsedForm> = doc.forms().to_vec();
+
+        // Заменяем "2" на "42" внутри второй формы
+        let start = doc.content().find('2').unwrap();
+        doc.apply_edit(start, start + 1, "42");
+
+        assert_eq!(doc.content(), "(fn f (x) (+ x 1))\n(let y 42)\n(let z 3)");
+        assert!(doc.error().is_none());
+        let after = doc.forms();
+        assert_eq!(after.len(), 3);
+
+        // Первая форма не тронута: те же ID и span
+        assert_eq!(ids(&after[0]), ids(&before[0]));
+        assert_eq!(after[0].nodes[0].span, before[0].nodes[0].span);
+
+        // Вторая форма перепарсена со свежими ID
+        assert!(ids(&after[1]).iter().all(|id| !ids(&before[1]).contains(id)));
+
+        // Третья форма: те же ID, span сдвинуты на 1 байт
+        assert_eq!(ids(&after[2]), ids(&before[2]));
+        assert_eq!(after[2].start, before[2].start + 1);
+        for (new, old) in after[2].nodes.iter().zip(&before[2].nodes) {
+            if let (Some(new), Some(old)) = (new.span, old.span) {
+                assert_eq!(new.start, old.start + 1);
+                assert_eq!(new.end, old.end + 1);
+            }
+        }
+
+        // Результат совпадает с полным перепарсингом (с точностью до нумерации)
+        let full = IncrementalParse::new(doc.content().to_string());
+        let shape = |asg: ASG| -> Vec<_> {
+            asg.nodes
+                .iter()
+                .map(|n| (n.node_type, n.payload.clone(), n.span))
+                .collect()
+        };
+        assert_eq!(shape(doc.asg().unwrap()), shape(full.asg().unwrap()));
+    }
+
+    #[test]
+    fn test_edit_creating_new_form_and_recovering_from_error() {
+        let mut doc = IncrementalParse::new("(let a 1)\n(let b 2)".to_string());
+
+        // Новая форма между существующими
+        let at = doc.content().find('\n').unwrap();
+        doc.apply_edit(at, at, "\n(let c 3)");
+        assert_eq!(doc.forms().len(), 3);
+
+        // Незакрытая скобка — ошибка, затем исправление восстанавливает формы
+        doc.apply_edit(0, 0, "(");
+        assert!(doc.error().is_some());
+        assert!(doc.asg().is_none());
+        doc.apply_edit(0, 1, "");
+        assert!(doc.error().is_none());
+        assert_eq!(doc.forms().len(), 3);
+    }
+
+    #[test]
+    fn test_edit_removing_quote_reparses_all() {
+        let content = "(let t 2)\n(let s \"a\")\n(let u 3)".to_string();
+        let mut doc = IncrementalParse::new(content);
+        assert_eq!(doc.forms().len(), 3);
+
+        // Удаляем закрывающую кавычку: документ разбирается так же, как с нуля
+        doc.apply_edit(19, 20, "");
+        let fresh = IncrementalParse::new(doc.content().to_string());
+        assert_eq!(doc.content(), "(let t 2)\n(let s \"a)\n(let u 3)");
+        assert_eq!(doc.error(), fresh.error());
+        assert_eq!(doc.forms().len(), fresh.forms().len());
+    }
+}
@@ -9,6 +9,12 @@ mod diagnostics;
 mod completion;
 mod hover;
 mod definition;
+mod formatting;
+mod incremental;
+mod rename;
+mod semantic_tokens;
+mod symbols;
+mod syntax;
 
 use server::ASGLanguageServer;
 
@@ -0,0 +1,252 @@
+//! Rename implementation.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+use asg_lang::parser::{Parser, SExpr, Span};
+
+use crate::syntax::{offset_to_position, position_to_offset, SPECIAL_FORMS};
+
+/// К чему относится имя: глобальное определение (fn/let) или локальная привязка
+/// (параметр fn/lambda, переменная for/loop-indexed), заданная span объявления.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Binding {
+    Global(String),
+    Local(usize),
+}
+
+/// Вхождение имени в документе.
+#[derive(Debug, Clone)]
+struct Occurrence {
+    span: Span,
+    binding: Binding,
+    /// Является ли вхождение объявлением
+    is_declaration: bool,
+}
+
+/// Проверить, что имя — допустимый идентификатор ASG.
+pub fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !SPECIAL_FORMS.contains(&name)
+}
+
+/// Переименовать символ под курсором: все вхождения с той же привязкой.
+/// None — курсор не на пользовательском имени или документ не парсится.
+pub fn rename_symbol(
+    content: &str,
+    position: Position,
+    new_name: &str,
+    uri: &Url,
+) -> Option<WorkspaceEdit> {
+    let exprs = Parser::new(content).parse_all().ok()?;
+    let mut occurrences = Vec::new();
+    for expr in &exprs {
+        collect(expr, &[], &mut occurrences);
+    }
+
+    let offset = position_to_offset(content, position);
+    let target = occurrences
+        .iter()
+        .find(|o| o.span.start <= offset && offset <= o.span.end)?
+        .binding
+        .clone();
+
+    // Встроенные и неопределённые в файле имена не трогаем
+    if !occurrences
+        .iter()
+        .any(|o| o.is_declaration && o.binding == target)
+    {
+        return None;
+    }
+
+    let edits = occurrences
+        .iter()
+        .filter(|o| o.binding == target)
+        .map(|o| TextEdit {
+            range: Range {
+                start: offset_to_position(content, o.span.start),
+                end: offset_to_position(content, o.span.end),
+            },
+            new_text: new_name.to_string(),
+        })
+        .collect();
+
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), edits);
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    })
+}
+
+/// Собрать вхождения имён; `scope` — локальные привязки (имя, начало объявления),
+/// внутренние в конце.
+fn collect(expr: &SExpr, scope: &[(String, usize)], out: &mut Vec<Occurrence>) {
+    let items = match expr {
+        SExpr::Atom(_) => {
+            if let Some(name) = expr.as_ident() {
+                if name != "true" && name != "false" {
+                    out.push(Occurrence {
+                        span: expr.span(),
+                        binding: resolve(name, scope),
+                        is_declaration: false,
+                    });
+                }
+            }
+            return;
+        }
+        SExpr::List(list) => &list.value,
+    };
+
+    let head = items.first().and_then(|e| e.as_ident()).unwrap_or("");
+    let mut scope = scope.to_vec();
+    let rest = match head {
+        // (fn name (params...) body...)
+        "fn" if items.len() >= 3 => {
+            declare(&items[1], Binding::Global, out);
+            bind_params(&items[2], &mut scope, out);
+            &items[3..]
+        }
+        // (lambda (params...) body...)
+        "lambda" if items.len() >= 2 => {
+            bind_params(&items[1], &mut scope, out);
+            &items[2..]
+        }
+        // (rec-lambda self (params...) body...): self — локальная привязка
+        "rec-lambda" if items.len() >= 3 => {
+            if let Some(name) = items[1].as_ident() {
+                let start = items[1].span().start;
+                scope.push((name.to_string(), start));
+                declare(&items[1], |_| Binding::Local(start), out);
+            }
+            bind_params(&items[2], &mut scope, out);
+            &items[3..]
+        }
+        // (let name value), (let (a b) value) — let всегда определяет глобальное имя
+        "let" if items.len() >= 2 => {
+            match &items[1] {
+                SExpr::List(names) => {
+                    for name in &names.value {
+                        declare(name, Binding::Global, out);
+                    }
+                }
+                name => declare(name, Binding::Global, out),
+            }
+            &items[2..]
+        }
+        // (for x iterable body), (for [k v] iterable body), (loop-indexed i cond body):
+        // переменные видны в остальной форме
+        "for" | "loop-indexed" if items.len() >= 3 => {
+            if items[1].as_list().is_some() {
+                bind_params(&items[1], &mut scope, out);
+            } else if let Some(name) = items[1].as_ident() {
+                let start = items[1].span().start;
+                scope.push((name.to_string(), start));
+                declare(&items[1], |_| Binding::Local(start), out);
+            }
+            &items[2..]
+        }
+        _ if SPECIAL_FORMS.contains(&head) => &items[1..],
+        _ => &items[..],
+    };
+
+    for item in rest {
+        collect(item, &scope, out);
+    }
+}
+
+/// Привязка имени: ближайшая локальная, иначе глобальная.
+fn resolve(name: &str, scope: &[(String, usize)]) -> Binding {
+    scope
+        .iter()
+        .rev()
+        .find(|(local, _)| local == name)
+        .map_or_else(|| Binding::Global(name.to_string()), |(_, start)| {
+            Binding::Local(*start)
+        })
+}
+
+/// Записать объявление имени.
+fn declare(expr: &SExpr, binding: impl Fn(String) -> Binding, out: &mut Vec<Occurrence>) {
+    if let Some(name) = expr.as_ident() {
+        out.push(Occurrence {
+            span: expr.span(),
+            binding: binding(name.to_string()),
+            is_declaration: true,
+        });
+    }
+}
+
+/// Объявить параметры и добавить их в область видимости.
+fn bind_params(expr: &SExpr, scope: &mut Vec<(String, usize)>, out: &mut Vec<Occurrence>) {
+    if let SExpr::List(list) = expr {
+        for param in &list.value {
+            // Параметр со значением по умолчанию: (name default)
+            let param = match param.as_list() {
+                Some([name, _]) => name,
+                _ => param,
+            };
+            if let Some(name) = param.as_ident() {
+                let start = param.span().start;
+                scope.push((name.to_string(), start));
+                declare(param, |_| Binding::Local(start), out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit_ranges(edit: &WorkspaceEdit, uri: &Url) -> Vec<(u32, u32)> {
+        let edits = &edit.changes.as_ref().unwrap()[uri];
+        edits
+            .iter()
+            .map(|e| (e.range.start.line, e.range.start.character))
+            .collect()
+    }
+
+    #[test]
+    fn test_rename_function_with_two_call_sites() {
+        let content = "(fn double (x) (* x 2))\n(let a (double 1))\n(let b (double a))";
+        let uri = Url::parse("file:///test.asg").unwrap();
+
+        // Курсор на втором вызове
+        let edit = rename_symbol(content, Position { line: 2, character: 9 }, "twice", &uri)
+            .unwrap();
+        assert_eq!(edit_ranges(&edit, &uri), vec![(0, 4), (1, 8), (2, 8)]);
+        let edits = &edit.changes.as_ref().unwrap()[&uri];
+        assert!(edits.iter().all(|e| e.new_text == "twice"));
+        assert!(edits.iter().all(|e| e.range.end.character == e.range.start.character + 6));
+    }
+
+    #[test]
+    fn test_rename_respects_local_scope() {
+        let content = "(fn f (x) (+ x 1))\n(fn g (x) (* x 2))\n(let x 5)";
+        let uri = Url::parse("file:///test.asg").unwrap();
+
+        // Параметр x функции f — только её объявление и использование
+        let edit = rename_symbol(content, Position { line: 0, character: 13 }, "y", &uri)
+            .unwrap();
+        assert_eq!(edit_ranges(&edit, &uri), vec![(0, 7), (0, 13)]);
+
+        // Глобальный x не затрагивает параметры
+        let edit = rename_symbol(content, Position { line: 2, character: 5 }, "y", &uri)
+            .unwrap();
+        assert_eq!(edit_ranges(&edit, &uri), vec![(2, 5)]);
+
+        // Встроенные функции не переименовываются
+        assert!(rename_symbol(content, Position { line: 0, character: 11 }, "y", &uri).is_none());
+    }
+
+    #[test]
+    fn test_is_valid_identifier() {
+        assert!(is_valid_identifier("new-name_2"));
+        assert!(!is_valid_identifier("2x"));
+        assert!(!is_valid_identifier("a b"));
+        assert!(!is_valid_identifier("let"));
+    }
+}
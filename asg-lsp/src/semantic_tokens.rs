@@ -0,0 +1,295 @@
+//! Semantic tokens implementation.
+
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::*;
+use asg_lang::asg::ASG;
+use asg_lang::nodecodes::NodeType;
+use asg_lang::parser::{Atom, Parser, SExpr, Span};
+
+use crate::syntax::{offset_to_position, SPECIAL_FORMS};
+
+/// Типы токенов в порядке legend (индекс = token_type).
+pub const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+];
+
+/// Модификаторы токенов в порядке legend (бит = индекс).
+pub const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[SemanticTokenModifier::DEFAULT_LIBRARY];
+
+const KEYWORD: u32 = 0;
+const FUNCTION: u32 = 1;
+const PARAMETER: u32 = 2;
+const VARIABLE: u32 = 3;
+const NUMBER: u32 = 4;
+const STRING: u32 = 5;
+
+/// Бит модификатора defaultLibrary (встроенные функции).
+const DEFAULT_LIBRARY: u32 = 1;
+
+/// Legend для ServerCapabilities.
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// Классифицированный атом.
+struct RawToken {
+    span: Span,
+    token_type: u32,
+    modifiers: u32,
+}
+
+/// Получить semantic tokens документа в кодировке LSP (относительные позиции).
+///
+/// Атомы берутся из S-выражений (у каждого есть span), пользовательские функции —
+/// из узлов Function в ASG, параметры — из списков параметров fn/lambda по областям видимости.
+pub fn get_semantic_tokens(content: &str, asg: &ASG) -> Vec<SemanticToken> {
+    let exprs = match Parser::new(content).parse_all() {
+        Ok(exprs) => exprs,
+        Err(_) => return Vec::new(),
+    };
+
+    let user_functions: HashSet<String> = asg
+        .nodes
+        .iter()
+        .filter(|n| n.node_type == NodeType::Function)
+        .filter_map(|n| n.get_name())
+        .collect();
+
+    let mut raw = Vec::new();
+    for expr in &exprs {
+        classify(expr, &user_functions, &[], &mut raw);
+    }
+    raw.sort_by_key(|t| t.span.start);
+
+    encode(content, &raw)
+}
+
+/// Классифицировать атомы выражения; `params` — параметры в области видимости.
+fn classify(
+    expr: &SExpr,
+    user_functions: &HashSet<String>,
+    params: &[String],
+    out: &mut Vec<RawToken>,
+) {
+    let items = match expr {
+        SExpr::Atom(atom) => {
+            let (token_type, modifiers) = match &atom.value {
+                Atom::Int(_) | Atom::BigInt(_) | Atom::Decimal(_) | Atom::Float(_) => {
+                    (NUMBER, 0)
+                }
+                Atom::String(_) => (STRING, 0),
+                Atom::Ident(name) if name == "true" || name == "false" => (KEYWORD, 0),
+                Atom::Ident(name) if params.contains(name) => (PARAMETER, 0),
+                Atom::Ident(name) if user_functions.contains(name) => (FUNCTION, 0),
+                Atom::Ident(_) => (VARIABLE, 0),
+                Atom::Symbol(_) => (FUNCTION, DEFAULT_LIBRARY),
+            };
+            out.push(RawToken {
+                span: atom.span,
+                token_type,
+                modifiers,
+            });
+            return;
+        }
+        SExpr::List(list) => &list.value,
+    };
+
+    let Some(SExpr::Atom(head)) = items.first() else {
+        for item in items {
+            classify(item, user_functions, params, out);
+        }
+        return;
+    };
+    let head_name = match &head.value {
+        Atom::Ident(name) | Atom::Symbol(name) => name.as_str(),
+        // Литерал в голове списка — обычные атомы
+        _ => {
+            for item in items {
+                classify(item, user_functions, params, out);
+            }
+            return;
+        }
+    };
+
+    let (token_type, modifiers) = if SPECIAL_FORMS.contains(&head_name) {
+        (KEYWORD, 0)
+    } else if params.iter().any(|p| p == head_name) {
+        (PARAMETER, 0)
+    } else if user_functions.contains(head_name) {
+        (FUNCTION, 0)
+    } else {
+        (FUNCTION, DEFAULT_LIBRARY)
+    };
+    out.push(RawToken {
+        span: head.span,
+        token_type,
+        modifiers,
+    });
+
+    let mut rest = &items[1..];
+    let mut scope = params.to_vec();
+    match head_name {
+        // (fn name (params...) body)
+        "fn" if rest.len() >= 2 => {
+            mark(&rest[0], FUNCTION, out);
+            bind_params(&rest[1], &mut scope, out);
+            rest = &rest[2..];
+        }
+        // (lambda (params...) body)
+        "lambda" if !rest.is_empty() => {
+            bind_params(&rest[0], &mut scope, out);
+            rest = &rest[1..];
+        }
+        // (rec-lambda self (params...) body): self подсвечивается как параметр
+        "rec-lambda" if rest.len() >= 2 => {
+            if let Some(name) = rest[0].as_ident() {
+                scope.push(name.to_string());
+                mark(&rest[0], PARAMETER, out);
+            }
+            bind_params(&rest[1], &mut scope, out);
+            rest = &rest[2..];
+        }
+        // (let name value), (let (a b) value)
+        "let" if !rest.is_empty() => {
+            match &rest[0] {
+                SExpr::List(names) => {
+                    for name in &names.value {
+                        mark(name, VARIABLE, out);
+                    }
+                }
+                name => mark(name, VARIABLE, out),
+            }
+            rest = &rest[1..];
+        }
+        _ => {}
+    }
+
+    for item in rest {
+        classify(item, user_functions, &scope, out);
+    }
+}
+
+/// Отметить идентификатор в позиции объявления заданным типом.
+fn mark(expr: &SExpr, token_type: u32, out: &mut Vec<RawToken>) {
+    if let SExpr::Atom(atom) = expr {
+        if matches!(atom.value, Atom::Ident(_)) {
+            out.push(RawToken {
+                span: atom.span,
+                token_type,
+                modifiers: 0,
+            });
+        }
+    }
+}
+
+/// Отметить список параметров и добавить их в область видимости.
+fn bind_params(expr: &SExpr, scope: &mut Vec<String>, out: &mut Vec<RawToken>) {
+    if let SExpr::List(list) = expr {
+        for param in &list.value {
+            // Параметр со значением по умолчанию: (name default)
+            let param = match param.as_list() {
+                Some([name, _]) => name,
+                _ => param,
+            };
+            if let Some(name) = param.as_ident() {
+                scope.push(name.to_string());
+                mark(param, PARAMETER, out);
+            }
+        }
+    }
+}
+
+/// Закодировать токены: (deltaLine, deltaStart, length, type, modifiers).
+fn encode(content: &str, tokens: &[RawToken]) -> Vec<SemanticToken> {
+    let mut result = Vec::with_capacity(tokens.len());
+    let (mut prev_line, mut prev_col) = (0u32, 0u32);
+
+    for token in tokens {
+        let Some(text) = content.get(token.span.start..token.span.end) else {
+            continue;
+        };
+        let position = offset_to_position(content, token.span.start);
+        let (line, col) = (position.line, position.character);
+        let delta_line = line - prev_line;
+        let delta_start = if delta_line == 0 { col - prev_col } else { col };
+
+        result.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length: text.chars().count() as u32,
+            token_type: token.token_type,
+            token_modifiers_bitset: token.modifiers,
+        });
+        prev_line = line;
+        prev_col = col;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asg_lang::parser;
+
+    #[test]
+    fn test_function_with_parameter() {
+        let content = "(fn f (x) (+ x 1))";
+        let (asg, _) = parser::parse(content).unwrap();
+
+        let tokens = get_semantic_tokens(content, &asg);
+        let decoded: Vec<(u32, u32, u32, u32)> = tokens
+            .iter()
+            .map(|t| {
+                (
+                    t.delta_start,
+                    t.length,
+                    t.token_type,
+                    t.token_modifiers_bitset,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            decoded,
+            vec![
+                (1, 2, KEYWORD, 0),                // fn
+                (3, 1, FUNCTION, 0),               // f
+                (3, 1, PARAMETER, 0),              // x
+                (4, 1, FUNCTION, DEFAULT_LIBRARY), // +
+                (2, 1, PARAMETER, 0),              // x
+                (2, 1, NUMBER, 0),                 // 1
+            ]
+        );
+        assert!(tokens.iter().all(|t| t.delta_line == 0));
+    }
+
+    #[test]
+    fn test_calls_and_variables_across_lines() {
+        let content = "(fn sq (n) (* n n))\n(let y (sq 3))\ny";
+        let (asg, _) = parser::parse(content).unwrap();
+
+        let tokens = get_semantic_tokens(content, &asg);
+        let types: Vec<u32> = tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                KEYWORD, FUNCTION, PARAMETER, FUNCTION, PARAMETER, PARAMETER, // fn sq (n) (* n n)
+                KEYWORD, VARIABLE, FUNCTION, NUMBER, // let y (sq 3)
+                VARIABLE, // y
+            ]
+        );
+        // "let" начинает вторую строку, "y" — третью
+        assert_eq!((tokens[6].delta_line, tokens[6].delta_start), (1, 1));
+        assert_eq!((tokens[10].delta_line, tokens[10].delta_start), (1, 0));
+    }
+}
@@ -8,12 +8,17 @@ use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer};
 
 use asg_lang::asg::ASG;
-use asg_lang::parser;
 
 use crate::completion::get_completions;
 use crate::definition::{find_definition, find_references};
 use crate::diagnostics::get_diagnostics;
+use crate::formatting::format_document;
 use crate::hover::get_hover_info;
+use crate::incremental::IncrementalParse;
+use crate::rename::{is_valid_identifier, rename_symbol};
+use crate::semantic_tokens::{self, get_semantic_tokens};
+use crate::symbols::get_document_symbols;
+use crate::syntax::position_to_offset;
 
 /// Документ в редакторе.
 #[derive(Debug, Clone)]
@@ -24,6 +29,8 @@ pub struct Document {
     pub asg: Option<ASG>,
     /// URI документа
     pub uri: Url,
+    /// Формы документа для инкрементального перепарсинга
+    pub parse: IncrementalParse,
 }
 
 /// ASG Language Server.
@@ -44,13 +51,12 @@ impl ASGLanguageServer {
     }
 
     /// Обновить документ и получить диагностику.
-    async fn update_document(&self, uri: Url, content: String) {
-        // Парсим документ
-        let parse_result = parser::parse(&content);
-
-        let (asg, diagnostics) = match parse_result {
-            Ok((asg, _)) => (Some(asg), vec![]),
-            Err(e) => (None, get_diagnostics(&e.to_string(), &content)),
+    async fn update_document(&self, uri: Url, parse: IncrementalParse) {
+        let content = parse.content().to_string();
+        let asg = parse.asg();
+        let diagnostics = match parse.error() {
+            Some(e) => get_diagnostics(e, &content),
+            None => vec![],
         };
 
         // Сохраняем документ
@@ -62,6 +68,7 @@ impl ASGLanguageServer {
                     content,
                     asg,
                     uri: uri.clone(),
+                    parse,
                 },
             );
         }
@@ -85,7 +92,7 @@ impl LanguageServer for ASGLanguageServer {
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 completion_provider: Some(CompletionOptions {
                     trigger_characters: Some(vec!["(".to_string(), " ".to_string()]),
@@ -95,7 +102,18 @@ impl LanguageServer for ASGLanguageServer {
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Left(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens::legend(),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            ..Default::default()
+                        },
+                    ),
+                ),
                 ..Default::default()
             },
             server_info: Some(ServerInfo {
@@ -118,16 +136,31 @@ impl LanguageServer for ASGLanguageServer {
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
         self.update_document(
             params.text_document.uri,
-            params.text_document.text,
+            IncrementalParse::new(params.text_document.text),
         )
         .await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        if let Some(change) = params.content_changes.into_iter().last() {
-            self.update_document(params.text_document.uri, change.text)
-                .await;
+        let uri = params.text_document.uri;
+        let mut parse = match self.get_document(&uri) {
+            Some(doc) => doc.parse,
+            None => IncrementalParse::new(String::new()),
+        };
+
+        // Правки применяются по порядку; без range клиент прислал весь текст
+        for change in params.content_changes {
+            match change.range {
+                Some(range) => {
+                    let start = position_to_offset(parse.content(), range.start);
+                    let end = position_to_offset(parse.content(), range.end);
+                    parse.apply_edit(start, end, &change.text);
+                }
+                None => parse = IncrementalParse::new(change.text),
+            }
         }
+
+        self.update_document(uri, parse).await;
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -187,6 +220,25 @@ impl LanguageServer for ASGLanguageServer {
         Ok(find_references(&doc.content, position, doc.asg.as_ref(), &uri))
     }
 
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        if !is_valid_identifier(&params.new_name) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "'{}' is not a valid identifier",
+                params.new_name
+            )));
+        }
+
+        let doc = match self.get_document(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+
+        Ok(rename_symbol(&doc.content, position, &params.new_name, &uri))
+    }
+
     async fn document_symbol(
         &self,
         params: DocumentSymbolParams,
@@ -198,78 +250,49 @@ impl LanguageServer for ASGLanguageServer {
             None => return Ok(None),
         };
 
-        let symbols = get_document_symbols(&doc.content, doc.asg.as_ref());
-        Ok(Some(DocumentSymbolResponse::Flat(symbols)))
+        let asg = match doc.asg.as_ref() {
+            Some(asg) => asg,
+            None => return Ok(None),
+        };
+        let roots: Vec<_> = doc.parse.forms().iter().map(|form| form.root).collect();
+
+        let symbols = get_document_symbols(&doc.content, asg, &roots);
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
     }
-}
 
-/// Получить символы документа.
-fn get_document_symbols(content: &str, asg: Option<&ASG>) -> Vec<SymbolInformation> {
-    let mut symbols = Vec::new();
+    async fn formatting(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri;
 
-    if let Some(asg) = asg {
-        for node in &asg.nodes {
-            use asg_lang::nodecodes::NodeType;
+        let doc = match self.get_document(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
 
-            let (name, kind) = match node.node_type {
-                NodeType::Function => {
-                    let name = node.get_name().unwrap_or_else(|| "<anonymous>".to_string());
-                    (name, SymbolKind::FUNCTION)
-                }
-                NodeType::Variable => {
-                    let name = node.get_name().unwrap_or_else(|| "<var>".to_string());
-                    (name, SymbolKind::VARIABLE)
-                }
-                NodeType::Module => {
-                    let name = node.get_name().unwrap_or_else(|| "<module>".to_string());
-                    (name, SymbolKind::MODULE)
-                }
-                _ => continue,
-            };
-
-            // Вычисляем позицию из span
-            let range = if let Some(span) = node.span {
-                let start = offset_to_position(content, span.start);
-                let end = offset_to_position(content, span.end);
-                Range { start, end }
-            } else {
-                Range::default()
-            };
-
-            #[allow(deprecated)]
-            symbols.push(SymbolInformation {
-                name,
-                kind,
-                tags: None,
-                deprecated: None,
-                location: Location {
-                    uri: Url::parse("file:///").unwrap(),
-                    range,
-                },
-                container_name: None,
-            });
-        }
+        Ok(Some(format_document(&doc.content)))
     }
 
-    symbols
-}
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let uri = params.text_document.uri;
 
-/// Конвертировать offset в Position.
-fn offset_to_position(content: &str, offset: usize) -> Position {
-    let mut line = 0u32;
-    let mut col = 0u32;
+        let doc = match self.get_document(&uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+        let asg = match doc.asg.as_ref() {
+            Some(asg) => asg,
+            None => return Ok(None),
+        };
 
-    for (i, ch) in content.chars().enumerate() {
-        if i >= offset {
-            break;
-        }
-        if ch == '\n' {
-            line += 1;
-            col = 0;
-        } else {
-            col += 1;
-        }
+        let data = get_semantic_tokens(&doc.content, asg);
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        })))
     }
-
-    Position { line, character: col }
 }
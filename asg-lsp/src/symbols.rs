@@ -0,0 +1,86 @@
+//! Document symbols (outline) implementation.
+
+use tower_lsp::lsp_types::*;
+use asg_lang::asg::{NodeID, ASG};
+use asg_lang::nodecodes::{EdgeType, NodeType};
+
+use crate::syntax::offset_to_position;
+
+/// Получить outline документа: top-level fn/let/module, функции модуля — вложенно.
+pub fn get_document_symbols(content: &str, asg: &ASG, roots: &[NodeID]) -> Vec<DocumentSymbol> {
+    roots
+        .iter()
+        .filter_map(|&id| node_symbol(content, asg, id))
+        .collect()
+}
+
+/// Символ для узла определения (None для прочих узлов).
+fn node_symbol(content: &str, asg: &ASG, id: NodeID) -> Option<DocumentSymbol> {
+    let node = asg.find_node(id)?;
+
+    let (kind, children) = match node.node_type {
+        NodeType::Function => (SymbolKind::FUNCTION, None),
+        NodeType::Variable => (SymbolKind::VARIABLE, None),
+        NodeType::Module => {
+            let children = node
+                .find_edges(EdgeType::ModuleContent)
+                .into_iter()
+                .filter_map(|edge| node_symbol(content, asg, edge.target_node_id))
+                .collect();
+            (SymbolKind::MODULE, Some(children))
+        }
+        _ => return None,
+    };
+
+    let range = node
+        .span
+        .map(|span| Range {
+            start: offset_to_position(content, span.start),
+            end: offset_to_position(content, span.end),
+        })
+        .unwrap_or_default();
+
+    #[allow(deprecated)]
+    Some(DocumentSymbol {
+        name: node.get_name()?,
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asg_lang::parser;
+
+    #[test]
+    fn test_module_with_two_functions() {
+        let content = "(module math\n  (fn square (x) (* x x))\n  (fn cube (x) (* x x x)))\n(let pi 3)";
+        let (asg, roots) = parser::parse(content).unwrap();
+
+        let symbols = get_document_symbols(content, &asg, &roots);
+        assert_eq!(symbols.len(), 2);
+
+        let module = &symbols[0];
+        assert_eq!(module.name, "math");
+        assert_eq!(module.kind, SymbolKind::MODULE);
+        assert_eq!(module.range.start, Position { line: 0, character: 0 });
+        assert_eq!(module.range.end, Position { line: 2, character: 26 });
+
+        let functions = module.children.as_ref().unwrap();
+        let names: Vec<&str> = functions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["square", "cube"]);
+        assert!(functions.iter().all(|s| s.kind == SymbolKind::FUNCTION));
+        assert_eq!(functions[0].range.start, Position { line: 1, character: 2 });
+        assert_eq!(functions[1].range.start, Position { line: 2, character: 2 });
+
+        assert_eq!(symbols[1].name, "pi");
+        assert_eq!(symbols[1].kind, SymbolKind::VARIABLE);
+        assert!(symbols[1].children.is_none());
+    }
+}
@@ -0,0 +1,74 @@
+//! Общие сведения о синтаксисе ASG: специальные формы и пересчёт позиций LSP.
+
+use tower_lsp::lsp_types::Position;
+
+/// Специальные формы: подсвечиваются как ключевые слова и не переименовываются.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "fn", "lambda", "rec-lambda", "let", "set", "if", "when", "unless", "do", "while",
+    "loop", "loop-indexed", "loop-timeout", "for", "break", "continue", "return", "match",
+    "module", "import", "export", "try", "catch", "throw", "defrecord", "defenum",
+];
+
+/// Конвертировать Position (строка, символ) в байтовый offset.
+/// Символ считается в единицах UTF-16, как требует LSP.
+pub fn position_to_offset(content: &str, position: Position) -> usize {
+    let mut offset = 0;
+    for (line_no, line) in content.split_inclusive('\n').enumerate() {
+        if line_no == position.line as usize {
+            let line = line.trim_end_matches('\n');
+            let mut units = 0;
+            for (i, ch) in line.char_indices() {
+                if units >= position.character as usize {
+                    return offset + i;
+                }
+                units += ch.len_utf16();
+            }
+            return offset + line.len();
+        }
+        offset += line.len();
+    }
+    content.len()
+}
+
+/// Конвертировать байтовый offset в Position (символ — в единицах UTF-16).
+pub fn offset_to_position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() as u32;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_to_offset() {
+        let content = "(let x 1)\n(let y 2)";
+        assert_eq!(position_to_offset(content, Position { line: 0, character: 0 }), 0);
+        assert_eq!(position_to_offset(content, Position { line: 1, character: 5 }), 15);
+        assert_eq!(position_to_offset(content, Position { line: 5, character: 0 }), content.len());
+    }
+
+    #[test]
+    fn test_position_to_offset_counts_utf16_units() {
+        // "я" — 2 байта и 1 единица UTF-16, "😀" — 4 байта и 2 единицы
+        let content = "(print \"я😀\")";
+        assert_eq!(position_to_offset(content, Position { line: 0, character: 8 }), 8);
+        assert_eq!(position_to_offset(content, Position { line: 0, character: 9 }), 10);
+        assert_eq!(position_to_offset(content, Position { line: 0, character: 11 }), 14);
+    }
+
+    #[test]
+    fn test_offset_to_position() {
+        let content = "(let x 1)\n(print \"я😀\")";
+        assert_eq!(offset_to_position(content, 0), Position { line: 0, character: 0 });
+        assert_eq!(offset_to_position(content, 15), Position { line: 1, character: 5 });
+        // "я" — 1 единица UTF-16, "😀" — 2
+        assert_eq!(offset_to_position(content, 24), Position { line: 1, character: 11 });
+        assert_eq!(offset_to_position(content, 99), Position { line: 1, character: 13 });
+    }
+}
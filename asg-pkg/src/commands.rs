@@ -319,6 +319,11 @@ pub fn build_project(release: bool, target: &str, verbose: bool) -> CommandResul
         cmd.arg("--release");
     }
 
+    // Кэш компиляции общий для debug/release и путей native/llvm:
+    // неизменённая программа не компилируется заново
+    cmd.arg("--cache-dir");
+    cmd.arg(target_dir.join(".asg-cache"));
+
     let status = cmd.status()?;
 
     if status.success() {
@@ -402,6 +407,44 @@ pub fn check_project(verbose: bool) -> CommandResult {
     }
 }
 
+/// Отформатировать исходники проекта (`asg --fmt` для каждого .asg/.syn файла).
+pub fn format_project(verbose: bool) -> CommandResult {
+    let manifest_path = Manifest::find().ok_or("No asg.toml found")?;
+    let project_dir = manifest_path.parent().unwrap();
+
+    let mut failed = 0;
+    for entry in walkdir::WalkDir::new(project_dir)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+    {
+        let entry = entry?;
+        let path = entry.path();
+        let is_source = path
+            .extension()
+            .is_some_and(|ext| ext == "asg" || ext == "syn");
+        if !entry.file_type().is_file() || !is_source {
+            continue;
+        }
+
+        if verbose {
+            println!("{} Formatting {}...", "→".blue(), path.display());
+        }
+
+        let output = Command::new("asg").arg("--fmt").arg(path).output()?;
+        if !output.status.success() {
+            eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+            failed += 1;
+        }
+    }
+
+    if failed == 0 {
+        println!("{} Formatted", "✓".green());
+        Ok(())
+    } else {
+        Err(format!("{} file(s) could not be formatted", failed).into())
+    }
+}
+
 /// Опубликовать пакет.
 pub fn publish_package(
     registry: Option<&str>,
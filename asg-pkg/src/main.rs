@@ -133,6 +133,9 @@ enum Commands {
     /// Check the project for errors
     Check,
 
+    /// Format project sources in place
+    Fmt,
+
     /// Publish package to registry
     Publish {
         /// Registry URL
@@ -197,6 +200,7 @@ fn main() -> ExitCode {
         }
         Commands::Run { release, args } => commands::run_project(release, &args, cli.verbose),
         Commands::Check => commands::check_project(cli.verbose),
+        Commands::Fmt => commands::format_project(cli.verbose),
         Commands::Publish { registry, dry_run } => {
             commands::publish_package(registry.as_deref(), dry_run, cli.verbose)
         }
@@ -15,6 +15,7 @@ fn main() {
         payload: Some(2.0f32.to_le_bytes().to_vec()),
         edges: vec![],
         span: None,
+        attributes: Default::default(),
     });
 
     // Литерал-тензор со значением 5.0
@@ -24,6 +25,7 @@ fn main() {
         payload: Some(5.0f32.to_le_bytes().to_vec()),
         edges: vec![],
         span: None,
+        attributes: Default::default(),
     });
 
     // Узел сложения тензоров
@@ -44,6 +46,7 @@ fn main() {
             },
         ],
         span: None,
+        attributes: Default::default(),
     });
 
     let mut interpreter = Interpreter::new();
@@ -11,6 +11,7 @@ fn main() {
         payload: Some(5i64.to_le_bytes().to_vec()),
         edges: vec![],
         span: None,
+        attributes: Default::default(),
     });
 
     asg.add_node(Node {
@@ -19,6 +20,7 @@ fn main() {
         payload: Some(8i64.to_le_bytes().to_vec()),
         edges: vec![],
         span: None,
+        attributes: Default::default(),
     });
 
     asg.add_node(Node {
@@ -38,6 +40,7 @@ fn main() {
             },
         ],
         span: None,
+        attributes: Default::default(),
     });
 
     let mut interpreter = Interpreter::new();
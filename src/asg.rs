@@ -3,12 +3,14 @@
 use crate::nodecodes::{EdgeType, NodeType};
 use crate::parser::token::Span;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
 /// Уникальный идентификатор узла в ASG.
 pub type NodeID = u64;
 
 /// Ребро графа, соединяющее узлы.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Edge {
     pub edge_type: EdgeType,
     pub target_node_id: NodeID,
@@ -36,6 +38,9 @@ impl Edge {
 }
 
 /// Узел ASG.
+///
+/// `PartialEq`/`Hash` сравнивают узлы структурно и не учитывают `span` и `attributes`;
+/// для сравнения с позициями — [`Node::eq_with_span`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub id: NodeID,
@@ -45,6 +50,10 @@ pub struct Node {
     /// Позиция в исходном коде (для LSP и сообщений об ошибках).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub span: Option<Span>,
+    /// Аннотации для инструментов (выведенный тип, происхождение, заметки оптимизатора):
+    /// заполняются проходами и анализом, на выполнение не влияют.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, String>,
 }
 
 impl Node {
@@ -56,6 +65,7 @@ impl Node {
             payload,
             edges: Vec::new(),
             span: None,
+            attributes: HashMap::new(),
         }
     }
 
@@ -72,6 +82,7 @@ impl Node {
             payload,
             edges: Vec::new(),
             span: Some(span),
+            attributes: HashMap::new(),
         }
     }
 
@@ -88,6 +99,7 @@ impl Node {
             payload,
             edges,
             span: None,
+            attributes: HashMap::new(),
         }
     }
 
@@ -105,6 +117,7 @@ impl Node {
             payload,
             edges,
             span: Some(span),
+            attributes: HashMap::new(),
         }
     }
 
@@ -137,10 +150,93 @@ impl Node {
             .as_ref()
             .and_then(|p| String::from_utf8(p.clone()).ok())
     }
+
+    /// Значение аннотации `key`.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+
+    /// Установить аннотацию `key` (заменяет прежнее значение).
+    pub fn set_attribute(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.attributes.insert(key.into(), value.into());
+    }
+
+    /// Сравнение с учётом позиции в исходном коде.
+    pub fn eq_with_span(&self, other: &Node) -> bool {
+        self == other && self.span == other.span
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.node_type == other.node_type
+            && self.payload == other.payload
+            && self.edges == other.edges
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.node_type.hash(state);
+        self.payload.hash(state);
+        self.edges.hash(state);
+    }
+}
+
+/// 64-битный FNV-1a: стабилен между запусками и версиями компилятора,
+/// в отличие от `DefaultHasher`. Целые числа (включая длины и `usize`)
+/// хешируются как little-endian фиксированной ширины, поэтому значение
+/// не зависит и от платформы.
+struct Fnv64(u64);
+
+impl Fnv64 {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv64 {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
 }
 
 /// Абстрактный Синтаксический Граф.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+///
+/// `PartialEq` сравнивает узлы по порядку и с их ID; сравнение, не зависящее
+/// от нумерации, — через [`ASG::structural_hash`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ASG {
     pub nodes: Vec<Node>,
 }
@@ -180,4 +276,234 @@ impl ASG {
     pub fn next_id(&self) -> NodeID {
         self.nodes.iter().map(|n| n.id).max().unwrap_or(0) + 1
     }
+
+    /// Множество узлов, достижимых из `root` по рёбрам любого типа (включая сам `root`).
+    /// Рёбра на отсутствующие узлы игнорируются.
+    pub fn reachable_from(&self, root: NodeID) -> HashSet<NodeID> {
+        let by_id: HashMap<NodeID, &Node> = self.nodes.iter().map(|n| (n.id, n)).collect();
+        let mut visited = HashSet::new();
+        let mut stack = vec![root];
+
+        while let Some(id) = stack.pop() {
+            let Some(node) = by_id.get(&id) else {
+                continue;
+            };
+            if !visited.insert(id) {
+                continue;
+            }
+            stack.extend(node.edges.iter().map(|e| e.target_node_id));
+        }
+
+        visited
+    }
+
+    /// Хеш структуры графа, не зависящий от нумерации узлов и их порядка в `nodes`.
+    ///
+    /// Хеш узла строится из его типа, payload и хешей детей по рёбрам (в порядке рёбер);
+    /// хеш графа — из отсортированного набора хешей всех узлов. Span не учитывается.
+    /// Типы хешируются по имени, поэтому значение стабильно между запусками.
+    pub fn structural_hash(&self) -> u64 {
+        let mut hashes = self.node_hashes();
+        hashes.sort_unstable();
+
+        let mut hasher = Fnv64::new();
+        hashes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Как [`ASG::structural_hash`], но с учётом порядка узлов в `nodes`
+    /// (от него зависит порядок top-level форм в скомпилированном модуле).
+    pub fn ordered_structural_hash(&self) -> u64 {
+        let mut hasher = Fnv64::new();
+        self.node_hashes().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Структурные хеши всех узлов в порядке `nodes`.
+    fn node_hashes(&self) -> Vec<u64> {
+        let by_id: HashMap<NodeID, &Node> = self.nodes.iter().map(|n| (n.id, n)).collect();
+        let mut memo = HashMap::new();
+        let mut in_progress = HashSet::new();
+        self.nodes
+            .iter()
+            .map(|n| Self::node_structural_hash(n.id, &by_id, &mut memo, &mut in_progress))
+            .collect()
+    }
+
+    /// Структурный хеш подграфа, достижимого из узла `id` (тот же хеш узла, что
+    /// используется в [`ASG::structural_hash`]).
+    pub fn subgraph_hash(&self, id: NodeID) -> u64 {
+        let by_id: HashMap<NodeID, &Node> = self.nodes.iter().map(|n| (n.id, n)).collect();
+        Self::node_structural_hash(id, &by_id, &mut HashMap::new(), &mut HashSet::new())
+    }
+
+    fn node_structural_hash(
+        id: NodeID,
+        by_id: &HashMap<NodeID, &Node>,
+        memo: &mut HashMap<NodeID, u64>,
+        in_progress: &mut HashSet<NodeID>,
+    ) -> u64 {
+        if let Some(&hash) = memo.get(&id) {
+            return hash;
+        }
+        let mut hasher = Fnv64::new();
+        let Some(node) = by_id.get(&id) else {
+            "<missing>".hash(&mut hasher);
+            return hasher.finish();
+        };
+        // Обратное ребро цикла: ID не хешируем, чтобы не зависеть от нумерации
+        if !in_progress.insert(id) {
+            "<cycle>".hash(&mut hasher);
+            return hasher.finish();
+        }
+
+        format!("{:?}", node.node_type).hash(&mut hasher);
+        node.payload.hash(&mut hasher);
+        node.edges.len().hash(&mut hasher);
+        for edge in &node.edges {
+            format!("{:?}", edge.edge_type).hash(&mut hasher);
+            edge.payload.hash(&mut hasher);
+            Self::node_structural_hash(edge.target_node_id, by_id, memo, in_progress)
+                .hash(&mut hasher);
+        }
+
+        in_progress.remove(&id);
+        let hash = hasher.finish();
+        memo.insert(id, hash);
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reachable_from_excludes_unreachable_subtree() {
+        let mut asg = ASG::new();
+        // 1 -> (2, 3), 3 -> 2 (общий узел); 4 -> 5 — отдельное поддерево
+        asg.add_node(Node::with_edges(
+            1,
+            NodeType::BinaryOperation,
+            None,
+            vec![
+                Edge::new(EdgeType::FirstOperand, 2),
+                Edge::new(EdgeType::SecondOperand, 3),
+            ],
+        ));
+        asg.add_node(Node::new(2, NodeType::LiteralInt, None));
+        asg.add_node(Node::with_edges(
+            3,
+            NodeType::Neg,
+            None,
+            vec![Edge::new(EdgeType::ApplicationArgument, 2)],
+        ));
+        asg.add_node(Node::with_edges(
+            4,
+            NodeType::Neg,
+            None,
+            vec![Edge::new(EdgeType::ApplicationArgument, 5)],
+        ));
+        asg.add_node(Node::new(5, NodeType::LiteralInt, None));
+
+        let reachable = asg.reachable_from(1);
+        assert_eq!(reachable, HashSet::from([1, 2, 3]));
+        assert_eq!(asg.reachable_from(4), HashSet::from([4, 5]));
+        // Неизвестный корень — пустое множество
+        assert!(asg.reachable_from(42).is_empty());
+    }
+
+    #[test]
+    fn test_node_attributes_round_trip() {
+        let mut asg = sum_graph(1, 2, 3);
+        let plain = asg.clone();
+        let node = asg.find_node_mut(1).unwrap();
+        node.set_attribute("type", "Int");
+        node.set_attribute("origin", "inlined from f");
+        assert_eq!(node.attribute("type"), Some("Int"));
+        assert_eq!(node.attribute("missing"), None);
+
+        // Аннотации не влияют на равенство и хеши
+        assert_eq!(asg, plain);
+        assert_eq!(asg.structural_hash(), plain.structural_hash());
+
+        let json = crate::ai_api::export_asg_to_json(&asg).unwrap();
+        let restored = crate::ai_api::import_asg_from_json(&json).unwrap();
+        assert_eq!(
+            restored.find_node(1).unwrap().attributes,
+            asg.find_node(1).unwrap().attributes
+        );
+        // Узлы без аннотаций не получают лишнего поля
+        assert_eq!(json.matches("\"attributes\"").count(), 1);
+    }
+
+    /// (+ 1 2) с заданными ID для узла операции и двух литералов.
+    fn sum_graph(op: NodeID, lhs: NodeID, rhs: NodeID) -> ASG {
+        let mut asg = ASG::new();
+        asg.add_node(Node::with_edges(
+            op,
+            NodeType::BinaryOperation,
+            Some(b"+".to_vec()),
+            vec![
+                Edge::new(EdgeType::FirstOperand, lhs),
+                Edge::new(EdgeType::SecondOperand, rhs),
+            ],
+        ));
+        asg.add_node(Node::new(
+            lhs,
+            NodeType::LiteralInt,
+            Some(1i64.to_le_bytes().to_vec()),
+        ));
+        asg.add_node(Node::new(
+            rhs,
+            NodeType::LiteralInt,
+            Some(2i64.to_le_bytes().to_vec()),
+        ));
+        asg
+    }
+
+    #[test]
+    fn test_structural_hash_is_platform_independent() {
+        let hash = |write: &dyn Fn(&mut Fnv64)| {
+            let mut hasher = Fnv64::new();
+            write(&mut hasher);
+            hasher.finish()
+        };
+        let le_bytes = hash(&|h| h.write(&7u64.to_le_bytes()));
+        assert_eq!(hash(&|h| 7usize.hash(h)), le_bytes);
+        assert_eq!(hash(&|h| 7u64.hash(h)), le_bytes);
+
+        // Эталонное значение: не меняется между платформами и запусками
+        assert_eq!(sum_graph(1, 2, 3).structural_hash(), 0x8c24_6683_c493_d7f6);
+    }
+
+    #[test]
+    fn test_structural_hash_ignores_id_numbering() {
+        let a = sum_graph(1, 2, 3);
+        let mut b = sum_graph(30, 10, 20);
+        b.nodes.reverse();
+
+        assert_ne!(a, b);
+        assert_eq!(a.structural_hash(), b.structural_hash());
+
+        // Другой литерал — другая структура
+        let mut changed = sum_graph(1, 2, 3);
+        changed.find_node_mut(3).unwrap().payload = Some(5i64.to_le_bytes().to_vec());
+        assert_ne!(a.structural_hash(), changed.structural_hash());
+
+        // Перестановка операндов меняет структуру
+        let mut swapped = sum_graph(1, 2, 3);
+        swapped.find_node_mut(1).unwrap().edges.reverse();
+        assert_ne!(a.structural_hash(), swapped.structural_hash());
+    }
+
+    #[test]
+    fn test_node_eq_ignores_span_by_default() {
+        let a = Node::with_span(1, NodeType::VarRef, Some(b"x".to_vec()), Span::new(0, 1));
+        let b = Node::with_span(1, NodeType::VarRef, Some(b"x".to_vec()), Span::new(10, 11));
+
+        assert_eq!(a, b);
+        assert!(!a.eq_with_span(&b));
+        assert!(a.eq_with_span(&a.clone()));
+    }
 }
@@ -4,16 +4,21 @@
 //!   asg              - запустить REPL
 //!   asg <file.asg>   - выполнить файл
 //!   asg -e "expr"    - выполнить выражение
+//!   asg --fmt <file> - отформатировать файл
+//!   asg <file> --compile [-o out]      - LLVM IR (out.ll) через кэш компиляции
+//!   asg <file> --compile-wasm [-o out] - WASM-модуль через кэш компиляции
 //!   asg --help       - справка
 
 use rustyline::error::ReadlineError;
 use rustyline::DefaultEditor;
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::process;
 
+use asg_lang::compiler::CompileCache;
 use asg_lang::interpreter::{Interpreter, Value};
-use asg_lang::parser::{parse, parse_expr};
+use asg_lang::parser::{format_source, parse, parse_expr};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -24,6 +29,11 @@ USAGE:
     asg                  Start REPL (interactive mode)
     asg <file.asg>       Execute a ASG file
     asg -e "<expr>"      Evaluate an expression
+    asg --fmt <file>     Format a file in place
+    asg <file> --compile [-o <out>]       Emit LLVM IR to <out>.ll
+    asg <file> --compile-wasm [-o <out>]  Emit a WASM module to <out>.wasm
+        --cache-dir <dir>  Compile cache (default: .asg-cache next to the output);
+                           unchanged programs are not recompiled between builds
     asg --help, -h       Show this help
     asg --version, -v    Show version
 
@@ -58,6 +68,14 @@ SYNTAX (S-Expression):
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    if args
+        .iter()
+        .any(|arg| arg == "--compile" || arg == "--compile-wasm")
+    {
+        compile_file(&args[1..]);
+        return;
+    }
+
     match args.len() {
         1 => run_repl(),
         2 => match args[1].as_str() {
@@ -72,6 +90,8 @@ fn main() {
         3 => {
             if args[1] == "-e" || args[1] == "--eval" {
                 run_expr(&args[2]);
+            } else if args[1] == "--fmt" {
+                format_file(&args[2]);
             } else {
                 eprintln!("Unknown option: {}", args[1]);
                 eprintln!("Use --help for usage information.");
@@ -293,6 +313,20 @@ fn print_value(value: &Value) {
         Value::LazySeq(_) => {
             println!("<lazy-seq>");
         }
+        #[cfg(feature = "bigint")]
+        Value::BigInt(n) => println!("{}", n),
+        #[cfg(feature = "decimal")]
+        Value::Decimal(d) => println!("{}", d),
+        Value::Variant { .. }
+        | Value::Char(_)
+        | Value::Option(_)
+        | Value::Builtin(_)
+        | Value::Bytes(_)
+        | Value::Generator(_)
+        | Value::Set(_)
+        | Value::Cell(_) => {
+            println!("{}", value.format_display());
+        }
     }
 }
 
@@ -320,6 +354,18 @@ fn format_value(value: &Value) -> String {
         }
         Value::ComposedFunction(fns) => format!("<composed({} fns)>", fns.len()),
         Value::LazySeq(_) => "<lazy-seq>".to_string(),
+        #[cfg(feature = "bigint")]
+        Value::BigInt(n) => n.to_string(),
+        #[cfg(feature = "decimal")]
+        Value::Decimal(d) => d.to_string(),
+        Value::Variant { .. }
+        | Value::Char(_)
+        | Value::Option(_)
+        | Value::Builtin(_)
+        | Value::Bytes(_)
+        | Value::Generator(_)
+        | Value::Set(_)
+        | Value::Cell(_) => value.format_display(),
     }
 }
 
@@ -420,6 +466,139 @@ fn show_functions(interpreter: &Interpreter) {
     }
 }
 
+/// Отформатировать файл на месте.
+fn format_file(path: &str) {
+    let source = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Error reading file '{}': {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let formatted = match format_source(&source) {
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("Cannot format '{}': {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    if formatted != source {
+        if let Err(e) = fs::write(path, formatted) {
+            eprintln!("Error writing file '{}': {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Скомпилировать файл: `<file> --compile|--compile-wasm [-o out] [--cache-dir dir]`.
+///
+/// Артефакт берётся из кэша компиляции, если программа не менялась с прошлой сборки.
+/// `--release` принимается для совместимости с `asg-pkg build` и ничего не меняет.
+fn compile_file(args: &[String]) {
+    let mut source_path = None;
+    let mut output = None;
+    let mut cache_dir = None;
+    let mut wasm = false;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--compile" => wasm = false,
+            "--compile-wasm" => wasm = true,
+            "--release" => {}
+            "-o" | "--cache-dir" => {
+                let Some(value) = rest.next() else {
+                    eprintln!("Option {} expects a path.", arg);
+                    process::exit(1);
+                };
+                if arg == "-o" {
+                    output = Some(PathBuf::from(value));
+                } else {
+                    cache_dir = Some(PathBuf::from(value));
+                }
+            }
+            path if source_path.is_none() && !path.starts_with('-') => source_path = Some(path),
+            other => {
+                eprintln!("Unknown option: {}", other);
+                eprintln!("Use --help for usage information.");
+                process::exit(1);
+            }
+        }
+    }
+    let Some(source_path) = source_path else {
+        eprintln!("No input file to compile.");
+        process::exit(1);
+    };
+
+    let source = match fs::read_to_string(source_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("Error reading file '{}': {}", source_path, e);
+            process::exit(1);
+        }
+    };
+    let asg = match parse(&source) {
+        Ok((asg, _)) => asg,
+        Err(e) => {
+            eprintln!("Parse error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let extension = if wasm { "wasm" } else { "ll" };
+    let output = output
+        .unwrap_or_else(|| PathBuf::from(source_path))
+        .with_extension(extension);
+    let cache_dir =
+        cache_dir.unwrap_or_else(|| output.parent().unwrap_or(Path::new(".")).join(".asg-cache"));
+    let mut cache = match CompileCache::open(&cache_dir) {
+        Ok(cache) => cache,
+        Err(e) => {
+            eprintln!("Cannot open compile cache: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let artifact = if wasm {
+        compile_wasm(&asg, &mut cache)
+    } else {
+        asg_lang::compiler::compile_llvm_cached(&asg, &mut cache).map(String::into_bytes)
+    };
+    let artifact = match artifact {
+        Ok(artifact) => artifact,
+        Err(e) => {
+            eprintln!("Compilation error: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = fs::write(&output, artifact) {
+        eprintln!("Error writing file '{}': {}", output.display(), e);
+        process::exit(1);
+    }
+    if cache.compile_count() == 0 {
+        println!("{} is up to date (cached)", output.display());
+    }
+}
+
+#[cfg(feature = "wasm_backend")]
+fn compile_wasm(
+    asg: &asg_lang::asg::ASG,
+    cache: &mut CompileCache,
+) -> asg_lang::ASGResult<Vec<u8>> {
+    asg_lang::compiler::compile_cached(asg, asg_lang::compiler::Target::Wasm, cache)
+}
+
+#[cfg(not(feature = "wasm_backend"))]
+fn compile_wasm(
+    _asg: &asg_lang::asg::ASG,
+    _cache: &mut CompileCache,
+) -> asg_lang::ASGResult<Vec<u8>> {
+    Err(asg_lang::error::ASGError::CompilationError(
+        "WASM backend not compiled in. Enable the 'wasm_backend' feature.".to_string(),
+    ))
+}
+
 /// Выполнить файл.
 fn run_file(path: &str) {
     let source = match fs::read_to_string(path) {
@@ -437,6 +616,7 @@ fn run_file(path: &str) {
             }
 
             let mut interpreter = Interpreter::new();
+            interpreter.set_current_file(path);
             let mut last_value = Value::Unit;
 
             // Выполняем все top-level выражения по порядку
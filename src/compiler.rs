@@ -6,9 +6,112 @@
 //!
 //! В будущем здесь появится поддержка LLVM/Wasm.
 
-use crate::asg::ASG;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::asg::{Node, NodeID, ASG};
+use crate::error::ASGError;
+use crate::nodecodes::NodeType;
+use crate::parser::token::Span;
 use crate::ASGResult;
 
+/// Целевая платформа WASM-компиляции.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Target {
+    /// Чистый WASM: `print` импортируется из хоста (`env.print_int`), экспортируется `main`.
+    #[default]
+    Wasm,
+    /// WASI: `print` пишет в stdout через `wasi_snapshot_preview1.fd_write`,
+    /// экспортируется `_start` (модуль запускается `wasmtime`).
+    Wasi,
+}
+
+impl Target {
+    /// Умеет ли WASM-backend компилировать узлы этого типа.
+    ///
+    /// Остальные узлы backend молча заменяет на `0`, поэтому их нужно
+    /// отсеивать заранее через [`check_supported`].
+    pub fn supports(self, node_type: NodeType) -> bool {
+        matches!(
+            node_type,
+            NodeType::LiteralInt
+                | NodeType::LiteralFloat
+                | NodeType::LiteralBool
+                | NodeType::LiteralString
+                | NodeType::LiteralUnit
+                | NodeType::BinaryOperation
+                | NodeType::Sub
+                | NodeType::Mul
+                | NodeType::Div
+                | NodeType::Mod
+                | NodeType::Eq
+                | NodeType::Ne
+                | NodeType::Lt
+                | NodeType::Le
+                | NodeType::Gt
+                | NodeType::Ge
+                | NodeType::And
+                | NodeType::Or
+                | NodeType::Not
+                | NodeType::Neg
+                | NodeType::If
+                | NodeType::Print
+                | NodeType::Block
+                | NodeType::Variable
+                | NodeType::VarRef
+                | NodeType::MathPi
+                | NodeType::MathE
+                | NodeType::MathSqrt
+                | NodeType::MathAbs
+                | NodeType::MathFloor
+                | NodeType::MathCeil
+                | NodeType::MathMin
+                | NodeType::MathMax
+        )
+    }
+}
+
+/// Узел, который выбранный backend не умеет компилировать.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedNode {
+    pub id: NodeID,
+    pub node_type: NodeType,
+    /// Позиция в исходном коде (если ASG построен парсером)
+    pub span: Option<Span>,
+}
+
+impl std::fmt::Display for UnsupportedNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} (node {})", self.node_type, self.id)?;
+        if let Some(span) = self.span {
+            write!(f, " at {}..{}", span.start, span.end)?;
+        }
+        Ok(())
+    }
+}
+
+/// Найти все узлы, которые backend для `target` не поддерживает, до начала компиляции.
+///
+/// Ошибка содержит полный список (в порядке узлов), а не первый встреченный узел.
+pub fn check_supported(asg: &ASG, target: Target) -> Result<(), Vec<UnsupportedNode>> {
+    let unsupported: Vec<_> = asg
+        .nodes
+        .iter()
+        .filter(|node| !target.supports(node.node_type))
+        .map(|node| UnsupportedNode {
+            id: node.id,
+            node_type: node.node_type,
+            span: node.span,
+        })
+        .collect();
+    if unsupported.is_empty() {
+        Ok(())
+    } else {
+        Err(unsupported)
+    }
+}
+
 /// Frontend-компилятор.
 /// На данном этапе реализует только заглушку анализа.
 pub fn analyze_asg(asg: &ASG) -> ASGResult<()> {
@@ -22,3 +125,305 @@ pub fn generate_ir(_asg: &ASG) -> ASGResult<String> {
     println!("Backend: generating IR (stub)...");
     Ok("// IR code (stub)".into())
 }
+
+/// Backend, породивший артефакт кэша.
+///
+/// Входит в ключ: артефакты одного и того же графа у разных backend-ов различаются.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// LLVM IR — общий для путей `native` и `llvm`
+    Llvm,
+    /// WASM-модуль для заданной платформы
+    Wasm(Target),
+}
+
+impl Backend {
+    /// Подкаталог кэша для артефактов этого backend-а.
+    fn dir_name(self) -> &'static str {
+        match self {
+            Backend::Llvm => "llvm",
+            Backend::Wasm(Target::Wasm) => "wasm",
+            Backend::Wasm(Target::Wasi) => "wasi",
+        }
+    }
+}
+
+/// Кэш компиляции: (backend, структурный хеш) → скомпилированный артефакт
+/// (IR, объектный код или WASM-модуль).
+///
+/// Хеш не зависит от нумерации узлов и span, поэтому неизменённая функция
+/// переиспользуется между сборками. Пути `native` и `llvm` порождают один и тот же
+/// LLVM IR и делят кэш; артефакты других backend-ов хранятся в отдельном каталоге.
+#[derive(Debug, Default)]
+pub struct CompileCache {
+    /// Каталог для хранения между сборками (None — только в памяти)
+    dir: Option<PathBuf>,
+    /// Загруженные артефакты
+    entries: HashMap<(Backend, u64), Vec<u8>>,
+    /// Сколько раз вызывался компилятор (промахи кэша)
+    compile_count: usize,
+}
+
+impl CompileCache {
+    /// Кэш в памяти.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Кэш, хранящий артефакты в каталоге `dir` (`<backend>/<hash>.bin`).
+    pub fn open(dir: impl Into<PathBuf>) -> ASGResult<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .map_err(|e| ASGError::IoError(format!("Failed to create {}: {}", dir.display(), e)))?;
+        Ok(Self {
+            dir: Some(dir),
+            ..Self::default()
+        })
+    }
+
+    /// Сколько раз вызывался компилятор (с момента создания кэша).
+    pub fn compile_count(&self) -> usize {
+        self.compile_count
+    }
+
+    /// Артефакт по ключу: из памяти, из каталога или скомпилированный `compile`.
+    pub fn get_or_compile<F>(
+        &mut self,
+        backend: Backend,
+        key: u64,
+        compile: F,
+    ) -> ASGResult<Vec<u8>>
+    where
+        F: FnOnce() -> ASGResult<Vec<u8>>,
+    {
+        if let Some(artifact) = self.entries.get(&(backend, key)) {
+            return Ok(artifact.clone());
+        }
+
+        let path = self.dir.as_ref().map(|dir| {
+            dir.join(backend.dir_name())
+                .join(format!("{:016x}.bin", key))
+        });
+        if let Some(artifact) = path.as_ref().and_then(|p| fs::read(p).ok()) {
+            self.entries.insert((backend, key), artifact.clone());
+            return Ok(artifact);
+        }
+
+        let artifact = compile()?;
+        self.compile_count += 1;
+        if let Some(path) = path {
+            let write = |path: &PathBuf| -> std::io::Result<()> {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(path, &artifact)
+            };
+            write(&path).map_err(|e| {
+                ASGError::IoError(format!("Failed to write {}: {}", path.display(), e))
+            })?;
+        }
+        self.entries.insert((backend, key), artifact.clone());
+        Ok(artifact)
+    }
+}
+
+/// Ключ кэша функции — структурный хеш её подграфа (имя, параметры, тело).
+pub fn function_key(asg: &ASG, id: NodeID) -> u64 {
+    asg.subgraph_hash(id)
+}
+
+/// Скомпилировать все функции ASG, переиспользуя артефакты из кэша.
+///
+/// `compile` получает узел `Function` и возвращает его артефакт для `backend`;
+/// результат — пары (имя функции, артефакт) в порядке узлов.
+pub fn compile_functions<F>(
+    asg: &ASG,
+    backend: Backend,
+    cache: &mut CompileCache,
+    mut compile: F,
+) -> ASGResult<Vec<(String, Vec<u8>)>>
+where
+    F: FnMut(&ASG, &Node) -> ASGResult<Vec<u8>>,
+{
+    asg.nodes
+        .iter()
+        .filter(|node| node.node_type == NodeType::Function)
+        .map(|node| {
+            let name = node.get_name().unwrap_or_default();
+            let artifact =
+                cache.get_or_compile(backend, function_key(asg, node.id), || compile(asg, node))?;
+            Ok((name, artifact))
+        })
+        .collect()
+}
+
+/// LLVM IR программы для путей `native` и `llvm`; из кэша, если граф не менялся.
+///
+/// Ключ тот же, что у [`compile_cached`]: структурный хеш графа с учётом порядка форм.
+pub fn compile_llvm_cached(asg: &ASG, cache: &mut CompileCache) -> ASGResult<String> {
+    let ir = cache.get_or_compile(Backend::Llvm, asg.ordered_structural_hash(), || {
+        llvm_ir(asg).map(String::into_bytes)
+    })?;
+    String::from_utf8(ir)
+        .map_err(|e| ASGError::CompilationError(format!("Cached LLVM IR is not UTF-8: {}", e)))
+}
+
+#[cfg(feature = "llvm_backend")]
+fn llvm_ir(asg: &ASG) -> ASGResult<String> {
+    let context = inkwell::context::Context::create();
+    crate::llvm_backend::LLVMBackend::new(&context, "main").compile(asg)
+}
+
+#[cfg(not(feature = "llvm_backend"))]
+fn llvm_ir(asg: &ASG) -> ASGResult<String> {
+    crate::llvm_backend::LLVMBackend::compile(asg)
+}
+
+/// Скомпилировать ASG в WASM-модуль для заданной платформы.
+///
+/// Неподдерживаемые узлы отклоняются до компиляции одним списком.
+#[cfg(feature = "wasm_backend")]
+pub fn compile(asg: &ASG, target: Target) -> ASGResult<Vec<u8>> {
+    check_supported(asg, target).map_err(|nodes| {
+        let list: Vec<_> = nodes.iter().map(ToString::to_string).collect();
+        ASGError::CompilationError(format!(
+            "{:?} backend does not support: {}",
+            target,
+            list.join(", ")
+        ))
+    })?;
+    crate::wasm_backend::WasmBackend::with_target(target).compile(asg)
+}
+
+/// Как [`compile`], но модуль берётся из кэша, если граф не менялся.
+///
+/// Ключ — структурный хеш графа с учётом порядка форм, так что перенумерация
+/// узлов и сдвиг span не приводят к пересборке.
+#[cfg(feature = "wasm_backend")]
+pub fn compile_cached(asg: &ASG, target: Target, cache: &mut CompileCache) -> ASGResult<Vec<u8>> {
+    cache.get_or_compile(Backend::Wasm(target), asg.ordered_structural_hash(), || {
+        compile(asg, target)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    /// Скомпилировать функции программы, артефакт — отладочное представление узла.
+    fn build(source: &str, cache: &mut CompileCache) -> Vec<(String, Vec<u8>)> {
+        let (asg, _) = parse(source).unwrap();
+        compile_functions(&asg, Backend::Llvm, cache, |asg, node| {
+            Ok(format!("{:?} {}", node.node_type, asg.subgraph_hash(node.id)).into_bytes())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_only_changed_function_is_recompiled() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = CompileCache::open(dir.path()).unwrap();
+
+        let first = build("(fn f (x) (* x 2))\n(fn g (y) (+ y 1))", &mut cache);
+        assert_eq!(cache.compile_count(), 2);
+
+        // g изменена; f сдвинута новой формой (другие ID и span) — но не изменена
+        let second = build(
+            "(let z 0)\n(fn f (x)   (* x 2))\n(fn g (y) (+ y 10))",
+            &mut cache,
+        );
+        assert_eq!(cache.compile_count(), 3);
+        assert_eq!(second[0], first[0]);
+        assert_ne!(second[1], first[1]);
+
+        // Новая сборка с тем же каталогом ничего не компилирует
+        let mut reopened = CompileCache::open(dir.path()).unwrap();
+        let third = build("(fn f (x) (* x 2))\n(fn g (y) (+ y 10))", &mut reopened);
+        assert_eq!(reopened.compile_count(), 0);
+        assert_eq!(third, second);
+    }
+
+    #[test]
+    fn test_backends_do_not_share_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = CompileCache::open(dir.path()).unwrap();
+        let (asg, _) = parse("(fn f (x) (* x 2))").unwrap();
+
+        let mut compile_for = |backend: Backend| {
+            compile_functions(&asg, backend, &mut cache, |_, _| {
+                Ok(format!("{:?}", backend).into_bytes())
+            })
+            .unwrap()
+        };
+        assert_eq!(compile_for(Backend::Llvm)[0].1, b"Llvm");
+        assert_eq!(compile_for(Backend::Wasm(Target::Wasi))[0].1, b"Wasm(Wasi)");
+        assert_eq!(compile_for(Backend::Llvm)[0].1, b"Llvm");
+        assert_eq!(cache.compile_count(), 2);
+        assert!(dir.path().join("llvm").is_dir());
+        assert!(dir.path().join("wasi").is_dir());
+    }
+
+    #[cfg(feature = "wasm_backend")]
+    #[test]
+    fn test_compile_cached_reuses_module() {
+        let mut cache = CompileCache::new();
+        let (asg, _) = parse("(print (+ 1 2))").unwrap();
+        let first = compile_cached(&asg, Target::Wasm, &mut cache).unwrap();
+        assert_eq!(first, compile(&asg, Target::Wasm).unwrap());
+
+        // Тот же граф с другими ID и span — из кэша
+        let (shifted, _) = parse("(print   (+ 1 2))").unwrap();
+        assert_eq!(
+            compile_cached(&shifted, Target::Wasm, &mut cache).unwrap(),
+            first
+        );
+        assert_eq!(cache.compile_count(), 1);
+
+        // Другая платформа — другой артефакт
+        let wasi = compile_cached(&asg, Target::Wasi, &mut cache).unwrap();
+        assert_ne!(wasi, first);
+        assert_eq!(cache.compile_count(), 2);
+    }
+
+    #[test]
+    fn test_llvm_path_reuses_cached_ir() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cache = CompileCache::open(dir.path()).unwrap();
+        let (asg, _) = parse("(fn f (x) (* x 2))\n(f 21)").unwrap();
+        let ir = compile_llvm_cached(&asg, &mut cache).unwrap();
+        assert_eq!(cache.compile_count(), 1);
+
+        // Тот же граф с другими span — из кэша в памяти
+        let (shifted, _) = parse("(fn f (x)   (* x 2))\n(f   21)").unwrap();
+        assert_eq!(compile_llvm_cached(&shifted, &mut cache).unwrap(), ir);
+        assert_eq!(cache.compile_count(), 1);
+
+        // Следующая сборка берёт IR из каталога, изменённая программа компилируется
+        let mut reopened = CompileCache::open(dir.path()).unwrap();
+        assert_eq!(compile_llvm_cached(&asg, &mut reopened).unwrap(), ir);
+        assert_eq!(reopened.compile_count(), 0);
+        let (changed, _) = parse("(fn f (x) (* x 3))\n(f 21)").unwrap();
+        compile_llvm_cached(&changed, &mut reopened).unwrap();
+        assert_eq!(reopened.compile_count(), 1);
+    }
+
+    #[test]
+    fn test_check_supported_lists_every_node() {
+        let (asg, _) = parse("(print (+ 1 2))").unwrap();
+        assert_eq!(check_supported(&asg, Target::Wasm), Ok(()));
+
+        let source = "(let d (dict \"a\" 1))\n(print (dict \"b\" 2))";
+        let (asg, _) = parse(source).unwrap();
+        let unsupported = check_supported(&asg, Target::Wasi).unwrap_err();
+        let dicts: Vec<_> = unsupported
+            .iter()
+            .filter(|node| node.node_type == NodeType::Dict)
+            .collect();
+        // Оба dict, каждый со своей позицией
+        assert_eq!(dicts.len(), 2);
+        let span = dicts[1].span.unwrap();
+        assert_eq!(&source[span.start..span.end], "(dict \"b\" 2)");
+        assert!(dicts[0].to_string().starts_with("Dict (node "));
+    }
+}
@@ -1,6 +1,7 @@
 //! Определения ошибок для ASG.
 
 use crate::nodecodes::EdgeType;
+use crate::parser::token::Span;
 use thiserror::Error;
 
 /// Основной тип `Result` для библиотеки.
@@ -56,7 +57,7 @@ pub enum ASGError {
     SerializationError(String),
 
     #[error("Parse error: {0}")]
-    ParseError(String),
+    ParseError(#[from] crate::parser::ParseError),
 
     #[error("Module not found: {0}")]
     ModuleNotFound(String),
@@ -64,6 +65,114 @@ pub enum ASGError {
     #[error("Module error: {0}")]
     ModuleError(String),
 
+    /// Ошибка разбора файла модуля; исходная ошибка со своим span — в `source`.
+    #[error("Parse error in {path}: {source}")]
+    ModuleParseError {
+        path: String,
+        #[source]
+        source: Box<ASGError>,
+    },
+
     #[error("Circular import detected: {0}")]
     CircularImport(String),
+
+    /// Сигнал `(break)`: раскручивает вычисление до ближайшего цикла, который его
+    /// перехватывает (значение `(break v)` хранит интерпретатор). Вне цикла — ошибка.
+    #[error("break outside of a loop")]
+    LoopBreak,
+
+    /// Сигнал `(continue)`: завершает текущую итерацию ближайшего цикла.
+    #[error("continue outside of a loop")]
+    LoopContinue,
+
+    /// Ошибка с позицией в исходном коде (см. [`ASGError::at`]).
+    #[error("{error}")]
+    Spanned { span: Span, error: Box<ASGError> },
+}
+
+impl ASGError {
+    /// Привязать ошибку к позиции в исходном коде.
+    pub fn at(self, span: Span) -> Self {
+        Self::Spanned {
+            span,
+            error: Box::new(self),
+        }
+    }
+
+    /// Позиция ошибки, если она известна.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::ParseError(e) => Some(e.span()),
+            Self::Spanned { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+/// Диагностика в стиле rustc: сообщение, `--> строка:столбец`, строка исходника
+/// и подчёркивание `^^^` под span. Без span — только сообщение.
+pub fn render_with_source(err: &ASGError, source: &str) -> String {
+    let mut out = format!("error: {}", err);
+    let Some(span) = err.span() else {
+        return out;
+    };
+
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    let line = source[..start].matches('\n').count() + 1;
+    let line_text = &source[line_start..line_end];
+
+    // Столбцы и длина подчёркивания — в символах, а не байтах
+    let column = source[line_start..start].chars().count() + 1;
+    let end = span.end.clamp(start, line_end);
+    let width = source[start..end].chars().count().max(1);
+
+    let number = line.to_string();
+    let pad = " ".repeat(number.len());
+    out.push_str(&format!("\n{}--> {}:{}", pad, line, column));
+    out.push_str(&format!("\n{} |", pad));
+    out.push_str(&format!("\n{} | {}", number, line_text));
+    out.push_str(&format!(
+        "\n{} | {}{}",
+        pad,
+        " ".repeat(column - 1),
+        "^".repeat(width)
+    ));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodecodes::NodeType;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_render_with_source_underlines_span() {
+        let source = "(let x 1)\n(+ x \"abc\")";
+        let (asg, _) = parse(source).unwrap();
+        let span = asg
+            .nodes
+            .iter()
+            .find(|n| n.node_type == NodeType::LiteralString)
+            .and_then(|n| n.span)
+            .unwrap();
+        let err = ASGError::TypeError("cannot add Int and String".to_string()).at(span);
+
+        assert_eq!(
+            render_with_source(&err, source),
+            "error: Type mismatch during execution: cannot add Int and String\n \
+             --> 2:6\n  |\n2 | (+ x \"abc\")\n  |      ^^^^^"
+        );
+
+        // Без позиции — только сообщение
+        let err = ASGError::UnknownVariable("y".to_string());
+        assert_eq!(
+            render_with_source(&err, source),
+            "error: Unknown variable: y"
+        );
+    }
 }
@@ -0,0 +1,240 @@
+//! Модуль `graph_builder`
+//!
+//! Программное построение ASG без исходного текста: [`GraphBuilder`] сам выделяет
+//! ID узлов и расставляет рёбра так же, как парсер (`parser::builder`), поэтому
+//! результат выполняется интерпретатором как обычная программа.
+//!
+//! ```rust,ignore
+//! use asg_lang::graph_builder::GraphBuilder;
+//!
+//! // (if (< 1 2) 10 20)
+//! let mut g = GraphBuilder::new();
+//! let (one, two) = (g.int(1), g.int(2));
+//! let cond = g.lt(one, two);
+//! let (then, otherwise) = (g.int(10), g.int(20));
+//! let root = g.if_(cond, then, Some(otherwise));
+//! let asg = g.finish();
+//! ```
+
+use crate::asg::{Edge, Node, NodeID, ASG};
+use crate::nodecodes::{EdgeType, NodeType};
+
+/// Построитель ASG: каждый метод добавляет узел и возвращает его ID.
+#[derive(Debug, Clone)]
+pub struct GraphBuilder {
+    asg: ASG,
+    next_id: NodeID,
+}
+
+impl Default for GraphBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphBuilder {
+    /// Пустой граф; ID выделяются с 1.
+    pub fn new() -> Self {
+        Self {
+            asg: ASG::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Добавить узел произвольного типа с готовыми рёбрами.
+    pub fn node(
+        &mut self,
+        node_type: NodeType,
+        payload: Option<Vec<u8>>,
+        edges: Vec<Edge>,
+    ) -> NodeID {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.asg
+            .add_node(Node::with_edges(id, node_type, payload, edges));
+        id
+    }
+
+    // === Литералы ===
+
+    /// Целое: `5`.
+    pub fn int(&mut self, value: i64) -> NodeID {
+        self.node(
+            NodeType::LiteralInt,
+            Some(value.to_le_bytes().to_vec()),
+            vec![],
+        )
+    }
+
+    /// Число с плавающей точкой: `2.5`.
+    pub fn float(&mut self, value: f64) -> NodeID {
+        self.node(
+            NodeType::LiteralFloat,
+            Some(value.to_le_bytes().to_vec()),
+            vec![],
+        )
+    }
+
+    /// `true` / `false`.
+    pub fn bool(&mut self, value: bool) -> NodeID {
+        self.node(NodeType::LiteralBool, Some(vec![value as u8]), vec![])
+    }
+
+    /// Строка: `"text"`.
+    pub fn string(&mut self, value: &str) -> NodeID {
+        self.node(
+            NodeType::LiteralString,
+            Some(value.as_bytes().to_vec()),
+            vec![],
+        )
+    }
+
+    /// `()`.
+    pub fn unit(&mut self) -> NodeID {
+        self.node(NodeType::LiteralUnit, None, vec![])
+    }
+
+    // === Переменные ===
+
+    /// Ссылка на переменную: `name`.
+    pub fn var(&mut self, name: &str) -> NodeID {
+        self.node(NodeType::VarRef, Some(name.as_bytes().to_vec()), vec![])
+    }
+
+    /// Объявление: `(let name value)`.
+    pub fn let_(&mut self, name: &str, value: NodeID) -> NodeID {
+        self.node(
+            NodeType::Variable,
+            Some(name.as_bytes().to_vec()),
+            vec![Edge::new(EdgeType::VarValue, value)],
+        )
+    }
+
+    // === Операции ===
+
+    /// Бинарная операция `node_type` с операндами `lhs` и `rhs`.
+    pub fn binary(&mut self, node_type: NodeType, lhs: NodeID, rhs: NodeID) -> NodeID {
+        self.node(
+            node_type,
+            None,
+            vec![
+                Edge::new(EdgeType::FirstOperand, lhs),
+                Edge::new(EdgeType::SecondOperand, rhs),
+            ],
+        )
+    }
+
+    /// `(+ a b)`.
+    pub fn add(&mut self, a: NodeID, b: NodeID) -> NodeID {
+        self.binary(NodeType::BinaryOperation, a, b)
+    }
+
+    /// `(- a b)`.
+    pub fn sub(&mut self, a: NodeID, b: NodeID) -> NodeID {
+        self.binary(NodeType::Sub, a, b)
+    }
+
+    /// `(* a b)`.
+    pub fn mul(&mut self, a: NodeID, b: NodeID) -> NodeID {
+        self.binary(NodeType::Mul, a, b)
+    }
+
+    /// `(/ a b)`.
+    pub fn div(&mut self, a: NodeID, b: NodeID) -> NodeID {
+        self.binary(NodeType::Div, a, b)
+    }
+
+    /// `(== a b)`.
+    pub fn eq(&mut self, a: NodeID, b: NodeID) -> NodeID {
+        self.binary(NodeType::Eq, a, b)
+    }
+
+    /// `(< a b)`.
+    pub fn lt(&mut self, a: NodeID, b: NodeID) -> NodeID {
+        self.binary(NodeType::Lt, a, b)
+    }
+
+    /// `(> a b)`.
+    pub fn gt(&mut self, a: NodeID, b: NodeID) -> NodeID {
+        self.binary(NodeType::Gt, a, b)
+    }
+
+    // === Управление ===
+
+    /// `(if cond then else)`; без `otherwise` — `(if cond then)`.
+    pub fn if_(&mut self, cond: NodeID, then: NodeID, otherwise: Option<NodeID>) -> NodeID {
+        let mut edges = vec![
+            Edge::new(EdgeType::Condition, cond),
+            Edge::new(EdgeType::ThenBranch, then),
+        ];
+        edges.extend(otherwise.map(|id| Edge::new(EdgeType::ElseBranch, id)));
+        self.node(NodeType::If, None, edges)
+    }
+
+    /// `(do stmt...)`: значение — последнее выражение.
+    pub fn block(&mut self, statements: &[NodeID]) -> NodeID {
+        let edges = statements
+            .iter()
+            .map(|&id| Edge::new(EdgeType::BlockStatement, id))
+            .collect();
+        self.node(NodeType::Block, None, edges)
+    }
+
+    /// `(print value)`.
+    pub fn print(&mut self, value: NodeID) -> NodeID {
+        self.node(
+            NodeType::Print,
+            None,
+            vec![Edge::new(EdgeType::ApplicationArgument, value)],
+        )
+    }
+
+    /// Построенный граф (для просмотра до завершения).
+    pub fn asg(&self) -> &ASG {
+        &self.asg
+    }
+
+    /// Завершить построение и забрать граф.
+    pub fn finish(self) -> ASG {
+        self.asg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, Value};
+
+    #[test]
+    fn test_build_if_fluently() {
+        // (if (< 1 2) 10 20)
+        let mut g = GraphBuilder::new();
+        let (one, two) = (g.int(1), g.int(2));
+        let cond = g.lt(one, two);
+        let (then, otherwise) = (g.int(10), g.int(20));
+        let root = g.if_(cond, then, Some(otherwise));
+        let asg = g.finish();
+
+        assert_eq!(asg.node_count(), 6);
+        let result = Interpreter::new().execute(&asg, root).unwrap();
+        assert_eq!(result, Value::Int(10));
+    }
+
+    #[test]
+    fn test_build_block_with_variables() {
+        // (do (let x 4) (print "x") (* (+ x 1) x))
+        let mut g = GraphBuilder::new();
+        let four = g.int(4);
+        let decl = g.let_("x", four);
+        let label = g.string("x");
+        let print = g.print(label);
+        let (x1, one, x2) = (g.var("x"), g.int(1), g.var("x"));
+        let sum = g.add(x1, one);
+        let product = g.mul(sum, x2);
+        let root = g.block(&[decl, print, product]);
+
+        let (result, printed) = Interpreter::new().run_capturing(g.asg(), root).unwrap();
+        assert_eq!(result, Value::Int(20));
+        assert_eq!(printed, "x\n");
+    }
+}
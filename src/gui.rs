@@ -1,6 +1,22 @@
 //! Native GUI module using egui/eframe.
 //!
 //! Provides a simple GUI runtime for ASG applications.
+//! The widget tree, [`value_to_widget`] and the headless [`render_to_string`]
+//! are always available; windows (`run_gui`, `ASGGuiApp`) need the `gui` feature.
+//!
+//! Layout forms: `gui-label`, `gui-button`, `gui-bind` (text field bound to a
+//! variable), `vbox`/`hbox` with an optional `(gui-spacing n)` child; see
+//! [`ASGGuiApp::value_to_widget`] for the widget tree they produce.
+//!
+//! Button callbacks: a `gui-button` whose second argument is a function gets an
+//! `on-click` field in its widget dict; `run_gui` invokes it through an
+//! [`EventHandler`] (normally `Interpreter::call`). Edits of a `gui-bind` field
+//! are delivered the same way and stored in the bound variable. Callbacks run
+//! synchronously on the GUI thread inside `update`, and the interpreter stays
+//! mutably borrowed for the whole `run_gui` call, so nothing crosses threads and
+//! neither `Value` nor `Interpreter` needs to be `Send`. State shared between
+//! callbacks lives in global variables updated with `set`; use named `fn`
+//! callbacks for it, since lambdas capture globals by value.
 
 #[cfg(feature = "gui")]
 use eframe::egui;
@@ -8,12 +24,10 @@ use eframe::egui;
 #[cfg(feature = "gui")]
 use std::collections::HashMap;
 
-#[cfg(feature = "gui")]
 use crate::interpreter::Value;
 
 /// Represents a GUI widget description from ASG
-#[cfg(feature = "gui")]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Widget {
     Window {
         title: String,
@@ -23,20 +37,26 @@ pub enum Widget {
     },
     VBox {
         children: Vec<Widget>,
+        /// Отступ между детьми из (gui-spacing n); None — по умолчанию egui
+        spacing: Option<f32>,
     },
     HBox {
         children: Vec<Widget>,
+        spacing: Option<f32>,
     },
     Label {
         text: String,
     },
     Button {
         text: String,
-        // Callback будет храниться отдельно
+        /// Функция из поля `on-click`, вызывается при нажатии
+        on_click: Option<Value>,
     },
     TextField {
         id: String,
         value: String,
+        /// Имя переменной, в которую записывается введённый текст (gui-bind)
+        bind: Option<String>,
     },
     Canvas {
         width: f32,
@@ -44,129 +64,292 @@ pub enum Widget {
     },
 }
 
+/// Convert ASG Value to Widget tree.
+///
+/// Works on the dicts produced by the GUI nodes and does not need a display,
+/// so it is available without the `gui` feature.
+pub fn value_to_widget(val: &Value) -> Option<Widget> {
+    match val {
+        Value::Dict(d) => {
+            let widget_type = match d.get("type") {
+                Some(Value::String(s)) => s.as_str(),
+                _ => return None,
+            };
+
+            let children = match d.get("children") {
+                Some(Value::Array(arr)) => arr.iter().filter_map(value_to_widget).collect(),
+                _ => Vec::new(),
+            };
+
+            match widget_type {
+                "GuiWindow" => {
+                    let title = match children.first() {
+                        Some(Widget::Label { text }) => text.clone(),
+                        _ => "Window".to_string(),
+                    };
+                    Some(Widget::Window {
+                        title,
+                        width: 400.0,
+                        height: 300.0,
+                        children: children.into_iter().skip(3).collect(),
+                    })
+                }
+                "GuiVBox" | "GuiHBox" => {
+                    let spacing = match d.get("children") {
+                        Some(Value::Array(arr)) => spacing_of(arr),
+                        _ => None,
+                    };
+                    if widget_type == "GuiVBox" {
+                        Some(Widget::VBox { children, spacing })
+                    } else {
+                        Some(Widget::HBox { children, spacing })
+                    }
+                }
+                "GuiLabel" => {
+                    let text = match children.first() {
+                        Some(Widget::Label { text }) => text.clone(),
+                        _ => "".to_string(),
+                    };
+                    Some(Widget::Label { text })
+                }
+                "GuiButton" => {
+                    let text = match children.first() {
+                        Some(Widget::Label { text }) => text.clone(),
+                        _ => "Button".to_string(),
+                    };
+                    let on_click = d.get("on-click").cloned();
+                    Some(Widget::Button { text, on_click })
+                }
+                "GuiTextField" => Some(Widget::TextField {
+                    id: "input".to_string(),
+                    value: String::new(),
+                    bind: None,
+                }),
+                "GuiBind" => {
+                    let bind = match d.get("bind") {
+                        Some(Value::String(s)) => s.clone(),
+                        _ => return None,
+                    };
+                    let value = match d.get("value") {
+                        Some(Value::String(s)) => s.clone(),
+                        Some(Value::Unit) | None => String::new(),
+                        Some(other) => other.format_display(),
+                    };
+                    Some(Widget::TextField {
+                        id: bind.clone(),
+                        value,
+                        bind: Some(bind),
+                    })
+                }
+                _ => None,
+            }
+        }
+        Value::String(s) => Some(Widget::Label { text: s.clone() }),
+        Value::Int(n) => Some(Widget::Label {
+            text: n.to_string(),
+        }),
+        Value::Float(f) => Some(Widget::Label {
+            text: f.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Spacing from a `(gui-spacing n)` child of a vbox/hbox dict.
+fn spacing_of(children: &[Value]) -> Option<f32> {
+    children.iter().find_map(|child| match child {
+        Value::Dict(d) if d.get("type") == Some(&Value::String("GuiSpacing".into())) => {
+            match d.get("spacing") {
+                Some(Value::Int(n)) => Some(*n as f32),
+                Some(Value::Float(f)) => Some(*f as f32),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Render a widget value as an indented text tree without opening a window.
+///
+/// One widget per line, children indented by two spaces; values that are not
+/// widgets render as an empty string. Intended for testing UI construction in CI.
+pub fn render_to_string(val: &Value) -> String {
+    let mut out = String::new();
+    if let Some(widget) = value_to_widget(val) {
+        render_lines(&widget, 0, &mut out);
+    }
+    out
+}
+
+fn render_lines(widget: &Widget, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let spacing_suffix = |spacing: &Option<f32>| match spacing {
+        Some(s) => format!(" spacing={}", s),
+        None => String::new(),
+    };
+    let (line, children): (String, &[Widget]) = match widget {
+        Widget::Window {
+            title,
+            width,
+            height,
+            children,
+        } => (format!("Window {:?} {}x{}", title, width, height), children),
+        Widget::VBox { children, spacing } => {
+            (format!("VBox{}", spacing_suffix(spacing)), children)
+        }
+        Widget::HBox { children, spacing } => {
+            (format!("HBox{}", spacing_suffix(spacing)), children)
+        }
+        Widget::Label { text } => (format!("Label {:?}", text), &[]),
+        Widget::Button { text, on_click } => {
+            let callback = if on_click.is_some() {
+                " [on-click]"
+            } else {
+                ""
+            };
+            (format!("Button {:?}{}", text, callback), &[])
+        }
+        Widget::TextField { id, value, bind } => {
+            let bind = match bind {
+                Some(var) => format!(" bind={}", var),
+                None => String::new(),
+            };
+            (format!("TextField {:?} value={:?}{}", id, value, bind), &[])
+        }
+        Widget::Canvas { width, height } => (format!("Canvas {}x{}", width, height), &[]),
+    };
+    out.push_str(&indent);
+    out.push_str(&line);
+    out.push('\n');
+    for child in children {
+        render_lines(child, depth + 1, out);
+    }
+}
+
+/// A GUI event delivered to the host interpreter.
+#[cfg(feature = "gui")]
+#[derive(Debug)]
+pub enum GuiEvent<'e> {
+    /// Button pressed: call the `on-click` function
+    Click(&'e Value),
+    /// Text of a bound field changed: store it in variable `bind`
+    Input { bind: &'e str, text: &'e str },
+}
+
+/// Handles GUI events (e.g. via `Interpreter::call`) and returns the callback result.
+#[cfg(feature = "gui")]
+pub type EventHandler<'a> = Box<dyn FnMut(GuiEvent) -> Result<Value, String> + 'a>;
+
 /// GUI Application state
 #[cfg(feature = "gui")]
-pub struct ASGGuiApp {
+pub struct ASGGuiApp<'a> {
     pub title: String,
     pub widgets: Vec<Widget>,
     pub text_fields: HashMap<String, String>,
     pub result: Option<String>,
+    event_handler: Option<EventHandler<'a>>,
 }
 
 #[cfg(feature = "gui")]
-impl Default for ASGGuiApp {
+impl Default for ASGGuiApp<'_> {
     fn default() -> Self {
         Self {
             title: "ASG App".to_string(),
             widgets: Vec::new(),
             text_fields: HashMap::new(),
             result: None,
+            event_handler: None,
         }
     }
 }
 
 #[cfg(feature = "gui")]
-impl ASGGuiApp {
+impl<'a> ASGGuiApp<'a> {
     pub fn new(title: &str, widgets: Vec<Widget>) -> Self {
         Self {
             title: title.to_string(),
             widgets,
             text_fields: HashMap::new(),
             result: None,
+            event_handler: None,
         }
     }
 
-    /// Convert ASG Value to Widget tree
-    pub fn value_to_widget(val: &Value) -> Option<Widget> {
-        match val {
-            Value::Dict(d) => {
-                let widget_type = match d.get("type") {
-                    Some(Value::String(s)) => s.as_str(),
-                    _ => return None,
-                };
-
-                let children = match d.get("children") {
-                    Some(Value::Array(arr)) => arr
-                        .iter()
-                        .filter_map(|v| Self::value_to_widget(v))
-                        .collect(),
-                    _ => Vec::new(),
-                };
-
-                match widget_type {
-                    "GuiWindow" => {
-                        let title = match children.get(0) {
-                            Some(Widget::Label { text }) => text.clone(),
-                            _ => "Window".to_string(),
-                        };
-                        Some(Widget::Window {
-                            title,
-                            width: 400.0,
-                            height: 300.0,
-                            children: children.into_iter().skip(3).collect(),
-                        })
-                    }
-                    "GuiVBox" => Some(Widget::VBox { children }),
-                    "GuiHBox" => Some(Widget::HBox { children }),
-                    "GuiLabel" => {
-                        let text = match children.get(0) {
-                            Some(Widget::Label { text }) => text.clone(),
-                            _ => "".to_string(),
-                        };
-                        Some(Widget::Label { text })
-                    }
-                    "GuiButton" => {
-                        let text = match children.get(0) {
-                            Some(Widget::Label { text }) => text.clone(),
-                            _ => "Button".to_string(),
-                        };
-                        Some(Widget::Button { text })
-                    }
-                    "GuiTextField" => Some(Widget::TextField {
-                        id: "input".to_string(),
-                        value: String::new(),
-                    }),
-                    _ => None,
-                }
+    /// Set the handler used to run `on-click` callbacks and bound-field updates.
+    pub fn with_event_handler(mut self, handler: EventHandler<'a>) -> Self {
+        self.event_handler = Some(handler);
+        self
+    }
+
+    /// Fire a click on a widget: runs its `on-click` callback (if any) and stores
+    /// the outcome in `result`. Non-button widgets are ignored.
+    pub fn click(&mut self, widget: &Widget) {
+        let Widget::Button { text, on_click } = widget else {
+            return;
+        };
+        self.result = match (on_click, self.event_handler.as_mut()) {
+            (Some(callback), Some(handler)) => match handler(GuiEvent::Click(callback)) {
+                Ok(Value::Unit) => None,
+                Ok(value) => Some(value.format_display()),
+                Err(e) => Some(format!("Error in '{}' callback: {}", text, e)),
+            },
+            _ => Some(format!("Button '{}' clicked", text)),
+        };
+    }
+
+    /// Set the text of a field and, if it is bound, store it in the bound variable.
+    pub fn input(&mut self, widget: &Widget, text: &str) {
+        let Widget::TextField { id, bind, .. } = widget else {
+            return;
+        };
+        self.text_fields.insert(id.clone(), text.to_string());
+        if let (Some(bind), Some(handler)) = (bind, self.event_handler.as_mut()) {
+            if let Err(e) = handler(GuiEvent::Input { bind, text }) {
+                self.result = Some(format!("Error updating '{}': {}", bind, e));
             }
-            Value::String(s) => Some(Widget::Label { text: s.clone() }),
-            Value::Int(n) => Some(Widget::Label {
-                text: n.to_string(),
-            }),
-            Value::Float(f) => Some(Widget::Label {
-                text: f.to_string(),
-            }),
-            _ => None,
         }
     }
 
+    /// Convert ASG Value to Widget tree (see [`value_to_widget`])
+    pub fn value_to_widget(val: &Value) -> Option<Widget> {
+        value_to_widget(val)
+    }
+
     fn render_widget(&mut self, ui: &mut egui::Ui, widget: &Widget) {
         match widget {
             Widget::Label { text } => {
                 ui.label(text);
             }
-            Widget::Button { text } => {
+            Widget::Button { text, .. } => {
                 if ui.button(text).clicked() {
-                    // Button clicked
-                    self.result = Some(format!("Button '{}' clicked", text));
+                    self.click(widget);
                 }
             }
-            Widget::TextField { id, value: _ } => {
+            Widget::TextField { id, value, .. } => {
                 let text = self
                     .text_fields
                     .entry(id.clone())
-                    .or_insert_with(String::new);
-                ui.text_edit_singleline(text);
+                    .or_insert_with(|| value.clone());
+                let mut edited = text.clone();
+                if ui.text_edit_singleline(&mut edited).changed() {
+                    self.input(widget, &edited);
+                }
             }
-            Widget::VBox { children } => {
+            Widget::VBox { children, spacing } => {
                 ui.vertical(|ui| {
+                    if let Some(spacing) = spacing {
+                        ui.spacing_mut().item_spacing.y = *spacing;
+                    }
                     for child in children {
                         self.render_widget(ui, child);
                     }
                 });
             }
-            Widget::HBox { children } => {
+            Widget::HBox { children, spacing } => {
                 ui.horizontal(|ui| {
+                    if let Some(spacing) = spacing {
+                        ui.spacing_mut().item_spacing.x = *spacing;
+                    }
                     for child in children {
                         self.render_widget(ui, child);
                     }
@@ -187,7 +370,7 @@ impl ASGGuiApp {
 }
 
 #[cfg(feature = "gui")]
-impl eframe::App for ASGGuiApp {
+impl eframe::App for ASGGuiApp<'_> {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading(&self.title);
@@ -206,9 +389,14 @@ impl eframe::App for ASGGuiApp {
     }
 }
 
-/// Run a GUI application
+/// Run a GUI application; `on_event` handles button clicks and bound-field edits
+/// on the GUI thread.
 #[cfg(feature = "gui")]
-pub fn run_gui(title: &str, widgets: Vec<Widget>) -> Result<(), String> {
+pub fn run_gui(
+    title: &str,
+    widgets: Vec<Widget>,
+    on_event: EventHandler<'_>,
+) -> Result<(), String> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([400.0, 300.0])
@@ -219,7 +407,11 @@ pub fn run_gui(title: &str, widgets: Vec<Widget>) -> Result<(), String> {
     eframe::run_native(
         title,
         options,
-        Box::new(|_cc| Ok(Box::new(ASGGuiApp::new(title, widgets)))),
+        Box::new(|_cc| {
+            Ok(Box::new(
+                ASGGuiApp::new(title, widgets).with_event_handler(on_event),
+            ))
+        }),
     )
     .map_err(|e| e.to_string())
 }
@@ -397,3 +589,125 @@ impl CalculatorApp {
         Ok((num, end))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_render_to_string_vbox_with_buttons() {
+        let (asg, root) = parse(
+            "(vbox (gui-spacing 4) (gui-button \"Save\" (lambda () 1)) (gui-button \"Cancel\" 0))",
+        )
+        .map(|(asg, roots)| (asg, roots[0]))
+        .unwrap();
+        let value = Interpreter::new().execute(&asg, root).unwrap();
+
+        assert_eq!(
+            render_to_string(&value),
+            "VBox spacing=4\n  Button \"Save\" [on-click]\n  Button \"Cancel\"\n"
+        );
+        assert_eq!(render_to_string(&Value::Unit), "");
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn test_button_click_invokes_callback() {
+        let (asg, roots) = parse(
+            "(let clicks 0)
+             (fn inc () (set clicks (+ clicks 1)))
+             (gui-button \"+\" inc)",
+        )
+        .unwrap();
+        let mut interp = Interpreter::new();
+        let mut button_val = Value::Unit;
+        for root in roots {
+            button_val = interp.execute(&asg, root).unwrap();
+        }
+
+        let button = ASGGuiApp::value_to_widget(&button_val).unwrap();
+        assert!(matches!(
+            &button,
+            Widget::Button {
+                on_click: Some(_),
+                ..
+            }
+        ));
+
+        {
+            let handler: EventHandler = Box::new(|event| match event {
+                GuiEvent::Click(f) => interp.call(&asg, f, &[]).map_err(|e| e.to_string()),
+                GuiEvent::Input { .. } => Ok(Value::Unit),
+            });
+            let mut app = ASGGuiApp::new("test", vec![button.clone()]).with_event_handler(handler);
+            app.click(&button);
+            app.click(&button);
+            assert_eq!(app.result, None);
+        }
+
+        assert_eq!(interp.get_variables().get("clicks"), Some(&Value::Int(2)));
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn test_form_layout_widget_tree() {
+        let (asg, roots) = parse(
+            "(let name \"Ann\")
+             (fn submit () (set name \"Bob\"))
+             (vbox (gui-spacing 8)
+                   (gui-label \"Name\")
+                   (hbox (gui-bind name) (gui-button \"OK\" submit)))",
+        )
+        .unwrap();
+        let mut interp = Interpreter::new();
+        let mut form_val = Value::Unit;
+        for root in roots {
+            form_val = interp.execute(&asg, root).unwrap();
+        }
+
+        let Some(Widget::VBox { children, spacing }) = ASGGuiApp::value_to_widget(&form_val) else {
+            panic!("expected vbox");
+        };
+        assert_eq!(spacing, Some(8.0));
+        assert_eq!(children.len(), 2);
+        assert_eq!(
+            children[0],
+            Widget::Label {
+                text: "Name".to_string()
+            }
+        );
+        let Widget::HBox {
+            children: row,
+            spacing: None,
+        } = &children[1]
+        else {
+            panic!("expected hbox without spacing");
+        };
+        let field = Widget::TextField {
+            id: "name".to_string(),
+            value: "Ann".to_string(),
+            bind: Some("name".to_string()),
+        };
+        assert_eq!(row[0], field);
+        assert!(matches!(&row[1], Widget::Button { text, on_click: Some(_) } if text == "OK"));
+
+        // Ввод в привязанное поле записывается в переменную
+        {
+            let handler: EventHandler = Box::new(|event| match event {
+                GuiEvent::Input { bind, text } => {
+                    interp.set_variable(bind, Value::String(text.to_string()));
+                    Ok(Value::Unit)
+                }
+                GuiEvent::Click(f) => interp.call(&asg, f, &[]).map_err(|e| e.to_string()),
+            });
+            let mut app = ASGGuiApp::new("form", Vec::new()).with_event_handler(handler);
+            app.input(&field, "Zed");
+        }
+        assert_eq!(
+            interp.get_variables().get("name"),
+            Some(&Value::String("Zed".to_string()))
+        );
+    }
+}
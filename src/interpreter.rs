@@ -2,13 +2,28 @@
 //!
 //! Поддерживает выполнение программ, представленных в виде ASG.
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "bigint")]
+use num_traits::{ToPrimitive, Zero};
+use rand::rngs::StdRng;
+use rand::seq::{IndexedRandom, SliceRandom};
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
 
 use crate::asg::{Node, NodeID, ASG};
 use crate::error::{ASGError, ASGResult};
-use crate::nodecodes::{EdgeType, NodeType};
+use crate::modules::{ModuleResolver, ResolveStrategy, PACKAGE_PREFIX};
+use crate::nodecodes::{extension_code, EdgeType, NodeType};
 use crate::ops::tensor_ops;
 use crate::parser::parse;
 use crate::runtime::diff_tensor::DifferentiableTensor;
@@ -18,12 +33,20 @@ use crate::runtime::diff_tensor::DifferentiableTensor;
 pub enum Value {
     /// Целое число
     Int(i64),
+    /// Целое произвольной точности: литералы вне i64 и результат [`IntMode::Promote`]
+    #[cfg(feature = "bigint")]
+    BigInt(BigInt),
+    /// Точное десятичное число (feature `decimal`): литерал `1.10d`
+    #[cfg(feature = "decimal")]
+    Decimal(Decimal),
     /// Число с плавающей точкой
     Float(f64),
     /// Булево значение
     Bool(bool),
     /// Строка
     String(String),
+    /// Символ (скаляр Unicode): (char-at s i)
+    Char(char),
     /// Unit (отсутствие значения)
     Unit,
     /// Тензор (для ML операций)
@@ -45,8 +68,110 @@ pub enum Value {
     Dict(HashMap<String, Value>),
     /// Скомпонованные функции (compose f g h) = (lambda (x) (h (g (f x))))
     ComposedFunction(Vec<Value>),
+    /// Встроенная операция как функция: (reduce add 0 xs), см. [`BUILTIN_FUNCTIONS`]
+    Builtin(&'static str),
     /// Ленивая последовательность
     LazySeq(Box<LazySeqKind>),
+    /// Последовательность байт (результат base64-decode, hex-decode)
+    Bytes(Vec<u8>),
+    /// Значение варианта из defenum: (Circle 5) -> Variant { enum_name: "Shape", tag: "Circle", .. }
+    Variant {
+        enum_name: String,
+        tag: String,
+        fields: Vec<Value>,
+    },
+    /// Генератор: вызов функции с yield в теле, продвигается через (next gen)
+    Generator(GeneratorRef),
+    /// Множество: (set 1 2 3); элементы упорядочены, см. [`SetKey`]
+    Set(BTreeSet<SetKey>),
+    /// Необязательное значение: (some v) или none
+    Option(Option<Box<Value>>),
+    /// Общая ячейка переменной, которую замыкание меняет через set. Хранится только
+    /// в областях видимости: чтение переменной возвращает содержимое ячейки.
+    Cell(CellRef),
+}
+
+/// Общая ссылка на состояние генератора: копии значения продвигают один генератор.
+#[derive(Debug, Clone)]
+pub struct GeneratorRef(Rc<RefCell<GeneratorState>>);
+
+impl PartialEq for GeneratorRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Элемент множества. Допускаются только значения с точным равенством и
+/// порядком: у Float нет точного равенства, функции и тензоры не сравниваются.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SetKey {
+    Bool(bool),
+    Int(i64),
+    String(String),
+}
+
+impl SetKey {
+    /// Ключ для значения; прочие типы — TypeError.
+    pub fn from_value(value: &Value) -> ASGResult<Self> {
+        match value {
+            Value::Bool(b) => Ok(SetKey::Bool(*b)),
+            Value::Int(n) => Ok(SetKey::Int(*n)),
+            Value::String(s) => Ok(SetKey::String(s.clone())),
+            other => Err(ASGError::TypeError(format!(
+                "Set elements must be Int, String or Bool, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    /// Значение элемента.
+    pub fn to_value(&self) -> Value {
+        match self {
+            SetKey::Bool(b) => Value::Bool(*b),
+            SetKey::Int(n) => Value::Int(*n),
+            SetKey::String(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+/// Ячейка захваченной переменной: создатель замыкания и само замыкание видят
+/// одни и те же изменения.
+#[derive(Clone)]
+pub struct CellRef(Rc<RefCell<Value>>);
+
+// Ячейка может содержать замыкание, захватившее её саму: содержимое не печатаем
+impl std::fmt::Debug for CellRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CellRef({:p})", Rc::as_ptr(&self.0))
+    }
+}
+
+impl PartialEq for CellRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Явная машина состояний генератора: локальные переменные вызова и стек
+/// продолжений. Пустой стек — генератор завершён.
+#[derive(Debug, Default)]
+pub struct GeneratorState {
+    locals: HashMap<String, Value>,
+    functions: HashMap<String, FunctionEntry>,
+    stack: Vec<GeneratorStep>,
+    /// Узлы, в поддереве которых есть yield: только они выполняются по шагам
+    resumable: Rc<HashSet<NodeID>>,
+}
+
+/// Продолжение генератора.
+#[derive(Debug)]
+enum GeneratorStep {
+    /// Выполнить узел
+    Eval(NodeID),
+    /// Выполнить инструкции блока, начиная с `next`
+    Block { stmts: Vec<NodeID>, next: usize },
+    /// Проверить условие while и, если истинно, выполнить тело
+    While { cond: NodeID, body: NodeID },
 }
 
 /// Виды ленивых последовательностей
@@ -75,6 +200,70 @@ pub enum LazySeqKind {
     },
 }
 
+impl LazySeqKind {
+    /// Конечна ли последовательность: её можно пройти до конца без `take-lazy`.
+    pub fn is_bounded(&self) -> bool {
+        match self {
+            LazySeqKind::Range { .. } => true,
+            LazySeqKind::Cycle { arr, .. } => arr.is_empty(),
+            LazySeqKind::Map { source, .. } | LazySeqKind::Filter { source, .. } => {
+                source.is_bounded()
+            }
+            LazySeqKind::Iterate { .. } | LazySeqKind::Repeat(_) => false,
+        }
+    }
+}
+
+/// Сколько элементов collect берёт из бесконечной ленивой последовательности.
+const COLLECT_LIMIT: usize = 10_000;
+
+/// Имена типов времени выполнения ([`Value::type_name`]) для типовых паттернов match: `(Int n)`.
+const RUNTIME_TYPE_NAMES: &[&str] = &[
+    "Int",
+    "BigInt",
+    "Decimal",
+    "Float",
+    "Bool",
+    "String",
+    "Char",
+    "Option",
+    "Unit",
+    "Tensor",
+    "Function",
+    "Record",
+    "Array",
+    "Error",
+    "Dict",
+    "ComposedFunction",
+    "LazySeq",
+    "Generator",
+    "Set",
+    "Bytes",
+    "Variant",
+];
+
+/// Встроенные операции, доступные по имени как значения-функции: имя, параметры и
+/// форма, которую вычисляет вызов. Имя действует, только если его не перекрывает
+/// переменная, функция или вариант с тем же именем.
+pub const BUILTIN_FUNCTIONS: &[(&str, &[&str], &str)] = &[
+    ("add", &["a", "b"], "(+ a b)"),
+    ("sub", &["a", "b"], "(- a b)"),
+    ("mul", &["a", "b"], "(* a b)"),
+    ("div", &["a", "b"], "(/ a b)"),
+    ("mod", &["a", "b"], "(% a b)"),
+    ("neg", &["a"], "(- a)"),
+    ("eq", &["a", "b"], "(== a b)"),
+    ("ne", &["a", "b"], "(!= a b)"),
+    ("lt", &["a", "b"], "(< a b)"),
+    ("le", &["a", "b"], "(<= a b)"),
+    ("gt", &["a", "b"], "(> a b)"),
+    ("ge", &["a", "b"], "(>= a b)"),
+    ("not", &["a"], "(not a)"),
+    ("min", &["a", "b"], "(min a b)"),
+    ("max", &["a", "b"], "(max a b)"),
+    ("str-concat", &["a", "b"], "(concat a b)"),
+];
+
 impl Value {
     /// Получить целое число из значения.
     pub fn as_int(&self) -> Option<i64> {
@@ -100,3514 +289,10306 @@ impl Value {
         }
     }
 
+    /// Можно ли вызвать значение как функцию: (f x), map, reduce, compose.
+    pub fn is_callable(&self) -> bool {
+        matches!(
+            self,
+            Value::Function { .. } | Value::ComposedFunction(_) | Value::Builtin(_)
+        )
+    }
+
+    /// Имя типа значения во время выполнения (для сообщений об ошибках).
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "Int",
+            #[cfg(feature = "bigint")]
+            Value::BigInt(_) => "BigInt",
+            #[cfg(feature = "decimal")]
+            Value::Decimal(_) => "Decimal",
+            Value::Float(_) => "Float",
+            Value::Bool(_) => "Bool",
+            Value::String(_) => "String",
+            Value::Char(_) => "Char",
+            Value::Option(_) => "Option",
+            Value::Unit => "Unit",
+            Value::Tensor(_) => "Tensor",
+            Value::Function { .. } => "Function",
+            Value::Record(_) => "Record",
+            Value::Array(_) => "Array",
+            Value::Error(_) => "Error",
+            Value::Dict(_) => "Dict",
+            Value::ComposedFunction(_) => "ComposedFunction",
+            Value::Builtin(_) => "Function",
+            Value::LazySeq(_) => "LazySeq",
+            Value::Generator(_) => "Generator",
+            Value::Set(_) => "Set",
+            Value::Cell(cell) => cell.0.borrow().type_name(),
+            Value::Bytes(_) => "Bytes",
+            Value::Variant { .. } => "Variant",
+        }
+    }
+
     /// Форматировать значение для вывода (человекочитаемый формат).
+    /// Вложенность глубже [`MAX_DISPLAY_DEPTH`] выводится как `...`.
     pub fn format_display(&self) -> String {
+        self.format_display_at(0)
+    }
+
+    fn format_display_at(&self, depth: usize) -> String {
+        if depth > MAX_DISPLAY_DEPTH {
+            return "...".to_string();
+        }
         match self {
             Value::Int(n) => n.to_string(),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(n) => n.to_string(),
+            #[cfg(feature = "decimal")]
+            Value::Decimal(d) => d.to_string(),
             Value::Float(f) => f.to_string(),
             Value::Bool(b) => b.to_string(),
             Value::String(s) => format!("\"{}\"", s),
+            Value::Char(c) => format!("'{}'", c),
+            Value::Option(Some(inner)) => format!("(some {})", inner.format_display_at(depth + 1)),
+            Value::Option(None) => "none".to_string(),
             Value::Unit => "()".to_string(),
             Value::Array(arr) => {
-                let items: Vec<String> = arr.iter().map(|v| v.format_display()).collect();
+                let items: Vec<String> =
+                    arr.iter().map(|v| v.format_display_at(depth + 1)).collect();
                 format!("[{}]", items.join(", "))
             }
             Value::Record(fields) => {
                 let items: Vec<String> = fields
                     .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.format_display()))
+                    .map(|(k, v)| format!("{}: {}", k, v.format_display_at(depth + 1)))
                     .collect();
                 format!("{{{}}}", items.join(", "))
             }
             Value::Dict(dict) => {
                 let items: Vec<String> = dict
                     .iter()
-                    .map(|(k, v)| format!("{}: {}", k, v.format_display()))
+                    .map(|(k, v)| format!("{}: {}", k, v.format_display_at(depth + 1)))
                     .collect();
                 format!("{{{}}}", items.join(", "))
             }
             Value::Function { params, .. } => format!("<fn({})>", params.join(", ")),
             Value::ComposedFunction(fns) => format!("<composed({})>", fns.len()),
+            Value::Builtin(name) => format!("<builtin {}>", name),
             Value::Tensor(t) => format!("<tensor {:?}>", t.data.borrow().shape()),
             Value::Error(msg) => format!("<error: {}>", msg),
             Value::LazySeq(_) => "<lazy-seq>".to_string(),
+            Value::Generator(_) => "<generator>".to_string(),
+            Value::Set(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|k| k.to_value().format_display_at(depth + 1))
+                    .collect();
+                format!("#{{{}}}", items.join(", "))
+            }
+            Value::Cell(cell) => cell.0.borrow().format_display_at(depth),
+            Value::Bytes(bytes) => format!("<bytes {}>", hex::encode(bytes)),
+            Value::Variant { tag, fields, .. } => {
+                if fields.is_empty() {
+                    tag.clone()
+                } else {
+                    let items: Vec<String> = fields
+                        .iter()
+                        .map(|v| v.format_display_at(depth + 1))
+                        .collect();
+                    format!("({} {})", tag, items.join(" "))
+                }
+            }
+        }
+    }
+
+    /// Многострочный вид для (pp v): элементы массивов и поля словарей/записей —
+    /// каждый на своей строке с отступом в два пробела, ключи по порядку.
+    /// Пустые контейнеры и скаляры — как в [`Value::format_display`].
+    pub fn format_pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, depth: usize) {
+        let entries: Vec<(Option<&str>, &Value)> = match self {
+            Value::Array(arr) if !arr.is_empty() => arr.iter().map(|v| (None, v)).collect(),
+            Value::Dict(fields) | Value::Record(fields) if !fields.is_empty() => {
+                let mut fields: Vec<_> =
+                    fields.iter().map(|(k, v)| (Some(k.as_str()), v)).collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                fields
+            }
+            _ => {
+                out.push_str(&self.format_display_at(depth));
+                return;
+            }
+        };
+        if depth > MAX_DISPLAY_DEPTH {
+            out.push_str("...");
+            return;
+        }
+        let (open, close) = if matches!(self, Value::Array(_)) {
+            ('[', ']')
+        } else {
+            ('{', '}')
+        };
+        let indent = "  ".repeat(depth + 1);
+        out.push(open);
+        for (i, (key, value)) in entries.into_iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            out.push_str(&indent);
+            if let Some(key) = key {
+                out.push_str(key);
+                out.push_str(": ");
+            }
+            value.write_pretty(out, depth + 1);
         }
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+        out.push(close);
     }
 }
 
-/// Фрейм вызова для рекурсии.
-/// Хранит локальные переменные, параметры и memo для этого вызова.
-#[derive(Debug, Clone, Default)]
-struct CallFrame {
-    /// Локальные переменные этого вызова
-    locals: HashMap<String, Value>,
-    /// Memo для этого вызова (кэш узлов тела функции)
-    memo: HashMap<NodeID, Value>,
+// === Преобразования между Value и типами Rust (для встраивания) ===
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Int(n)
+    }
 }
 
-/// Контекст выполнения, хранит вычисленные значения для каждого узла.
-pub struct Interpreter {
-    /// Кэш вычисленных значений узлов
-    memo: HashMap<NodeID, Value>,
-    /// Глобальные переменные
-    variables: HashMap<String, Value>,
-    /// Функции: имя -> (параметры, body_id, опциональный ASG для импортированных функций)
-    functions: HashMap<String, (Vec<String>, NodeID, Option<ASG>)>,
-    /// Стек вызовов для рекурсии
-    call_stack: Vec<CallFrame>,
+impl From<f64> for Value {
+    fn from(f: f64) -> Self {
+        Value::Float(f)
+    }
 }
 
-impl Default for Interpreter {
-    fn default() -> Self {
-        Self {
-            memo: HashMap::new(),
-            variables: HashMap::new(),
-            functions: HashMap::new(),
-            call_stack: Vec::new(),
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::Array(items)
+    }
+}
+
+/// `iter.collect::<Value>()` — массив из элементов, преобразованных в Value.
+impl<T: Into<Value>> FromIterator<T> for Value {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Value::Array(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Ошибка преобразования значения в тип Rust.
+fn conversion_error(expected: &str, value: &Value) -> ASGError {
+    ASGError::TypeError(format!("Expected {}, got {}", expected, value.type_name()))
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = ASGError;
+
+    fn try_from(value: Value) -> ASGResult<Self> {
+        match value {
+            Value::Int(n) => Ok(n),
+            other => Err(conversion_error("Int", &other)),
         }
     }
 }
 
-impl Interpreter {
-    pub fn new() -> Self {
-        Self::default()
+impl TryFrom<Value> for f64 {
+    type Error = ASGError;
+
+    fn try_from(value: Value) -> ASGResult<Self> {
+        match value {
+            Value::Float(f) => Ok(f),
+            other => Err(conversion_error("Float", &other)),
+        }
     }
+}
 
-    /// Разрешает переменную с приоритетом стека вызовов.
-    /// Сначала проверяет локальные переменные в call_stack (от вершины к основанию),
-    /// затем глобальные переменные.
-    fn resolve_variable(&self, name: &str) -> Option<&Value> {
-        // Сначала проверяем стек вызовов (от вершины к основанию)
-        for frame in self.call_stack.iter().rev() {
-            if let Some(val) = frame.locals.get(name) {
-                return Some(val);
-            }
+impl TryFrom<Value> for bool {
+    type Error = ASGError;
+
+    fn try_from(value: Value) -> ASGResult<Self> {
+        match value {
+            Value::Bool(b) => Ok(b),
+            other => Err(conversion_error("Bool", &other)),
         }
-        // Fallback на глобальные переменные
-        self.variables.get(name)
     }
+}
 
-    /// Выполняет ASG, вычисляя узлы по требованию начиная с корневого.
-    pub fn execute(&mut self, asg: &ASG, root_id: NodeID) -> ASGResult<Value> {
-        // Оцениваем только корневой узел, остальные по требованию
-        self.ensure_evaluated(asg, root_id)
+impl TryFrom<Value> for String {
+    type Error = ASGError;
+
+    fn try_from(value: Value) -> ASGResult<Self> {
+        match value {
+            Value::String(s) => Ok(s),
+            other => Err(conversion_error("String", &other)),
+        }
     }
+}
 
-    /// Вычисляет значение для одного узла и сохраняет его в кэш.
-    fn eval_node(&mut self, asg: &ASG, node: &Node) -> ASGResult<()> {
-        if self.memo.contains_key(&node.id) {
-            return Ok(());
+impl TryFrom<Value> for Vec<Value> {
+    type Error = ASGError;
+
+    fn try_from(value: Value) -> ASGResult<Self> {
+        match value {
+            Value::Array(items) => Ok(items),
+            other => Err(conversion_error("Array", &other)),
         }
+    }
+}
 
-        let result_value = match node.node_type {
-            // === Литералы ===
-            NodeType::LiteralInt => {
-                let payload = node
-                    .payload
-                    .as_ref()
-                    .ok_or(ASGError::MissingPayload(node.id))?;
-                let bytes: [u8; 8] = payload
-                    .clone()
-                    .try_into()
-                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
-                Value::Int(i64::from_le_bytes(bytes))
-            }
+/// Максимальная глубина вложенности при выводе и сериализации в JSON;
+/// глубже — `...` вместо содержимого, чтобы не переполнить стек.
+pub const MAX_DISPLAY_DEPTH: usize = 64;
+
+/// Порядок значений для сортировки: числа и строки сравниваются между собой,
+/// массивы — лексикографически. Несравнимые пары считаются равными, поэтому
+/// сортировки сначала проверяют элементы через [`check_orderable`].
+fn compare_values(a: &Value, b: &Value) -> std::cmp::Ordering {
+    match (a, b) {
+        (Value::Int(x), Value::Int(y)) => x.cmp(y),
+        // total_cmp: NaN тоже упорядочен, иначе сортировка и выборка не корректны
+        (Value::Float(x), Value::Float(y)) => x.total_cmp(y),
+        (Value::Int(x), Value::Float(y)) => (*x as f64).total_cmp(y),
+        (Value::Float(x), Value::Int(y)) => x.total_cmp(&(*y as f64)),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => x
+            .iter()
+            .zip(y)
+            .map(|(a, b)| compare_values(a, b))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+        #[cfg(feature = "bigint")]
+        (Value::BigInt(_), _) | (_, Value::BigInt(_)) => {
+            big_cmp(a, b).unwrap_or(std::cmp::Ordering::Equal)
+        }
+        #[cfg(feature = "decimal")]
+        (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+            decimal_cmp(a, b).unwrap_or(std::cmp::Ordering::Equal)
+        }
+        _ => std::cmp::Ordering::Equal,
+    }
+}
 
-            NodeType::LiteralFloat => {
-                let payload = node
-                    .payload
-                    .as_ref()
-                    .ok_or(ASGError::MissingPayload(node.id))?;
-                let bytes: [u8; 8] = payload
-                    .clone()
-                    .try_into()
-                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
-                Value::Float(f64::from_le_bytes(bytes))
-            }
+/// Ошибка, если элементы нельзя упорядочить [`compare_values`] между собой:
+/// допустимы только строки, только числа (Decimal — с Int, но не с Float и BigInt)
+/// или только массивы, элементы которых на каждой позиции сами упорядочиваемы.
+fn check_orderable(op: &str, items: &[Value]) -> ASGResult<()> {
+    check_orderable_refs(op, &items.iter().collect::<Vec<_>>())
+}
 
-            NodeType::LiteralBool => {
-                let payload = node
-                    .payload
-                    .as_ref()
-                    .ok_or(ASGError::MissingPayload(node.id))?;
-                let val = payload.first().map(|&b| b != 0).unwrap_or(false);
-                Value::Bool(val)
+fn check_orderable_refs(op: &str, items: &[&Value]) -> ASGResult<()> {
+    const INT: u8 = 1;
+    const FLOAT: u8 = 2;
+    const BIG: u8 = 4;
+    const DECIMAL: u8 = 8;
+    const STRING: u8 = 16;
+    const ARRAY: u8 = 32;
+    let mut kinds = 0;
+    for item in items {
+        kinds |= match item {
+            Value::Int(_) => INT,
+            Value::Float(_) => FLOAT,
+            #[cfg(feature = "bigint")]
+            Value::BigInt(_) => BIG,
+            #[cfg(feature = "decimal")]
+            Value::Decimal(_) => DECIMAL,
+            Value::String(_) => STRING,
+            Value::Array(_) => ARRAY,
+            other => {
+                return Err(ASGError::TypeError(format!(
+                    "{} cannot order values of type {}",
+                    op,
+                    other.type_name()
+                )))
             }
+        };
+    }
+    if kinds == ARRAY {
+        // Массивы сравниваются поэлементно: проверяем каждую позицию отдельно
+        let arrays: Vec<&Vec<Value>> = items
+            .iter()
+            .filter_map(|item| match item {
+                Value::Array(arr) => Some(arr),
+                _ => None,
+            })
+            .collect();
+        let width = arrays.iter().map(|arr| arr.len()).max().unwrap_or(0);
+        return (0..width).try_for_each(|i| {
+            let column: Vec<&Value> = arrays.iter().filter_map(|arr| arr.get(i)).collect();
+            check_orderable_refs(op, &column)
+        });
+    }
+    let numbers = kinds & !(INT | FLOAT | BIG) == 0 || kinds & !(INT | DECIMAL) == 0;
+    if numbers || kinds == STRING {
+        Ok(())
+    } else {
+        Err(ASGError::TypeError(format!(
+            "{} expects numbers, strings or arrays, not a mix of them",
+            op
+        )))
+    }
+}
 
-            NodeType::LiteralString => {
-                let payload = node
-                    .payload
-                    .as_ref()
-                    .ok_or(ASGError::MissingPayload(node.id))?;
-                let s = String::from_utf8(payload.clone())
-                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
-                Value::String(s)
-            }
+/// Целочисленная операция; поведение при переполнении i64 задаёт [`IntMode`].
+#[derive(Debug, Clone, Copy)]
+enum IntOp {
+    Add,
+    Sub,
+    Mul,
+    IntDiv,
+    Mod,
+    /// Показатель — неотрицательный; отрицательный даёт Float до вызова.
+    Pow,
+}
 
-            NodeType::LiteralUnit => Value::Unit,
+impl IntOp {
+    #[cfg(feature = "bigint")]
+    fn name(self) -> &'static str {
+        match self {
+            IntOp::Add => "Add",
+            IntOp::Sub => "Sub",
+            IntOp::Mul => "Mul",
+            IntOp::IntDiv => "IntDiv",
+            IntOp::Mod => "Mod",
+            IntOp::Pow => "Pow",
+        }
+    }
 
-            NodeType::LiteralTensor => {
-                let payload = node
-                    .payload
-                    .as_ref()
-                    .ok_or(ASGError::MissingPayload(node.id))?;
-                let bytes: [u8; 4] = payload
-                    .clone()
-                    .try_into()
-                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
-                let val = f32::from_le_bytes(bytes);
-                let tensor = DifferentiableTensor::new(ndarray::arr0(val).into_dyn(), true);
-                Value::Tensor(tensor)
-            }
+    /// Результат в i64 или None при переполнении (делитель уже проверен на ноль).
+    fn checked(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            IntOp::Add => a.checked_add(b),
+            IntOp::Sub => a.checked_sub(b),
+            IntOp::Mul => a.checked_mul(b),
+            IntOp::IntDiv => a.checked_div(b),
+            IntOp::Mod => a.checked_rem(b),
+            IntOp::Pow => match a {
+                // Основания 0, 1 и -1 не переполняются при любом показателе
+                0 | 1 => Some(if b == 0 { 1 } else { a }),
+                -1 => Some(if b % 2 == 0 { 1 } else { -1 }),
+                _ => u32::try_from(b).ok().and_then(|e| a.checked_pow(e)),
+            },
+        }
+    }
 
-            // === Арифметические операции ===
-            NodeType::BinaryOperation => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Int(a + b),
-                    (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
-                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64) + b),
-                    (Value::Float(a), Value::Int(b)) => Value::Float(a + (b as f64)),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for BinaryOperation".to_string(),
-                        ))
-                    }
-                }
-            }
+    fn wrapping(self, a: i64, b: i64) -> i64 {
+        match self {
+            IntOp::Add => a.wrapping_add(b),
+            IntOp::Sub => a.wrapping_sub(b),
+            IntOp::Mul => a.wrapping_mul(b),
+            IntOp::IntDiv => a.wrapping_div(b),
+            IntOp::Mod => a.wrapping_rem(b),
+            IntOp::Pow => wrapping_pow(a, b as u64),
+        }
+    }
 
-            NodeType::Sub => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Int(a - b),
-                    (Value::Float(a), Value::Float(b)) => Value::Float(a - b),
-                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64) - b),
-                    (Value::Float(a), Value::Int(b)) => Value::Float(a - (b as f64)),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for Sub".to_string(),
-                        ))
-                    }
+    /// Деление и остаток усекаются к нулю, как у i64. None — показатель степени
+    /// не помещается в u32 при основании по модулю больше 1.
+    #[cfg(feature = "bigint")]
+    fn big(self, a: &BigInt, b: &BigInt) -> Option<BigInt> {
+        match self {
+            IntOp::Add => Some(a + b),
+            IntOp::Sub => Some(a - b),
+            IntOp::Mul => Some(a * b),
+            IntOp::IntDiv => Some(a / b),
+            IntOp::Mod => Some(a % b),
+            IntOp::Pow => match i64::try_from(a) {
+                Ok(base @ -1..=1) => {
+                    // Важна только чётность показателя
+                    let exp = if b.is_zero() {
+                        0
+                    } else if (b % 2u32).is_zero() {
+                        2
+                    } else {
+                        1
+                    };
+                    IntOp::Pow.checked(base, exp).map(BigInt::from)
                 }
-            }
+                _ => u32::try_from(b).ok().map(|e| a.pow(e)),
+            },
+        }
+    }
+}
 
-            NodeType::Mul => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Int(a * b),
-                    (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
-                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64) * b),
-                    (Value::Float(a), Value::Int(b)) => Value::Float(a * (b as f64)),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for Mul".to_string(),
-                        ))
-                    }
-                }
-            }
+/// `base^exp` по модулю 2^64: показатель учитывается целиком, без усечения до u32.
+fn wrapping_pow(mut base: i64, mut exp: u64) -> i64 {
+    let mut result: i64 = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+    result
+}
 
-            NodeType::Div => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => {
-                        if b == 0 {
-                            return Err(ASGError::InvalidOperation("Division by zero".to_string()));
-                        }
-                        // True division returns float
-                        Value::Float(a as f64 / b as f64)
-                    }
-                    (Value::Float(a), Value::Float(b)) => Value::Float(a / b),
-                    (Value::Int(a), Value::Float(b)) => Value::Float(a as f64 / b),
-                    (Value::Float(a), Value::Int(b)) => Value::Float(a / b as f64),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for Div".to_string(),
-                        ))
-                    }
-                }
-            }
+/// Ошибка для степени, которую нельзя вычислить даже в BigInt.
+#[cfg(feature = "bigint")]
+fn exponent_too_large() -> ASGError {
+    ASGError::InvalidOperation("Exponent too large in Pow".to_string())
+}
 
-            NodeType::Mod => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => {
-                        if b == 0 {
-                            return Err(ASGError::InvalidOperation("Modulo by zero".to_string()));
-                        }
-                        Value::Int(a % b)
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two integers for Mod".to_string(),
-                        ))
-                    }
-                }
-            }
+/// BigInt, который помещается в i64, снова становится Int.
+#[cfg(feature = "bigint")]
+fn normalize_big(n: BigInt) -> Value {
+    i64::try_from(&n).map_or(Value::BigInt(n), Value::Int)
+}
 
-            NodeType::IntDiv => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => {
-                        if b == 0 {
-                            return Err(ASGError::InvalidOperation("Division by zero".to_string()));
-                        }
-                        Value::Int(a / b)
-                    }
-                    (Value::Float(a), Value::Float(b)) => {
-                        if b == 0.0 {
-                            return Err(ASGError::InvalidOperation("Division by zero".to_string()));
-                        }
-                        Value::Int((a / b).floor() as i64)
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for IntDiv".to_string(),
-                        ))
-                    }
-                }
-            }
+/// Int или BigInt как BigInt.
+#[cfg(feature = "bigint")]
+fn to_big(val: &Value) -> Option<BigInt> {
+    match val {
+        Value::Int(n) => Some(BigInt::from(*n)),
+        Value::BigInt(n) => Some(n.clone()),
+        _ => None,
+    }
+}
 
-            NodeType::Neg => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Int(a) => Value::Int(-a),
-                    Value::Float(a) => Value::Float(-a),
-                    _ => return Err(ASGError::TypeError("Expected number for Neg".to_string())),
-                }
-            }
+/// Int/BigInt-операция, где хотя бы один операнд BigInt.
+#[cfg(feature = "bigint")]
+fn big_arith(op: IntOp, a: &Value, b: &Value) -> ASGResult<Value> {
+    let (Some(x), Some(y)) = (to_big(a), to_big(b)) else {
+        return Err(ASGError::TypeError(format!(
+            "Expected two integers for {}, got {} and {}",
+            op.name(),
+            a.type_name(),
+            b.type_name()
+        )));
+    };
+    if y.is_zero() {
+        match op {
+            IntOp::IntDiv => {
+                return Err(ASGError::InvalidOperation("Division by zero".to_string()))
+            }
+            IntOp::Mod => return Err(ASGError::InvalidOperation("Modulo by zero".to_string())),
+            _ => {}
+        }
+    }
+    op.big(&x, &y)
+        .map(normalize_big)
+        .ok_or_else(exponent_too_large)
+}
 
-            // === Операции сравнения ===
-            NodeType::Eq => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                let result = match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => a == b,
-                    (Value::Float(a), Value::Float(b)) => (a - b).abs() < f64::EPSILON,
-                    (Value::Bool(a), Value::Bool(b)) => a == b,
-                    (Value::String(a), Value::String(b)) => a == b,
-                    _ => false,
-                };
-                Value::Bool(result)
-            }
+/// Сравнение, где хотя бы один операнд BigInt: с Int точно, с Float — через f64.
+#[cfg(feature = "bigint")]
+fn big_cmp(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    match (to_big(a), to_big(b)) {
+        (Some(x), Some(y)) => Some(x.cmp(&y)),
+        _ => big_to_f64(a)?.partial_cmp(&big_to_f64(b)?),
+    }
+}
 
-            NodeType::Ne => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                let result = match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => a != b,
-                    (Value::Float(a), Value::Float(b)) => (a - b).abs() >= f64::EPSILON,
-                    (Value::Bool(a), Value::Bool(b)) => a != b,
-                    (Value::String(a), Value::String(b)) => a != b,
-                    _ => true,
-                };
-                Value::Bool(result)
-            }
+/// Decimal или Int как Decimal; Float приводится только явно через (cast Decimal x).
+#[cfg(feature = "decimal")]
+fn to_decimal(val: &Value) -> Option<Decimal> {
+    match val {
+        Value::Decimal(d) => Some(*d),
+        Value::Int(n) => Some(Decimal::from(*n)),
+        _ => None,
+    }
+}
 
-            NodeType::Lt => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Bool(a < b),
-                    (Value::Float(a), Value::Float(b)) => Value::Bool(a < b),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for Lt".to_string(),
-                        ))
-                    }
-                }
-            }
+/// Операция, где хотя бы один операнд Decimal; результат точный или ошибка.
+#[cfg(feature = "decimal")]
+fn decimal_arith(
+    name: &str,
+    a: &Value,
+    b: &Value,
+    op: fn(Decimal, Decimal) -> Option<Decimal>,
+) -> ASGResult<Value> {
+    let (Some(x), Some(y)) = (to_decimal(a), to_decimal(b)) else {
+        return Err(ASGError::TypeError(format!(
+            "Expected Decimal or Int for {}, got {} and {}",
+            name,
+            a.type_name(),
+            b.type_name()
+        )));
+    };
+    if y.is_zero() && matches!(name, "Div" | "Mod") {
+        return Err(ASGError::InvalidOperation(format!(
+            "{} by zero",
+            if name == "Div" { "Division" } else { "Modulo" }
+        )));
+    }
+    op(x, y)
+        .map(Value::Decimal)
+        .ok_or_else(|| ASGError::InvalidOperation(format!("Decimal overflow in {}", name)))
+}
 
-            NodeType::Le => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Bool(a <= b),
-                    (Value::Float(a), Value::Float(b)) => Value::Bool(a <= b),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for Le".to_string(),
-                        ))
-                    }
-                }
-            }
+/// Сравнение, где хотя бы один операнд Decimal.
+#[cfg(feature = "decimal")]
+fn decimal_cmp(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
+    Some(to_decimal(a)?.cmp(&to_decimal(b)?))
+}
 
-            NodeType::Gt => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Bool(a > b),
-                    (Value::Float(a), Value::Float(b)) => Value::Bool(a > b),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for Gt".to_string(),
-                        ))
-                    }
-                }
-            }
+/// Число (Int, BigInt, Float) как f64.
+#[cfg(feature = "bigint")]
+fn big_to_f64(val: &Value) -> Option<f64> {
+    match val {
+        Value::Int(n) => Some(*n as f64),
+        Value::BigInt(n) => n.to_f64(),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
 
-            NodeType::Ge => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Bool(a >= b),
-                    (Value::Float(a), Value::Float(b)) => Value::Bool(a >= b),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two numbers for Ge".to_string(),
-                        ))
-                    }
-                }
-            }
+/// Байты значения для кодирования: Bytes, строка (UTF-8) или массив Int 0..=255.
+fn value_to_bytes(val: &Value) -> Option<Vec<u8>> {
+    match val {
+        Value::Bytes(bytes) => Some(bytes.clone()),
+        Value::String(s) => Some(s.as_bytes().to_vec()),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Int(n) => u8::try_from(*n).ok(),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
 
-            // === Логические операции ===
-            NodeType::And => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Bool(a), Value::Bool(b)) => Value::Bool(a && b),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two booleans for And".to_string(),
-                        ))
-                    }
-                }
+/// Раскрыть вложенные массивы в `out`: `depth` уровней или полностью (None).
+fn flatten_into(items: Vec<Value>, depth: Option<usize>, out: &mut Vec<Value>) {
+    for item in items {
+        match item {
+            Value::Array(inner) if depth != Some(0) => {
+                flatten_into(inner, depth.map(|d| d - 1), out)
             }
+            other => out.push(other),
+        }
+    }
+}
 
-            NodeType::Or => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Bool(a), Value::Bool(b)) => Value::Bool(a || b),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two booleans for Or".to_string(),
-                        ))
-                    }
-                }
-            }
+/// Имя, под которым определение модуля привязывается при импорте:
+/// как есть, `alias.name` для `:as` или None, если `:only` его не перечисляет.
+fn imported_name(form: Option<&str>, name: &str) -> Option<String> {
+    match form {
+        None => Some(name.to_string()),
+        Some(form) => match form.strip_prefix("only:") {
+            Some(list) => list
+                .split(',')
+                .any(|only| only == name)
+                .then(|| name.to_string()),
+            None => Some(format!("{}.{}", form, name)),
+        },
+    }
+}
 
-            NodeType::Not => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Bool(a) => Value::Bool(!a),
-                    _ => return Err(ASGError::TypeError("Expected boolean for Not".to_string())),
-                }
-            }
+/// Разложить именованные аргументы вызова по позициям параметров `params`.
+/// Позиционные аргументы занимают первые параметры; параметры, пропущенные
+/// перед именованными, остаются `None`.
+fn bind_keyword_args(
+    callee: &str,
+    params: &[String],
+    positional: Vec<Value>,
+    keywords: Vec<(String, Value)>,
+) -> ASGResult<Vec<Option<Value>>> {
+    if keywords.is_empty() {
+        return Ok(positional.into_iter().map(Some).collect());
+    }
+    let callee = if callee.is_empty() { "call" } else { callee };
+    let mut slots: Vec<Option<Value>> = positional.into_iter().map(Some).collect();
+    for (name, value) in keywords {
+        let index = params.iter().position(|p| *p == name).ok_or_else(|| {
+            ASGError::TypeError(format!("{}: unknown keyword argument :{}", callee, name))
+        })?;
+        if slots.len() <= index {
+            slots.resize(index + 1, None);
+        }
+        if slots[index].is_some() {
+            return Err(ASGError::TypeError(format!(
+                "{}: argument '{}' is given more than once",
+                callee, name
+            )));
+        }
+        slots[index] = Some(value);
+    }
+    Ok(slots)
+}
 
-            // === If выражение ===
-            NodeType::If => {
-                let cond_edge = node
-                    .find_edge(EdgeType::Condition)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::Condition))?;
+/// Отметить узлы, в поддереве которых есть yield (вложенные функции не в счёт).
+/// Возвращает, отмечен ли `id`.
+fn collect_resumable(
+    asg: &ASG,
+    id: NodeID,
+    resumable: &mut HashSet<NodeID>,
+    visited: &mut HashSet<NodeID>,
+) -> bool {
+    if !visited.insert(id) {
+        return resumable.contains(&id);
+    }
+    let Some(node) = asg.find_node(id) else {
+        return false;
+    };
+    if matches!(
+        node.node_type,
+        NodeType::Function | NodeType::Lambda | NodeType::Generator
+    ) {
+        return false;
+    }
+    let mut found = node.node_type == NodeType::Yield;
+    for edge in &node.edges {
+        found |= collect_resumable(asg, edge.target_node_id, resumable, visited);
+    }
+    if found {
+        resumable.insert(id);
+    }
+    found
+}
 
-                let cond_val = self.ensure_evaluated(asg, cond_edge.target_node_id)?;
-                let cond = cond_val
-                    .as_bool()
-                    .ok_or(ASGError::TypeError("Condition must be boolean".to_string()))?;
+/// Аргумент-множество формы `form`.
+fn expect_set(value: Value, form: &str) -> ASGResult<BTreeSet<SetKey>> {
+    match value {
+        Value::Set(items) => Ok(items),
+        other => Err(ASGError::TypeError(format!(
+            "Expected set for {}, got {}",
+            form,
+            other.type_name()
+        ))),
+    }
+}
 
-                if cond {
-                    let then_edge = node
-                        .find_edge(EdgeType::ThenBranch)
-                        .ok_or(ASGError::MissingEdge(node.id, EdgeType::ThenBranch))?;
-                    self.ensure_evaluated(asg, then_edge.target_node_id)?
-                } else if let Some(else_edge) = node.find_edge(EdgeType::ElseBranch) {
-                    self.ensure_evaluated(asg, else_edge.target_node_id)?
-                } else {
-                    Value::Unit
-                }
-            }
+/// Собрать имена переменных, которым поддерево присваивает через set или
+/// составное присваивание (включая вложенные lambda).
+fn collect_assigned_names(
+    asg: &ASG,
+    id: NodeID,
+    names: &mut HashSet<String>,
+    visited: &mut HashSet<NodeID>,
+) {
+    if !visited.insert(id) {
+        return;
+    }
+    let Some(node) = asg.find_node(id) else {
+        return;
+    };
+    if matches!(node.node_type, NodeType::Assign | NodeType::CompoundAssign) {
+        // Для (set (index xs 0) v) меняется переменная в основании места
+        let mut target = node
+            .find_edge(EdgeType::AssignTarget)
+            .and_then(|e| asg.find_node(e.target_node_id));
+        while let Some(place) = target {
+            if place.node_type == NodeType::VarRef {
+                names.extend(place.get_name());
+                break;
+            }
+            target = place
+                .edges
+                .first()
+                .and_then(|e| asg.find_node(e.target_node_id));
+        }
+    }
+    for edge in &node.edges {
+        collect_assigned_names(asg, edge.target_node_id, names, visited);
+    }
+}
 
-            // === Block ===
-            NodeType::Block => {
-                let stmt_edges: Vec<_> = node
-                    .find_edges(EdgeType::BlockStatement)
-                    .into_iter()
-                    .map(|e| e.target_node_id)
-                    .collect();
-                let mut result = Value::Unit;
-                for target_id in stmt_edges {
-                    result = self.ensure_evaluated(asg, target_id)?;
-                }
-                result
-            }
+/// Имена из payload (let (a b c) value): 4 байта — число имён, затем имена через \0.
+fn destructure_names(payload: &[u8]) -> Vec<String> {
+    let count = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+    let mut names = Vec::with_capacity(count);
+    let mut pos = 4;
+    for _ in 0..count {
+        let end = payload[pos..]
+            .iter()
+            .position(|&b| b == 0)
+            .map(|p| pos + p)
+            .unwrap_or(payload.len());
+        names.push(String::from_utf8_lossy(&payload[pos..end]).to_string());
+        pos = end + 1;
+    }
+    names
+}
 
-            // === Loop (while) ===
-            NodeType::Loop => {
-                let body_edge = node
-                    .find_edge(EdgeType::LoopBody)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopBody))?;
+/// Сведения о графе для анализа циклов; вычисляются один раз на граф.
+struct GraphFacts {
+    /// Позиция узла в `asg.nodes` по его id
+    positions: HashMap<NodeID, usize>,
+    /// Имена, которым граф присваивает: цели set, составных присваиваний и set-index
+    assigned: HashSet<String>,
+}
 
-                let body_node = asg
-                    .find_node(body_edge.target_node_id)
-                    .ok_or(ASGError::NodeNotFound(body_edge.target_node_id))?
-                    .clone();
+impl GraphFacts {
+    fn new(asg: &ASG) -> Self {
+        let mut facts = GraphFacts {
+            positions: asg
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(i, node)| (node.id, i))
+                .collect(),
+            assigned: HashSet::new(),
+        };
+        for node in &asg.nodes {
+            let target = match node.node_type {
+                NodeType::Assign | NodeType::CompoundAssign => node
+                    .find_edge(EdgeType::AssignTarget)
+                    .map(|e| e.target_node_id),
+                NodeType::ArraySetIndex => node.edges.first().map(|e| e.target_node_id),
+                _ => None,
+            };
+            // Для (set (index xs 0) v) меняется переменная в основании места
+            let mut target = target.and_then(|id| facts.node(asg, id));
+            while let Some(place) = target {
+                if place.node_type == NodeType::VarRef {
+                    facts.assigned.extend(place.get_name());
+                    break;
+                }
+                target = place
+                    .edges
+                    .first()
+                    .and_then(|e| facts.node(asg, e.target_node_id));
+            }
+        }
+        facts
+    }
 
-                // Если есть условие - это while loop
-                if let Some(cond_edge) = node.find_edge(EdgeType::Condition) {
-                    let mut result = Value::Unit;
-                    loop {
-                        // Очищаем весь кеш для пересчёта (переменные могли измениться)
-                        self.memo.clear();
+    fn node<'a>(&self, asg: &'a ASG, id: NodeID) -> Option<&'a Node> {
+        self.positions.get(&id).map(|&i| &asg.nodes[i])
+    }
+}
+
+/// Отметить в `invariant`, не меняется ли узел между итерациями цикла: это
+/// переменная не из `mutable` или чистая операция над такими узлами.
+fn classify_invariant(
+    asg: &ASG,
+    facts: &GraphFacts,
+    id: NodeID,
+    mutable: &HashSet<String>,
+    invariant: &mut HashMap<NodeID, bool>,
+) -> bool {
+    if let Some(&known) = invariant.get(&id) {
+        return known;
+    }
+    // Предварительно false: цикл в графе не делает узел инвариантным
+    invariant.insert(id, false);
+    let Some(node) = facts.node(asg, id) else {
+        return false;
+    };
+    // Обходим всех потомков, даже если узел уже вариантный: их memo тоже решается здесь
+    let mut children_invariant = true;
+    for edge in &node.edges {
+        children_invariant &=
+            classify_invariant(asg, facts, edge.target_node_id, mutable, invariant);
+    }
+    let result = match node.node_type {
+        NodeType::VarRef => node.get_name().is_some_and(|name| !mutable.contains(&name)),
+        other => other.is_pure() && children_invariant,
+    };
+    invariant.insert(id, result);
+    result
+}
 
-                        let cond_val = self.ensure_evaluated(asg, cond_edge.target_node_id)?;
-                        let cond = cond_val.as_bool().ok_or(ASGError::TypeError(
-                            "Loop condition must be boolean".to_string(),
-                        ))?;
+/// Строковое представление как у to-string: строки без кавычек, остальное — format_display.
+fn display_string(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        Value::Char(c) => c.to_string(),
+        other => other.format_display(),
+    }
+}
 
-                        if !cond {
-                            break;
+/// Подстановка `{name}` из словаря; `{{` и `}}` — литеральные скобки.
+/// Без `missing` отсутствующий ключ — ошибка, иначе подставляется `missing`.
+fn render_template(
+    template: &str,
+    ctx: &HashMap<String, Value>,
+    missing: Option<&str>,
+) -> ASGResult<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => name.push(ch),
+                        None => {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "render: unclosed placeholder '{{{}'",
+                                name
+                            )))
                         }
-
-                        // Выполняем тело
-                        self.memo.clear();
-                        result = self.ensure_evaluated(asg, body_edge.target_node_id)?;
                     }
-                    result
-                } else {
-                    // Бесконечный цикл без условия
-                    loop {
-                        self.memo.clear();
-                        self.eval_node(asg, &body_node)?;
+                }
+                match (ctx.get(&name), missing) {
+                    (Some(value), _) => out.push_str(&display_string(value.clone())),
+                    (None, Some(fallback)) => out.push_str(fallback),
+                    (None, None) => {
+                        return Err(ASGError::InvalidOperation(format!(
+                            "render: missing key '{}'",
+                            name
+                        )))
                     }
                 }
             }
+            '}' => {
+                return Err(ASGError::InvalidOperation(
+                    "render: unmatched '}' (use '}}' for a literal brace)".to_string(),
+                ))
+            }
+            _ => out.push(c),
+        }
+    }
+    Ok(out)
+}
 
-            // === Переменные ===
-            NodeType::Variable => {
-                let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+/// Разбиение для str-split: не более `limit` разрезов, остаток целиком.
+/// Пустой разделитель режет строку на символы.
+fn split_with_limit(s: &str, delim: &str, limit: Option<usize>) -> Vec<String> {
+    if delim.is_empty() {
+        let split_at = match limit {
+            Some(n) => s.char_indices().nth(n).map_or(s.len(), |(i, _)| i),
+            None => s.len(),
+        };
+        let mut parts: Vec<String> = s[..split_at].chars().map(String::from).collect();
+        if split_at < s.len() {
+            parts.push(s[split_at..].to_string());
+        }
+        return parts;
+    }
+    match limit {
+        Some(n) => s.splitn(n + 1, delim).map(str::to_string).collect(),
+        None => s.split(delim).map(str::to_string).collect(),
+    }
+}
 
-                let value = if let Some(val_edge) = node.find_edge(EdgeType::VarValue) {
-                    self.ensure_evaluated(asg, val_edge.target_node_id)?
-                } else {
-                    Value::Unit
-                };
+/// Шаг пути в lvalue: (index place i), (dict-get place key), (field place name).
+#[derive(Debug, Clone)]
+enum PlaceStep {
+    Index(usize),
+    Key(String),
+    Field(String),
+}
 
-                self.variables.insert(var_name, value.clone());
-                value
+/// Записать значение по пути внутри переменной, изменяя её на месте.
+/// Ключ словаря может быть новым, индекс массива и поле записи — только существующими.
+fn assign_place(slot: &mut Value, path: &[PlaceStep], value: Value) -> ASGResult<()> {
+    let Some((step, rest)) = path.split_first() else {
+        *slot = value;
+        return Ok(());
+    };
+    let next = match (step, slot) {
+        (PlaceStep::Index(i), Value::Array(arr)) => {
+            let len = arr.len();
+            arr.get_mut(*i).ok_or_else(|| {
+                ASGError::InvalidOperation(format!("Array index {} out of bounds ({})", i, len))
+            })?
+        }
+        (PlaceStep::Key(key), Value::Dict(dict)) => {
+            if rest.is_empty() {
+                dict.insert(key.clone(), value);
+                return Ok(());
             }
+            dict.get_mut(key)
+                .ok_or_else(|| ASGError::InvalidOperation(format!("Key '{}' not found", key)))?
+        }
+        (PlaceStep::Field(field), Value::Record(record)) => record
+            .get_mut(field)
+            .ok_or_else(|| ASGError::InvalidOperation(format!("No field '{}' in record", field)))?,
+        (step, other) => {
+            return Err(ASGError::TypeError(format!(
+                "Cannot assign {:?} in {}",
+                step,
+                other.type_name()
+            )))
+        }
+    };
+    assign_place(next, rest, value)
+}
 
-            NodeType::LetDestructure => {
-                // Декодируем имена из payload
-                let payload = node
-                    .payload
-                    .as_ref()
-                    .ok_or(ASGError::MissingPayload(node.id))?;
+/// Ошибка проверки assert / for-all.
+fn assertion_failed(message: &str) -> ASGError {
+    ASGError::InvalidOperation(format!("Assertion failed: {}", message))
+}
 
-                // Первые 4 байта - количество имён
-                let count =
-                    u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
-                let mut names = Vec::with_capacity(count);
-                let mut pos = 4;
+/// Значение условия requires/ensures: ложь — ошибка `<kind> failed`.
+fn contract_holds(value: Value, kind: &str) -> ASGResult<()> {
+    match value {
+        Value::Bool(true) => Ok(()),
+        Value::Bool(false) => Err(ASGError::InvalidOperation(format!("{} failed", kind))),
+        other => Err(ASGError::TypeError(format!(
+            "Expected boolean for {} condition, got {}",
+            kind.to_lowercase(),
+            other.type_name()
+        ))),
+    }
+}
 
-                for _ in 0..count {
-                    let end = payload[pos..]
-                        .iter()
-                        .position(|&b| b == 0)
-                        .map(|p| pos + p)
-                        .unwrap_or(payload.len());
-                    let name = String::from_utf8_lossy(&payload[pos..end]).to_string();
-                    names.push(name);
-                    pos = end + 1;
-                }
+/// Строка для (debug label expr): строковая метка печатается без кавычек.
+fn debug_line(label: &Value, value: &Value) -> String {
+    match label {
+        Value::String(s) => format!("{} = {}", s, value.format_display()),
+        other => format!("{} = {}", other.format_display(), value.format_display()),
+    }
+}
 
-                // Вычисляем значение
-                let val_edge = node
-                    .find_edge(EdgeType::VarValue)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::VarValue))?;
-                let value = self.ensure_evaluated(asg, val_edge.target_node_id)?;
+/// Имена полей из узла RecordDef в порядке объявления.
+fn record_def_fields(asg: &ASG, node: &Node) -> Vec<String> {
+    node.find_edges(EdgeType::RecordFieldDef)
+        .into_iter()
+        .filter_map(|e| asg.find_node(e.target_node_id))
+        .filter_map(|n| n.get_name())
+        .collect()
+}
 
-                // Деструктуризация
-                match &value {
-                    Value::Array(arr) => {
-                        for (i, name) in names.iter().enumerate() {
-                            let val = arr.get(i).cloned().unwrap_or(Value::Unit);
-                            self.variables.insert(name.clone(), val);
-                        }
-                    }
-                    Value::Record(rec) => {
-                        for name in &names {
-                            let val = rec.get(name).cloned().unwrap_or(Value::Unit);
-                            self.variables.insert(name.clone(), val);
-                        }
-                    }
-                    Value::Dict(dict) => {
-                        for name in &names {
-                            let val = dict.get(name).cloned().unwrap_or(Value::Unit);
-                            self.variables.insert(name.clone(), val);
-                        }
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Destructuring requires Array, Record, or Dict".to_string(),
-                        ));
-                    }
-                }
+/// Фрейм вызова для рекурсии.
+/// Хранит локальные переменные, параметры и memo для этого вызова.
+#[derive(Debug, Clone, Default)]
+struct CallFrame {
+    /// Локальные переменные этого вызова
+    locals: HashMap<String, Value>,
+    /// Memo для этого вызова (кэш узлов тела функции)
+    memo: HashMap<NodeID, Value>,
+}
 
-                value
-            }
+/// Определение функции: (параметры, body_id, модуль для импортированных функций).
+type FunctionEntry = (Vec<String>, NodeID, Option<Rc<ImportedModule>>);
+
+/// Выполненный импортированный файл: его ASG и все определения верхнего уровня.
+/// Тела функций модуля видят эти определения, даже если (import ... :only ...)
+/// не привязал их или (import ... :as m) переименовал.
+#[derive(Debug)]
+struct ImportedModule {
+    asg: ASG,
+    /// Функции модуля; None в третьем поле — функция из этого же ASG
+    functions: HashMap<String, FunctionEntry>,
+    variables: HashMap<String, Value>,
+}
 
-            NodeType::VarRef => {
-                let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
-                // Сначала ищем в переменных
-                if let Some(val) = self.resolve_variable(&var_name) {
-                    val.clone()
-                } else if let Some((params, body_id, _)) = self.functions.get(&var_name) {
-                    // Если не нашли в переменных, ищем в функциях
-                    Value::Function {
-                        params: params.clone(),
-                        body_id: *body_id,
-                        captured: HashMap::new(),
-                    }
-                } else {
-                    return Err(ASGError::UnknownVariable(var_name));
-                }
-            }
+/// Обработчик узла расширения: получает вычисленные аргументы, возвращает результат.
+pub type ExtensionHandler = Box<dyn Fn(&[Value]) -> ASGResult<Value>>;
 
-            NodeType::Assign => {
-                let target_edge = node
-                    .find_edge(EdgeType::AssignTarget)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignTarget))?;
-                let value_edge = node
-                    .find_edge(EdgeType::AssignValue)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignValue))?;
+/// Монотонные часы для (time ...) и (bench ...): время, прошедшее с некоторой точки отсчёта.
+pub type Clock = Box<dyn Fn() -> Duration>;
 
-                let target_node = asg
-                    .find_node(target_edge.target_node_id)
-                    .ok_or(ASGError::NodeNotFound(target_edge.target_node_id))?;
-                let var_name = target_node
-                    .get_name()
-                    .ok_or(ASGError::MissingPayload(target_node.id))?;
+/// Приёмник строк, выводимых (print ...); по умолчанию stdout.
+pub type Output = Box<dyn FnMut(&str)>;
 
-                let value = self.ensure_evaluated(asg, value_edge.target_node_id)?;
-                self.variables.insert(var_name, value);
-                Value::Unit
-            }
+/// Контекст выполнения, хранит вычисленные значения для каждого узла.
+pub struct Interpreter {
+    /// Кэш вычисленных значений узлов
+    memo: HashMap<NodeID, Value>,
+    /// Глобальные переменные
+    variables: HashMap<String, Value>,
+    /// Глобальные функции: имя -> (параметры, body_id, опциональный ASG для импортированных функций)
+    functions: HashMap<String, FunctionEntry>,
+    /// Области видимости локальных функций: по одной на блок и вызов функции.
+    /// fn внутри них не попадает в `functions` и исчезает при выходе
+    function_scopes: Vec<HashMap<String, FunctionEntry>>,
+    /// Модули выполняемых сейчас импортированных функций (вершина — текущий)
+    modules: Vec<Rc<ImportedModule>>,
+    /// Стек вызовов для рекурсии
+    call_stack: Vec<CallFrame>,
+    /// Обработчики расширений: код -> (имя, обработчик)
+    extensions: HashMap<u16, (String, ExtensionHandler)>,
+    /// Часы для профилирования
+    clock: Clock,
+    /// Схемы записей из defrecord: имя типа -> имена полей
+    record_schemas: HashMap<String, Vec<String>>,
+    /// Варианты из defenum: имя варианта -> (имя enum, число полей)
+    enum_variants: HashMap<String, (String, usize)>,
+    /// Допуск сравнения float (см. `floats_equal`)
+    float_tolerance: f64,
+    /// Политика для NaN/inf
+    nan_policy: NanPolicy,
+    /// Поведение при переполнении i64
+    int_mode: IntMode,
+    /// Наибольший размер массива, словаря или множества; None — без ограничения
+    max_collection_size: Option<usize>,
+    /// Факты о графах для анализа циклов, по адресу графа (см. `reset_loop_analysis`)
+    graph_facts: HashMap<usize, Rc<GraphFacts>>,
+    /// Узлы циклов, пересчитываемые на каждой итерации: (адрес графа, id цикла)
+    loop_variants: HashMap<(usize, NodeID), Rc<HashSet<NodeID>>>,
+    /// Разобранные формы встроенных функций-значений (по первому вызову)
+    builtin_bodies: HashMap<&'static str, Rc<(ASG, NodeID)>>,
+    /// Файлы, импорт которых ещё не завершён (канонические пути), для обнаружения циклов
+    importing: Vec<PathBuf>,
+    /// Файл верхнего уровня: от его каталога разрешаются относительные импорты
+    current_file: Option<PathBuf>,
+    /// Дополнительные каталоги для поиска импортируемых файлов
+    search_paths: Vec<PathBuf>,
+    /// Каталог установленных пакетов для (import "pkg:..."); None — искать .asg/packages вверх
+    packages_dir: Option<PathBuf>,
+    /// Генератор для gen-int (детерминированный, см. `with_seed`)
+    rng: StdRng,
+    /// Куда пишет print
+    output: Output,
+    /// Куда пишут debug, time и bench (по умолчанию stderr)
+    debug_output: Output,
+    /// Значение `(break v)` на пути к перехватившему сигнал циклу
+    break_value: Option<Value>,
+}
 
-            // === Функции ===
-            NodeType::Function => {
-                let func_name = node.get_name().unwrap_or_else(|| format!("fn_{}", node.id));
+/// Что делать с NaN/inf, полученными из арифметики и математических функций.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanPolicy {
+    /// Оставлять значения IEEE 754 как есть
+    #[default]
+    Propagate,
+    /// Превращать NaN/inf в Value::Error
+    Error,
+}
 
-                // Собираем имена параметров
-                let param_edges = node.find_edges(EdgeType::FunctionParameter);
-                let mut params = Vec::new();
-                for edge in param_edges {
-                    let param_node = asg
-                        .find_node(edge.target_node_id)
-                        .ok_or(ASGError::NodeNotFound(edge.target_node_id))?;
-                    let param_name = param_node
-                        .get_name()
-                        .unwrap_or_else(|| format!("param_{}", param_node.id));
-                    params.push(param_name);
-                }
+/// Что делать при переполнении i64 в +, -, *, neg, //, % и pow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntMode {
+    /// Ошибка "integer overflow"
+    Checked,
+    /// Переполнение по модулю 2^64 (как прежняя арифметика i64)
+    #[default]
+    Wrapping,
+    /// Результат становится Value::BigInt (feature `bigint`)
+    #[cfg(feature = "bigint")]
+    Promote,
+}
 
-                // Получаем ID тела
-                let body_id = node
-                    .find_edge(EdgeType::FunctionBody)
-                    .map(|e| e.target_node_id)
-                    .unwrap_or(0);
+/// Допуск сравнения float по умолчанию.
+pub const DEFAULT_FLOAT_TOLERANCE: f64 = 1e-9;
 
-                self.functions
-                    .insert(func_name.clone(), (params.clone(), body_id, None));
+/// Seed генератора gen-int по умолчанию: прогоны воспроизводимы.
+pub const DEFAULT_SEED: u64 = 0;
 
-                Value::Function {
-                    params,
-                    body_id,
-                    captured: HashMap::new(),
-                }
-            }
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self {
+            memo: HashMap::new(),
+            variables: HashMap::new(),
+            functions: HashMap::new(),
+            function_scopes: Vec::new(),
+            modules: Vec::new(),
+            call_stack: Vec::new(),
+            extensions: HashMap::new(),
+            clock: {
+                let start = Instant::now();
+                Box::new(move || start.elapsed())
+            },
+            record_schemas: HashMap::new(),
+            enum_variants: HashMap::new(),
+            float_tolerance: DEFAULT_FLOAT_TOLERANCE,
+            nan_policy: NanPolicy::default(),
+            int_mode: IntMode::default(),
+            max_collection_size: None,
+            graph_facts: HashMap::new(),
+            loop_variants: HashMap::new(),
+            builtin_bodies: HashMap::new(),
+            importing: Vec::new(),
+            current_file: None,
+            search_paths: Vec::new(),
+            packages_dir: None,
+            rng: StdRng::seed_from_u64(DEFAULT_SEED),
+            break_value: None,
+            output: Box::new(|line| println!("{}", line)),
+            debug_output: Box::new(|line| eprintln!("{}", line)),
+        }
+    }
+}
 
-            NodeType::Parameter => {
-                let param_name = node
-                    .get_name()
-                    .unwrap_or_else(|| format!("param_{}", node.id));
-                // Параметр получает значение из стека вызовов или глобальных переменных
-                self.resolve_variable(&param_name)
-                    .cloned()
-                    .unwrap_or(Value::Unit)
-            }
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-            NodeType::Lambda => {
-                // Lambda — анонимная функция с захватом переменных (closure)
-                let param_edges = node.find_edges(EdgeType::FunctionParameter);
-                let mut params = Vec::new();
-                for edge in param_edges {
-                    let param_node = asg
-                        .find_node(edge.target_node_id)
-                        .ok_or(ASGError::NodeNotFound(edge.target_node_id))?;
-                    let param_name = param_node
-                        .get_name()
-                        .unwrap_or_else(|| format!("param_{}", param_node.id));
-                    params.push(param_name);
-                }
+    /// Задаёт допуск сравнения float для ==, !=, values_equal и match.
+    pub fn with_float_tolerance(mut self, tolerance: f64) -> Self {
+        self.float_tolerance = tolerance;
+        self
+    }
 
-                let body_id = node
-                    .find_edge(EdgeType::FunctionBody)
-                    .map(|e| e.target_node_id)
-                    .unwrap_or(0);
+    /// Задаёт seed генератора выборок gen-int.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
 
-                // Захватываем текущий scope для closure
-                let mut captured = HashMap::new();
-                // Сначала глобальные переменные
-                for (name, val) in &self.variables {
-                    captured.insert(name.clone(), val.clone());
-                }
-                // Затем переменные из стека вызовов (перезаписывают глобальные)
-                for frame in &self.call_stack {
-                    for (name, val) in &frame.locals {
-                        captured.insert(name.clone(), val.clone());
-                    }
-                }
+    /// Задаёт политику для NaN/inf после арифметики, деления и math-функций.
+    pub fn with_nan_policy(mut self, policy: NanPolicy) -> Self {
+        self.nan_policy = policy;
+        self
+    }
 
-                Value::Function {
-                    params,
-                    body_id,
-                    captured,
-                }
-            }
+    /// Задаёт поведение при переполнении целочисленной арифметики.
+    pub fn with_int_mode(mut self, mode: IntMode) -> Self {
+        self.int_mode = mode;
+        self
+    }
 
-            NodeType::Call => {
-                // Получаем функцию
-                let call_target = node
-                    .find_edge(EdgeType::CallTarget)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::CallTarget))?;
-                let target_node = asg
-                    .find_node(call_target.target_node_id)
-                    .ok_or(ASGError::NodeNotFound(call_target.target_node_id))?;
-                let func_name = target_node.get_name().unwrap_or_default();
+    /// Включает проверку переполнения i64: `true` — [`IntMode::Checked`],
+    /// `false` — [`IntMode::Wrapping`] (по умолчанию).
+    pub fn with_checked_arithmetic(self, checked: bool) -> Self {
+        self.with_int_mode(if checked {
+            IntMode::Checked
+        } else {
+            IntMode::Wrapping
+        })
+    }
 
-                // Собираем аргументы сначала
-                let arg_edges: Vec<_> = node
-                    .edges
-                    .iter()
-                    .filter(|e| {
-                        e.edge_type == EdgeType::CallArgument
-                            || e.edge_type == EdgeType::ApplicationArgument
-                    })
-                    .collect();
+    /// Ограничивает размер массивов, словарей и множеств: операция, которая
+    /// создала бы коллекцию больше `limit`, завершается ошибкой.
+    pub fn with_max_collection_size(mut self, limit: usize) -> Self {
+        self.max_collection_size = Some(limit);
+        self
+    }
 
-                let arg_ids: Vec<_> = arg_edges.iter().map(|e| e.target_node_id).collect();
-                let mut arg_values = Vec::with_capacity(arg_ids.len());
-                for arg_id in &arg_ids {
-                    let arg_val = self.ensure_evaluated(asg, *arg_id)?;
-                    arg_values.push(arg_val);
-                }
+    /// Ошибка, если коллекция из `len` элементов превышает `max_collection_size`.
+    fn check_collection_size(&self, len: usize) -> ASGResult<()> {
+        match self.max_collection_size {
+            Some(limit) if len > limit => Err(ASGError::InvalidOperation(
+                "collection size limit exceeded".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
 
-                // Пробуем найти именованную функцию
-                if let Some((params, body_id, opt_asg)) = self.functions.get(&func_name).cloned() {
-                    // Именованная функция (возможно из импортированного модуля)
-                    let mut frame = CallFrame::default();
-                    for (i, arg_val) in arg_values.into_iter().enumerate() {
-                        if i < params.len() {
-                            frame.locals.insert(params[i].clone(), arg_val);
-                        }
-                    }
+    /// Целочисленная операция над i64 с учётом [`IntMode`].
+    fn int_arith(&self, op: IntOp, a: i64, b: i64) -> ASGResult<Value> {
+        if let Some(n) = op.checked(a, b) {
+            return Ok(Value::Int(n));
+        }
+        match self.int_mode {
+            IntMode::Checked => Err(ASGError::InvalidOperation("integer overflow".to_string())),
+            IntMode::Wrapping => Ok(Value::Int(op.wrapping(a, b))),
+            #[cfg(feature = "bigint")]
+            IntMode::Promote => op
+                .big(&BigInt::from(a), &BigInt::from(b))
+                .map(normalize_big)
+                .ok_or_else(exponent_too_large),
+        }
+    }
 
-                    let saved_memo = std::mem::take(&mut self.memo);
-                    frame.memo = saved_memo;
-                    self.call_stack.push(frame);
+    /// Сравнение float с допуском: |a - b| <= tol * max(1, |a|, |b|).
+    /// Для малых чисел допуск абсолютный, для больших — относительный.
+    fn floats_equal(&self, a: f64, b: f64) -> bool {
+        if a == b {
+            return true;
+        }
+        // inf и NaN равны только точно: иначе допуск tol * inf принимает всё
+        if !a.is_finite() || !b.is_finite() {
+            return false;
+        }
+        let scale = 1.0f64.max(a.abs()).max(b.abs());
+        (a - b).abs() <= self.float_tolerance * scale
+    }
 
-                    let result = if body_id != 0 {
-                        if let Some(ref imported_asg) = opt_asg {
-                            self.ensure_evaluated(imported_asg, body_id)?
-                        } else {
-                            self.ensure_evaluated(asg, body_id)?
-                        }
-                    } else {
-                        Value::Unit
-                    };
+    /// Заменяет приёмник вывода (print ...) (например, на буфер в тестах).
+    pub fn set_output<F>(&mut self, output: F)
+    where
+        F: FnMut(&str) + 'static,
+    {
+        self.output = Box::new(output);
+    }
 
-                    if let Some(popped_frame) = self.call_stack.pop() {
-                        self.memo = popped_frame.memo;
-                    }
-                    result
-                } else {
-                    // Попробуем вычислить target как значение
-                    let fn_val = self.ensure_evaluated(asg, call_target.target_node_id)?;
-                    match fn_val {
-                        Value::Function {
-                            params,
-                            body_id,
-                            captured,
-                        } => {
-                            let mut frame = CallFrame::default();
-                            for (name, val) in &captured {
-                                frame.locals.insert(name.clone(), val.clone());
-                            }
-                            for (i, arg_val) in arg_values.into_iter().enumerate() {
-                                if i < params.len() {
-                                    frame.locals.insert(params[i].clone(), arg_val);
-                                }
-                            }
+    /// Заменяет приёмник (debug ...), (time ...) и (bench ...), по умолчанию stderr
+    /// (например, на буфер в тестах).
+    pub fn set_debug_output<F>(&mut self, output: F)
+    where
+        F: FnMut(&str) + 'static,
+    {
+        self.debug_output = Box::new(output);
+    }
 
-                            let saved_memo = std::mem::take(&mut self.memo);
-                            frame.memo = saved_memo;
-                            self.call_stack.push(frame);
+    /// Задаёт выполняемый файл: относительные (import ...) разрешаются от его каталога.
+    pub fn set_current_file(&mut self, path: impl Into<PathBuf>) {
+        self.current_file = Some(path.into());
+    }
 
-                            let result = self.ensure_evaluated(asg, body_id)?;
+    /// Добавляет каталог поиска для (import ...), проверяемый после каталога текущего файла.
+    pub fn add_search_path(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        if !self.search_paths.contains(&path) {
+            self.search_paths.push(path);
+        }
+    }
 
-                            if let Some(popped_frame) = self.call_stack.pop() {
-                                self.memo = popped_frame.memo;
-                            }
-                            result
-                        }
-                        Value::ComposedFunction(_) => {
-                            // Для ComposedFunction используем первый аргумент
-                            let arg = arg_values.into_iter().next().unwrap_or(Value::Unit);
-                            self.call_function_value(asg, fn_val, arg)?
-                        }
-                        _ => return Err(ASGError::UnknownFunction(func_name)),
-                    }
-                }
-            }
+    /// Задаёт каталог установленных пакетов (по умолчанию — .asg/packages в каталоге
+    /// выполняемого файла или его предках).
+    pub fn set_packages_dir(&mut self, path: impl Into<PathBuf>) {
+        self.packages_dir = Some(path.into());
+    }
 
-            NodeType::Return => {
-                if let Some(edge) = node.find_edge(EdgeType::ReturnValue) {
-                    self.ensure_evaluated(asg, edge.target_node_id)?
-                } else {
-                    Value::Unit
-                }
-            }
+    /// Разрешает путь импорта: "pkg:name" — среди установленных пакетов; иначе каталог
+    /// импортирующего файла, затем пути поиска, затем текущий рабочий каталог.
+    fn resolve_import_path(&self, path: &str) -> Option<PathBuf> {
+        let importer_dir = self
+            .importing
+            .last()
+            .or(self.current_file.as_ref())
+            .and_then(|file| file.parent());
+
+        if path.starts_with(PACKAGE_PREFIX) {
+            let packages_dir = self.packages_dir.clone().or_else(|| {
+                let start = match importer_dir {
+                    Some(dir) => dir.to_path_buf(),
+                    None => std::env::current_dir().ok()?,
+                };
+                ModuleResolver::find_packages_dir(&start)
+            })?;
+            let mut resolver = ModuleResolver::with_search_paths(Vec::new());
+            resolver.set_packages_dir(packages_dir);
+            resolver.set_strategy(ResolveStrategy::Registry);
+            return resolver.resolve(path).ok();
+        }
 
-            // === Тензорные операции ===
-            NodeType::TensorAdd => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Tensor(a), Value::Tensor(b)) => Value::Tensor(tensor_ops::add(&a, &b)),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two Tensors for TensorAdd".to_string(),
-                        ))
-                    }
-                }
-            }
+        let path = Path::new(path);
+        if path.is_absolute() {
+            return path.exists().then(|| path.to_path_buf());
+        }
 
-            NodeType::TensorMul => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Tensor(a), Value::Tensor(b)) => {
-                        // Поэлементное умножение
-                        let lhs = a.data.borrow();
-                        let rhs = b.data.borrow();
-                        let result_data = &*lhs * &*rhs;
-                        Value::Tensor(DifferentiableTensor::new(result_data, false))
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two Tensors for TensorMul".to_string(),
-                        ))
-                    }
-                }
+        importer_dir
+            .into_iter()
+            .chain(self.search_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(path))
+            .chain(std::iter::once(path.to_path_buf()))
+            .find(|candidate| candidate.exists())
+    }
+
+    /// Заменяет часы, используемые (time ...) и (bench ...) (например, на фиктивные в тестах).
+    pub fn set_clock<F>(&mut self, clock: F)
+    where
+        F: Fn() -> Duration + 'static,
+    {
+        self.clock = Box::new(clock);
+    }
+
+    /// Регистрирует обработчик для узлов (ext:name ...).
+    /// Возвращает код расширения; ошибка, если код уже занят другим именем.
+    pub fn register_extension<F>(&mut self, name: &str, handler: F) -> ASGResult<u16>
+    where
+        F: Fn(&[Value]) -> ASGResult<Value> + 'static,
+    {
+        let code = extension_code(name);
+        if let Some((existing, _)) = self.extensions.get(&code) {
+            if existing != name {
+                return Err(ASGError::InvalidOperation(format!(
+                    "Extension code {} for '{}' is already used by '{}'",
+                    code, name, existing
+                )));
             }
+        }
+        self.extensions
+            .insert(code, (name.to_string(), Box::new(handler)));
+        Ok(code)
+    }
 
-            // === Массивы ===
-            NodeType::Array => {
-                let element_ids: Vec<_> = node
-                    .find_edges(EdgeType::ArrayElement)
-                    .into_iter()
-                    .map(|e| e.target_node_id)
-                    .collect();
-                let mut elements = Vec::new();
-                for elem_id in element_ids {
-                    let elem_val = self.ensure_evaluated(asg, elem_id)?;
-                    elements.push(elem_val);
-                }
-                Value::Array(elements)
+    /// Изменяемая ссылка на переменную: локальные фреймы (от вершины), затем глобальные.
+    fn resolve_variable_mut(&mut self, name: &str) -> Option<&mut Value> {
+        for frame in self.call_stack.iter_mut().rev() {
+            if let Some(val) = frame.locals.get_mut(name) {
+                return Some(val);
             }
+        }
+        self.variables.get_mut(name)
+    }
+
+    /// Изменить переменную там, откуда её читает `resolve_variable`: локальные кадры,
+    /// переменные текущего модуля, глобальные. Общая ячейка меняется на месте.
+    /// None — переменной нет.
+    fn with_variable_mut<R>(&mut self, name: &str, f: impl FnOnce(&mut Value) -> R) -> Option<R> {
+        let local = self
+            .call_stack
+            .iter()
+            .any(|frame| frame.locals.contains_key(name));
+        let module_cell = match self.modules.last().and_then(|m| m.variables.get(name)) {
+            Some(Value::Cell(cell)) if !local => Some(cell.clone()),
+            _ => None,
+        };
+        let cell = match module_cell {
+            Some(cell) => cell,
+            None => match self.resolve_variable_mut(name)? {
+                Value::Cell(cell) => cell.clone(),
+                slot => return Some(f(slot)),
+            },
+        };
+        let mut value = cell.0.borrow_mut();
+        Some(f(&mut value))
+    }
+
+    /// Привязка (let name value): в текущий кадр (блок, вызов, итерация), а вне
+    /// кадров — глобальная. Привязка исчезает вместе с кадром.
+    fn bind_variable(&mut self, name: String, value: Value) {
+        match self.call_stack.last_mut() {
+            Some(frame) => frame.locals.insert(name, value),
+            None => self.variables.insert(name, value),
+        };
+    }
 
+    /// Разобрать lvalue из (set place value) в имя переменной и путь внутри неё.
+    /// Ключи вычисляются от корня к листу, слева направо.
+    fn resolve_place(&mut self, asg: &ASG, node_id: NodeID) -> ASGResult<(String, Vec<PlaceStep>)> {
+        let node = asg
+            .find_node(node_id)
+            .ok_or(ASGError::NodeNotFound(node_id))?
+            .clone();
+        match node.node_type {
+            NodeType::VarRef => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                Ok((name, Vec::new()))
+            }
             NodeType::ArrayIndex => {
-                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
+                let base_edge = node.edges.first().ok_or(ASGError::MissingEdge(
                     node.id,
                     EdgeType::ApplicationArgument,
                 ))?;
                 let index_edge = node
                     .find_edge(EdgeType::ArrayIndexExpr)
                     .ok_or(ASGError::MissingEdge(node.id, EdgeType::ArrayIndexExpr))?;
-
-                let array_id = array_edge.target_node_id;
-                let index_id = index_edge.target_node_id;
-                let array_val = self.ensure_evaluated(asg, array_id)?;
-                let index_val = self.ensure_evaluated(asg, index_id)?;
-
-                match (&array_val, &index_val) {
-                    (Value::Array(arr), Value::Int(idx)) => {
-                        let idx = *idx as usize;
-                        arr.get(idx)
-                            .cloned()
-                            .ok_or(ASGError::InvalidOperation(format!(
-                                "Array index {} out of bounds",
-                                idx
-                            )))?
+                let (name, mut path) = self.resolve_place(asg, base_edge.target_node_id)?;
+                match self.ensure_evaluated(asg, index_edge.target_node_id)? {
+                    Value::Int(i) if i >= 0 => path.push(PlaceStep::Index(i as usize)),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Index must be a non-negative integer".to_string(),
+                        ))
                     }
+                }
+                Ok((name, path))
+            }
+            NodeType::DictGet => {
+                let (base_id, key_id) = self.get_binary_operand_ids(&node)?;
+                let (name, mut path) = self.resolve_place(asg, base_id)?;
+                match self.ensure_evaluated(asg, key_id)? {
+                    Value::String(key) => path.push(PlaceStep::Key(key)),
+                    Value::Int(n) => path.push(PlaceStep::Key(n.to_string())),
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected array and integer index".to_string(),
+                            "Dict key must be string or integer".to_string(),
                         ))
                     }
                 }
+                Ok((name, path))
             }
+            NodeType::RecordField => {
+                let base_edge = node
+                    .find_edge(EdgeType::RecordFieldAccess)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::RecordFieldAccess))?;
+                let field = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let (name, mut path) = self.resolve_place(asg, base_edge.target_node_id)?;
+                path.push(PlaceStep::Field(field));
+                Ok((name, path))
+            }
+            other => Err(ASGError::InvalidOperation(format!(
+                "Invalid assignment target: {:?}",
+                other
+            ))),
+        }
+    }
 
-            NodeType::ArrayLength => {
-                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
-                    node.id,
-                    EdgeType::ApplicationArgument,
-                ))?;
-
-                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
-
-                match &array_val {
-                    Value::Array(arr) => Value::Int(arr.len() as i64),
-                    _ => return Err(ASGError::TypeError("Expected array for length".to_string())),
-                }
+    /// Разрешает переменную с приоритетом стека вызовов.
+    /// Сначала проверяет локальные переменные в call_stack (от вершины к основанию),
+    /// затем определения текущего модуля и глобальные переменные.
+    fn resolve_variable(&self, name: &str) -> Option<&Value> {
+        // Сначала проверяем стек вызовов (от вершины к основанию)
+        for frame in self.call_stack.iter().rev() {
+            if let Some(val) = frame.locals.get(name) {
+                return Some(val);
             }
+        }
+        // Затем определения модуля, из которого вызвана функция
+        if let Some(val) = self.modules.last().and_then(|m| m.variables.get(name)) {
+            return Some(val);
+        }
+        // Fallback на глобальные переменные
+        self.variables.get(name)
+    }
 
-            NodeType::ArrayLast => {
-                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
-                    node.id,
-                    EdgeType::ApplicationArgument,
-                ))?;
+    /// Значение переменной с разыменованием общей ячейки.
+    fn read_variable(&self, name: &str) -> Option<Value> {
+        self.resolve_variable(name).map(|val| match val {
+            Value::Cell(cell) => cell.0.borrow().clone(),
+            other => other.clone(),
+        })
+    }
 
-                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
+    /// Найти функцию: сначала локальные области (от внутренней к внешней),
+    /// затем текущий импортированный модуль, затем глобальные.
+    fn resolve_function(&self, name: &str) -> Option<&FunctionEntry> {
+        self.function_scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.modules.last().and_then(|m| m.functions.get(name)))
+            .or_else(|| self.functions.get(name))
+    }
 
-                match array_val {
-                    Value::Array(arr) => arr.last().cloned().unwrap_or(Value::Unit),
-                    _ => return Err(ASGError::TypeError("Expected array for last".to_string())),
-                }
-            }
+    /// Видимые локальные функции как замыкания в общих ячейках. Каждое замыкание
+    /// захватывает ячейки всех этих функций, включая свою, поэтому рекурсия и
+    /// взаимные вызовы работают и после выхода замыкания из блока.
+    fn local_function_cells(&self) -> HashMap<String, CellRef> {
+        let mut visible = HashMap::new();
+        for scope in &self.function_scopes {
+            visible.extend(scope.iter());
+        }
+        let cells: HashMap<String, CellRef> = visible
+            .keys()
+            .map(|name| ((*name).clone(), CellRef(Rc::new(RefCell::new(Value::Unit)))))
+            .collect();
+        let captured: HashMap<String, Value> = cells
+            .iter()
+            .map(|(name, cell)| (name.clone(), Value::Cell(cell.clone())))
+            .collect();
+        for (name, (params, body_id, _)) in visible {
+            *cells[name].0.borrow_mut() = Value::Function {
+                params: params.clone(),
+                body_id: *body_id,
+                captured: captured.clone(),
+            };
+        }
+        cells
+    }
 
-            NodeType::ArraySetIndex => {
-                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
-                    node.id,
-                    EdgeType::ApplicationArgument,
-                ))?;
-                let index_edge = node
-                    .find_edge(EdgeType::ArrayIndexExpr)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ArrayIndexExpr))?;
-                let value_edge = node
-                    .find_edge(EdgeType::AssignValue)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignValue))?;
+    /// Значение локальной функции `name`, если она видна.
+    fn local_function_value(&self, name: &str) -> Option<Value> {
+        if !self
+            .function_scopes
+            .iter()
+            .any(|scope| scope.contains_key(name))
+        {
+            return None;
+        }
+        let cells = self.local_function_cells();
+        let value = cells[name].0.borrow().clone();
+        Some(value)
+    }
 
-                // Получаем имя переменной массива
-                let array_node = asg
-                    .find_node(array_edge.target_node_id)
-                    .ok_or(ASGError::NodeNotFound(array_edge.target_node_id))?;
-                let var_name = array_node
-                    .get_name()
-                    .ok_or(ASGError::MissingPayload(array_node.id))?;
+    /// Выполнить `f` в новой области видимости локальных функций.
+    fn with_function_scope<T>(
+        &mut self,
+        scope: HashMap<String, FunctionEntry>,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> (T, HashMap<String, FunctionEntry>) {
+        self.function_scopes.push(scope);
+        let result = f(self);
+        let scope = self.function_scopes.pop().unwrap_or_default();
+        (result, scope)
+    }
 
-                let index_val = self.ensure_evaluated(asg, index_edge.target_node_id)?;
-                let new_value = self.ensure_evaluated(asg, value_edge.target_node_id)?;
+    /// Выполняет ASG, вычисляя узлы по требованию начиная с корневого.
+    pub fn execute(&mut self, asg: &ASG, root_id: NodeID) -> ASGResult<Value> {
+        self.reset_loop_analysis();
+        // Оцениваем только корневой узел, остальные по требованию
+        let result = self.ensure_evaluated(asg, root_id);
+        self.outside_loop(result)
+    }
 
-                let idx = match &index_val {
-                    Value::Int(i) => *i as usize,
-                    _ => return Err(ASGError::TypeError("Index must be integer".to_string())),
-                };
+    /// Выполняет `root_id`, собирая вывод (print ...) в строку вместо приёмника вывода.
+    ///
+    /// Каждая напечатанная строка завершается `\n`. Прежний приёмник восстанавливается
+    /// и после ошибки.
+    pub fn run_capturing(&mut self, asg: &ASG, root_id: NodeID) -> ASGResult<(Value, String)> {
+        let captured = Rc::new(RefCell::new(String::new()));
+        let sink = Rc::clone(&captured);
+        let previous = std::mem::replace(
+            &mut self.output,
+            Box::new(move |line| {
+                let mut text = sink.borrow_mut();
+                text.push_str(line);
+                text.push('\n');
+            }),
+        );
+        let result = self.execute(asg, root_id);
+        self.output = previous;
+        let value = result?;
+        let text = captured.take();
+        Ok((value, text))
+    }
 
-                // Мутируем массив в переменной
-                if let Some(Value::Array(ref mut arr)) = self.variables.get_mut(&var_name) {
-                    if idx < arr.len() {
-                        arr[idx] = new_value;
-                    } else {
-                        return Err(ASGError::InvalidOperation(format!(
-                            "Array index {} out of bounds",
-                            idx
-                        )));
-                    }
-                } else {
-                    return Err(ASGError::TypeError(
-                        "Expected array variable for set-index".to_string(),
-                    ));
+    /// Вызывает значение-функцию (Function или ComposedFunction) из Rust-кода.
+    /// `asg` должен быть графом, в котором определено тело функции.
+    /// Для ComposedFunction аргументы получает первая функция, остальные — результат предыдущей.
+    pub fn call(&mut self, asg: &ASG, f: &Value, args: &[Value]) -> ASGResult<Value> {
+        match f {
+            Value::Function {
+                params,
+                body_id,
+                captured,
+            } => {
+                let args = args.iter().cloned().map(Some).collect();
+                self.invoke(asg, params, *body_id, captured, args)
+            }
+            Value::ComposedFunction(fns) => {
+                let mut fns = fns.iter();
+                let mut current = match fns.next() {
+                    Some(first) => self.call(asg, first, args)?,
+                    None => return Ok(args.first().cloned().unwrap_or(Value::Unit)),
+                };
+                for f in fns {
+                    current = self.call(asg, f, std::slice::from_ref(&current))?;
                 }
-                Value::Unit
+                Ok(current)
             }
+            Value::Builtin(name) => self.call_builtin(name, args),
+            _ => Err(ASGError::TypeError("Expected function".to_string())),
+        }
+    }
 
-            // === Higher-order array functions ===
-            NodeType::ArrayMap => {
-                let array_edge = node
-                    .find_edge(EdgeType::SourceArray)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SourceArray))?;
-                let fn_edge = node
-                    .find_edge(EdgeType::MapFunction)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::MapFunction))?;
-
-                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
-                let fn_val = self.ensure_evaluated(asg, fn_edge.target_node_id)?;
+    /// Вызвать встроенную функцию-значение: её форма из [`BUILTIN_FUNCTIONS`]
+    /// вычисляется в отдельном графе с параметрами, связанными с `args`.
+    fn call_builtin(&mut self, name: &'static str, args: &[Value]) -> ASGResult<Value> {
+        let (_, params, source) = BUILTIN_FUNCTIONS
+            .iter()
+            .find(|(builtin, _, _)| *builtin == name)
+            .ok_or_else(|| ASGError::UnknownVariable(name.to_string()))?;
+        if args.len() != params.len() {
+            return Err(ASGError::TypeError(format!(
+                "{} expects {} arguments, got {}",
+                name,
+                params.len(),
+                args.len()
+            )));
+        }
+        let body = match self.builtin_bodies.get(name) {
+            Some(body) => Rc::clone(body),
+            None => {
+                let body = Rc::new(crate::parser::parse_expr(source)?);
+                self.builtin_bodies.insert(name, Rc::clone(&body));
+                body
+            }
+        };
+        let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+        let args = args.iter().cloned().map(Some).collect();
+        self.invoke(&body.0, &params, body.1, &HashMap::new(), args)
+    }
 
-                let arr = match &array_val {
-                    Value::Array(a) => a.clone(),
-                    _ => return Err(ASGError::TypeError("Expected array for map".to_string())),
-                };
+    /// Вычисляет тело функции в новом кадре: `args[i]` связывается с `params[i]`.
+    /// Параметр без аргумента (`None` или нехватка аргументов) получает значение
+    /// по умолчанию, если оно задано: `(fn f (x (y 10)) ...)`.
+    fn invoke(
+        &mut self,
+        asg: &ASG,
+        params: &[String],
+        body_id: NodeID,
+        captured: &HashMap<String, Value>,
+        args: Vec<Option<Value>>,
+    ) -> ASGResult<Value> {
+        let mut frame = CallFrame::default();
+        for (name, val) in captured {
+            frame.locals.insert(name.clone(), val.clone());
+        }
+        let mut missing = Vec::new();
+        let mut args = args.into_iter();
+        for param in params {
+            match args.next().flatten() {
+                Some(val) => {
+                    frame.locals.insert(param.clone(), val);
+                }
+                None => missing.push(param.as_str()),
+            }
+        }
 
-                let (params, body_id, captured) = match &fn_val {
-                    Value::Function {
-                        params,
-                        body_id,
-                        captured,
-                    } => (params.clone(), *body_id, captured.clone()),
-                    _ => return Err(ASGError::TypeError("Expected function for map".to_string())),
-                };
+        frame.memo = std::mem::take(&mut self.memo);
+        self.call_stack.push(frame);
 
-                let mut result = Vec::with_capacity(arr.len());
-                for elem in arr {
-                    // Создаём frame для вызова функции
-                    let saved_memo = std::mem::take(&mut self.memo);
-                    let mut frame = CallFrame::default();
-                    // Добавляем captured переменные (closure)
-                    for (name, val) in &captured {
-                        frame.locals.insert(name.clone(), val.clone());
-                    }
-                    if !params.is_empty() {
-                        frame.locals.insert(params[0].clone(), elem);
-                    }
-                    frame.memo = saved_memo;
-                    self.call_stack.push(frame);
+        let (result, _) = self.with_function_scope(HashMap::new(), |this| {
+            this.bind_param_defaults(asg, body_id, &missing)
+                .and_then(|()| this.eval_function_body(asg, body_id))
+        });
 
-                    let mapped = self.ensure_evaluated(asg, body_id)?;
-                    result.push(mapped);
+        if let Some(popped_frame) = self.call_stack.pop() {
+            self.memo = popped_frame.memo;
+        }
+        // (break) и (continue) не выходят из тела функции в цикл вызывающего кода
+        self.outside_loop(result)
+    }
 
-                    if let Some(popped_frame) = self.call_stack.pop() {
-                        self.memo = popped_frame.memo;
-                    }
-                }
-                Value::Array(result)
+    /// Сигнал цикла, дошедший до границы функции или программы, — ошибка.
+    fn outside_loop(&mut self, result: ASGResult<Value>) -> ASGResult<Value> {
+        match result {
+            Err(err @ (ASGError::LoopBreak | ASGError::LoopContinue)) => {
+                self.break_value = None;
+                Err(ASGError::InvalidOperation(err.to_string()))
             }
+            other => other,
+        }
+    }
 
-            NodeType::ArrayFilter => {
-                let array_edge = node
-                    .find_edge(EdgeType::SourceArray)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SourceArray))?;
-                let pred_edge = node
-                    .find_edge(EdgeType::FilterPredicate)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::FilterPredicate))?;
+    /// Вычислить тело функции и проверить его `(ensures cond)`. Тело-блок
+    /// выполняется здесь же, и условия проверяются до выхода из него: им видны `let` тела.
+    fn eval_function_body(&mut self, asg: &ASG, body_id: NodeID) -> ASGResult<Value> {
+        if body_id == 0 {
+            return Ok(Value::Unit);
+        }
+        let body = asg
+            .find_node(body_id)
+            .ok_or(ASGError::NodeNotFound(body_id))?;
+        if body.node_type != NodeType::Block {
+            let result = self.ensure_evaluated(asg, body_id)?;
+            return self.check_postconditions(asg, body_id, result);
+        }
+        let stmts: Vec<NodeID> = body
+            .find_edges(EdgeType::BlockStatement)
+            .into_iter()
+            .map(|e| e.target_node_id)
+            .collect();
+        self.call_stack.push(CallFrame::default());
+        let (result, _) = self.with_function_scope(HashMap::new(), |this| {
+            stmts
+                .into_iter()
+                .try_fold(Value::Unit, |_, id| this.ensure_evaluated(asg, id))
+                .and_then(|result| this.check_postconditions(asg, body_id, result))
+        });
+        self.call_stack.pop();
+        result
+    }
 
-                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
-                let pred_val = self.ensure_evaluated(asg, pred_edge.target_node_id)?;
+    /// Проверить `(ensures cond)` тела функции: `result` связывается с
+    /// результатом вызова в текущем кадре.
+    fn check_postconditions(
+        &mut self,
+        asg: &ASG,
+        body_id: NodeID,
+        result: Value,
+    ) -> ASGResult<Value> {
+        let conditions = crate::proof::contract_conditions(asg, body_id, NodeType::Ensures);
+        if conditions.is_empty() {
+            return Ok(result);
+        }
+        if let Some(frame) = self.call_stack.last_mut() {
+            frame.locals.insert("result".to_string(), result.clone());
+        }
+        for cond_id in conditions {
+            let holds = self.ensure_evaluated(asg, cond_id)?;
+            contract_holds(holds, "Postcondition")?;
+        }
+        Ok(result)
+    }
 
-                let arr = match &array_val {
-                    Value::Array(a) => a.clone(),
-                    _ => return Err(ASGError::TypeError("Expected array for filter".to_string())),
-                };
+    /// Связать в текущем кадре значения по умолчанию пропущенных параметров.
+    /// Умолчания вычисляются по порядку и видят уже связанные параметры.
+    fn bind_param_defaults(
+        &mut self,
+        asg: &ASG,
+        body_id: NodeID,
+        missing: &[&str],
+    ) -> ASGResult<()> {
+        if missing.is_empty() || body_id == 0 {
+            return Ok(());
+        }
+        // Определение находим по ребру к телу: умолчания — рёбра его параметров
+        let Some(def) = asg.nodes.iter().find(|n| {
+            matches!(n.node_type, NodeType::Function | NodeType::Lambda)
+                && n.find_edge(EdgeType::FunctionBody)
+                    .is_some_and(|e| e.target_node_id == body_id)
+        }) else {
+            return Ok(());
+        };
+        for edge in def.find_edges(EdgeType::FunctionParameter) {
+            let Some(param) = asg.find_node(edge.target_node_id) else {
+                continue;
+            };
+            let (Some(name), Some(default)) = (
+                param.get_name(),
+                param.find_edge(EdgeType::ParameterDefault),
+            ) else {
+                continue;
+            };
+            if missing.contains(&name.as_str()) {
+                let value = self.ensure_evaluated(asg, default.target_node_id)?;
+                if let Some(frame) = self.call_stack.last_mut() {
+                    frame.locals.insert(name, value);
+                }
+            }
+        }
+        Ok(())
+    }
 
-                let (params, body_id, captured) = match &pred_val {
-                    Value::Function {
-                        params,
-                        body_id,
-                        captured,
-                    } => (params.clone(), *body_id, captured.clone()),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected function for filter".to_string(),
-                        ))
-                    }
-                };
+    /// Вычисляет значение для одного узла и сохраняет его в кэш.
+    fn eval_node(&mut self, asg: &ASG, node: &Node) -> ASGResult<()> {
+        if self.memo.contains_key(&node.id) {
+            return Ok(());
+        }
 
-                let mut result = Vec::new();
-                for elem in arr {
-                    // Создаём frame для вызова предиката
-                    let saved_memo = std::mem::take(&mut self.memo);
-                    let mut frame = CallFrame::default();
-                    // Добавляем captured переменные (closure)
-                    for (name, val) in &captured {
-                        frame.locals.insert(name.clone(), val.clone());
-                    }
-                    if !params.is_empty() {
-                        frame.locals.insert(params[0].clone(), elem.clone());
-                    }
-                    frame.memo = saved_memo;
-                    self.call_stack.push(frame);
+        let result_value = match node.node_type {
+            // === Литералы ===
+            NodeType::LiteralInt => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let bytes: [u8; 8] = payload
+                    .clone()
+                    .try_into()
+                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
+                Value::Int(i64::from_le_bytes(bytes))
+            }
 
-                    let pred_result = self.ensure_evaluated(asg, body_id)?;
+            #[cfg(feature = "bigint")]
+            NodeType::LiteralBigInt => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let n = std::str::from_utf8(payload)
+                    .ok()
+                    .and_then(|s| s.parse::<BigInt>().ok())
+                    .ok_or(ASGError::InvalidPayload(node.id))?;
+                Value::BigInt(n)
+            }
 
-                    if let Some(popped_frame) = self.call_stack.pop() {
-                        self.memo = popped_frame.memo;
-                    }
+            #[cfg(feature = "decimal")]
+            NodeType::LiteralDecimal => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let d = std::str::from_utf8(payload)
+                    .ok()
+                    .and_then(|s| s.parse::<Decimal>().ok())
+                    .ok_or(ASGError::InvalidPayload(node.id))?;
+                Value::Decimal(d)
+            }
 
-                    if let Value::Bool(true) = pred_result {
-                        result.push(elem);
-                    }
-                }
-                Value::Array(result)
+            NodeType::LiteralFloat => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let bytes: [u8; 8] = payload
+                    .clone()
+                    .try_into()
+                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
+                Value::Float(f64::from_le_bytes(bytes))
             }
 
-            NodeType::ArrayReduce => {
-                let array_edge = node
-                    .find_edge(EdgeType::SourceArray)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SourceArray))?;
-                let init_edge = node
-                    .find_edge(EdgeType::ReduceInit)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ReduceInit))?;
-                let fn_edge = node
-                    .find_edge(EdgeType::ReduceFunction)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ReduceFunction))?;
+            NodeType::LiteralBool => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let val = payload.first().map(|&b| b != 0).unwrap_or(false);
+                Value::Bool(val)
+            }
 
-                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
-                let init_val = self.ensure_evaluated(asg, init_edge.target_node_id)?;
-                let fn_val = self.ensure_evaluated(asg, fn_edge.target_node_id)?;
+            NodeType::LiteralString => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let s = String::from_utf8(payload.clone())
+                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
+                Value::String(s)
+            }
 
-                let arr = match &array_val {
-                    Value::Array(a) => a.clone(),
-                    _ => return Err(ASGError::TypeError("Expected array for reduce".to_string())),
-                };
+            NodeType::LiteralUnit => Value::Unit,
 
-                let (params, body_id, captured) = match &fn_val {
-                    Value::Function {
-                        params,
-                        body_id,
-                        captured,
-                    } => (params.clone(), *body_id, captured.clone()),
+            NodeType::LiteralTensor => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let bytes: [u8; 4] = payload
+                    .clone()
+                    .try_into()
+                    .map_err(|_| ASGError::InvalidPayload(node.id))?;
+                let val = f32::from_le_bytes(bytes);
+                let tensor = DifferentiableTensor::new(ndarray::arr0(val).into_dyn(), true);
+                Value::Tensor(tensor)
+            }
+
+            // === Арифметические операции ===
+            NodeType::BinaryOperation => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => self.int_arith(IntOp::Add, a, b)?,
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
+                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64) + b),
+                    (Value::Float(a), Value::Int(b)) => Value::Float(a + (b as f64)),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        big_arith(IntOp::Add, &a, &b)?
+                    }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        decimal_arith("Add", &a, &b, Decimal::checked_add)?
+                    }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected function for reduce".to_string(),
+                            "Expected two numbers for BinaryOperation".to_string(),
                         ))
                     }
-                };
+                }
+            }
 
-                let mut acc = init_val;
-                for elem in arr {
-                    // Создаём frame для вызова функции с acc и elem
-                    let saved_memo = std::mem::take(&mut self.memo);
-                    let mut frame = CallFrame::default();
-                    // Добавляем captured переменные (closure)
-                    for (name, val) in &captured {
-                        frame.locals.insert(name.clone(), val.clone());
-                    }
-                    if params.len() >= 1 {
-                        frame.locals.insert(params[0].clone(), acc);
+            NodeType::Sub => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => self.int_arith(IntOp::Sub, a, b)?,
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a - b),
+                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64) - b),
+                    (Value::Float(a), Value::Int(b)) => Value::Float(a - (b as f64)),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        big_arith(IntOp::Sub, &a, &b)?
                     }
-                    if params.len() >= 2 {
-                        frame.locals.insert(params[1].clone(), elem);
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        decimal_arith("Sub", &a, &b, Decimal::checked_sub)?
                     }
-                    frame.memo = saved_memo;
-                    self.call_stack.push(frame);
-
-                    acc = self.ensure_evaluated(asg, body_id)?;
-
-                    if let Some(popped_frame) = self.call_stack.pop() {
-                        self.memo = popped_frame.memo;
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected two numbers for Sub".to_string(),
+                        ))
                     }
                 }
-                acc
             }
 
-            NodeType::ListComprehension => {
-                // (list-comp expr var iter [condition])
-                let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
-
-                let expr_edge = node
-                    .find_edge(EdgeType::MapFunction)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::MapFunction))?;
-                let iter_edge = node
-                    .find_edge(EdgeType::LoopInit)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopInit))?;
-                let cond_edge = node.find_edge(EdgeType::Condition);
-
-                let iter_val = self.ensure_evaluated(asg, iter_edge.target_node_id)?;
-
-                let arr = match &iter_val {
-                    Value::Array(a) => a.clone(),
+            NodeType::Mul => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => self.int_arith(IntOp::Mul, a, b)?,
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
+                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64) * b),
+                    (Value::Float(a), Value::Int(b)) => Value::Float(a * (b as f64)),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        big_arith(IntOp::Mul, &a, &b)?
+                    }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        decimal_arith("Mul", &a, &b, Decimal::checked_mul)?
+                    }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected array for list comprehension".to_string(),
+                            "Expected two numbers for Mul".to_string(),
                         ))
                     }
-                };
+                }
+            }
 
-                let mut result = Vec::new();
-                for elem in arr {
-                    // Создаём frame для итерации
-                    let saved_memo = std::mem::take(&mut self.memo);
-                    let mut frame = CallFrame::default();
-                    frame.locals.insert(var_name.clone(), elem);
-                    frame.memo = saved_memo;
-                    self.call_stack.push(frame);
-
-                    // Проверяем условие (если есть)
-                    let include = if let Some(cond) = &cond_edge {
-                        let cond_val = self.ensure_evaluated(asg, cond.target_node_id)?;
-                        match cond_val {
-                            Value::Bool(b) => b,
-                            _ => true,
+            NodeType::Div => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => {
+                        if b == 0 {
+                            return Err(ASGError::InvalidOperation("Division by zero".to_string()));
                         }
-                    } else {
-                        true
-                    };
-
-                    if include {
-                        let expr_val = self.ensure_evaluated(asg, expr_edge.target_node_id)?;
-                        result.push(expr_val);
+                        // True division returns float
+                        Value::Float(a as f64 / b as f64)
+                    }
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a / b),
+                    (Value::Int(a), Value::Float(b)) => Value::Float(a as f64 / b),
+                    (Value::Float(a), Value::Int(b)) => Value::Float(a / b as f64),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        // Нормализованный BigInt не бывает нулём: проверяем только Int
+                        if b == Value::Int(0) {
+                            return Err(ASGError::InvalidOperation("Division by zero".to_string()));
+                        }
+                        match (big_to_f64(&a), big_to_f64(&b)) {
+                            (Some(x), Some(y)) => Value::Float(x / y),
+                            _ => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Div".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        decimal_arith("Div", &a, &b, Decimal::checked_div)?
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected two numbers for Div".to_string(),
+                        ))
                     }
+                }
+            }
 
-                    if let Some(popped_frame) = self.call_stack.pop() {
-                        self.memo = popped_frame.memo;
+            NodeType::Mod => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => {
+                        if b == 0 {
+                            return Err(ASGError::InvalidOperation("Modulo by zero".to_string()));
+                        }
+                        self.int_arith(IntOp::Mod, a, b)?
+                    }
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        big_arith(IntOp::Mod, &a, &b)?
+                    }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        decimal_arith("Mod", &a, &b, Decimal::checked_rem)?
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected two integers for Mod".to_string(),
+                        ))
                     }
                 }
-                Value::Array(result)
             }
 
-            // === Lazy Sequences ===
-            NodeType::Iterate => {
-                // (iterate fn init) -> lazy [init, fn(init), fn(fn(init)), ...]
-                let (fn_val, init_val) = self.get_binary_operands(asg, node)?;
-                Value::LazySeq(Box::new(LazySeqKind::Iterate {
-                    func: Box::new(fn_val),
-                    current: Box::new(init_val),
-                }))
+            NodeType::IntDiv => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => {
+                        if b == 0 {
+                            return Err(ASGError::InvalidOperation("Division by zero".to_string()));
+                        }
+                        self.int_arith(IntOp::IntDiv, a, b)?
+                    }
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        big_arith(IntOp::IntDiv, &a, &b)?
+                    }
+                    (Value::Float(a), Value::Float(b)) => {
+                        if b == 0.0 {
+                            return Err(ASGError::InvalidOperation("Division by zero".to_string()));
+                        }
+                        Value::Int((a / b).floor() as i64)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected two numbers for IntDiv".to_string(),
+                        ))
+                    }
+                }
             }
 
-            NodeType::Repeat => {
-                // (repeat val) -> lazy [val, val, val, ...]
+            NodeType::Neg => {
                 let val = self.get_single_operand(asg, node)?;
-                Value::LazySeq(Box::new(LazySeqKind::Repeat(Box::new(val))))
+                match val {
+                    // 0 - a переполняется ровно при a = i64::MIN
+                    Value::Int(a) => self.int_arith(IntOp::Sub, 0, a)?,
+                    #[cfg(feature = "bigint")]
+                    Value::BigInt(a) => normalize_big(-a),
+                    #[cfg(feature = "decimal")]
+                    Value::Decimal(a) => Value::Decimal(-a),
+                    Value::Float(a) => Value::Float(-a),
+                    _ => return Err(ASGError::TypeError("Expected number for Neg".to_string())),
+                }
             }
 
-            NodeType::Cycle => {
-                // (cycle arr) -> lazy [a,b,c,a,b,c,...]
+            NodeType::Cast => {
+                let target = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
                 let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Array(arr) => {
-                        Value::LazySeq(Box::new(LazySeqKind::Cycle { arr, index: 0 }))
+                match (target.as_str(), val) {
+                    ("Float", Value::Int(n)) => Value::Float(n as f64),
+                    ("Float", Value::Float(f)) => Value::Float(f),
+                    ("Int", Value::Float(f)) => Value::Int(f as i64),
+                    ("Int", Value::Int(n)) => Value::Int(n),
+                    #[cfg(feature = "decimal")]
+                    ("Decimal", Value::Decimal(d)) => Value::Decimal(d),
+                    #[cfg(feature = "decimal")]
+                    ("Decimal", Value::Int(n)) => Value::Decimal(Decimal::from(n)),
+                    #[cfg(feature = "decimal")]
+                    ("Decimal", Value::Float(f)) => {
+                        Value::Decimal(Decimal::try_from(f).map_err(|_| {
+                            ASGError::InvalidOperation(format!("{} does not fit in Decimal", f))
+                        })?)
+                    }
+                    #[cfg(feature = "decimal")]
+                    ("Float", Value::Decimal(d)) => {
+                        Value::Float(f64::try_from(d).map_err(|_| {
+                            ASGError::InvalidOperation(format!("{} does not fit in Float", d))
+                        })?)
+                    }
+                    // Дробная часть отбрасывается, как у Float -> Int
+                    #[cfg(feature = "decimal")]
+                    ("Int", Value::Decimal(d)) => {
+                        Value::Int(i64::try_from(d.trunc()).map_err(|_| {
+                            ASGError::InvalidOperation(format!("{} does not fit in Int", d))
+                        })?)
+                    }
+                    #[cfg(feature = "bigint")]
+                    ("Float", Value::BigInt(n)) => Value::Float(n.to_f64().unwrap_or(f64::NAN)),
+                    #[cfg(feature = "bigint")]
+                    ("Int", Value::BigInt(n)) => Value::Int(i64::try_from(&n).map_err(|_| {
+                        ASGError::InvalidOperation(format!("{} does not fit in Int", n))
+                    })?),
+                    (target, other) => {
+                        return Err(ASGError::TypeError(format!(
+                            "Cannot cast {} to {}",
+                            other.format_display(),
+                            target
+                        )))
                     }
-                    _ => return Err(ASGError::TypeError("Expected array for cycle".to_string())),
                 }
             }
 
-            NodeType::LazyRange => {
-                // (lazy-range start end [step])
-                let start_val = self.get_first_operand(asg, node)?;
-                let end_val = self.get_second_operand(asg, node)?;
-                let step_edge = node.find_edge(EdgeType::LoopStep);
-
-                let start = match start_val {
-                    Value::Int(n) => n,
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected integer for lazy-range start".to_string(),
-                        ))
-                    }
-                };
-                let end = match end_val {
-                    Value::Int(n) => n,
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected integer for lazy-range end".to_string(),
-                        ))
-                    }
-                };
-                let step = if let Some(step_e) = step_edge {
-                    let step_val = self.ensure_evaluated(asg, step_e.target_node_id)?;
-                    match step_val {
-                        Value::Int(n) => n,
-                        _ => {
-                            return Err(ASGError::TypeError(
-                                "Expected integer for lazy-range step".to_string(),
-                            ))
-                        }
+            // === Операции сравнения ===
+            NodeType::Eq => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                let result = match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => a == b,
+                    (Value::Float(a), Value::Float(b)) => self.floats_equal(a, b),
+                    (Value::Bool(a), Value::Bool(b)) => a == b,
+                    (Value::String(a), Value::String(b)) => a == b,
+                    (Value::Char(a), Value::Char(b)) => a == b,
+                    (a @ Value::Option(_), b @ Value::Option(_)) => self.values_equal(&a, &b),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        big_cmp(&a, &b) == Some(std::cmp::Ordering::Equal)
                     }
-                } else {
-                    if start <= end {
-                        1
-                    } else {
-                        -1
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        decimal_cmp(&a, &b) == Some(std::cmp::Ordering::Equal)
                     }
+                    _ => false,
                 };
-
-                Value::LazySeq(Box::new(LazySeqKind::Range {
-                    current: start,
-                    end,
-                    step,
-                }))
+                Value::Bool(result)
             }
 
-            NodeType::TakeLazy => {
-                // (take-lazy n seq) -> take n elements from lazy seq
-                let (n_val, seq_val) = self.get_binary_operands(asg, node)?;
-                let n = match n_val {
-                    Value::Int(n) => n as usize,
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected integer for take-lazy count".to_string(),
-                        ))
+            NodeType::Ne => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                let result = match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => a != b,
+                    (Value::Float(a), Value::Float(b)) => !self.floats_equal(a, b),
+                    (Value::Bool(a), Value::Bool(b)) => a != b,
+                    (Value::String(a), Value::String(b)) => a != b,
+                    (Value::Char(a), Value::Char(b)) => a != b,
+                    (a @ Value::Option(_), b @ Value::Option(_)) => !self.values_equal(&a, &b),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        big_cmp(&a, &b) != Some(std::cmp::Ordering::Equal)
                     }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        decimal_cmp(&a, &b) != Some(std::cmp::Ordering::Equal)
+                    }
+                    _ => true,
                 };
+                Value::Bool(result)
+            }
 
-                match seq_val {
-                    Value::LazySeq(kind) => {
-                        let result = self.take_from_lazy(asg, *kind, n)?;
-                        Value::Array(result)
+            NodeType::Lt => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => Value::Bool(a < b),
+                    (Value::Float(a), Value::Float(b)) => Value::Bool(a < b),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        match big_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_lt()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Lt".to_string(),
+                                ))
+                            }
+                        }
                     }
-                    Value::Array(arr) => {
-                        // Поддержка take для обычных массивов тоже
-                        Value::Array(arr.into_iter().take(n).collect())
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        match decimal_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_lt()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Lt".to_string(),
+                                ))
+                            }
+                        }
                     }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected lazy sequence or array for take-lazy".to_string(),
+                            "Expected two numbers for Lt".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::LazyMap => {
-                // (lazy-map fn seq) -> lazy mapped seq
-                let (fn_val, seq_val) = self.get_binary_operands(asg, node)?;
-                match seq_val {
-                    Value::LazySeq(kind) => Value::LazySeq(Box::new(LazySeqKind::Map {
-                        func: Box::new(fn_val),
-                        source: kind,
-                    })),
+            NodeType::Le => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => Value::Bool(a <= b),
+                    (Value::Float(a), Value::Float(b)) => Value::Bool(a <= b),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        match big_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_le()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Le".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        match decimal_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_le()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Le".to_string(),
+                                ))
+                            }
+                        }
+                    }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected lazy sequence for lazy-map".to_string(),
+                            "Expected two numbers for Le".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::LazyFilter => {
-                // (lazy-filter fn seq) -> lazy filtered seq
-                let (fn_val, seq_val) = self.get_binary_operands(asg, node)?;
-                match seq_val {
-                    Value::LazySeq(kind) => Value::LazySeq(Box::new(LazySeqKind::Filter {
-                        func: Box::new(fn_val),
-                        source: kind,
-                    })),
+            NodeType::Gt => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => Value::Bool(a > b),
+                    (Value::Float(a), Value::Float(b)) => Value::Bool(a > b),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        match big_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_gt()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Gt".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        match decimal_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_gt()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Gt".to_string(),
+                                ))
+                            }
+                        }
+                    }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected lazy sequence for lazy-filter".to_string(),
+                            "Expected two numbers for Gt".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::Collect => {
-                // (collect seq) -> materialize lazy seq (limited to 10000 elements)
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::LazySeq(kind) => {
-                        let result = self.take_from_lazy(asg, *kind, 10000)?;
-                        Value::Array(result)
+            NodeType::Ge => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => Value::Bool(a >= b),
+                    (Value::Float(a), Value::Float(b)) => Value::Bool(a >= b),
+                    #[cfg(feature = "bigint")]
+                    (a @ Value::BigInt(_), b) | (a, b @ Value::BigInt(_)) => {
+                        match big_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_ge()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Ge".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                    #[cfg(feature = "decimal")]
+                    (a @ Value::Decimal(_), b) | (a, b @ Value::Decimal(_)) => {
+                        match decimal_cmp(&a, &b) {
+                            Some(o) => Value::Bool(o.is_ge()),
+                            None => {
+                                return Err(ASGError::TypeError(
+                                    "Expected two numbers for Ge".to_string(),
+                                ))
+                            }
+                        }
                     }
-                    Value::Array(arr) => Value::Array(arr),
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected lazy sequence for collect".to_string(),
+                            "Expected two numbers for Ge".to_string(),
                         ))
                     }
                 }
             }
 
-            // === Range and iterators ===
-            NodeType::Range => {
-                let start_val = self.get_first_operand(asg, node)?;
-                let end_val = self.get_second_operand(asg, node)?;
-
-                let start = match start_val {
-                    Value::Int(n) => n,
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected integer for range start".to_string(),
-                        ))
-                    }
-                };
-                let end = match end_val {
-                    Value::Int(n) => n,
+            // === Логические операции ===
+            NodeType::And => {
+                // Короткое замыкание: правый операнд вычисляется только при необходимости
+                let (lhs, rhs) = self.get_binary_operand_ids(node)?;
+                match self.ensure_evaluated(asg, lhs)? {
+                    Value::Bool(false) => Value::Bool(false),
+                    Value::Bool(_) => match self.ensure_evaluated(asg, rhs)? {
+                        Value::Bool(b) => Value::Bool(b),
+                        _ => {
+                            return Err(ASGError::TypeError(
+                                "Expected two booleans for And".to_string(),
+                            ))
+                        }
+                    },
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected integer for range end".to_string(),
+                            "Expected two booleans for And".to_string(),
                         ))
                     }
-                };
+                }
+            }
 
-                let step = if let Some(step_edge) = node.find_edge(EdgeType::LoopStep) {
-                    let step_val = self.ensure_evaluated(asg, step_edge.target_node_id)?;
-                    match step_val {
-                        Value::Int(n) => n,
+            NodeType::Or => {
+                // Короткое замыкание: правый операнд вычисляется только при необходимости
+                let (lhs, rhs) = self.get_binary_operand_ids(node)?;
+                match self.ensure_evaluated(asg, lhs)? {
+                    Value::Bool(true) => Value::Bool(true),
+                    Value::Bool(_) => match self.ensure_evaluated(asg, rhs)? {
+                        Value::Bool(b) => Value::Bool(b),
                         _ => {
                             return Err(ASGError::TypeError(
-                                "Expected integer for range step".to_string(),
+                                "Expected two booleans for Or".to_string(),
                             ))
                         }
+                    },
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected two booleans for Or".to_string(),
+                        ))
                     }
-                } else {
-                    1
-                };
-
-                if step == 0 {
-                    return Err(ASGError::InvalidOperation(
-                        "Range step cannot be zero".to_string(),
-                    ));
                 }
+            }
 
-                let mut result = Vec::new();
-                let mut i = start;
-                if step > 0 {
-                    while i < end {
-                        result.push(Value::Int(i));
-                        i += step;
-                    }
-                } else {
-                    while i > end {
-                        result.push(Value::Int(i));
-                        i += step;
-                    }
+            NodeType::Not => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Bool(a) => Value::Bool(!a),
+                    _ => return Err(ASGError::TypeError("Expected boolean for Not".to_string())),
                 }
-                Value::Array(result)
             }
 
-            NodeType::For => {
-                let var_edge = node
-                    .find_edge(EdgeType::LoopInit)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopInit))?;
-                let iterable_edge = node
+            // === If выражение ===
+            NodeType::If => {
+                let cond_edge = node
                     .find_edge(EdgeType::Condition)
                     .ok_or(ASGError::MissingEdge(node.id, EdgeType::Condition))?;
-                let body_edge = node
-                    .find_edge(EdgeType::LoopBody)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopBody))?;
-
-                let var_node = asg
-                    .find_node(var_edge.target_node_id)
-                    .ok_or(ASGError::NodeNotFound(var_edge.target_node_id))?;
-                let var_name = var_node.get_name().unwrap_or_default();
-
-                let iterable_val = self.ensure_evaluated(asg, iterable_edge.target_node_id)?;
-                let items = match iterable_val {
-                    Value::Array(arr) => arr,
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected array for for loop".to_string(),
-                        ))
-                    }
-                };
-
-                let mut last_result = Value::Unit;
-                for item in items {
-                    let saved_memo = std::mem::take(&mut self.memo);
-                    let mut frame = CallFrame::default();
-                    frame.locals.insert(var_name.clone(), item);
-                    frame.memo = saved_memo;
-                    self.call_stack.push(frame);
 
-                    last_result = self.ensure_evaluated(asg, body_edge.target_node_id)?;
+                let cond_val = self.ensure_evaluated(asg, cond_edge.target_node_id)?;
+                let cond = cond_val
+                    .as_bool()
+                    .ok_or(ASGError::TypeError("Condition must be boolean".to_string()))?;
 
-                    if let Some(popped_frame) = self.call_stack.pop() {
-                        self.memo = popped_frame.memo;
-                    }
+                if cond {
+                    let then_edge = node
+                        .find_edge(EdgeType::ThenBranch)
+                        .ok_or(ASGError::MissingEdge(node.id, EdgeType::ThenBranch))?;
+                    self.ensure_evaluated(asg, then_edge.target_node_id)?
+                } else if let Some(else_edge) = node.find_edge(EdgeType::ElseBranch) {
+                    self.ensure_evaluated(asg, else_edge.target_node_id)?
+                } else {
+                    Value::Unit
                 }
-                last_result
             }
 
-            NodeType::ArrayReverse => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Array(mut arr) => {
-                        arr.reverse();
-                        Value::Array(arr)
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected array for reverse".to_string(),
-                        ))
-                    }
-                }
+            // === Block ===
+            NodeType::Block => {
+                let stmt_edges: Vec<_> = node
+                    .find_edges(EdgeType::BlockStatement)
+                    .into_iter()
+                    .map(|e| e.target_node_id)
+                    .collect();
+                // Инструкции выполняются по порядку; fn и let внутри блока видны только в нём
+                self.call_stack.push(CallFrame::default());
+                let (result, _) = self.with_function_scope(HashMap::new(), |this| {
+                    stmt_edges
+                        .into_iter()
+                        .try_fold(Value::Unit, |_, id| this.ensure_evaluated(asg, id))
+                });
+                self.call_stack.pop();
+                result?
             }
 
-            NodeType::ArraySort => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Array(mut arr) => {
-                        arr.sort_by(|a, b| match (a, b) {
-                            (Value::Int(x), Value::Int(y)) => x.cmp(y),
-                            (Value::Float(x), Value::Float(y)) => {
-                                x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal)
+            // === Loop (while) ===
+            NodeType::Loop => {
+                let body_edge = node
+                    .find_edge(EdgeType::LoopBody)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopBody))?;
+
+                let body_node = asg
+                    .find_node(body_edge.target_node_id)
+                    .ok_or(ASGError::NodeNotFound(body_edge.target_node_id))?
+                    .clone();
+
+                // Между итерациями сбрасываем memo только у узлов, зависящих от изменяемых переменных
+                let variant = self.loop_variant_nodes(asg, node.id);
+
+                // Если есть условие - это while loop
+                if let Some(cond_edge) = node.find_edge(EdgeType::Condition) {
+                    // (loop-indexed i cond body): имя переменной-счётчика в payload
+                    let index_var = node.get_name();
+                    // (loop-timeout ms cond body): дедлайн по self.clock, проверяется перед итерацией
+                    let deadline = match node.find_edge(EdgeType::LoopTimeout) {
+                        Some(edge) => match self.ensure_evaluated(asg, edge.target_node_id)? {
+                            Value::Int(ms) if ms >= 0 => {
+                                Some((self.clock)() + Duration::from_millis(ms as u64))
                             }
-                            (Value::String(x), Value::String(y)) => x.cmp(y),
-                            _ => std::cmp::Ordering::Equal,
-                        });
-                        Value::Array(arr)
-                    }
-                    _ => return Err(ASGError::TypeError("Expected array for sort".to_string())),
-                }
-            }
+                            _ => {
+                                return Err(ASGError::TypeError(
+                                    "loop-timeout: timeout must be a non-negative integer (ms)"
+                                        .to_string(),
+                                ))
+                            }
+                        },
+                        None => None,
+                    };
+                    let mut result = Value::Unit;
+                    let mut iteration: i64 = 0;
+                    loop {
+                        if deadline.is_some_and(|deadline| (self.clock)() >= deadline) {
+                            break;
+                        }
+                        // Счётчик виден в условии и теле через отдельный фрейм
+                        if let Some(name) = &index_var {
+                            let mut frame = CallFrame::default();
+                            frame.locals.insert(name.clone(), Value::Int(iteration));
+                            self.call_stack.push(frame);
+                        }
+                        let step = self.eval_while_step(
+                            asg,
+                            cond_edge.target_node_id,
+                            body_edge.target_node_id,
+                            &variant,
+                        );
+                        if index_var.is_some() {
+                            self.call_stack.pop();
+                        }
 
-            NodeType::ArraySum => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Array(arr) => {
-                        let mut int_sum = 0i64;
-                        let mut float_sum = 0.0f64;
-                        let mut has_float = false;
-                        for item in arr {
-                            match item {
-                                Value::Int(n) => int_sum += n,
-                                Value::Float(f) => {
-                                    float_sum += f;
-                                    has_float = true;
-                                }
-                                _ => {
-                                    return Err(ASGError::TypeError(
-                                        "Expected numbers in array for sum".to_string(),
-                                    ))
+                        match step {
+                            Ok(Some(value)) => result = value,
+                            Ok(None) => break,
+                            Err(err) => {
+                                if self.loop_broken(Err(err), &mut result)? {
+                                    break;
                                 }
                             }
                         }
-                        if has_float {
-                            Value::Float(int_sum as f64 + float_sum)
-                        } else {
-                            Value::Int(int_sum)
+                        iteration += 1;
+                    }
+                    result
+                } else {
+                    // Бесконечный цикл без условия: завершается только (break)
+                    let mut result = Value::Unit;
+                    loop {
+                        self.memo.retain(|id, _| !variant.contains(id));
+                        let step = self.eval_node(asg, &body_node);
+                        if self.loop_broken(step, &mut result)? {
+                            break result;
                         }
                     }
-                    _ => return Err(ASGError::TypeError("Expected array for sum".to_string())),
                 }
             }
 
-            NodeType::ArrayProduct => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
+            // === Переменные ===
+            NodeType::Variable => {
+                let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+
+                let value = if let Some(val_edge) = node.find_edge(EdgeType::VarValue) {
+                    self.ensure_evaluated(asg, val_edge.target_node_id)?
+                } else {
+                    Value::Unit
+                };
+
+                self.bind_variable(var_name, value.clone());
+                value
+            }
+
+            NodeType::LetDestructure => {
+                let payload = node
+                    .payload
+                    .as_ref()
+                    .ok_or(ASGError::MissingPayload(node.id))?;
+                let names = destructure_names(payload);
+
+                // Вычисляем значение
+                let val_edge = node
+                    .find_edge(EdgeType::VarValue)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::VarValue))?;
+                let value = self.ensure_evaluated(asg, val_edge.target_node_id)?;
+
+                // Деструктуризация
+                match &value {
                     Value::Array(arr) => {
-                        if arr.is_empty() {
-                            return Ok(());
-                        }
-                        let mut int_prod = 1i64;
-                        let mut float_prod = 1.0f64;
-                        let mut has_float = false;
-                        for item in arr {
-                            match item {
-                                Value::Int(n) => int_prod *= n,
-                                Value::Float(f) => {
-                                    float_prod *= f;
-                                    has_float = true;
-                                }
-                                _ => {
-                                    return Err(ASGError::TypeError(
-                                        "Expected numbers in array for product".to_string(),
-                                    ))
-                                }
-                            }
+                        // Значений меньше, чем имён — ошибка (например, функция
+                        // вернула меньше значений, чем ожидает деструктуризация)
+                        if arr.len() < names.len() {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "Destructuring expects {} values, got {}",
+                                names.len(),
+                                arr.len()
+                            )));
                         }
-                        if has_float {
-                            Value::Float(int_prod as f64 * float_prod)
-                        } else {
-                            Value::Int(int_prod)
+                        for (name, val) in names.iter().zip(arr) {
+                            self.bind_variable(name.clone(), val.clone());
                         }
                     }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected array for product".to_string(),
-                        ))
+                    Value::Record(rec) => {
+                        for name in &names {
+                            let val = rec.get(name).cloned().unwrap_or(Value::Unit);
+                            self.bind_variable(name.clone(), val);
+                        }
                     }
-                }
-            }
-
-            NodeType::ArrayContains => {
-                let (arr_val, elem_val) = self.get_binary_operands(asg, node)?;
-                match arr_val {
-                    Value::Array(arr) => {
-                        let found = arr.iter().any(|item| self.values_equal(item, &elem_val));
-                        Value::Bool(found)
+                    Value::Dict(dict) => {
+                        for name in &names {
+                            let val = dict.get(name).cloned().unwrap_or(Value::Unit);
+                            self.bind_variable(name.clone(), val);
+                        }
                     }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected array for contains".to_string(),
-                        ))
+                            "Destructuring requires Array, Record, or Dict".to_string(),
+                        ));
                     }
                 }
+
+                value
             }
 
-            NodeType::ArrayIndexOf => {
-                let (arr_val, elem_val) = self.get_binary_operands(asg, node)?;
-                match arr_val {
-                    Value::Array(arr) => {
-                        let idx = arr
-                            .iter()
-                            .position(|item| self.values_equal(item, &elem_val));
-                        match idx {
-                            Some(i) => Value::Int(i as i64),
-                            None => Value::Int(-1),
-                        }
+            NodeType::VarRef => {
+                let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                // Сначала ищем в переменных
+                if let Some(val) = self.read_variable(&var_name) {
+                    val
+                } else if let Some(val) = self.local_function_value(&var_name) {
+                    val
+                } else if let Some((params, body_id, _)) = self.resolve_function(&var_name) {
+                    // Если не нашли в переменных, ищем в функциях
+                    Value::Function {
+                        params: params.clone(),
+                        body_id: *body_id,
+                        captured: HashMap::new(),
                     }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected array for index-of".to_string(),
-                        ))
+                } else if let Some((enum_name, 0)) = self.enum_variants.get(&var_name) {
+                    // Вариант без полей можно писать без скобок
+                    Value::Variant {
+                        enum_name: enum_name.clone(),
+                        tag: var_name,
+                        fields: vec![],
                     }
+                } else if let Some((name, _, _)) = BUILTIN_FUNCTIONS
+                    .iter()
+                    .find(|(name, _, _)| *name == var_name)
+                {
+                    Value::Builtin(name)
+                } else {
+                    return Err(ASGError::UnknownVariable(var_name));
                 }
             }
 
-            NodeType::ArrayTake => {
-                let (arr_val, n_val) = self.get_binary_operands(asg, node)?;
-                match (arr_val, n_val) {
-                    (Value::Array(arr), Value::Int(n)) => {
-                        let n = n.max(0) as usize;
-                        Value::Array(arr.into_iter().take(n).collect())
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected (array, int) for take".to_string(),
-                        ))
+            NodeType::Assign => {
+                let target_edge = node
+                    .find_edge(EdgeType::AssignTarget)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignTarget))?;
+                let value_edge = node
+                    .find_edge(EdgeType::AssignValue)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignValue))?;
+
+                let target_node = asg
+                    .find_node(target_edge.target_node_id)
+                    .ok_or(ASGError::NodeNotFound(target_edge.target_node_id))?;
+
+                if target_node.node_type == NodeType::VarRef {
+                    // (set name value)
+                    let var_name = target_node
+                        .get_name()
+                        .ok_or(ASGError::MissingPayload(target_node.id))?;
+                    let value = self.ensure_evaluated(asg, value_edge.target_node_id)?;
+                    // Пишем туда, откуда x читается; неизвестное имя — новая глобальная
+                    let mut value = Some(value);
+                    let found =
+                        self.with_variable_mut(&var_name, |slot| *slot = value.take().unwrap());
+                    if found.is_none() {
+                        self.variables.insert(var_name, value.take().unwrap());
                     }
+                } else {
+                    // (set (index ...) / (dict-get ...) / (field ...) value): изменение на месте
+                    let (var_name, path) = self.resolve_place(asg, target_edge.target_node_id)?;
+                    let value = self.ensure_evaluated(asg, value_edge.target_node_id)?;
+                    self.with_variable_mut(&var_name, |slot| assign_place(slot, &path, value))
+                        .ok_or_else(|| ASGError::UnknownVariable(var_name.clone()))??;
                 }
+                Value::Unit
             }
 
-            NodeType::ArrayDrop => {
-                let (arr_val, n_val) = self.get_binary_operands(asg, node)?;
-                match (arr_val, n_val) {
-                    (Value::Array(arr), Value::Int(n)) => {
-                        let n = n.max(0) as usize;
-                        Value::Array(arr.into_iter().skip(n).collect())
+            NodeType::CompoundAssign => {
+                let target_edge = node
+                    .find_edge(EdgeType::AssignTarget)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignTarget))?;
+                let value_edge = node
+                    .find_edge(EdgeType::AssignValue)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignValue))?;
+                let var_name = asg
+                    .find_node(target_edge.target_node_id)
+                    .ok_or(ASGError::NodeNotFound(target_edge.target_node_id))?
+                    .get_name()
+                    .ok_or(ASGError::MissingPayload(target_edge.target_node_id))?;
+
+                // Пишем туда, откуда x читается: локальная переменная (let в блоке,
+                // параметр, переменная for) или глобальная
+                let value = self.ensure_evaluated(asg, value_edge.target_node_id)?;
+                self.with_variable_mut(&var_name, |slot| *slot = value.clone())
+                    .ok_or(ASGError::UnknownVariable(var_name))?;
+                value
+            }
+
+            // === Функции ===
+            NodeType::Function => {
+                let func_name = node.get_name().unwrap_or_else(|| format!("fn_{}", node.id));
+
+                // Собираем имена параметров
+                let param_edges = node.find_edges(EdgeType::FunctionParameter);
+                let mut params = Vec::new();
+                for edge in param_edges {
+                    let param_node = asg
+                        .find_node(edge.target_node_id)
+                        .ok_or(ASGError::NodeNotFound(edge.target_node_id))?;
+                    let param_name = param_node
+                        .get_name()
+                        .unwrap_or_else(|| format!("param_{}", param_node.id));
+                    params.push(param_name);
+                }
+
+                // Получаем ID тела
+                let body_id = node
+                    .find_edge(EdgeType::FunctionBody)
+                    .map(|e| e.target_node_id)
+                    .unwrap_or(0);
+
+                // Внутри блока или вызова функция локальна для этой области
+                let entry = (params.clone(), body_id, None);
+                match self.function_scopes.last_mut() {
+                    Some(scope) => {
+                        scope.insert(func_name.clone(), entry);
+                        // Значение уносит с собой видимые локальные функции
+                        self.local_function_value(&func_name).unwrap_or(Value::Unit)
                     }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected (array, int) for drop".to_string(),
-                        ))
+                    None => {
+                        self.functions.insert(func_name, entry);
+                        Value::Function {
+                            params,
+                            body_id,
+                            captured: HashMap::new(),
+                        }
                     }
                 }
             }
 
-            NodeType::ArrayAppend => {
-                let (arr_val, elem_val) = self.get_binary_operands(asg, node)?;
-                match arr_val {
-                    Value::Array(mut arr) => {
-                        arr.push(elem_val);
-                        Value::Array(arr)
+            NodeType::Parameter => {
+                let param_name = node
+                    .get_name()
+                    .unwrap_or_else(|| format!("param_{}", node.id));
+                // Параметр получает значение из стека вызовов или глобальных переменных
+                self.read_variable(&param_name).unwrap_or(Value::Unit)
+            }
+
+            NodeType::Lambda => {
+                // Lambda — анонимная функция с захватом переменных (closure)
+                let param_edges = node.find_edges(EdgeType::FunctionParameter);
+                let mut params = Vec::new();
+                for edge in param_edges {
+                    let param_node = asg
+                        .find_node(edge.target_node_id)
+                        .ok_or(ASGError::NodeNotFound(edge.target_node_id))?;
+                    let param_name = param_node
+                        .get_name()
+                        .unwrap_or_else(|| format!("param_{}", param_node.id));
+                    params.push(param_name);
+                }
+
+                let body_id = node
+                    .find_edge(EdgeType::FunctionBody)
+                    .map(|e| e.target_node_id)
+                    .unwrap_or(0);
+
+                // Переменные, которые тело меняет через set, переводим в общие ячейки:
+                // изменение видно и создателю closure, и другим closure с этой ячейкой
+                let mut assigned = HashSet::new();
+                collect_assigned_names(asg, body_id, &mut assigned, &mut HashSet::new());
+                for name in assigned.iter().filter(|name| !params.contains(name)) {
+                    if let Some(slot) = self.resolve_variable_mut(name) {
+                        if !matches!(slot, Value::Cell(_)) {
+                            let value = std::mem::replace(slot, Value::Unit);
+                            *slot = Value::Cell(CellRef(Rc::new(RefCell::new(value))));
+                        }
                     }
-                    _ => return Err(ASGError::TypeError("Expected array for append".to_string())),
                 }
-            }
 
-            NodeType::ArrayConcat => {
-                let (arr1_val, arr2_val) = self.get_binary_operands(asg, node)?;
-                match (arr1_val, arr2_val) {
-                    (Value::Array(mut arr1), Value::Array(arr2)) => {
-                        arr1.extend(arr2);
-                        Value::Array(arr1)
+                // Захватываем текущий scope для closure
+                let mut captured = HashMap::new();
+                // Локальные функции видимых блоков: после выхода из блока их больше негде искать
+                for (name, cell) in self.local_function_cells() {
+                    captured.insert(name, Value::Cell(cell));
+                }
+                // Затем глобальные переменные
+                for (name, val) in &self.variables {
+                    captured.insert(name.clone(), val.clone());
+                }
+                // Затем переменные из стека вызовов (перезаписывают глобальные)
+                for frame in &self.call_stack {
+                    for (name, val) in &frame.locals {
+                        captured.insert(name.clone(), val.clone());
                     }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two arrays for array-concat".to_string(),
-                        ))
+                }
+
+                // rec-lambda: имя самоссылки указывает на ячейку с самим closure, поэтому
+                // оно доступно, куда бы closure ни попало
+                if let Some(self_name) = node.get_name() {
+                    let cell = CellRef(Rc::new(RefCell::new(Value::Unit)));
+                    captured.insert(self_name, Value::Cell(cell.clone()));
+                    let self_value = Value::Function {
+                        params: params.clone(),
+                        body_id,
+                        captured: captured.clone(),
+                    };
+                    *cell.0.borrow_mut() = self_value.clone();
+                    self_value
+                } else {
+                    Value::Function {
+                        params,
+                        body_id,
+                        captured,
                     }
                 }
             }
 
-            NodeType::ArraySlice => {
-                let edges: Vec<_> = node.edges.iter().collect();
-                if edges.len() != 3 {
-                    return Err(ASGError::InvalidOperation(
-                        "slice requires 3 arguments".to_string(),
-                    ));
+            NodeType::Call => {
+                // Получаем функцию
+                let call_target = node
+                    .find_edge(EdgeType::CallTarget)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::CallTarget))?;
+                let target_node = asg
+                    .find_node(call_target.target_node_id)
+                    .ok_or(ASGError::NodeNotFound(call_target.target_node_id))?;
+                // Именованный вызов возможен только через VarRef; иначе цель — произвольное выражение
+                let func_name = if target_node.node_type == NodeType::VarRef {
+                    target_node.get_name().unwrap_or_default()
+                } else {
+                    String::new()
+                };
+
+                // Собираем аргументы сначала, слева направо
+                let arg_edges: Vec<_> = node
+                    .edges
+                    .iter()
+                    .filter(|e| {
+                        e.edge_type == EdgeType::CallArgument
+                            || e.edge_type == EdgeType::ApplicationArgument
+                    })
+                    .collect();
+
+                // Именованные аргументы (:name value) несут имя в payload ребра
+                let mut arg_values = Vec::with_capacity(arg_edges.len());
+                let mut keywords = Vec::new();
+                for edge in &arg_edges {
+                    let arg_val = self.ensure_evaluated(asg, edge.target_node_id)?;
+                    match &edge.payload {
+                        Some(name) => {
+                            keywords.push((String::from_utf8_lossy(name).into_owned(), arg_val))
+                        }
+                        None => arg_values.push(arg_val),
+                    }
                 }
-                let arr_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
-                let start_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
-                let end_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
 
-                match (arr_val, start_val, end_val) {
-                    (Value::Array(arr), Value::Int(start), Value::Int(end)) => {
-                        let start = start.max(0) as usize;
-                        let end = (end as usize).min(arr.len());
-                        if start >= end {
-                            Value::Array(vec![])
-                        } else {
-                            Value::Array(arr[start..end].to_vec())
+                // Конструктор варианта из defenum
+                if let Some((enum_name, arity)) = self.enum_variants.get(&func_name) {
+                    // Без имён полей любой именованный аргумент — ошибка, пропусков нет
+                    let arg_values: Vec<Value> =
+                        bind_keyword_args(&func_name, &[], arg_values, keywords)?
+                            .into_iter()
+                            .flatten()
+                            .collect();
+                    if *arity != arg_values.len() {
+                        return Err(ASGError::TypeError(format!(
+                            "Variant {} expects {} fields, got {}",
+                            func_name,
+                            arity,
+                            arg_values.len()
+                        )));
+                    }
+                    Value::Variant {
+                        enum_name: enum_name.clone(),
+                        tag: func_name,
+                        fields: arg_values,
+                    }
+                // Конструктор записи из defrecord: аргументы связываются с полями по порядку
+                } else if let Some(fields) = self.record_schemas.get(&func_name) {
+                    let slots = bind_keyword_args(&func_name, fields, arg_values, keywords)?;
+                    if let Some(i) = slots.iter().position(Option::is_none) {
+                        return Err(ASGError::TypeError(format!(
+                            "Record {} is missing field {}",
+                            func_name, fields[i]
+                        )));
+                    }
+                    let arg_values: Vec<Value> = slots.into_iter().flatten().collect();
+                    if fields.len() != arg_values.len() {
+                        return Err(ASGError::TypeError(format!(
+                            "Record {} expects {} fields ({}), got {}",
+                            func_name,
+                            fields.len(),
+                            fields.join(", "),
+                            arg_values.len()
+                        )));
+                    }
+                    let record = fields.iter().cloned().zip(arg_values).collect();
+                    Value::Record(record)
+                } else if let Some((params, body_id, opt_asg)) =
+                    self.resolve_function(&func_name).cloned()
+                {
+                    // Именованная функция (возможно из импортированного модуля)
+                    let args = bind_keyword_args(&func_name, &params, arg_values, keywords)?;
+                    match opt_asg {
+                        Some(module) => {
+                            self.modules.push(module.clone());
+                            let result =
+                                self.invoke(&module.asg, &params, body_id, &HashMap::new(), args);
+                            self.modules.pop();
+                            result?
                         }
+                        None => self.invoke(asg, &params, body_id, &HashMap::new(), args)?,
                     }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected (array, int, int) for slice".to_string(),
-                        ))
+                } else {
+                    // Попробуем вычислить target как значение
+                    let fn_val = self.ensure_evaluated(asg, call_target.target_node_id)?;
+                    match &fn_val {
+                        Value::Function {
+                            params,
+                            body_id,
+                            captured,
+                        } => {
+                            let args = bind_keyword_args(&func_name, params, arg_values, keywords)?;
+                            self.invoke(asg, params, *body_id, captured, args)?
+                        }
+                        Value::ComposedFunction(_) | Value::Builtin(_) => {
+                            let arg_values: Vec<Value> =
+                                bind_keyword_args(&func_name, &[], arg_values, keywords)?
+                                    .into_iter()
+                                    .flatten()
+                                    .collect();
+                            self.call(asg, &fn_val, &arg_values)?
+                        }
+                        other => {
+                            return Err(ASGError::TypeError(format!(
+                                "cannot call value of type {}",
+                                other.type_name()
+                            )))
+                        }
                     }
                 }
             }
 
-            // === Dict operations ===
-            NodeType::Dict => {
-                let mut dict = HashMap::new();
-                let edges: Vec<_> = node.edges.iter().collect();
-                let mut i = 0;
-                while i + 1 < edges.len() {
-                    let key_val = self.ensure_evaluated(asg, edges[i].target_node_id)?;
-                    let val = self.ensure_evaluated(asg, edges[i + 1].target_node_id)?;
-                    let key = match key_val {
-                        Value::String(s) => s,
-                        Value::Int(n) => n.to_string(),
-                        _ => {
-                            return Err(ASGError::TypeError(
-                                "Dict keys must be strings or ints".to_string(),
-                            ))
-                        }
-                    };
-                    dict.insert(key, val);
-                    i += 2;
+            NodeType::Break => {
+                self.break_value = match node.find_edge(EdgeType::ReturnValue) {
+                    Some(edge) => Some(self.ensure_evaluated(asg, edge.target_node_id)?),
+                    None => None,
+                };
+                return Err(ASGError::LoopBreak);
+            }
+
+            NodeType::Continue => return Err(ASGError::LoopContinue),
+
+            NodeType::Return => {
+                if let Some(edge) = node.find_edge(EdgeType::ReturnValue) {
+                    self.ensure_evaluated(asg, edge.target_node_id)?
+                } else {
+                    Value::Unit
                 }
-                Value::Dict(dict)
             }
 
-            NodeType::DictGet => {
-                let (dict_val, key_val) = self.get_binary_operands(asg, node)?;
-                match (dict_val, key_val) {
-                    (Value::Dict(dict), Value::String(key)) => {
-                        dict.get(&key).cloned().unwrap_or(Value::Unit)
-                    }
-                    (Value::Dict(dict), Value::Int(n)) => {
-                        dict.get(&n.to_string()).cloned().unwrap_or(Value::Unit)
-                    }
+            // === Тензорные операции ===
+            NodeType::TensorAdd => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Tensor(a), Value::Tensor(b)) => Value::Tensor(tensor_ops::add(&a, &b)),
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected (dict, key) for dict-get".to_string(),
+                            "Expected two Tensors for TensorAdd".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::DictSet => {
-                let edges: Vec<_> = node.edges.iter().collect();
-                if edges.len() < 3 {
-                    return Err(ASGError::MissingEdge(
-                        node.id,
-                        EdgeType::ApplicationArgument,
-                    ));
-                }
-                let dict_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
-                let key_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
-                let new_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
-
-                match (dict_val, key_val) {
-                    (Value::Dict(mut dict), Value::String(key)) => {
-                        dict.insert(key, new_val);
-                        Value::Dict(dict)
-                    }
-                    (Value::Dict(mut dict), Value::Int(n)) => {
-                        dict.insert(n.to_string(), new_val);
-                        Value::Dict(dict)
+            NodeType::TensorMul => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Tensor(a), Value::Tensor(b)) => {
+                        // Поэлементное умножение
+                        let lhs = a.data.borrow();
+                        let rhs = b.data.borrow();
+                        let result_data = &*lhs * &*rhs;
+                        Value::Tensor(DifferentiableTensor::new(result_data, false))
                     }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected (dict, key, value) for dict-set".to_string(),
+                            "Expected two Tensors for TensorMul".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::DictHas => {
-                let (dict_val, key_val) = self.get_binary_operands(asg, node)?;
-                match (dict_val, key_val) {
-                    (Value::Dict(dict), Value::String(key)) => Value::Bool(dict.contains_key(&key)),
-                    (Value::Dict(dict), Value::Int(n)) => {
-                        Value::Bool(dict.contains_key(&n.to_string()))
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected (dict, key) for dict-has".to_string(),
-                        ))
-                    }
+            // === Массивы ===
+            NodeType::Array => {
+                let element_ids: Vec<_> = node
+                    .find_edges(EdgeType::ArrayElement)
+                    .into_iter()
+                    .map(|e| e.target_node_id)
+                    .collect();
+                let mut elements = Vec::new();
+                for elem_id in element_ids {
+                    let elem_val = self.ensure_evaluated(asg, elem_id)?;
+                    elements.push(elem_val);
                 }
+                Value::Array(elements)
             }
 
-            NodeType::DictRemove => {
-                let (dict_val, key_val) = self.get_binary_operands(asg, node)?;
-                match (dict_val, key_val) {
-                    (Value::Dict(mut dict), Value::String(key)) => {
-                        dict.remove(&key);
-                        Value::Dict(dict)
-                    }
-                    (Value::Dict(mut dict), Value::Int(n)) => {
-                        dict.remove(&n.to_string());
-                        Value::Dict(dict)
+            NodeType::ArrayIndex => {
+                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
+                    node.id,
+                    EdgeType::ApplicationArgument,
+                ))?;
+                let index_edge = node
+                    .find_edge(EdgeType::ArrayIndexExpr)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ArrayIndexExpr))?;
+
+                let array_id = array_edge.target_node_id;
+                let index_id = index_edge.target_node_id;
+                let array_val = self.ensure_evaluated(asg, array_id)?;
+                let index_val = self.ensure_evaluated(asg, index_id)?;
+
+                match (&array_val, &index_val) {
+                    (Value::Array(arr), Value::Int(idx)) => {
+                        let idx = *idx as usize;
+                        arr.get(idx)
+                            .cloned()
+                            .ok_or(ASGError::InvalidOperation(format!(
+                                "Array index {} out of bounds",
+                                idx
+                            )))?
                     }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected (dict, key) for dict-remove".to_string(),
+                            "Expected array and integer index".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::DictKeys => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Dict(dict) => {
-                        Value::Array(dict.keys().map(|k| Value::String(k.clone())).collect())
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected dict for dict-keys".to_string(),
-                        ))
-                    }
+            NodeType::ArrayLength => {
+                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
+                    node.id,
+                    EdgeType::ApplicationArgument,
+                ))?;
+
+                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
+
+                match &array_val {
+                    Value::Array(arr) => Value::Int(arr.len() as i64),
+                    _ => return Err(ASGError::TypeError("Expected array for length".to_string())),
                 }
             }
 
-            NodeType::DictValues => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Dict(dict) => Value::Array(dict.values().cloned().collect()),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected dict for dict-values".to_string(),
-                        ))
-                    }
+            NodeType::ArrayLast => {
+                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
+                    node.id,
+                    EdgeType::ApplicationArgument,
+                ))?;
+
+                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
+
+                match array_val {
+                    Value::Array(arr) => arr.last().cloned().unwrap_or(Value::Unit),
+                    _ => return Err(ASGError::TypeError("Expected array for last".to_string())),
                 }
             }
 
-            NodeType::DictMerge => {
-                let (dict1_val, dict2_val) = self.get_binary_operands(asg, node)?;
-                match (dict1_val, dict2_val) {
-                    (Value::Dict(mut d1), Value::Dict(d2)) => {
-                        for (k, v) in d2 {
-                            d1.insert(k, v);
-                        }
-                        Value::Dict(d1)
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected two dicts for dict-merge".to_string(),
-                        ))
-                    }
-                }
+            NodeType::ArraySetIndex => {
+                let array_edge = node.edges.first().ok_or(ASGError::MissingEdge(
+                    node.id,
+                    EdgeType::ApplicationArgument,
+                ))?;
+                let index_edge = node
+                    .find_edge(EdgeType::ArrayIndexExpr)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ArrayIndexExpr))?;
+                let value_edge = node
+                    .find_edge(EdgeType::AssignValue)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::AssignValue))?;
+
+                // Получаем имя переменной массива
+                let array_node = asg
+                    .find_node(array_edge.target_node_id)
+                    .ok_or(ASGError::NodeNotFound(array_edge.target_node_id))?;
+                let var_name = array_node
+                    .get_name()
+                    .ok_or(ASGError::MissingPayload(array_node.id))?;
+
+                let index_val = self.ensure_evaluated(asg, index_edge.target_node_id)?;
+                let new_value = self.ensure_evaluated(asg, value_edge.target_node_id)?;
+
+                let idx = match &index_val {
+                    Value::Int(i) => *i as usize,
+                    _ => return Err(ASGError::TypeError("Index must be integer".to_string())),
+                };
+
+                // Мутируем массив в переменной
+                let not_array =
+                    || ASGError::TypeError("Expected array variable for set-index".to_string());
+                self.with_variable_mut(&var_name, |slot| match slot {
+                    Value::Array(arr) if idx < arr.len() => {
+                        arr[idx] = new_value;
+                        Ok(())
+                    }
+                    Value::Array(_) => Err(ASGError::InvalidOperation(format!(
+                        "Array index {} out of bounds",
+                        idx
+                    ))),
+                    _ => Err(not_array()),
+                })
+                .ok_or_else(not_array)??;
+                Value::Unit
             }
 
-            NodeType::DictSize => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Dict(dict) => Value::Int(dict.len() as i64),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected dict for dict-size".to_string(),
-                        ))
+            // === Higher-order array functions ===
+            NodeType::ArrayMap => {
+                let array_edge = node
+                    .find_edge(EdgeType::SourceArray)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SourceArray))?;
+                let fn_edge = node
+                    .find_edge(EdgeType::MapFunction)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::MapFunction))?;
+
+                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
+                let fn_val = self.ensure_evaluated(asg, fn_edge.target_node_id)?;
+
+                match array_val {
+                    // Ленивый источник (например, большой range) остаётся ленивым
+                    Value::LazySeq(source) => Value::LazySeq(Box::new(LazySeqKind::Map {
+                        func: Box::new(fn_val),
+                        source,
+                    })),
+                    Value::Array(arr) => {
+                        if !fn_val.is_callable() {
+                            return Err(ASGError::TypeError(
+                                "Expected function for map".to_string(),
+                            ));
+                        }
+
+                        let mut result = Vec::with_capacity(arr.len());
+                        for elem in arr {
+                            result.push(self.call(asg, &fn_val, &[elem])?);
+                        }
+                        Value::Array(result)
                     }
+                    _ => return Err(ASGError::TypeError("Expected array for map".to_string())),
                 }
             }
 
-            // === Pipe and Compose ===
-            NodeType::Pipe => {
-                // (|> value fn1 fn2 ...)
-                let edges: Vec<_> = node.edges.iter().collect();
-                if edges.is_empty() {
-                    return Err(ASGError::InvalidOperation(
-                        "Pipe requires at least one argument".to_string(),
-                    ));
-                }
+            NodeType::ArrayFilter => {
+                let array_edge = node
+                    .find_edge(EdgeType::SourceArray)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SourceArray))?;
+                let pred_edge = node
+                    .find_edge(EdgeType::FilterPredicate)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::FilterPredicate))?;
 
-                let mut current = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
+                let pred_val = self.ensure_evaluated(asg, pred_edge.target_node_id)?;
 
-                for edge in &edges[1..] {
-                    let fn_val = self.ensure_evaluated(asg, edge.target_node_id)?;
-                    current = self.call_function_value(asg, fn_val, current)?;
-                }
-                current
-            }
+                let arr = match &array_val {
+                    Value::Array(a) => a.clone(),
+                    _ => return Err(ASGError::TypeError("Expected array for filter".to_string())),
+                };
 
-            NodeType::Compose => {
-                // (compose fn1 fn2 ...) - создаём композицию функций
-                let edges: Vec<_> = node.edges.iter().collect();
-                if edges.is_empty() {
-                    return Err(ASGError::InvalidOperation(
-                        "Compose requires at least one function".to_string(),
+                if !pred_val.is_callable() {
+                    return Err(ASGError::TypeError(
+                        "Expected function for filter".to_string(),
                     ));
                 }
 
-                let mut fns = Vec::new();
-                for edge in &edges {
-                    let fn_val = self.ensure_evaluated(asg, edge.target_node_id)?;
-                    match &fn_val {
-                        Value::Function { .. } | Value::ComposedFunction(_) => fns.push(fn_val),
-                        _ => {
-                            return Err(ASGError::TypeError(
-                                "Compose expects functions".to_string(),
-                            ))
-                        }
+                let mut result = Vec::new();
+                for elem in arr {
+                    let pred_result = self.call(asg, &pred_val, std::slice::from_ref(&elem))?;
+                    if let Value::Bool(true) = pred_result {
+                        result.push(elem);
                     }
                 }
-                Value::ComposedFunction(fns)
+                Value::Array(result)
             }
 
-            // === Строковые операции ===
-            NodeType::StringConcat => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::String(s1), Value::String(s2)) => {
-                        Value::String(format!("{}{}", s1, s2))
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected strings for concat".to_string(),
-                        ))
-                    }
-                }
-            }
+            NodeType::ArrayScan => {
+                let array_edge = node
+                    .find_edge(EdgeType::SourceArray)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SourceArray))?;
+                let init_edge = node
+                    .find_edge(EdgeType::ReduceInit)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ReduceInit))?;
+                let fn_edge = node
+                    .find_edge(EdgeType::ReduceFunction)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ReduceFunction))?;
 
-            NodeType::StringLength => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(s) => Value::Int(s.chars().count() as i64),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected string for str-length".to_string(),
-                        ))
-                    }
+                let array_val = self.ensure_evaluated(asg, array_edge.target_node_id)?;
+                let init_val = self.ensure_evaluated(asg, init_edge.target_node_id)?;
+                let fn_val = self.ensure_evaluated(asg, fn_edge.target_node_id)?;
+
+                let arr = match array_val {
+                    Value::Array(a) => a,
+                    _ => return Err(ASGError::TypeError("Expected array for scan".to_string())),
+                };
+
+                // Инклюзивный scan: init не входит в результат
+                let mut acc = init_val;
+                let mut steps = Vec::with_capacity(arr.len());
+                for elem in arr {
+                    acc = self.call(asg, &fn_val, &[acc, elem])?;
+                    steps.push(acc.clone());
                 }
+                Value::Array(steps)
             }
 
-            NodeType::StringSubstring => {
-                let edges: Vec<_> = node.edges.iter().collect();
-                if edges.len() < 3 {
-                    return Err(ASGError::MissingEdge(
-                        node.id,
-                        EdgeType::ApplicationArgument,
+            NodeType::ArrayReduce => {
+                let array_edge = node
+                    .find_edge(EdgeType::SourceArray)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SourceArray))?;
+                let init_edge = node
+                    .find_edge(EdgeType::ReduceInit)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ReduceInit))?;
+                let fn_edge = node
+                    .find_edge(EdgeType::ReduceFunction)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::ReduceFunction))?;
+
+                // (reduce (range a b) ...) сворачивает границы напрямую, без массива
+                let array_val = self.streamed_operand(asg, array_edge.target_node_id)?;
+                let init_val = self.ensure_evaluated(asg, init_edge.target_node_id)?;
+                let fn_val = self.ensure_evaluated(asg, fn_edge.target_node_id)?;
+
+                if !fn_val.is_callable() {
+                    return Err(ASGError::TypeError(
+                        "Expected function for reduce".to_string(),
                     ));
                 }
-                let str_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
-                let start_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
-                let end_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
 
-                match (str_val, start_val, end_val) {
-                    (Value::String(s), Value::Int(start), Value::Int(end)) => {
-                        let chars: Vec<char> = s.chars().collect();
-                        let start = start.max(0) as usize;
-                        let end = (end as usize).min(chars.len());
-                        if start >= end {
-                            Value::String(String::new())
-                        } else {
-                            Value::String(chars[start..end].iter().collect())
-                        }
+                let mut acc = init_val;
+                match array_val {
+                    // Конечная ленивая последовательность сворачивается поэлементно
+                    Value::LazySeq(kind) => {
+                        self.for_each_lazy(asg, *kind, "reduce", |this, elem| {
+                            let prev = std::mem::replace(&mut acc, Value::Unit);
+                            acc = this.call(asg, &fn_val, &[prev, elem])?;
+                            Ok(())
+                        })?;
                     }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected (string, int, int) for substring".to_string(),
-                        ))
+                    Value::Array(arr) => {
+                        for elem in arr {
+                            acc = self.call(asg, &fn_val, &[acc, elem])?;
+                        }
                     }
+                    _ => return Err(ASGError::TypeError("Expected array for reduce".to_string())),
                 }
+                acc
             }
 
-            NodeType::StringSplit => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::String(s), Value::String(delim)) => {
-                        let parts: Vec<Value> = s
-                            .split(&delim)
-                            .map(|p| Value::String(p.to_string()))
-                            .collect();
-                        Value::Array(parts)
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected strings for str-split".to_string(),
-                        ))
-                    }
-                }
-            }
+            NodeType::ListComprehension => {
+                // (list-comp expr var iter [condition])
+                let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
 
-            NodeType::StringJoin => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Array(arr), Value::String(delim)) => {
-                        let strings: Result<Vec<String>, _> = arr
-                            .into_iter()
-                            .map(|v| match v {
-                                Value::String(s) => Ok(s),
-                                _ => Err(ASGError::TypeError(
-                                    "Array elements must be strings for str-join".to_string(),
-                                )),
-                            })
-                            .collect();
-                        Value::String(strings?.join(&delim))
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected (array, string) for str-join".to_string(),
-                        ))
-                    }
-                }
-            }
+                let expr_edge = node
+                    .find_edge(EdgeType::MapFunction)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::MapFunction))?;
+                let iter_edge = node
+                    .find_edge(EdgeType::LoopInit)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopInit))?;
+                let cond_edge = node.find_edge(EdgeType::Condition);
 
-            NodeType::StringContains => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::String(s), Value::String(substr)) => Value::Bool(s.contains(&substr)),
+                let iter_val = self.ensure_evaluated(asg, iter_edge.target_node_id)?;
+
+                let arr = match &iter_val {
+                    Value::Array(a) => a.clone(),
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected strings for str-contains".to_string(),
+                            "Expected array for list comprehension".to_string(),
                         ))
                     }
-                }
-            }
+                };
 
-            NodeType::StringReplace => {
-                let edges: Vec<_> = node.edges.iter().collect();
-                if edges.len() < 3 {
-                    return Err(ASGError::MissingEdge(
-                        node.id,
-                        EdgeType::ApplicationArgument,
-                    ));
-                }
-                let str_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
-                let from_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
-                let to_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+                let mut result = Vec::new();
+                for elem in arr {
+                    // Создаём frame для итерации
+                    let saved_memo = std::mem::take(&mut self.memo);
+                    let mut frame = CallFrame::default();
+                    frame.locals.insert(var_name.clone(), elem);
+                    frame.memo = saved_memo;
+                    self.call_stack.push(frame);
 
-                match (str_val, from_val, to_val) {
-                    (Value::String(s), Value::String(from), Value::String(to)) => {
-                        Value::String(s.replace(&from, &to))
+                    // Проверяем условие (если есть)
+                    let include = if let Some(cond) = &cond_edge {
+                        let cond_val = self.ensure_evaluated(asg, cond.target_node_id)?;
+                        match cond_val {
+                            Value::Bool(b) => b,
+                            _ => true,
+                        }
+                    } else {
+                        true
+                    };
+
+                    if include {
+                        let expr_val = self.ensure_evaluated(asg, expr_edge.target_node_id)?;
+                        result.push(expr_val);
                     }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected strings for str-replace".to_string(),
-                        ))
+
+                    if let Some(popped_frame) = self.call_stack.pop() {
+                        self.memo = popped_frame.memo;
                     }
                 }
+                Value::Array(result)
             }
 
-            NodeType::ToString => {
+            // === Lazy Sequences ===
+            NodeType::Iterate => {
+                // (iterate fn init) -> lazy [init, fn(init), fn(fn(init)), ...]
+                let (fn_val, init_val) = self.get_binary_operands(asg, node)?;
+                Value::LazySeq(Box::new(LazySeqKind::Iterate {
+                    func: Box::new(fn_val),
+                    current: Box::new(init_val),
+                }))
+            }
+
+            NodeType::Repeat => {
+                // (repeat val) -> lazy [val, val, val, ...]
                 let val = self.get_single_operand(asg, node)?;
-                // Для строк возвращаем как есть (без кавычек), для остальных format_display
-                let s = match val {
-                    Value::String(s) => s,
-                    other => other.format_display(),
-                };
-                Value::String(s)
+                Value::LazySeq(Box::new(LazySeqKind::Repeat(Box::new(val))))
             }
 
-            NodeType::ParseInt => {
+            NodeType::Cycle => {
+                // (cycle arr) -> lazy [a,b,c,a,b,c,...]
                 let val = self.get_single_operand(asg, node)?;
                 match val {
-                    Value::String(s) => match s.trim().parse::<i64>() {
-                        Ok(n) => Value::Int(n),
-                        Err(_) => {
-                            return Err(ASGError::InvalidOperation(format!(
-                                "Cannot parse '{}' as int",
-                                s
-                            )))
-                        }
-                    },
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected string for parse-int".to_string(),
-                        ))
+                    Value::Array(arr) => {
+                        Value::LazySeq(Box::new(LazySeqKind::Cycle { arr, index: 0 }))
                     }
+                    _ => return Err(ASGError::TypeError("Expected array for cycle".to_string())),
                 }
             }
 
-            NodeType::ParseFloat => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(s) => match s.trim().parse::<f64>() {
-                        Ok(f) => Value::Float(f),
-                        Err(_) => {
-                            return Err(ASGError::InvalidOperation(format!(
-                                "Cannot parse '{}' as float",
-                                s
-                            )))
-                        }
-                    },
+            NodeType::LazyRange => {
+                // (lazy-range start end [step])
+                let start_val = self.get_first_operand(asg, node)?;
+                let end_val = self.get_second_operand(asg, node)?;
+                let step_edge = node.find_edge(EdgeType::LoopStep);
+
+                let start = match start_val {
+                    Value::Int(n) => n,
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected string for parse-float".to_string(),
+                            "Expected integer for lazy-range start".to_string(),
                         ))
                     }
-                }
-            }
-
-            NodeType::StringTrim => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(s) => Value::String(s.trim().to_string()),
+                };
+                let end = match end_val {
+                    Value::Int(n) => n,
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected string for str-trim".to_string(),
+                            "Expected integer for lazy-range end".to_string(),
                         ))
                     }
-                }
-            }
-
-            NodeType::StringUpper => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(s) => Value::String(s.to_uppercase()),
+                };
+                let step = if let Some(step_e) = step_edge {
+                    let step_val = self.ensure_evaluated(asg, step_e.target_node_id)?;
+                    match step_val {
+                        Value::Int(n) => n,
+                        _ => {
+                            return Err(ASGError::TypeError(
+                                "Expected integer for lazy-range step".to_string(),
+                            ))
+                        }
+                    }
+                } else {
+                    if start <= end {
+                        1
+                    } else {
+                        -1
+                    }
+                };
+
+                Value::LazySeq(Box::new(LazySeqKind::Range {
+                    current: start,
+                    end,
+                    step,
+                }))
+            }
+
+            NodeType::TakeLazy => {
+                // (take-lazy n seq) -> take n elements from lazy seq
+                let (n_val, seq_val) = self.get_binary_operands(asg, node)?;
+                let n = match n_val {
+                    Value::Int(n) => n as usize,
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected string for str-upper".to_string(),
+                            "Expected integer for take-lazy count".to_string(),
                         ))
                     }
-                }
-            }
+                };
 
-            NodeType::StringLower => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(s) => Value::String(s.to_lowercase()),
+                match seq_val {
+                    Value::LazySeq(kind) => {
+                        let result = self.take_from_lazy(asg, *kind, n)?;
+                        Value::Array(result)
+                    }
+                    Value::Array(arr) => {
+                        // Поддержка take для обычных массивов тоже
+                        Value::Array(arr.into_iter().take(n).collect())
+                    }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected string for str-lower".to_string(),
+                            "Expected lazy sequence or array for take-lazy".to_string(),
                         ))
                     }
                 }
             }
 
-            // === Math functions ===
-            NodeType::MathSqrt => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.sqrt()),
-                    Value::Int(n) => Value::Float((n as f64).sqrt()),
-                    _ => return Err(ASGError::TypeError("Expected number for sqrt".to_string())),
+            NodeType::LazyMap => {
+                // (lazy-map fn seq) -> lazy mapped seq
+                let (fn_val, seq_val) = self.get_binary_operands(asg, node)?;
+                match seq_val {
+                    Value::LazySeq(kind) => Value::LazySeq(Box::new(LazySeqKind::Map {
+                        func: Box::new(fn_val),
+                        source: kind,
+                    })),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected lazy sequence for lazy-map".to_string(),
+                        ))
+                    }
                 }
             }
 
-            NodeType::MathSin => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.sin()),
-                    Value::Int(n) => Value::Float((n as f64).sin()),
-                    _ => return Err(ASGError::TypeError("Expected number for sin".to_string())),
+            NodeType::LazyFilter => {
+                // (lazy-filter fn seq) -> lazy filtered seq
+                let (fn_val, seq_val) = self.get_binary_operands(asg, node)?;
+                match seq_val {
+                    Value::LazySeq(kind) => Value::LazySeq(Box::new(LazySeqKind::Filter {
+                        func: Box::new(fn_val),
+                        source: kind,
+                    })),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected lazy sequence for lazy-filter".to_string(),
+                        ))
+                    }
                 }
             }
 
-            NodeType::MathCos => {
-                let val = self.get_single_operand(asg, node)?;
+            NodeType::Collect => {
+                // (collect seq) -> materialize lazy seq (infinite ones limited to 10000 elements)
+                let val = self.streamed_single_operand(asg, node)?;
                 match val {
-                    Value::Float(f) => Value::Float(f.cos()),
-                    Value::Int(n) => Value::Float((n as f64).cos()),
-                    _ => return Err(ASGError::TypeError("Expected number for cos".to_string())),
+                    // Конечная последовательность материализуется целиком
+                    Value::LazySeq(kind) if kind.is_bounded() => {
+                        // Проверяем по ходу, чтобы не материализовать огромный диапазон
+                        let mut result = Vec::new();
+                        self.for_each_lazy(asg, *kind, "collect", |this, item| {
+                            this.check_collection_size(result.len() + 1)?;
+                            result.push(item);
+                            Ok(())
+                        })?;
+                        Value::Array(result)
+                    }
+                    Value::LazySeq(kind) => {
+                        let result = self.take_from_lazy(asg, *kind, COLLECT_LIMIT)?;
+                        Value::Array(result)
+                    }
+                    Value::Array(arr) => Value::Array(arr),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected lazy sequence for collect".to_string(),
+                        ))
+                    }
                 }
             }
 
-            NodeType::MathTan => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.tan()),
-                    Value::Int(n) => Value::Float((n as f64).tan()),
-                    _ => return Err(ASGError::TypeError("Expected number for tan".to_string())),
-                }
+            // === Генераторы ===
+            NodeType::Generator => {
+                // Вычисляется в кадре вызова: параметры уже связаны
+                let body_id = node
+                    .find_edge(EdgeType::FunctionBody)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::FunctionBody))?
+                    .target_node_id;
+                let locals = self
+                    .call_stack
+                    .last()
+                    .map(|frame| frame.locals.clone())
+                    .unwrap_or_default();
+                let mut resumable = HashSet::new();
+                collect_resumable(asg, body_id, &mut resumable, &mut HashSet::new());
+                Value::Generator(GeneratorRef(Rc::new(RefCell::new(GeneratorState {
+                    locals,
+                    functions: HashMap::new(),
+                    stack: vec![GeneratorStep::Eval(body_id)],
+                    resumable: Rc::new(resumable),
+                }))))
+            }
+
+            NodeType::Yield => {
+                // Внутри генератора yield обрабатывает run_generator
+                return Err(ASGError::InvalidOperation(
+                    "yield outside of a generator body".to_string(),
+                ));
             }
 
-            NodeType::MathAsin => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.asin()),
-                    Value::Int(n) => Value::Float((n as f64).asin()),
-                    _ => return Err(ASGError::TypeError("Expected number for asin".to_string())),
+            NodeType::GeneratorNext => match self.get_single_operand(asg, node)? {
+                Value::Generator(generator) => self.generator_next(asg, &generator)?,
+                other => {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected generator for next, got {}",
+                        other.type_name()
+                    )))
                 }
-            }
+            },
 
-            NodeType::MathAcos => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.acos()),
-                    Value::Int(n) => Value::Float((n as f64).acos()),
-                    _ => return Err(ASGError::TypeError("Expected number for acos".to_string())),
+            // === Range and iterators ===
+            NodeType::Range => {
+                let (start, end, step) = self.range_bounds(asg, node)?;
+
+                let len = if step > 0 {
+                    end.saturating_sub(start).max(0).saturating_add(step - 1) / step
+                } else {
+                    start.saturating_sub(end).max(0).saturating_add(-step - 1) / -step
+                };
+                // Лимит проверяется до выделения массива; без лимита слишком
+                // большой диапазон — ошибка, а не паника при выделении памяти
+                self.check_collection_size(len as usize)?;
+                let mut result = Vec::new();
+                result.try_reserve_exact(len as usize).map_err(|_| {
+                    ASGError::InvalidOperation(format!(
+                        "range of {} elements is too large to allocate",
+                        len
+                    ))
+                })?;
+                let mut i = start;
+                if step > 0 {
+                    while i < end {
+                        result.push(Value::Int(i));
+                        i += step;
+                    }
+                } else {
+                    while i > end {
+                        result.push(Value::Int(i));
+                        i += step;
+                    }
                 }
+                Value::Array(result)
             }
 
-            NodeType::MathAtan => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.atan()),
-                    Value::Int(n) => Value::Float((n as f64).atan()),
-                    _ => return Err(ASGError::TypeError("Expected number for atan".to_string())),
+            NodeType::For => {
+                let var_edge = node
+                    .find_edge(EdgeType::LoopInit)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopInit))?;
+                let var_node = asg
+                    .find_node(var_edge.target_node_id)
+                    .ok_or(ASGError::NodeNotFound(var_edge.target_node_id))?;
+                // Переменная цикла или паттерн пары (for [k v] ...)
+                let var_names = match (var_node.node_type, var_node.payload.as_deref()) {
+                    (NodeType::LetDestructure, Some(payload)) => destructure_names(payload),
+                    _ => vec![var_node.get_name().unwrap_or_default()],
+                };
+                let iterable_edge = node
+                    .find_edge(EdgeType::Condition)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::Condition))?;
+                let body_id = node
+                    .find_edge(EdgeType::LoopBody)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::LoopBody))?
+                    .target_node_id;
+
+                // (for i (range a b)) обходит границы напрямую, без массива
+                let iterable_val = self.streamed_operand(asg, iterable_edge.target_node_id)?;
+                let mut last_result = Value::Unit;
+                let items = match iterable_val {
+                    Value::Array(arr) => arr,
+                    // Ленивая последовательность — поэлементно, без материализации
+                    Value::LazySeq(kind) => {
+                        let run = self.for_each_lazy(asg, *kind, "for", |this, item| {
+                            match this.run_for_body(asg, &var_names, item, body_id) {
+                                Ok(value) => last_result = value,
+                                Err(ASGError::LoopContinue) => {}
+                                Err(err) => return Err(err),
+                            }
+                            Ok(())
+                        });
+                        self.loop_broken(run, &mut last_result)?;
+                        Vec::new()
+                    }
+                    // Множество — по элементам в порядке возрастания
+                    Value::Set(items) => items.iter().map(SetKey::to_value).collect(),
+                    // Строка — по символам (скалярам Unicode), как str-to-chars
+                    Value::String(s) => s.chars().map(|c| Value::String(c.to_string())).collect(),
+                    // Словарь — пары [ключ значение] в порядке ключей
+                    Value::Dict(dict) => {
+                        let mut entries: Vec<_> = dict.into_iter().collect();
+                        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                        entries
+                            .into_iter()
+                            .map(|(k, v)| Value::Array(vec![Value::String(k), v]))
+                            .collect()
+                    }
+                    other => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected array, string, dict or set for for loop, got {}",
+                            other.type_name()
+                        )))
+                    }
+                };
+
+                for item in items {
+                    let step = self
+                        .run_for_body(asg, &var_names, item, body_id)
+                        .map(|value| last_result = value);
+                    if self.loop_broken(step, &mut last_result)? {
+                        break;
+                    }
                 }
+                last_result
             }
 
-            NodeType::MathExp => {
+            NodeType::ArrayReverse => {
                 let val = self.get_single_operand(asg, node)?;
                 match val {
-                    Value::Float(f) => Value::Float(f.exp()),
-                    Value::Int(n) => Value::Float((n as f64).exp()),
-                    _ => return Err(ASGError::TypeError("Expected number for exp".to_string())),
+                    Value::Array(mut arr) => {
+                        arr.reverse();
+                        Value::Array(arr)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected array for reverse".to_string(),
+                        ))
+                    }
                 }
             }
 
-            NodeType::MathLn => {
+            NodeType::ArraySort => {
                 let val = self.get_single_operand(asg, node)?;
                 match val {
-                    Value::Float(f) => Value::Float(f.ln()),
-                    Value::Int(n) => Value::Float((n as f64).ln()),
-                    _ => return Err(ASGError::TypeError("Expected number for ln".to_string())),
+                    Value::Array(mut arr) => {
+                        check_orderable("sort", &arr)?;
+                        arr.sort_by(compare_values);
+                        Value::Array(arr)
+                    }
+                    _ => return Err(ASGError::TypeError("Expected array for sort".to_string())),
                 }
             }
 
-            NodeType::MathLog10 => {
+            NodeType::ArraySortDescending => {
                 let val = self.get_single_operand(asg, node)?;
                 match val {
-                    Value::Float(f) => Value::Float(f.log10()),
-                    Value::Int(n) => Value::Float((n as f64).log10()),
-                    _ => return Err(ASGError::TypeError("Expected number for log10".to_string())),
+                    Value::Array(mut arr) => {
+                        check_orderable("sort-desc", &arr)?;
+                        arr.sort_by(|a, b| compare_values(b, a));
+                        Value::Array(arr)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected array for sort-desc".to_string(),
+                        ))
+                    }
                 }
             }
 
-            NodeType::MathPow => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Float(base), Value::Float(exp)) => Value::Float(base.powf(exp)),
-                    (Value::Float(base), Value::Int(exp)) => Value::Float(base.powi(exp as i32)),
-                    (Value::Int(base), Value::Float(exp)) => Value::Float((base as f64).powf(exp)),
-                    (Value::Int(base), Value::Int(exp)) => {
-                        if exp >= 0 {
-                            Value::Int(base.pow(exp as u32))
-                        } else {
-                            Value::Float((base as f64).powi(exp as i32))
+            NodeType::ArrayNthLargest => {
+                let (arr_val, k_val) = self.get_binary_operands(asg, node)?;
+                match (arr_val, k_val) {
+                    (Value::Array(mut arr), Value::Int(k)) => {
+                        if k < 1 || k as usize > arr.len() {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "nth-largest: k = {} out of range for array of length {}",
+                                k,
+                                arr.len()
+                            )));
                         }
+                        check_orderable("nth-largest", &arr)?;
+                        // Частичная выборка вместо полной сортировки
+                        let (_, nth, _) =
+                            arr.select_nth_unstable_by(k as usize - 1, |a, b| compare_values(b, a));
+                        nth.clone()
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected array and integer for nth-largest".to_string(),
+                        ))
                     }
-                    _ => return Err(ASGError::TypeError("Expected numbers for pow".to_string())),
                 }
             }
 
-            NodeType::MathAbs => {
-                let val = self.get_single_operand(asg, node)?;
+            NodeType::ArraySum => {
+                let val = self.streamed_single_operand(asg, node)?;
+                let mut int_sum = 0i64;
+                let mut float_sum = 0.0f64;
+                let mut has_float = false;
+                let mut add = |item: Value| match item {
+                    Value::Int(n) => {
+                        int_sum += n;
+                        Ok(())
+                    }
+                    Value::Float(f) => {
+                        float_sum += f;
+                        has_float = true;
+                        Ok(())
+                    }
+                    _ => Err(ASGError::TypeError(
+                        "Expected numbers in array for sum".to_string(),
+                    )),
+                };
                 match val {
-                    Value::Float(f) => Value::Float(f.abs()),
-                    Value::Int(n) => Value::Int(n.abs()),
-                    _ => return Err(ASGError::TypeError("Expected number for abs".to_string())),
+                    Value::Array(arr) => arr.into_iter().try_for_each(&mut add)?,
+                    // Конечная ленивая последовательность суммируется без материализации
+                    Value::LazySeq(kind) => {
+                        self.for_each_lazy(asg, *kind, "sum", |_, item| add(item))?
+                    }
+                    _ => return Err(ASGError::TypeError("Expected array for sum".to_string())),
+                }
+                if has_float {
+                    Value::Float(int_sum as f64 + float_sum)
+                } else {
+                    Value::Int(int_sum)
                 }
             }
 
-            NodeType::MathFloor => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.floor()),
-                    Value::Int(n) => Value::Int(n),
-                    _ => return Err(ASGError::TypeError("Expected number for floor".to_string())),
-                }
-            }
-
-            NodeType::MathCeil => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Float(f) => Value::Float(f.ceil()),
-                    Value::Int(n) => Value::Int(n),
-                    _ => return Err(ASGError::TypeError("Expected number for ceil".to_string())),
-                }
-            }
-
-            NodeType::MathRound => {
-                let val = self.get_single_operand(asg, node)?;
+            NodeType::ArrayProduct => {
+                let val = self.streamed_single_operand(asg, node)?;
+                let mut int_prod = 1i64;
+                let mut float_prod = 1.0f64;
+                let mut has_float = false;
+                let mut is_empty = true;
+                let mut mul = |item: Value| {
+                    is_empty = false;
+                    match item {
+                        Value::Int(n) => int_prod *= n,
+                        Value::Float(f) => {
+                            float_prod *= f;
+                            has_float = true;
+                        }
+                        _ => {
+                            return Err(ASGError::TypeError(
+                                "Expected numbers in array for product".to_string(),
+                            ))
+                        }
+                    }
+                    Ok(())
+                };
                 match val {
-                    Value::Float(f) => Value::Float(f.round()),
-                    Value::Int(n) => Value::Int(n),
-                    _ => return Err(ASGError::TypeError("Expected number for round".to_string())),
-                }
-            }
-
-            NodeType::MathMin => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Int(a.min(b)),
-                    (Value::Float(a), Value::Float(b)) => Value::Float(a.min(b)),
-                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64).min(b)),
-                    (Value::Float(a), Value::Int(b)) => Value::Float(a.min(b as f64)),
-                    _ => return Err(ASGError::TypeError("Expected numbers for min".to_string())),
+                    Value::Array(arr) => arr.into_iter().try_for_each(&mut mul)?,
+                    Value::LazySeq(kind) => {
+                        self.for_each_lazy(asg, *kind, "product", |_, item| mul(item))?
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected array for product".to_string(),
+                        ))
+                    }
                 }
-            }
-
-            NodeType::MathMax => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::Int(a), Value::Int(b)) => Value::Int(a.max(b)),
-                    (Value::Float(a), Value::Float(b)) => Value::Float(a.max(b)),
-                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64).max(b)),
-                    (Value::Float(a), Value::Int(b)) => Value::Float(a.max(b as f64)),
-                    _ => return Err(ASGError::TypeError("Expected numbers for max".to_string())),
+                if is_empty {
+                    return Ok(());
                 }
-            }
-
-            NodeType::MathPi => Value::Float(std::f64::consts::PI),
-            NodeType::MathE => Value::Float(std::f64::consts::E),
-
-            // === I/O ===
-            NodeType::Print => {
-                let arg_edge = node.edges.first().ok_or(ASGError::MissingEdge(
-                    node.id,
-                    EdgeType::ApplicationArgument,
-                ))?;
-
-                let value = self.ensure_evaluated(asg, arg_edge.target_node_id)?;
-
-                // Вывод значения - для строк без кавычек, для остальных format_display
-                match &value {
-                    Value::String(s) => println!("{}", s),
-                    other => println!("{}", other.format_display()),
+                if has_float {
+                    Value::Float(int_prod as f64 * float_prod)
+                } else {
+                    Value::Int(int_prod)
                 }
-                Value::Unit
             }
 
-            NodeType::Input => {
-                // Показать prompt если есть
-                if let Some(edge) = node.edges.first() {
-                    let prompt = self.ensure_evaluated(asg, edge.target_node_id)?;
-                    if let Value::String(s) = prompt {
-                        print!("{}", s);
-                        use std::io::Write;
-                        std::io::stdout().flush().ok();
+            NodeType::ArrayContains => {
+                let (arr_val, elem_val) = self.get_binary_operands(asg, node)?;
+                match arr_val {
+                    Value::Array(arr) => {
+                        let found = arr.iter().any(|item| self.values_equal(item, &elem_val));
+                        Value::Bool(found)
                     }
-                }
-
-                // Читаем строку
-                let mut input = String::new();
-                std::io::stdin()
-                    .read_line(&mut input)
-                    .map_err(|e| ASGError::InvalidOperation(format!("Input error: {}", e)))?;
-                Value::String(input.trim_end().to_string())
-            }
-
-            NodeType::InputInt => {
-                // Показать prompt если есть
-                if let Some(edge) = node.edges.first() {
-                    let prompt = self.ensure_evaluated(asg, edge.target_node_id)?;
-                    if let Value::String(s) = prompt {
-                        print!("{}", s);
-                        use std::io::Write;
-                        std::io::stdout().flush().ok();
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected array for contains".to_string(),
+                        ))
                     }
                 }
-
-                let mut input = String::new();
-                std::io::stdin()
-                    .read_line(&mut input)
-                    .map_err(|e| ASGError::InvalidOperation(format!("Input error: {}", e)))?;
-                let n: i64 = input.trim().parse().map_err(|_| {
-                    ASGError::TypeError(format!("Cannot parse '{}' as integer", input.trim()))
-                })?;
-                Value::Int(n)
             }
 
-            NodeType::InputFloat => {
-                // Показать prompt если есть
-                if let Some(edge) = node.edges.first() {
-                    let prompt = self.ensure_evaluated(asg, edge.target_node_id)?;
-                    if let Value::String(s) = prompt {
-                        print!("{}", s);
-                        use std::io::Write;
-                        std::io::stdout().flush().ok();
+            NodeType::ArrayIndexOf => {
+                let (arr_val, elem_val) = self.get_binary_operands(asg, node)?;
+                match arr_val {
+                    Value::Array(arr) => {
+                        let idx = arr
+                            .iter()
+                            .position(|item| self.values_equal(item, &elem_val));
+                        match idx {
+                            Some(i) => Value::Int(i as i64),
+                            None => Value::Int(-1),
+                        }
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected array for index-of".to_string(),
+                        ))
                     }
                 }
-
-                let mut input = String::new();
-                std::io::stdin()
-                    .read_line(&mut input)
-                    .map_err(|e| ASGError::InvalidOperation(format!("Input error: {}", e)))?;
-                let f: f64 = input.trim().parse().map_err(|_| {
-                    ASGError::TypeError(format!("Cannot parse '{}' as float", input.trim()))
-                })?;
-                Value::Float(f)
-            }
-
-            NodeType::ClearScreen => {
-                // ANSI escape для очистки экрана
-                print!("\x1B[2J\x1B[1;1H");
-                use std::io::Write;
-                std::io::stdout().flush().ok();
-                Value::Unit
             }
 
-            NodeType::ReadFile => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(path) => match fs::read_to_string(&path) {
-                        Ok(content) => Value::String(content),
-                        Err(e) => {
-                            return Err(ASGError::InvalidOperation(format!(
-                                "Cannot read file '{}': {}",
-                                path, e
-                            )))
+            NodeType::ArrayBinarySearch => {
+                let (arr_val, target) = self.get_binary_operands(asg, node)?;
+                match arr_val {
+                    Value::Array(arr) => {
+                        // Сравнимы только однородные Int, Float или String
+                        let comparable = |v: &Value| {
+                            matches!(
+                                (v, &target),
+                                (Value::Int(_), Value::Int(_))
+                                    | (Value::Float(_), Value::Float(_))
+                                    | (Value::String(_), Value::String(_))
+                            )
+                        };
+                        if !comparable(&target) || !arr.iter().all(comparable) {
+                            return Err(ASGError::TypeError(
+                                "binary-search expects an array of Int, Float or String \
+                                 and a value of the same type"
+                                    .to_string(),
+                            ));
                         }
-                    },
+                        match arr.binary_search_by(|item| compare_values(item, &target)) {
+                            Ok(i) => Value::Int(i as i64),
+                            Err(i) => Value::Int(-(i as i64) - 1),
+                        }
+                    }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected string path for read-file".to_string(),
+                            "Expected array for binary-search".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::WriteFile => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::String(path), Value::String(content)) => {
-                        match fs::write(&path, &content) {
-                            Ok(_) => Value::Unit,
-                            Err(e) => {
-                                return Err(ASGError::InvalidOperation(format!(
-                                    "Cannot write file '{}': {}",
-                                    path, e
-                                )))
-                            }
-                        }
+            NodeType::ArrayTake => {
+                let (arr_val, n_val) = self.get_binary_operands(asg, node)?;
+                match (arr_val, n_val) {
+                    (Value::Array(arr), Value::Int(n)) => {
+                        let n = n.max(0) as usize;
+                        Value::Array(arr.into_iter().take(n).collect())
                     }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected (path, content) strings for write-file".to_string(),
+                            "Expected (array, int) for take".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::AppendFile => {
-                let (val1, val2) = self.get_binary_operands(asg, node)?;
-                match (val1, val2) {
-                    (Value::String(path), Value::String(content)) => {
-                        match fs::OpenOptions::new().create(true).append(true).open(&path) {
-                            Ok(mut file) => match file.write_all(content.as_bytes()) {
-                                Ok(_) => Value::Unit,
-                                Err(e) => {
-                                    return Err(ASGError::InvalidOperation(format!(
-                                        "Cannot append to file '{}': {}",
-                                        path, e
-                                    )))
-                                }
-                            },
-                            Err(e) => {
-                                return Err(ASGError::InvalidOperation(format!(
-                                    "Cannot open file '{}': {}",
-                                    path, e
-                                )))
-                            }
-                        }
+            NodeType::ArrayDrop => {
+                let (arr_val, n_val) = self.get_binary_operands(asg, node)?;
+                match (arr_val, n_val) {
+                    (Value::Array(arr), Value::Int(n)) => {
+                        let n = n.max(0) as usize;
+                        Value::Array(arr.into_iter().skip(n).collect())
                     }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected (path, content) strings for append-file".to_string(),
+                            "Expected (array, int) for drop".to_string(),
                         ))
                     }
                 }
             }
 
-            NodeType::FileExists => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(path) => Value::Bool(std::path::Path::new(&path).exists()),
+            NodeType::ArrayAppend => {
+                let (arr_val, elem_val) = self.get_binary_operands(asg, node)?;
+                match arr_val {
+                    Value::Array(mut arr) => {
+                        arr.push(elem_val);
+                        Value::Array(arr)
+                    }
+                    _ => return Err(ASGError::TypeError("Expected array for append".to_string())),
+                }
+            }
+
+            NodeType::ArrayConcat => {
+                let (arr1_val, arr2_val) = self.get_binary_operands(asg, node)?;
+                match (arr1_val, arr2_val) {
+                    (Value::Array(mut arr1), Value::Array(arr2)) => {
+                        arr1.extend(arr2);
+                        Value::Array(arr1)
+                    }
                     _ => {
                         return Err(ASGError::TypeError(
-                            "Expected string path for file-exists".to_string(),
+                            "Expected two arrays for array-concat".to_string(),
                         ))
                     }
                 }
             }
 
-            // === Error Handling ===
-            NodeType::TryCatch => {
-                let try_edge = node
-                    .find_edge(EdgeType::TryBody)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::TryBody))?;
-                let var_edge = node
-                    .find_edge(EdgeType::CatchVariable)
+            NodeType::ArrayUnion | NodeType::ArrayIntersect | NodeType::ArrayDifference => {
+                let form = match node.node_type {
+                    NodeType::ArrayUnion => "array-union",
+                    NodeType::ArrayIntersect => "array-intersect",
+                    _ => "array-diff",
+                };
+                let (a, b) = match self.get_binary_operands(asg, node)? {
+                    (Value::Array(a), Value::Array(b)) => (a, b),
+                    _ => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected two arrays for {}",
+                            form
+                        )))
+                    }
+                };
+                // Сравнение через values_equal (float с допуском), поэтому без хеширования
+                let contains = |this: &Self, items: &[Value], value: &Value| {
+                    items
+                        .iter()
+                        .any(|item| this.values_equal(item, value) || item == value)
+                };
+                let mut result: Vec<Value> = Vec::new();
+                for item in a.iter() {
+                    let keep = match node.node_type {
+                        NodeType::ArrayUnion => true,
+                        NodeType::ArrayIntersect => contains(self, &b, item),
+                        _ => !contains(self, &b, item),
+                    };
+                    if keep && !contains(self, &result, item) {
+                        result.push(item.clone());
+                    }
+                }
+                if node.node_type == NodeType::ArrayUnion {
+                    for item in b {
+                        if !contains(self, &result, &item) {
+                            result.push(item);
+                        }
+                    }
+                }
+                Value::Array(result)
+            }
+
+            NodeType::ArraySlice => {
+                let edges: Vec<_> = node.edges.iter().collect();
+                if edges.len() != 3 {
+                    return Err(ASGError::InvalidOperation(
+                        "slice requires 3 arguments".to_string(),
+                    ));
+                }
+                let arr_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let start_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
+                let end_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+
+                match (arr_val, start_val, end_val) {
+                    (Value::Array(arr), Value::Int(start), Value::Int(end)) => {
+                        let start = start.max(0) as usize;
+                        let end = (end as usize).min(arr.len());
+                        if start >= end {
+                            Value::Array(vec![])
+                        } else {
+                            Value::Array(arr[start..end].to_vec())
+                        }
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (array, int, int) for slice".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::ArrayCompact => {
+                let val = self.get_single_operand(asg, node)?;
+                // (compact arr true): Error-значения тоже отбрасываются
+                let drop_errors = match node.find_edge(EdgeType::SecondOperand) {
+                    Some(edge) => match self.ensure_evaluated(asg, edge.target_node_id)? {
+                        Value::Bool(b) => b,
+                        _ => {
+                            return Err(ASGError::TypeError(
+                                "Expected bool drop-errors flag for compact".to_string(),
+                            ))
+                        }
+                    },
+                    None => false,
+                };
+                match val {
+                    Value::Array(mut arr) => {
+                        arr.retain(|v| match v {
+                            Value::Unit => false,
+                            Value::Error(_) => !drop_errors,
+                            _ => true,
+                        });
+                        Value::Array(arr)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected array for compact".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::ArrayFlattenDeep => {
+                let val = self.get_single_operand(asg, node)?;
+                // (flatten-n arr depth): не глубже depth уровней; без depth — до конца
+                let depth = match node.find_edge(EdgeType::SecondOperand) {
+                    Some(edge) => match self.ensure_evaluated(asg, edge.target_node_id)? {
+                        Value::Int(n) if n >= 0 => Some(n as usize),
+                        other => {
+                            return Err(ASGError::TypeError(format!(
+                                "Expected non-negative Int depth for flatten-n, got {}",
+                                other.format_display()
+                            )))
+                        }
+                    },
+                    None => None,
+                };
+                match val {
+                    Value::Array(arr) => {
+                        let mut flat = Vec::new();
+                        flatten_into(arr, depth, &mut flat);
+                        Value::Array(flat)
+                    }
+                    other => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected array for flatten, got {}",
+                            other.type_name()
+                        )))
+                    }
+                }
+            }
+
+            NodeType::ArrayShuffle => match self.get_single_operand(asg, node)? {
+                Value::Array(mut arr) => {
+                    // SliceRandom::shuffle — Fisher–Yates; генератор тот же, что у gen-int
+                    arr.shuffle(&mut self.rng);
+                    Value::Array(arr)
+                }
+                other => {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected array for shuffle, got {}",
+                        other.type_name()
+                    )))
+                }
+            },
+
+            NodeType::ArraySample => match self.get_binary_operands(asg, node)? {
+                (Value::Array(arr), Value::Int(n)) => {
+                    let count = usize::try_from(n)
+                        .ok()
+                        .filter(|&n| n <= arr.len())
+                        .ok_or_else(|| {
+                            ASGError::InvalidOperation(format!(
+                                "sample: cannot take {} elements from array of length {}",
+                                n,
+                                arr.len()
+                            ))
+                        })?;
+                    // Индексы в случайном порядке, без повторов
+                    Value::Array(
+                        rand::seq::index::sample(&mut self.rng, arr.len(), count)
+                            .into_iter()
+                            .map(|i| arr[i].clone())
+                            .collect(),
+                    )
+                }
+                _ => {
+                    return Err(ASGError::TypeError(
+                        "Expected (sample arr n) with array and int".to_string(),
+                    ))
+                }
+            },
+
+            NodeType::ArraySampleOne => match self.get_single_operand(asg, node)? {
+                Value::Array(arr) => arr.choose(&mut self.rng).cloned().ok_or_else(|| {
+                    ASGError::InvalidOperation("sample-one: array is empty".to_string())
+                })?,
+                other => {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected array for sample-one, got {}",
+                        other.type_name()
+                    )))
+                }
+            },
+
+            // === Dict operations ===
+            NodeType::Dict => {
+                let mut dict = HashMap::new();
+                let edges: Vec<_> = node.edges.iter().collect();
+                let mut i = 0;
+                while i < edges.len() {
+                    // Распаковка: записи base, более поздние ключи их перекрывают
+                    if edges[i].edge_type == EdgeType::ApplicationArgument {
+                        match self.ensure_evaluated(asg, edges[i].target_node_id)? {
+                            Value::Dict(base) => dict.extend(base),
+                            Value::Record(fields) => dict.extend(fields),
+                            other => {
+                                return Err(ASGError::TypeError(format!(
+                                    "Cannot spread {} into dict",
+                                    other.type_name()
+                                )))
+                            }
+                        }
+                        i += 1;
+                        continue;
+                    }
+                    if i + 1 >= edges.len() {
+                        break;
+                    }
+                    let key_val = self.ensure_evaluated(asg, edges[i].target_node_id)?;
+                    let val = self.ensure_evaluated(asg, edges[i + 1].target_node_id)?;
+                    let key = match key_val {
+                        Value::String(s) => s,
+                        Value::Int(n) => n.to_string(),
+                        _ => {
+                            return Err(ASGError::TypeError(
+                                "Dict keys must be strings or ints".to_string(),
+                            ))
+                        }
+                    };
+                    dict.insert(key, val);
+                    i += 2;
+                }
+                Value::Dict(dict)
+            }
+
+            NodeType::DictGet => {
+                let (dict_val, key_val) = self.get_binary_operands(asg, node)?;
+                match (dict_val, key_val) {
+                    (Value::Dict(dict), Value::String(key)) => {
+                        dict.get(&key).cloned().unwrap_or(Value::Unit)
+                    }
+                    (Value::Dict(dict), Value::Int(n)) => {
+                        dict.get(&n.to_string()).cloned().unwrap_or(Value::Unit)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (dict, key) for dict-get".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::DictSet => {
+                let edges: Vec<_> = node.edges.iter().collect();
+                if edges.len() < 3 {
+                    return Err(ASGError::MissingEdge(
+                        node.id,
+                        EdgeType::ApplicationArgument,
+                    ));
+                }
+                let dict_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let key_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
+                let new_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+
+                match (dict_val, key_val) {
+                    (Value::Dict(mut dict), Value::String(key)) => {
+                        dict.insert(key, new_val);
+                        Value::Dict(dict)
+                    }
+                    (Value::Dict(mut dict), Value::Int(n)) => {
+                        dict.insert(n.to_string(), new_val);
+                        Value::Dict(dict)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (dict, key, value) for dict-set".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::DictUpdate => {
+                let edges = node.find_edges(EdgeType::ApplicationArgument);
+                if edges.len() < 3 {
+                    return Err(ASGError::MissingEdge(
+                        node.id,
+                        EdgeType::ApplicationArgument,
+                    ));
+                }
+                let dict_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let key_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
+                let func = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+
+                let (mut dict, key) = match (dict_val, key_val) {
+                    (Value::Dict(dict), Value::String(key)) => (dict, key),
+                    (Value::Dict(dict), Value::Int(n)) => (dict, n.to_string()),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (dict, key, fn) for dict-update".to_string(),
+                        ))
+                    }
+                };
+                let current = match (dict.get(&key), edges.get(3)) {
+                    (Some(val), _) => val.clone(),
+                    (None, Some(default_edge)) => {
+                        self.ensure_evaluated(asg, default_edge.target_node_id)?
+                    }
+                    (None, None) => {
+                        return Err(ASGError::InvalidOperation(format!(
+                            "dict-update: key '{}' not found and no default given",
+                            key
+                        )))
+                    }
+                };
+                let updated = self.call_function_value(asg, func, current)?;
+                dict.insert(key, updated);
+                Value::Dict(dict)
+            }
+
+            NodeType::CallMethod => {
+                let edges = node.find_edges(EdgeType::ApplicationArgument);
+                if edges.len() < 2 {
+                    return Err(ASGError::MissingEdge(
+                        node.id,
+                        EdgeType::ApplicationArgument,
+                    ));
+                }
+                let obj = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let name = match self.ensure_evaluated(asg, edges[1].target_node_id)? {
+                    Value::String(name) => name,
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "call-method: method name must be a string".to_string(),
+                        ))
+                    }
+                };
+                let method = match &obj {
+                    Value::Dict(fields) | Value::Record(fields) => fields.get(&name).cloned(),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "call-method: expected Dict or Record".to_string(),
+                        ))
+                    }
+                }
+                .ok_or_else(|| {
+                    ASGError::InvalidOperation(format!("call-method: no method '{}'", name))
+                })?;
+                if !method.is_callable() {
+                    return Err(ASGError::TypeError(format!(
+                        "call-method: field '{}' is not a function",
+                        name
+                    )));
+                }
+
+                // Объект всегда передаётся первым аргументом (self)
+                let mut args = vec![obj];
+                for edge in &edges[2..] {
+                    args.push(self.ensure_evaluated(asg, edge.target_node_id)?);
+                }
+                self.call(asg, &method, &args)?
+            }
+
+            NodeType::DictHas => {
+                let (dict_val, key_val) = self.get_binary_operands(asg, node)?;
+                match (dict_val, key_val) {
+                    (Value::Dict(dict), Value::String(key)) => Value::Bool(dict.contains_key(&key)),
+                    (Value::Dict(dict), Value::Int(n)) => {
+                        Value::Bool(dict.contains_key(&n.to_string()))
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (dict, key) for dict-has".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::DictRemove => {
+                let (dict_val, key_val) = self.get_binary_operands(asg, node)?;
+                match (dict_val, key_val) {
+                    (Value::Dict(mut dict), Value::String(key)) => {
+                        dict.remove(&key);
+                        Value::Dict(dict)
+                    }
+                    (Value::Dict(mut dict), Value::Int(n)) => {
+                        dict.remove(&n.to_string());
+                        Value::Dict(dict)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (dict, key) for dict-remove".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::DictKeys => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Dict(dict) => {
+                        Value::Array(dict.keys().map(|k| Value::String(k.clone())).collect())
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected dict for dict-keys".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::DictValues => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Dict(dict) => Value::Array(dict.values().cloned().collect()),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected dict for dict-values".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::DictMerge => {
+                let (dict1_val, dict2_val) = self.get_binary_operands(asg, node)?;
+                match (dict1_val, dict2_val) {
+                    (Value::Dict(mut d1), Value::Dict(d2)) => {
+                        for (k, v) in d2 {
+                            d1.insert(k, v);
+                        }
+                        Value::Dict(d1)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected two dicts for dict-merge".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::DictSize => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Dict(dict) => Value::Int(dict.len() as i64),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected dict for dict-size".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            // === Set ===
+            NodeType::SetNew => {
+                let mut items = BTreeSet::new();
+                for edge in node.find_edges(EdgeType::ArrayElement) {
+                    let value = self.ensure_evaluated(asg, edge.target_node_id)?;
+                    items.insert(SetKey::from_value(&value)?);
+                }
+                Value::Set(items)
+            }
+
+            NodeType::SetAdd => {
+                let (set, value) = self.get_binary_operands(asg, node)?;
+                let mut items = expect_set(set, "set-add")?;
+                items.insert(SetKey::from_value(&value)?);
+                Value::Set(items)
+            }
+
+            NodeType::SetContains => {
+                let (set, value) = self.get_binary_operands(asg, node)?;
+                let items = expect_set(set, "set-contains")?;
+                // Значение, которое не может быть элементом, просто не содержится
+                Value::Bool(SetKey::from_value(&value).is_ok_and(|key| items.contains(&key)))
+            }
+
+            NodeType::SetUnion | NodeType::SetIntersect | NodeType::SetDiff => {
+                let form = match node.node_type {
+                    NodeType::SetUnion => "set-union",
+                    NodeType::SetIntersect => "set-intersect",
+                    _ => "set-diff",
+                };
+                let (a, b) = self.get_binary_operands(asg, node)?;
+                let (a, b) = (expect_set(a, form)?, expect_set(b, form)?);
+                Value::Set(match node.node_type {
+                    NodeType::SetUnion => a.union(&b).cloned().collect(),
+                    NodeType::SetIntersect => a.intersection(&b).cloned().collect(),
+                    _ => a.difference(&b).cloned().collect(),
+                })
+            }
+
+            NodeType::SetSize => {
+                let set = self.get_single_operand(asg, node)?;
+                Value::Int(expect_set(set, "set-size")?.len() as i64)
+            }
+
+            // === Pipe and Compose ===
+            NodeType::Pipe => {
+                // (|> value fn1 fn2 ...)
+                let edges: Vec<_> = node.edges.iter().collect();
+                if edges.is_empty() {
+                    return Err(ASGError::InvalidOperation(
+                        "Pipe requires at least one argument".to_string(),
+                    ));
+                }
+
+                let mut current = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+
+                for edge in &edges[1..] {
+                    let fn_val = self.ensure_evaluated(asg, edge.target_node_id)?;
+                    current = self.call_function_value(asg, fn_val, current)?;
+                }
+                current
+            }
+
+            NodeType::Compose => {
+                // (compose fn1 fn2 ...) - создаём композицию функций
+                let edges: Vec<_> = node.edges.iter().collect();
+                if edges.is_empty() {
+                    return Err(ASGError::InvalidOperation(
+                        "Compose requires at least one function".to_string(),
+                    ));
+                }
+
+                let mut fns = Vec::new();
+                for edge in &edges {
+                    let fn_val = self.ensure_evaluated(asg, edge.target_node_id)?;
+                    if !fn_val.is_callable() {
+                        return Err(ASGError::TypeError("Compose expects functions".to_string()));
+                    }
+                    fns.push(fn_val);
+                }
+                Value::ComposedFunction(fns)
+            }
+
+            // === Строковые операции ===
+            NodeType::StringConcat => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::String(s1), Value::String(s2)) => {
+                        Value::String(format!("{}{}", s1, s2))
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected strings for concat".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringLength => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => Value::Int(s.chars().count() as i64),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for str-length".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringSubstring => {
+                let edges: Vec<_> = node.edges.iter().collect();
+                if edges.len() < 3 {
+                    return Err(ASGError::MissingEdge(
+                        node.id,
+                        EdgeType::ApplicationArgument,
+                    ));
+                }
+                let str_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let start_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
+                let end_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+
+                match (str_val, start_val, end_val) {
+                    (Value::String(s), Value::Int(start), Value::Int(end)) => {
+                        let chars: Vec<char> = s.chars().collect();
+                        let start = start.max(0) as usize;
+                        let end = (end as usize).min(chars.len());
+                        if start >= end {
+                            Value::String(String::new())
+                        } else {
+                            Value::String(chars[start..end].iter().collect())
+                        }
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (string, int, int) for substring".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringSplit => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                // (str-split s delim n): необязательное максимальное число разрезов
+                let limit = match node.find_edge(EdgeType::ApplicationArgument) {
+                    Some(edge) => match self.ensure_evaluated(asg, edge.target_node_id)? {
+                        Value::Int(n) if n >= 0 => Some(n as usize),
+                        _ => {
+                            return Err(ASGError::TypeError(
+                                "Expected non-negative integer split limit for str-split"
+                                    .to_string(),
+                            ))
+                        }
+                    },
+                    None => None,
+                };
+                match (val1, val2) {
+                    (Value::String(s), Value::String(delim)) => Value::Array(
+                        split_with_limit(&s, &delim, limit)
+                            .into_iter()
+                            .map(Value::String)
+                            .collect(),
+                    ),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected strings for str-split".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringLines => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => {
+                        Value::Array(s.lines().map(|l| Value::String(l.to_string())).collect())
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for str-lines".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringWords => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => Value::Array(
+                        s.split_whitespace()
+                            .map(|w| Value::String(w.to_string()))
+                            .collect(),
+                    ),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for str-words".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringToChars => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => {
+                        Value::Array(s.chars().map(|c| Value::String(c.to_string())).collect())
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for str-to-chars".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringCharAt => {
+                let (val, index) = self.get_binary_operands(asg, node)?;
+                let (Value::String(s), Value::Int(i)) = (&val, &index) else {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected string and int for char-at, got {} and {}",
+                        val.type_name(),
+                        index.type_name()
+                    )));
+                };
+                let c = usize::try_from(*i).ok().and_then(|i| s.chars().nth(i));
+                match c {
+                    Some(c) => Value::Char(c),
+                    None => {
+                        return Err(ASGError::InvalidOperation(format!(
+                            "char-at: index {} out of bounds for string of {} chars",
+                            i,
+                            s.chars().count()
+                        )))
+                    }
+                }
+            }
+
+            NodeType::CharToInt => match self.get_single_operand(asg, node)? {
+                Value::Char(c) => Value::Int(c as i64),
+                other => {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected char for char->int, got {}",
+                        other.type_name()
+                    )))
+                }
+            },
+
+            NodeType::IntToChar => match self.get_single_operand(asg, node)? {
+                Value::Int(n) => {
+                    let c = u32::try_from(n).ok().and_then(char::from_u32);
+                    let c = c.ok_or_else(|| {
+                        ASGError::InvalidOperation(format!(
+                            "int->char: {} is not a Unicode scalar value",
+                            n
+                        ))
+                    })?;
+                    Value::Char(c)
+                }
+                other => {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected int for int->char, got {}",
+                        other.type_name()
+                    )))
+                }
+            },
+
+            NodeType::StringChars => match self.get_single_operand(asg, node)? {
+                Value::String(s) => Value::Array(s.chars().map(Value::Char).collect()),
+                other => {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected string for string->chars, got {}",
+                        other.type_name()
+                    )))
+                }
+            },
+
+            NodeType::CharsToString => {
+                let val = self.get_single_operand(asg, node)?;
+                let Value::Array(items) = val else {
+                    return Err(ASGError::TypeError(
+                        "Expected array of strings for chars-to-str".to_string(),
+                    ));
+                };
+                let mut result = String::new();
+                for item in items {
+                    match item {
+                        Value::String(s) => result.push_str(&s),
+                        Value::Char(c) => result.push(c),
+                        other => {
+                            return Err(ASGError::TypeError(format!(
+                                "chars-to-str expects strings, got {}",
+                                other.type_name()
+                            )))
+                        }
+                    }
+                }
+                Value::String(result)
+            }
+
+            // === Кодирование ===
+            NodeType::Base64Encode | NodeType::HexEncode => {
+                use base64::Engine;
+
+                let val = self.get_single_operand(asg, node)?;
+                let bytes = value_to_bytes(&val).ok_or_else(|| {
+                    ASGError::TypeError(format!(
+                        "Expected bytes or string for {:?}, got {}",
+                        node.node_type,
+                        val.type_name()
+                    ))
+                })?;
+                if node.node_type == NodeType::Base64Encode {
+                    Value::String(base64::engine::general_purpose::STANDARD.encode(bytes))
+                } else {
+                    Value::String(hex::encode(bytes))
+                }
+            }
+
+            NodeType::Base64Decode | NodeType::HexDecode => {
+                use base64::Engine;
+
+                let val = self.get_single_operand(asg, node)?;
+                let s = match val {
+                    Value::String(s) => s,
+                    other => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected string for {:?}, got {}",
+                            node.node_type,
+                            other.type_name()
+                        )))
+                    }
+                };
+                let decoded = if node.node_type == NodeType::Base64Decode {
+                    base64::engine::general_purpose::STANDARD
+                        .decode(s.trim())
+                        .map_err(|e| e.to_string())
+                } else {
+                    hex::decode(s.trim()).map_err(|e| e.to_string())
+                };
+                match decoded {
+                    Ok(bytes) => Value::Bytes(bytes),
+                    Err(msg) => Value::Error(msg),
+                }
+            }
+
+            // === Хеширование (requires 'hashing' feature) ===
+            #[cfg(feature = "hashing")]
+            NodeType::Sha256 | NodeType::Md5 => {
+                use md5::Md5;
+                use sha2::{Digest, Sha256};
+
+                let val = self.get_single_operand(asg, node)?;
+                let bytes = value_to_bytes(&val).ok_or_else(|| {
+                    ASGError::TypeError(format!(
+                        "Expected bytes or string for {:?}, got {}",
+                        node.node_type,
+                        val.type_name()
+                    ))
+                })?;
+                if node.node_type == NodeType::Sha256 {
+                    Value::String(hex::encode(Sha256::digest(&bytes)))
+                } else {
+                    Value::String(hex::encode(Md5::digest(&bytes)))
+                }
+            }
+
+            #[cfg(not(feature = "hashing"))]
+            NodeType::Sha256 | NodeType::Md5 => {
+                return Err(ASGError::InvalidOperation(
+                    "Hashing requires 'hashing' feature. Recompile with: cargo build --features hashing".to_string()
+                ));
+            }
+
+            NodeType::StringJoin => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Array(arr), Value::String(delim)) => {
+                        let strings: Result<Vec<String>, _> = arr
+                            .into_iter()
+                            .map(|v| match v {
+                                Value::String(s) => Ok(s),
+                                _ => Err(ASGError::TypeError(
+                                    "Array elements must be strings for str-join".to_string(),
+                                )),
+                            })
+                            .collect();
+                        Value::String(strings?.join(&delim))
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (array, string) for str-join".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringContains => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::String(s), Value::String(substr)) => Value::Bool(s.contains(&substr)),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected strings for str-contains".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringCount | NodeType::StringFindAll => {
+                let form = if node.node_type == NodeType::StringCount {
+                    "str-count"
+                } else {
+                    "str-find-all"
+                };
+                let (s, needle) = match self.get_binary_operands(asg, node)? {
+                    (Value::String(s), Value::String(needle)) => (s, needle),
+                    _ => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected strings for {}",
+                            form
+                        )))
+                    }
+                };
+                if needle.is_empty() {
+                    return Err(ASGError::InvalidOperation(format!(
+                        "{}: needle must not be empty",
+                        form
+                    )));
+                }
+                if node.node_type == NodeType::StringCount {
+                    Value::Int(s.matches(needle.as_str()).count() as i64)
+                } else {
+                    // Байтовые смещения переводятся в индексы символов за один проход
+                    let mut positions = Vec::new();
+                    let (mut byte_pos, mut char_pos) = (0, 0);
+                    for (start, _) in s.match_indices(needle.as_str()) {
+                        char_pos += s[byte_pos..start].chars().count();
+                        byte_pos = start;
+                        positions.push(Value::Int(char_pos as i64));
+                    }
+                    Value::Array(positions)
+                }
+            }
+
+            NodeType::StringReplace | NodeType::StringReplaceFirst => {
+                let edges: Vec<_> = node.edges.iter().collect();
+                if edges.len() < 3 {
+                    return Err(ASGError::MissingEdge(
+                        node.id,
+                        EdgeType::ApplicationArgument,
+                    ));
+                }
+                let str_val = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let from_val = self.ensure_evaluated(asg, edges[1].target_node_id)?;
+                let to_val = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+
+                match (str_val, from_val, to_val) {
+                    (Value::String(s), Value::String(from), Value::String(to)) => {
+                        if node.node_type == NodeType::StringReplaceFirst {
+                            Value::String(s.replacen(&from, &to, 1))
+                        } else {
+                            Value::String(s.replace(&from, &to))
+                        }
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected strings for str-replace".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::ToString => {
+                let val = self.get_single_operand(asg, node)?;
+                Value::String(display_string(val))
+            }
+
+            NodeType::ParseInt => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => match s.trim().parse::<i64>() {
+                        Ok(n) => Value::Int(n),
+                        Err(_) => {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "Cannot parse '{}' as int",
+                                s
+                            )))
+                        }
+                    },
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for parse-int".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::ParseFloat => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => match s.trim().parse::<f64>() {
+                        Ok(f) => Value::Float(f),
+                        Err(_) => {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "Cannot parse '{}' as float",
+                                s
+                            )))
+                        }
+                    },
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for parse-float".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringTrim => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => Value::String(s.trim().to_string()),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for str-trim".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringUpper => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => Value::String(s.to_uppercase()),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for str-upper".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringLower => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => Value::String(s.to_lowercase()),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for str-lower".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::StringRender => {
+                let (template, ctx) = self.get_binary_operands(asg, node)?;
+                // (render template ctx missing): подстановка для отсутствующих ключей
+                let missing = match node.find_edge(EdgeType::ApplicationArgument) {
+                    Some(edge) => Some(display_string(
+                        self.ensure_evaluated(asg, edge.target_node_id)?,
+                    )),
+                    None => None,
+                };
+                match (template, ctx) {
+                    (Value::String(template), Value::Dict(ctx) | Value::Record(ctx)) => {
+                        Value::String(render_template(&template, &ctx, missing.as_deref())?)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string template and dict for render".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            // === Math functions ===
+            NodeType::MathSqrt => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.sqrt()),
+                    Value::Int(n) => Value::Float((n as f64).sqrt()),
+                    _ => return Err(ASGError::TypeError("Expected number for sqrt".to_string())),
+                }
+            }
+
+            NodeType::MathSin => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.sin()),
+                    Value::Int(n) => Value::Float((n as f64).sin()),
+                    _ => return Err(ASGError::TypeError("Expected number for sin".to_string())),
+                }
+            }
+
+            NodeType::MathCos => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.cos()),
+                    Value::Int(n) => Value::Float((n as f64).cos()),
+                    _ => return Err(ASGError::TypeError("Expected number for cos".to_string())),
+                }
+            }
+
+            NodeType::MathTan => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.tan()),
+                    Value::Int(n) => Value::Float((n as f64).tan()),
+                    _ => return Err(ASGError::TypeError("Expected number for tan".to_string())),
+                }
+            }
+
+            NodeType::MathAsin => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.asin()),
+                    Value::Int(n) => Value::Float((n as f64).asin()),
+                    _ => return Err(ASGError::TypeError("Expected number for asin".to_string())),
+                }
+            }
+
+            NodeType::MathAcos => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.acos()),
+                    Value::Int(n) => Value::Float((n as f64).acos()),
+                    _ => return Err(ASGError::TypeError("Expected number for acos".to_string())),
+                }
+            }
+
+            NodeType::MathAtan => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.atan()),
+                    Value::Int(n) => Value::Float((n as f64).atan()),
+                    _ => return Err(ASGError::TypeError("Expected number for atan".to_string())),
+                }
+            }
+
+            NodeType::MathExp => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.exp()),
+                    Value::Int(n) => Value::Float((n as f64).exp()),
+                    _ => return Err(ASGError::TypeError("Expected number for exp".to_string())),
+                }
+            }
+
+            NodeType::MathLn => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.ln()),
+                    Value::Int(n) => Value::Float((n as f64).ln()),
+                    _ => return Err(ASGError::TypeError("Expected number for ln".to_string())),
+                }
+            }
+
+            NodeType::MathLog10 => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.log10()),
+                    Value::Int(n) => Value::Float((n as f64).log10()),
+                    _ => return Err(ASGError::TypeError("Expected number for log10".to_string())),
+                }
+            }
+
+            NodeType::MathPow => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Float(base), Value::Float(exp)) => Value::Float(base.powf(exp)),
+                    (Value::Float(base), Value::Int(exp)) => Value::Float(base.powi(exp as i32)),
+                    (Value::Int(base), Value::Float(exp)) => Value::Float((base as f64).powf(exp)),
+                    (Value::Int(base), Value::Int(exp)) => {
+                        if exp >= 0 {
+                            self.int_arith(IntOp::Pow, base, exp)?
+                        } else {
+                            Value::Float((base as f64).powi(exp as i32))
+                        }
+                    }
+                    _ => return Err(ASGError::TypeError("Expected numbers for pow".to_string())),
+                }
+            }
+
+            NodeType::MathAbs => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.abs()),
+                    Value::Int(n) => Value::Int(n.abs()),
+                    _ => return Err(ASGError::TypeError("Expected number for abs".to_string())),
+                }
+            }
+
+            NodeType::MathFloor => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.floor()),
+                    Value::Int(n) => Value::Int(n),
+                    _ => return Err(ASGError::TypeError("Expected number for floor".to_string())),
+                }
+            }
+
+            NodeType::MathCeil => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.ceil()),
+                    Value::Int(n) => Value::Int(n),
+                    _ => return Err(ASGError::TypeError("Expected number for ceil".to_string())),
+                }
+            }
+
+            NodeType::MathRound => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Float(f) => Value::Float(f.round()),
+                    Value::Int(n) => Value::Int(n),
+                    _ => return Err(ASGError::TypeError("Expected number for round".to_string())),
+                }
+            }
+
+            NodeType::MathMin => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => Value::Int(a.min(b)),
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a.min(b)),
+                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64).min(b)),
+                    (Value::Float(a), Value::Int(b)) => Value::Float(a.min(b as f64)),
+                    _ => return Err(ASGError::TypeError("Expected numbers for min".to_string())),
+                }
+            }
+
+            NodeType::MathMax => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::Int(a), Value::Int(b)) => Value::Int(a.max(b)),
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a.max(b)),
+                    (Value::Int(a), Value::Float(b)) => Value::Float((a as f64).max(b)),
+                    (Value::Float(a), Value::Int(b)) => Value::Float(a.max(b as f64)),
+                    _ => return Err(ASGError::TypeError("Expected numbers for max".to_string())),
+                }
+            }
+
+            NodeType::MathPi => Value::Float(std::f64::consts::PI),
+            NodeType::MathE => Value::Float(std::f64::consts::E),
+
+            // === I/O ===
+            NodeType::Print => {
+                if node.edges.is_empty() {
+                    return Err(ASGError::MissingEdge(
+                        node.id,
+                        EdgeType::ApplicationArgument,
+                    ));
+                }
+
+                // Аргументы через пробел: строки без кавычек, остальное — format_display
+                let mut parts = Vec::with_capacity(node.edges.len());
+                for edge in &node.edges {
+                    parts.push(display_string(
+                        self.ensure_evaluated(asg, edge.target_node_id)?,
+                    ));
+                }
+                (self.output)(&parts.join(" "));
+                Value::Unit
+            }
+
+            NodeType::PrettyPrint => {
+                let value = self.get_single_operand(asg, node)?;
+                (self.output)(&value.format_pretty());
+                Value::Unit
+            }
+
+            NodeType::PrintJson => {
+                let value = self.get_single_operand(asg, node)?;
+                let json = self.value_to_json(&value);
+                (self.output)(&json);
+                Value::Unit
+            }
+
+            NodeType::Debug => {
+                let (label, value) = self.get_binary_operands(asg, node)?;
+                (self.debug_output)(&debug_line(&label, &value));
+                value
+            }
+
+            NodeType::Input => {
+                // Показать prompt если есть
+                if let Some(edge) = node.edges.first() {
+                    let prompt = self.ensure_evaluated(asg, edge.target_node_id)?;
+                    if let Value::String(s) = prompt {
+                        print!("{}", s);
+                        use std::io::Write;
+                        std::io::stdout().flush().ok();
+                    }
+                }
+
+                // Читаем строку
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_line(&mut input)
+                    .map_err(|e| ASGError::InvalidOperation(format!("Input error: {}", e)))?;
+                Value::String(input.trim_end().to_string())
+            }
+
+            NodeType::InputInt => {
+                // Показать prompt если есть
+                if let Some(edge) = node.edges.first() {
+                    let prompt = self.ensure_evaluated(asg, edge.target_node_id)?;
+                    if let Value::String(s) = prompt {
+                        print!("{}", s);
+                        use std::io::Write;
+                        std::io::stdout().flush().ok();
+                    }
+                }
+
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_line(&mut input)
+                    .map_err(|e| ASGError::InvalidOperation(format!("Input error: {}", e)))?;
+                let n: i64 = input.trim().parse().map_err(|_| {
+                    ASGError::TypeError(format!("Cannot parse '{}' as integer", input.trim()))
+                })?;
+                Value::Int(n)
+            }
+
+            NodeType::InputFloat => {
+                // Показать prompt если есть
+                if let Some(edge) = node.edges.first() {
+                    let prompt = self.ensure_evaluated(asg, edge.target_node_id)?;
+                    if let Value::String(s) = prompt {
+                        print!("{}", s);
+                        use std::io::Write;
+                        std::io::stdout().flush().ok();
+                    }
+                }
+
+                let mut input = String::new();
+                std::io::stdin()
+                    .read_line(&mut input)
+                    .map_err(|e| ASGError::InvalidOperation(format!("Input error: {}", e)))?;
+                let f: f64 = input.trim().parse().map_err(|_| {
+                    ASGError::TypeError(format!("Cannot parse '{}' as float", input.trim()))
+                })?;
+                Value::Float(f)
+            }
+
+            NodeType::ClearScreen => {
+                // ANSI escape для очистки экрана
+                print!("\x1B[2J\x1B[1;1H");
+                use std::io::Write;
+                std::io::stdout().flush().ok();
+                Value::Unit
+            }
+
+            NodeType::ReadFile => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(path) => match fs::read_to_string(&path) {
+                        Ok(content) => Value::String(content),
+                        Err(e) => {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "Cannot read file '{}': {}",
+                                path, e
+                            )))
+                        }
+                    },
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string path for read-file".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::WriteFile => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::String(path), Value::String(content)) => {
+                        match fs::write(&path, &content) {
+                            Ok(_) => Value::Unit,
+                            Err(e) => {
+                                return Err(ASGError::InvalidOperation(format!(
+                                    "Cannot write file '{}': {}",
+                                    path, e
+                                )))
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (path, content) strings for write-file".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::AppendFile => {
+                let (val1, val2) = self.get_binary_operands(asg, node)?;
+                match (val1, val2) {
+                    (Value::String(path), Value::String(content)) => {
+                        match fs::OpenOptions::new().create(true).append(true).open(&path) {
+                            Ok(mut file) => match file.write_all(content.as_bytes()) {
+                                Ok(_) => Value::Unit,
+                                Err(e) => {
+                                    return Err(ASGError::InvalidOperation(format!(
+                                        "Cannot append to file '{}': {}",
+                                        path, e
+                                    )))
+                                }
+                            },
+                            Err(e) => {
+                                return Err(ASGError::InvalidOperation(format!(
+                                    "Cannot open file '{}': {}",
+                                    path, e
+                                )))
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (path, content) strings for append-file".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::FileExists => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(path) => Value::Bool(std::path::Path::new(&path).exists()),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string path for file-exists".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            // === Профилирование ===
+            NodeType::Time => {
+                let edge = node.edges.first().ok_or(ASGError::MissingEdge(
+                    node.id,
+                    EdgeType::ApplicationArgument,
+                ))?;
+                let start = (self.clock)();
+                let value = self.ensure_evaluated(asg, edge.target_node_id)?;
+                let elapsed = (self.clock)().saturating_sub(start);
+                (self.debug_output)(&format!(
+                    "Elapsed time: {:.3} ms",
+                    elapsed.as_secs_f64() * 1000.0
+                ));
+                value
+            }
+
+            NodeType::Bench => {
+                let n_edge = node
+                    .find_edge(EdgeType::FirstOperand)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::FirstOperand))?;
+                let expr_edge = node
+                    .find_edge(EdgeType::SecondOperand)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::SecondOperand))?;
+                let runs = match self.ensure_evaluated(asg, n_edge.target_node_id)? {
+                    Value::Int(n) if n > 0 => n,
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected positive integer run count for bench".to_string(),
+                        ))
+                    }
+                };
+
+                // Каждый прогон начинается с memo до первого вычисления expr,
+                // иначе повторные прогоны вернули бы закэшированный результат.
+                // Сбрасываем только узлы, достижимые из expr.
+                let subgraph = asg.reachable_from(expr_edge.target_node_id);
+                let base_memo: HashMap<NodeID, Value> = subgraph
+                    .iter()
+                    .filter_map(|id| self.memo.get(id).map(|v| (*id, v.clone())))
+                    .collect();
+                let mut timings = Vec::with_capacity(runs as usize);
+                for _ in 0..runs {
+                    for id in &subgraph {
+                        match base_memo.get(id) {
+                            Some(v) => self.memo.insert(*id, v.clone()),
+                            None => self.memo.remove(id),
+                        };
+                    }
+                    let start = (self.clock)();
+                    self.ensure_evaluated(asg, expr_edge.target_node_id)?;
+                    let elapsed = (self.clock)().saturating_sub(start);
+                    timings.push(elapsed.as_secs_f64() * 1000.0);
+                }
+
+                let mean = timings.iter().sum::<f64>() / timings.len() as f64;
+                let min = timings.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = timings.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                (self.debug_output)(&format!(
+                    "Bench ({} runs): mean {:.3} ms, min {:.3} ms, max {:.3} ms",
+                    runs, mean, min, max
+                ));
+
+                let mut stats = HashMap::new();
+                stats.insert("runs".to_string(), Value::Int(runs));
+                stats.insert("mean".to_string(), Value::Float(mean));
+                stats.insert("min".to_string(), Value::Float(min));
+                stats.insert("max".to_string(), Value::Float(max));
+                Value::Dict(stats)
+            }
+
+            // === Тестирование ===
+            NodeType::Requires => {
+                let holds = self.get_single_operand(asg, node)?;
+                contract_holds(holds, "Precondition")?;
+                Value::Unit
+            }
+
+            // Условие проверяет invoke после тела функции
+            NodeType::Ensures => Value::Unit,
+
+            NodeType::Assert => {
+                let cond_edge = node.edges.first().ok_or(ASGError::MissingEdge(
+                    node.id,
+                    EdgeType::ApplicationArgument,
+                ))?;
+                match self.ensure_evaluated(asg, cond_edge.target_node_id)? {
+                    Value::Bool(true) => Value::Bool(true),
+                    Value::Bool(false) => {
+                        let message = match node.edges.get(1) {
+                            Some(edge) => match self.ensure_evaluated(asg, edge.target_node_id)? {
+                                Value::String(s) => s,
+                                other => other.format_display(),
+                            },
+                            None => "condition is false".to_string(),
+                        };
+                        return Err(assertion_failed(&message));
+                    }
+                    other => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected boolean for assert, got {}",
+                            other.type_name()
+                        )))
+                    }
+                }
+            }
+
+            NodeType::AssertEq => {
+                let (expected, actual) = self.get_binary_operands(asg, node)?;
+                match self.first_difference(&expected, &actual, String::new()) {
+                    None => Value::Bool(true),
+                    Some((path, detail)) => {
+                        let mut message = format!(
+                            "expected {}, got {}",
+                            expected.format_display(),
+                            actual.format_display()
+                        );
+                        // Для массивов и словарей — где именно они расходятся
+                        if matches!(
+                            (&expected, &actual),
+                            (Value::Array(_), Value::Array(_)) | (Value::Dict(_), Value::Dict(_))
+                        ) {
+                            let location = if path.is_empty() {
+                                String::new()
+                            } else {
+                                format!(" at {}", path)
+                            };
+                            message
+                                .push_str(&format!("\n  first difference{}: {}", location, detail));
+                        }
+                        return Err(assertion_failed(&message));
+                    }
+                }
+            }
+
+            NodeType::GenInt => {
+                let edges: Vec<_> = node.edges.iter().collect();
+                if edges.len() != 3 {
+                    return Err(ASGError::InvalidOperation(
+                        "gen-int requires 3 arguments".to_string(),
+                    ));
+                }
+                let lo = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let hi = self.ensure_evaluated(asg, edges[1].target_node_id)?;
+                let count = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+                match (lo, hi, count) {
+                    (Value::Int(lo), Value::Int(hi), Value::Int(count))
+                        if lo <= hi && count >= 0 =>
+                    {
+                        self.check_collection_size(count as usize)?;
+                        Value::Array(
+                            (0..count)
+                                .map(|_| Value::Int(self.rng.random_range(lo..=hi)))
+                                .collect(),
+                        )
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected (gen-int lo hi count) with lo <= hi and count >= 0"
+                                .to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::ForAll => {
+                let (samples, pred) = self.get_binary_operands(asg, node)?;
+                let samples = match samples {
+                    Value::Array(items) => items,
+                    other => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected array of samples for for-all, got {}",
+                            other.type_name()
+                        )))
+                    }
+                };
+                for sample in &samples {
+                    match self.call(asg, &pred, std::slice::from_ref(sample))? {
+                        Value::Bool(true) => {}
+                        Value::Bool(false) => {
+                            return Err(assertion_failed(&format!(
+                                "for-all counterexample: {}",
+                                sample.format_display()
+                            )))
+                        }
+                        other => {
+                            return Err(ASGError::TypeError(format!(
+                                "for-all predicate must return boolean, got {}",
+                                other.type_name()
+                            )))
+                        }
+                    }
+                }
+                Value::Bool(true)
+            }
+
+            // === Error Handling ===
+            NodeType::TryCatch => {
+                let try_edge = node
+                    .find_edge(EdgeType::TryBody)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::TryBody))?;
+                let var_edge = node
+                    .find_edge(EdgeType::CatchVariable)
                     .ok_or(ASGError::MissingEdge(node.id, EdgeType::CatchVariable))?;
                 let handler_edge = node
                     .find_edge(EdgeType::CatchHandler)
                     .ok_or(ASGError::MissingEdge(node.id, EdgeType::CatchHandler))?;
 
-                // Get error variable name
-                let var_node = asg
-                    .find_node(var_edge.target_node_id)
-                    .ok_or(ASGError::NodeNotFound(var_edge.target_node_id))?;
-                let error_var_name = var_node.get_name().unwrap_or_default();
+                // Get error variable name
+                let var_node = asg
+                    .find_node(var_edge.target_node_id)
+                    .ok_or(ASGError::NodeNotFound(var_edge.target_node_id))?;
+                let error_var_name = var_node.get_name().unwrap_or_default();
+
+                // Try to evaluate the try body
+                match self.ensure_evaluated(asg, try_edge.target_node_id) {
+                    Ok(Value::Error(msg)) => {
+                        // Error was thrown, execute handler
+                        let saved_memo = std::mem::take(&mut self.memo);
+                        let mut frame = CallFrame::default();
+                        frame.locals.insert(error_var_name, Value::Error(msg));
+                        frame.memo = saved_memo;
+                        self.call_stack.push(frame);
+
+                        let result = self.ensure_evaluated(asg, handler_edge.target_node_id)?;
+
+                        if let Some(popped_frame) = self.call_stack.pop() {
+                            self.memo = popped_frame.memo;
+                        }
+                        result
+                    }
+                    Ok(val) => val, // No error, return value
+                    // break/continue — не ошибки: идут к циклу мимо catch
+                    Err(e @ (ASGError::LoopBreak | ASGError::LoopContinue)) => return Err(e),
+                    Err(e) => {
+                        // Runtime error, convert to Value::Error and execute handler
+                        let saved_memo = std::mem::take(&mut self.memo);
+                        let mut frame = CallFrame::default();
+                        frame
+                            .locals
+                            .insert(error_var_name, Value::Error(e.to_string()));
+                        frame.memo = saved_memo;
+                        self.call_stack.push(frame);
+
+                        let result = self.ensure_evaluated(asg, handler_edge.target_node_id)?;
+
+                        if let Some(popped_frame) = self.call_stack.pop() {
+                            self.memo = popped_frame.memo;
+                        }
+                        result
+                    }
+                }
+            }
+
+            NodeType::Throw => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(msg) => Value::Error(msg),
+                    _ => Value::Error(format!("{:?}", val)),
+                }
+            }
+
+            NodeType::IsError => {
+                let val = self.get_single_operand(asg, node)?;
+                Value::Bool(matches!(val, Value::Error(_)))
+            }
+
+            NodeType::ErrorMessage => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Error(msg) => Value::String(msg),
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected error for error-message".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            // === Option/Result ===
+            NodeType::OptionNone => Value::Option(None),
+
+            NodeType::OptionSome => {
+                let val = self.get_single_operand(asg, node)?;
+                Value::Option(Some(Box::new(val)))
+            }
+
+            NodeType::ResultOk => {
+                let val = self.get_single_operand(asg, node)?;
+                Value::Variant {
+                    enum_name: "Result".to_string(),
+                    tag: "Ok".to_string(),
+                    fields: vec![val],
+                }
+            }
+
+            NodeType::OptionIsSome => {
+                let val = self.get_single_operand(asg, node)?;
+                Value::Bool(!matches!(val, Value::Unit | Value::Option(None)))
+            }
+
+            NodeType::IsOk => {
+                let val = self.get_single_operand(asg, node)?;
+                Value::Bool(!matches!(val, Value::Error(_)))
+            }
+
+            NodeType::OptionUnwrap => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::Option(Some(inner)) => *inner,
+                    Value::Option(None) | Value::Unit => {
+                        return Err(ASGError::InvalidOperation(
+                            "unwrap: value is none".to_string(),
+                        ))
+                    }
+                    Value::Error(msg) => return Err(ASGError::InvalidOperation(msg)),
+                    Value::Variant {
+                        enum_name,
+                        tag,
+                        mut fields,
+                    } if enum_name == "Result" && tag == "Ok" => {
+                        fields.pop().unwrap_or(Value::Unit)
+                    }
+                    other => other,
+                }
+            }
+
+            NodeType::OptionUnwrapOr => {
+                let (val, default) = self.get_binary_operands(asg, node)?;
+                match val {
+                    Value::Option(Some(inner)) => *inner,
+                    Value::Option(None) | Value::Unit | Value::Error(_) => default,
+                    Value::Variant {
+                        enum_name,
+                        tag,
+                        mut fields,
+                    } if enum_name == "Result" && tag == "Ok" => {
+                        fields.pop().unwrap_or(Value::Unit)
+                    }
+                    other => other,
+                }
+            }
+
+            NodeType::OptionMap => {
+                let (val, func) = self.get_binary_operands(asg, node)?;
+                match val {
+                    Value::Option(Some(inner)) => {
+                        Value::Option(Some(Box::new(self.call_function_value(asg, func, *inner)?)))
+                    }
+                    Value::Option(None) => Value::Option(None),
+                    other => {
+                        return Err(ASGError::TypeError(format!(
+                            "Expected Option for map-option, got {}",
+                            other.type_name()
+                        )))
+                    }
+                }
+            }
+
+            NodeType::Expect => {
+                let (val, message) = self.get_binary_operands(asg, node)?;
+                match val {
+                    Value::Unit | Value::Option(None) | Value::Error(_) => {
+                        return Err(ASGError::InvalidOperation(display_string(message)))
+                    }
+                    Value::Option(Some(inner)) => *inner,
+                    other => other,
+                }
+            }
+
+            // === Record ===
+            NodeType::Record => {
+                let field_data: Vec<_> = node
+                    .find_edges(EdgeType::RecordFieldDef)
+                    .into_iter()
+                    .map(|e| {
+                        let field_node = asg.find_node(e.target_node_id);
+                        (e.target_node_id, field_node.and_then(|n| n.get_name()))
+                    })
+                    .collect();
+                let mut fields = HashMap::new();
+                for (field_id, field_name_opt) in field_data {
+                    let field_name = field_name_opt.unwrap_or_default();
+                    // Узел определения поля хранит значение по ребру VarValue
+                    let value_id = asg
+                        .find_node(field_id)
+                        .and_then(|n| n.find_edge(EdgeType::VarValue))
+                        .map(|e| e.target_node_id)
+                        .unwrap_or(field_id);
+                    let field_val = self.ensure_evaluated(asg, value_id)?;
+                    fields.insert(field_name, field_val);
+                }
+                Value::Record(fields)
+            }
+
+            NodeType::RecordDef => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let fields = record_def_fields(asg, node);
+                self.record_schemas.insert(name, fields);
+                Value::Unit
+            }
+
+            NodeType::EnumDef => {
+                let enum_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                for edge in node.find_edges(EdgeType::ApplicationArgument) {
+                    let variant = asg
+                        .find_node(edge.target_node_id)
+                        .ok_or(ASGError::NodeNotFound(edge.target_node_id))?;
+                    let tag = variant
+                        .get_name()
+                        .ok_or(ASGError::MissingPayload(variant.id))?;
+                    let arity = variant.find_edges(EdgeType::RecordFieldDef).len();
+                    self.enum_variants.insert(tag, (enum_name.clone(), arity));
+                }
+                Value::Unit
+            }
+
+            NodeType::RecordUpdate => {
+                // Поле должно существовать: with-field не добавляет новых полей
+                let field_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let record_edge = node
+                    .find_edge(EdgeType::RecordFieldAccess)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::RecordFieldAccess))?;
+                let value_edge = node
+                    .find_edge(EdgeType::VarValue)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::VarValue))?;
+
+                let record_val = self.ensure_evaluated(asg, record_edge.target_node_id)?;
+                let new_val = self.ensure_evaluated(asg, value_edge.target_node_id)?;
+                match record_val {
+                    Value::Record(mut fields) => {
+                        if !fields.contains_key(&field_name) {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "Field {} not found",
+                                field_name
+                            )));
+                        }
+                        fields.insert(field_name, new_val);
+                        Value::Record(fields)
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected record for with-field".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            NodeType::RecordField => {
+                let field_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let record_edge = node
+                    .find_edge(EdgeType::RecordFieldAccess)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::RecordFieldAccess))?;
+
+                let record_val = self.ensure_evaluated(asg, record_edge.target_node_id)?;
+                match record_val {
+                    Value::Record(fields) => {
+                        fields
+                            .get(&field_name)
+                            .cloned()
+                            .ok_or(ASGError::InvalidOperation(format!(
+                                "Field {} not found",
+                                field_name
+                            )))?
+                    }
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected record for field access".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            // === Pattern Matching ===
+            NodeType::Match => {
+                let subject_edge = node
+                    .find_edge(EdgeType::MatchSubject)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::MatchSubject))?;
+
+                let subject_val = self.ensure_evaluated(asg, subject_edge.target_node_id)?;
+
+                // Get all match arms
+                let arm_edges: Vec<_> = node.find_edges(EdgeType::ApplicationArgument);
+
+                for arm_edge in arm_edges {
+                    let arm_node = asg
+                        .find_node(arm_edge.target_node_id)
+                        .ok_or(ASGError::NodeNotFound(arm_edge.target_node_id))?
+                        .clone();
+
+                    if arm_node.node_type != NodeType::MatchArm {
+                        continue;
+                    }
+
+                    let pattern_edge = arm_node
+                        .find_edge(EdgeType::MatchPattern)
+                        .ok_or(ASGError::MissingEdge(arm_node.id, EdgeType::MatchPattern))?;
+                    let body_edge = arm_node
+                        .find_edge(EdgeType::MatchBody)
+                        .ok_or(ASGError::MissingEdge(arm_node.id, EdgeType::MatchBody))?;
+
+                    // Check if pattern matches
+                    let pattern_node = asg
+                        .find_node(pattern_edge.target_node_id)
+                        .ok_or(ASGError::NodeNotFound(pattern_edge.target_node_id))?
+                        .clone();
+
+                    let (matches, bindings) =
+                        self.match_pattern(asg, &pattern_node, &subject_val)?;
+
+                    if matches {
+                        // Pattern matched! Evaluate body with bindings
+                        let result = if !bindings.is_empty() {
+                            let saved_memo = std::mem::take(&mut self.memo);
+                            let mut frame = CallFrame::default();
+                            for (name, val) in bindings {
+                                frame.locals.insert(name, val);
+                            }
+                            frame.memo = saved_memo;
+                            self.call_stack.push(frame);
+
+                            let res = self.ensure_evaluated(asg, body_edge.target_node_id)?;
+
+                            if let Some(popped_frame) = self.call_stack.pop() {
+                                self.memo = popped_frame.memo;
+                            }
+                            res
+                        } else {
+                            self.ensure_evaluated(asg, body_edge.target_node_id)?
+                        };
+                        self.memo.insert(node.id, result);
+                        return Ok(());
+                    }
+                }
+
+                return Err(ASGError::InvalidOperation(format!(
+                    "No matching pattern for value {}",
+                    subject_val.format_display()
+                )));
+            }
+
+            NodeType::MatchArm => {
+                // MatchArm nodes are processed by Match, should not be evaluated directly
+                Value::Unit
+            }
+
+            // === Modules ===
+            NodeType::Module => {
+                // (module name body...)
+                // Просто выполняем все body выражения
+                let content_edges: Vec<_> = node.find_edges(EdgeType::ModuleContent);
+                let mut last_value = Value::Unit;
+                for edge in content_edges {
+                    last_value = self.ensure_evaluated(asg, edge.target_node_id)?;
+                }
+                last_value
+            }
+
+            NodeType::Import => {
+                // (import "path"), (import "path" :as alias) или (import "path" :only (a b))
+                let payload_str = node.get_name().unwrap_or_default();
+
+                // Разбираем payload: path, path|alias или path|only:a,b
+                let (path, form) = match payload_str.split_once('|') {
+                    Some((path, form)) => (path, Some(form)),
+                    None => (payload_str.as_str(), None),
+                };
+
+                let resolved = self.resolve_import_path(path).ok_or_else(|| {
+                    ASGError::InvalidOperation(format!("Cannot import '{}': file not found", path))
+                })?;
+                let canonical = fs::canonicalize(&resolved).map_err(|e| {
+                    ASGError::InvalidOperation(format!("Cannot import '{}': {}", path, e))
+                })?;
+
+                // Файл уже импортируется выше по стеку — цикл
+                if let Some(start) = self.importing.iter().position(|p| *p == canonical) {
+                    let chain: Vec<String> = self.importing[start..]
+                        .iter()
+                        .chain(std::iter::once(&canonical))
+                        .map(|p| p.display().to_string())
+                        .collect();
+                    return Err(ASGError::InvalidOperation(format!(
+                        "circular import: {}",
+                        chain.join(" -> ")
+                    )));
+                }
+
+                // Читаем и парсим файл
+                let source = match fs::read_to_string(&canonical) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        return Err(ASGError::InvalidOperation(format!(
+                            "Cannot import '{}': {}",
+                            path, e
+                        )));
+                    }
+                };
+
+                // Парсим файл
+                let (imported_asg, root_ids) = match parse(&source) {
+                    Ok((asg, ids)) => (asg, ids),
+                    Err(e) => {
+                        return Err(ASGError::InvalidOperation(format!(
+                            "Parse error in '{}': {:?}",
+                            path, e
+                        )));
+                    }
+                };
+
+                // Запоминаем какие функции и переменные были до импорта
+                let functions_before: HashSet<String> = self.functions.keys().cloned().collect();
+                let variables_before: HashSet<String> = self.variables.keys().cloned().collect();
+
+                // Выполняем все top-level выражения
+                // Сохраняем текущее состояние memo
+                let saved_memo = std::mem::take(&mut self.memo);
+
+                // Определения модуля глобальны, даже если import стоит внутри блока
+                let saved_scopes = std::mem::take(&mut self.function_scopes);
+                let saved_stack = std::mem::take(&mut self.call_stack);
+                self.importing.push(canonical);
+                // Граф модуля живёт на стеке до переноса в ImportedModule
+                self.reset_loop_analysis();
+                let result = root_ids.iter().try_for_each(|root_id| {
+                    self.ensure_evaluated(&imported_asg, *root_id).map(|_| ())
+                });
+                self.reset_loop_analysis();
+                self.importing.pop();
+                self.call_stack = saved_stack;
+                self.function_scopes = saved_scopes;
+                result?;
+
+                // Собираем определения модуля; его функции выполняются в контексте модуля
+                // (функции из вложенных импортов уже привязаны к своему модулю)
+                let new_functions: Vec<String> = self
+                    .functions
+                    .keys()
+                    .filter(|k| !functions_before.contains(*k))
+                    .cloned()
+                    .collect();
+                let new_variables: Vec<String> = self
+                    .variables
+                    .keys()
+                    .filter(|k| !variables_before.contains(*k))
+                    .cloned()
+                    .collect();
+                // Переменные модуля — общие ячейки: set из функций модуля виден и
+                // через импортированное имя, и наоборот
+                for name in &new_variables {
+                    if let Some(slot) = self.variables.get_mut(name) {
+                        if !matches!(slot, Value::Cell(_)) {
+                            let value = std::mem::replace(slot, Value::Unit);
+                            *slot = Value::Cell(CellRef(Rc::new(RefCell::new(value))));
+                        }
+                    }
+                }
+                let module = Rc::new(ImportedModule {
+                    asg: imported_asg,
+                    functions: new_functions
+                        .iter()
+                        .map(|name| (name.clone(), self.functions[name].clone()))
+                        .collect(),
+                    variables: new_variables
+                        .iter()
+                        .map(|name| (name.clone(), self.variables[name].clone()))
+                        .collect(),
+                });
+                for name in &new_functions {
+                    if let Some((_, _, slot @ None)) = self.functions.get_mut(name) {
+                        *slot = Some(module.clone());
+                    }
+                }
+
+                // Привязываем только запрошенные имена (:only) или с префиксом (:as)
+                let only = form.and_then(|form| form.strip_prefix("only:"));
+                if let Some(missing) =
+                    only.into_iter()
+                        .flat_map(|list| list.split(','))
+                        .find(|name| {
+                            !module.functions.contains_key(*name)
+                                && !module.variables.contains_key(*name)
+                        })
+                {
+                    self.memo = saved_memo;
+                    return Err(ASGError::InvalidOperation(format!(
+                        "Cannot import '{}' from '{}': not defined there",
+                        missing, path
+                    )));
+                }
+                for name in new_functions {
+                    if let Some(entry) = self.functions.remove(&name) {
+                        if let Some(bound) = imported_name(form, &name) {
+                            self.functions.insert(bound, entry);
+                        }
+                    }
+                }
+                for name in new_variables {
+                    if let Some(value) = self.variables.remove(&name) {
+                        if let Some(bound) = imported_name(form, &name) {
+                            self.variables.insert(bound, value);
+                        }
+                    }
+                }
+
+                // Восстанавливаем memo (импортированные определения остаются в self.functions и self.variables)
+                self.memo = saved_memo;
+
+                Value::Unit
+            }
+
+            NodeType::Export => {
+                // Export пока просто возвращает Unit (для полноценной модульной системы нужен отдельный механизм)
+                Value::Unit
+            }
+
+            // === HTML Generation ===
+            NodeType::HtmlElement => {
+                let raw_tag = node.get_name().unwrap_or_else(|| "div".to_string());
+                // Преобразуем html-* теги в обычные HTML теги
+                let tag_name = if raw_tag.starts_with("html-") {
+                    raw_tag[5..].to_string()
+                } else {
+                    raw_tag
+                };
+                let mut html = format!("<{}", tag_name);
+
+                // Собираем children
+                let children: Vec<_> = node.find_edges(EdgeType::ApplicationArgument);
+                let mut content = String::new();
+
+                for child_edge in children {
+                    let child_val = self.ensure_evaluated(asg, child_edge.target_node_id)?;
+                    match child_val {
+                        Value::String(s) => {
+                            // Проверяем, атрибут ли это (начинается с @)
+                            if s.starts_with("@") {
+                                // @attr=value формат
+                                if let Some((attr, val)) = s[1..].split_once('=') {
+                                    html.push_str(&format!(" {}=\"{}\"", attr, val));
+                                }
+                            } else if s.starts_with('<') {
+                                // Вложенный HTML
+                                content.push_str(&s);
+                            } else {
+                                // Текстовый контент
+                                content.push_str(&s);
+                            }
+                        }
+                        _ => content.push_str(&child_val.format_display()),
+                    }
+                }
+
+                // Самозакрывающиеся теги
+                let self_closing = matches!(
+                    tag_name.as_str(),
+                    "br" | "hr" | "img" | "input" | "meta" | "link"
+                );
+                if self_closing {
+                    html.push_str(" />");
+                } else {
+                    html.push('>');
+                    html.push_str(&content);
+                    html.push_str(&format!("</{}>", tag_name));
+                }
+
+                Value::String(html)
+            }
+
+            NodeType::HtmlAttr => {
+                // Не должно напрямую вызываться
+                Value::Unit
+            }
+
+            // === JSON ===
+            NodeType::JsonEncode => {
+                let val = self.get_single_operand(asg, node)?;
+                let json = self.value_to_json(&val);
+                Value::String(json)
+            }
+
+            NodeType::JsonDecode => {
+                let val = self.get_single_operand(asg, node)?;
+                match val {
+                    Value::String(s) => match serde_json::from_str::<serde_json::Value>(&s) {
+                        Ok(json) => self.json_to_value(json),
+                        Err(e) => {
+                            return Err(ASGError::InvalidOperation(format!(
+                                "JSON parse error: {}",
+                                e
+                            )))
+                        }
+                    },
+                    _ => {
+                        return Err(ASGError::TypeError(
+                            "Expected string for json-decode".to_string(),
+                        ))
+                    }
+                }
+            }
+
+            // === HTTP Server (requires 'web' feature) ===
+            #[cfg(feature = "web")]
+            NodeType::HttpServe => {
+                let (port_val, handler_val) = self.get_binary_operands(asg, node)?;
+                match port_val {
+                    Value::Int(port) => {
+                        #[allow(unused_imports)]
+                        use std::io::Read;
+                        use tiny_http::{Method, Response, Server};
+
+                        let server = Server::http(format!("0.0.0.0:{}", port)).map_err(|e| {
+                            ASGError::InvalidOperation(format!("Cannot start HTTP server: {}", e))
+                        })?;
+
+                        println!("╔════════════════════════════════════════╗");
+                        println!("║   ASG HTTP Server                  ║");
+                        println!("║   http://localhost:{}                 ║", port);
+                        println!("║   Press Ctrl+C to stop                 ║");
+                        println!("╚════════════════════════════════════════╝");
+
+                        for mut request in server.incoming_requests() {
+                            let method = match request.method() {
+                                Method::Get => "GET",
+                                Method::Post => "POST",
+                                Method::Put => "PUT",
+                                Method::Delete => "DELETE",
+                                _ => "OTHER",
+                            };
+                            let path = request.url().to_string();
+
+                            // Read body for POST requests
+                            let mut body_content = String::new();
+                            request.as_reader().read_to_string(&mut body_content).ok();
+
+                            // Create request dict for handler
+                            let mut req_dict = HashMap::new();
+                            req_dict
+                                .insert("method".to_string(), Value::String(method.to_string()));
+                            req_dict.insert("path".to_string(), Value::String(path.clone()));
+                            req_dict.insert("body".to_string(), Value::String(body_content));
+
+                            // Parse query params
+                            let mut params = HashMap::new();
+                            if let Some(query) = path.split('?').nth(1) {
+                                for pair in query.split('&') {
+                                    if let Some((k, v)) = pair.split_once('=') {
+                                        params.insert(k.to_string(), Value::String(v.to_string()));
+                                    }
+                                }
+                            }
+                            req_dict.insert("params".to_string(), Value::Dict(params));
+
+                            let req_value = Value::Dict(req_dict);
+
+                            // Call handler with request
+                            let response_val =
+                                self.call_function_value(asg, handler_val.clone(), req_value)?;
+
+                            // Process response
+                            let (status_code, content_type, body): (u32, String, String) =
+                                match response_val {
+                                    Value::String(s) => (200, "text/html".to_string(), s),
+                                    Value::Dict(d) => {
+                                        let status = match d.get("status") {
+                                            Some(Value::Int(s)) => *s as u32,
+                                            _ => 200,
+                                        };
+                                        let ctype = match d.get("content-type") {
+                                            Some(Value::String(s)) => s.clone(),
+                                            _ => "text/html".to_string(),
+                                        };
+                                        let body = match d.get("body") {
+                                            Some(Value::String(s)) => s.clone(),
+                                            Some(v) => v.format_display(),
+                                            _ => "".to_string(),
+                                        };
+                                        (status, ctype, body)
+                                    }
+                                    other => {
+                                        (200, "text/plain".to_string(), other.format_display())
+                                    }
+                                };
+
+                            println!(
+                                "[{}] {} {} -> {}",
+                                method,
+                                path.split('?').next().unwrap_or(&path),
+                                status_code,
+                                body.len()
+                            );
+
+                            let response = Response::from_string(body)
+                                .with_status_code(status_code as u16)
+                                .with_header(
+                                    format!("Content-Type: {}", content_type)
+                                        .parse::<tiny_http::Header>()
+                                        .unwrap(),
+                                );
+                            request.respond(response).ok();
+                        }
+                        Value::Unit
+                    }
+                    _ => return Err(ASGError::TypeError("Expected integer port".to_string())),
+                }
+            }
+
+            #[cfg(not(feature = "web"))]
+            NodeType::HttpServe => {
+                return Err(ASGError::InvalidOperation(
+                    "HTTP server requires 'web' feature. Recompile with: cargo build --features web".to_string()
+                ));
+            }
+
+            NodeType::HttpResponse => {
+                // Создание HTTP response (используется внутри handler)
+                let edges: Vec<_> = node.find_edges(EdgeType::ApplicationArgument);
+                if edges.len() != 3 {
+                    return Err(ASGError::InvalidOperation(
+                        "http-response requires 3 arguments".to_string(),
+                    ));
+                }
+                let status = self.ensure_evaluated(asg, edges[0].target_node_id)?;
+                let _headers = self.ensure_evaluated(asg, edges[1].target_node_id)?;
+                let body = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+
+                // Возвращаем как Dict
+                let mut response = HashMap::new();
+                response.insert("status".to_string(), status);
+                response.insert("body".to_string(), body);
+                Value::Dict(response)
+            }
+
+            // === Native GUI (requires 'gui' feature) ===
+            #[cfg(feature = "gui")]
+            NodeType::GuiRun => {
+                let window_val = self.get_single_operand(asg, node)?;
+
+                // Check if it's the special calculator command
+                let is_calculator = matches!(&window_val, Value::String(s) if s == "calculator");
+
+                if is_calculator {
+                    crate::gui::run_calculator()
+                        .map_err(|e| ASGError::InvalidOperation(format!("GUI error: {}", e)))?;
+                } else {
+                    // Convert Value to Widget tree
+                    let widgets = match crate::gui::ASGGuiApp::value_to_widget(&window_val) {
+                        Some(w) => vec![w],
+                        None => Vec::new(),
+                    };
+
+                    let title = match &window_val {
+                        Value::Dict(d) => match d.get("title") {
+                            Some(Value::String(s)) => s.clone(),
+                            _ => "ASG App".to_string(),
+                        },
+                        _ => "ASG App".to_string(),
+                    };
+
+                    let on_event: crate::gui::EventHandler = Box::new(|event| match event {
+                        crate::gui::GuiEvent::Click(callback) => {
+                            self.call(asg, callback, &[]).map_err(|e| e.to_string())
+                        }
+                        crate::gui::GuiEvent::Input { bind, text } => {
+                            self.set_variable(bind, Value::String(text.to_string()));
+                            Ok(Value::Unit)
+                        }
+                    });
+                    crate::gui::run_gui(&title, widgets, on_event)
+                        .map_err(|e| ASGError::InvalidOperation(format!("GUI error: {}", e)))?;
+                }
+
+                Value::Unit
+            }
+
+            #[cfg(not(feature = "gui"))]
+            NodeType::GuiRun => {
+                return Err(ASGError::InvalidOperation(
+                    "Native GUI requires 'gui' feature. Recompile with: cargo build --features gui"
+                        .to_string(),
+                ));
+            }
+
+            NodeType::GuiWindow
+            | NodeType::GuiButton
+            | NodeType::GuiTextField
+            | NodeType::GuiLabel
+            | NodeType::GuiVBox
+            | NodeType::GuiHBox
+            | NodeType::GuiCanvas => {
+                // GUI widgets - возвращаем описание для gui-run
+                let mut widget = HashMap::new();
+                widget.insert(
+                    "type".to_string(),
+                    Value::String(format!("{:?}", node.node_type)),
+                );
+
+                let children: Vec<_> = node
+                    .find_edges(EdgeType::ApplicationArgument)
+                    .into_iter()
+                    .filter_map(|e| self.ensure_evaluated(asg, e.target_node_id).ok())
+                    .collect();
+                // (gui-button text onclick): функция становится полем on-click
+                if node.node_type == NodeType::GuiButton {
+                    if let Some(callback @ (Value::Function { .. } | Value::ComposedFunction(_))) =
+                        children.get(1)
+                    {
+                        widget.insert("on-click".to_string(), callback.clone());
+                    }
+                }
+                widget.insert("children".to_string(), Value::Array(children));
+
+                Value::Dict(widget)
+            }
+
+            NodeType::GuiBind => {
+                // (gui-bind var): поле ввода с текущим значением переменной
+                let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let value = self
+                    .variables
+                    .get(&var_name)
+                    .cloned()
+                    .unwrap_or(Value::Unit);
+                let mut widget = HashMap::new();
+                widget.insert("type".to_string(), Value::String("GuiBind".to_string()));
+                widget.insert("bind".to_string(), Value::String(var_name));
+                widget.insert("value".to_string(), value);
+                Value::Dict(widget)
+            }
+
+            NodeType::GuiSpacing => {
+                let spacing = self.get_single_operand(asg, node)?;
+                if !matches!(spacing, Value::Int(_) | Value::Float(_)) {
+                    return Err(ASGError::TypeError(format!(
+                        "Expected number for gui-spacing, got {}",
+                        spacing.type_name()
+                    )));
+                }
+                let mut widget = HashMap::new();
+                widget.insert("type".to_string(), Value::String("GuiSpacing".to_string()));
+                widget.insert("spacing".to_string(), spacing);
+                Value::Dict(widget)
+            }
+
+            // === Расширения ===
+            NodeType::Extension(code) => {
+                let name = node.get_name().unwrap_or_default();
+                let mut args = Vec::new();
+                for edge in node.find_edges(EdgeType::ApplicationArgument) {
+                    args.push(self.ensure_evaluated(asg, edge.target_node_id)?);
+                }
+                match self.extensions.get(&code) {
+                    Some((registered, handler)) if *registered == name => handler(&args)?,
+                    _ => {
+                        return Err(ASGError::InvalidOperation(format!(
+                            "No handler registered for extension '{}'",
+                            name
+                        )))
+                    }
+                }
+            }
+
+            // По умолчанию — Unit
+            _ => Value::Unit,
+        };
+
+        let result_value = self.apply_nan_policy(node.node_type, result_value);
+        match &result_value {
+            Value::Array(items) => self.check_collection_size(items.len())?,
+            Value::Dict(entries) => self.check_collection_size(entries.len())?,
+            Value::Set(items) => self.check_collection_size(items.len())?,
+            _ => {}
+        }
+        self.memo.insert(node.id, result_value);
+        Ok(())
+    }
+
+    /// Одна итерация while: условие, затем тело. None — условие ложно.
+    fn eval_while_step(
+        &mut self,
+        asg: &ASG,
+        cond_id: NodeID,
+        body_id: NodeID,
+        variant: &HashSet<NodeID>,
+    ) -> ASGResult<Option<Value>> {
+        // Пересчитываем только то, что могло измениться с прошлой итерации
+        self.memo.retain(|id, _| !variant.contains(id));
+
+        let cond_val = self.ensure_evaluated(asg, cond_id)?;
+        let cond = cond_val.as_bool().ok_or(ASGError::TypeError(
+            "Loop condition must be boolean".to_string(),
+        ))?;
+
+        if !cond {
+            return Ok(None);
+        }
+
+        // Выполняем тело
+        self.ensure_evaluated(asg, body_id).map(Some)
+    }
+
+    /// Узлы цикла `loop_id`, которые нужно пересчитывать на каждой итерации.
+    ///
+    /// Изменяемыми считаются цели set, составных присваиваний и set-index во всём
+    /// графе и в импортированных модулях (их может выполнить любая вызванная
+    /// функция), а также let, деструктуризация и счётчики loop-indexed внутри цикла.
+    /// Остальные узлы — литералы и чистые операции над неизменяемыми переменными —
+    /// сохраняют memo между итерациями.
+    ///
+    /// Результат кэшируется по циклу, факты о графах — по графу (см. [`GraphFacts`]).
+    fn loop_variant_nodes(&mut self, asg: &ASG, loop_id: NodeID) -> Rc<HashSet<NodeID>> {
+        let key = (asg as *const ASG as usize, loop_id);
+        if let Some(variant) = self.loop_variants.get(&key) {
+            return Rc::clone(variant);
+        }
+
+        let mut modules: Vec<Rc<ImportedModule>> = Vec::new();
+        for module in self
+            .functions
+            .values()
+            .chain(self.function_scopes.iter().flat_map(|scope| scope.values()))
+            .filter_map(|(_, _, module)| module.as_ref())
+            .chain(self.modules.iter())
+        {
+            if !modules.iter().any(|seen| Rc::ptr_eq(seen, module)) {
+                modules.push(Rc::clone(module));
+            }
+        }
+        let facts = self.graph_facts(asg);
+        let mut mutable = facts.assigned.clone();
+        for module in &modules {
+            mutable.extend(self.graph_facts(&module.asg).assigned.iter().cloned());
+        }
+
+        // Привязки внутри самого цикла
+        let mut stack = vec![loop_id];
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            let Some(node) = facts.node(asg, id) else {
+                continue;
+            };
+            match node.node_type {
+                NodeType::Variable | NodeType::Loop => mutable.extend(node.get_name()),
+                NodeType::LetDestructure => mutable.extend(
+                    node.payload
+                        .as_deref()
+                        .map(destructure_names)
+                        .unwrap_or_default(),
+                ),
+                _ => {}
+            }
+            stack.extend(node.edges.iter().map(|e| e.target_node_id));
+        }
+
+        let mut invariant = HashMap::new();
+        classify_invariant(asg, &facts, loop_id, &mutable, &mut invariant);
+        let variant: Rc<HashSet<NodeID>> = Rc::new(
+            invariant
+                .into_iter()
+                .filter(|&(_, is_invariant)| !is_invariant)
+                .map(|(id, _)| id)
+                .collect(),
+        );
+        self.loop_variants.insert(key, Rc::clone(&variant));
+        variant
+    }
+
+    /// Факты о графе из кэша или вычисленные заново.
+    fn graph_facts(&mut self, asg: &ASG) -> Rc<GraphFacts> {
+        Rc::clone(
+            self.graph_facts
+                .entry(asg as *const ASG as usize)
+                .or_insert_with(|| Rc::new(GraphFacts::new(asg))),
+        )
+    }
+
+    /// Сбросить кэши анализа циклов: они привязаны к адресам графов, которые
+    /// меняются между вызовами `execute` и при импорте.
+    fn reset_loop_analysis(&mut self) {
+        self.graph_facts.clear();
+        self.loop_variants.clear();
+    }
+
+    /// Продвинуть генератор до следующего yield. Завершённый генератор
+    /// (тело выполнено до конца или упало с ошибкой) возвращает `()`.
+    fn generator_next(&mut self, asg: &ASG, generator: &GeneratorRef) -> ASGResult<Value> {
+        // Состояние забирается на время шага: next на этом же генераторе
+        // изнутри тела видит пустой стек и возвращает ()
+        let mut state = std::mem::take(&mut *generator.0.borrow_mut());
+        if state.stack.is_empty() {
+            return Ok(Value::Unit);
+        }
+
+        let frame = CallFrame {
+            locals: std::mem::take(&mut state.locals),
+            memo: std::mem::take(&mut self.memo),
+        };
+        self.call_stack.push(frame);
+        // Локальные функции тела генератора живут между шагами в его состоянии
+        let functions = std::mem::take(&mut state.functions);
+        let (result, functions) =
+            self.with_function_scope(functions, |this| this.run_generator(asg, &mut state));
+        state.functions = functions;
+        if let Some(frame) = self.call_stack.pop() {
+            self.memo = frame.memo;
+            state.locals = frame.locals;
+        }
+
+        let value = match result {
+            Ok(Some(value)) => value,
+            Ok(None) => Value::Unit,
+            Err(e) => {
+                state.stack.clear();
+                *generator.0.borrow_mut() = state;
+                return Err(e);
+            }
+        };
+        *generator.0.borrow_mut() = state;
+        Ok(value)
+    }
+
+    /// Выполнять продолжения генератора до yield (`Some`) или до конца тела (`None`).
+    /// По шагам идут только do/if/while, содержащие yield; остальное вычисляется целиком.
+    fn run_generator(&mut self, asg: &ASG, state: &mut GeneratorState) -> ASGResult<Option<Value>> {
+        while let Some(step) = state.stack.pop() {
+            match self.generator_step(asg, state, step) {
+                Ok(Some(value)) => return Ok(Some(value)),
+                Ok(None) => {}
+                // break/continue в теле while: снимаем продолжения до ближайшего цикла,
+                // break снимает и сам цикл
+                Err(err @ (ASGError::LoopBreak | ASGError::LoopContinue)) => {
+                    let Some(pos) = state
+                        .stack
+                        .iter()
+                        .rposition(|step| matches!(step, GeneratorStep::While { .. }))
+                    else {
+                        return Err(err);
+                    };
+                    if matches!(err, ASGError::LoopBreak) {
+                        self.break_value = None;
+                        state.stack.truncate(pos);
+                    } else {
+                        state.stack.truncate(pos + 1);
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Выполнить одно продолжение генератора; `Some` — значение yield.
+    fn generator_step(
+        &mut self,
+        asg: &ASG,
+        state: &mut GeneratorState,
+        step: GeneratorStep,
+    ) -> ASGResult<Option<Value>> {
+        match step {
+            GeneratorStep::Eval(id) if !state.resumable.contains(&id) => {
+                self.eval_statement(asg, id)?;
+            }
+            GeneratorStep::Eval(id) => {
+                let node = asg.find_node(id).ok_or(ASGError::NodeNotFound(id))?;
+                match node.node_type {
+                    NodeType::Yield => {
+                        let value = match node.edges.first() {
+                            Some(edge) => self.eval_statement(asg, edge.target_node_id)?,
+                            None => Value::Unit,
+                        };
+                        return Ok(Some(value));
+                    }
+                    NodeType::Block => state.stack.push(GeneratorStep::Block {
+                        stmts: node
+                            .find_edges(EdgeType::BlockStatement)
+                            .iter()
+                            .map(|e| e.target_node_id)
+                            .collect(),
+                        next: 0,
+                    }),
+                    NodeType::If => {
+                        let cond = node
+                            .find_edge(EdgeType::Condition)
+                            .ok_or(ASGError::MissingEdge(id, EdgeType::Condition))?;
+                        let branch = match self.eval_statement(asg, cond.target_node_id)? {
+                            Value::Bool(true) => node.find_edge(EdgeType::ThenBranch),
+                            Value::Bool(false) => node.find_edge(EdgeType::ElseBranch),
+                            _ => {
+                                return Err(ASGError::TypeError(
+                                    "Condition must be boolean".to_string(),
+                                ))
+                            }
+                        };
+                        if let Some(edge) = branch {
+                            state.stack.push(GeneratorStep::Eval(edge.target_node_id));
+                        }
+                    }
+                    NodeType::Loop
+                        if node.get_name().is_none()
+                            && node.find_edge(EdgeType::LoopTimeout).is_none() =>
+                    {
+                        let cond = node
+                            .find_edge(EdgeType::Condition)
+                            .ok_or(ASGError::MissingEdge(id, EdgeType::Condition))?;
+                        let body = node
+                            .find_edge(EdgeType::LoopBody)
+                            .ok_or(ASGError::MissingEdge(id, EdgeType::LoopBody))?;
+                        state.stack.push(GeneratorStep::While {
+                            cond: cond.target_node_id,
+                            body: body.target_node_id,
+                        });
+                    }
+                    _ => {
+                        return Err(ASGError::InvalidOperation(
+                            "yield is only supported directly inside do, if and while \
+                             of a generator body"
+                                .to_string(),
+                        ))
+                    }
+                }
+            }
+            GeneratorStep::Block { stmts, next } => {
+                if let Some(&stmt) = stmts.get(next) {
+                    state.stack.push(GeneratorStep::Block {
+                        stmts,
+                        next: next + 1,
+                    });
+                    state.stack.push(GeneratorStep::Eval(stmt));
+                }
+            }
+            GeneratorStep::While { cond, body } => match self.eval_statement(asg, cond)? {
+                Value::Bool(true) => {
+                    state.stack.push(GeneratorStep::While { cond, body });
+                    state.stack.push(GeneratorStep::Eval(body));
+                }
+                Value::Bool(false) => {}
+                _ => {
+                    return Err(ASGError::TypeError(
+                        "Loop condition must be boolean".to_string(),
+                    ))
+                }
+            },
+        }
+        Ok(None)
+    }
+
+    /// Вычислить узел заново: переменные могли измениться с прошлого шага.
+    fn eval_statement(&mut self, asg: &ASG, id: NodeID) -> ASGResult<Value> {
+        self.memo.clear();
+        self.ensure_evaluated(asg, id)
+    }
+
+    /// Вычислить узел если не в кеше, и вернуть значение.
+    /// Использует stacker для автоматического расширения стека при глубокой рекурсии.
+    fn ensure_evaluated(&mut self, asg: &ASG, node_id: NodeID) -> ASGResult<Value> {
+        // Предотвращаем stack overflow при глубокой рекурсии
+        // 256KB red zone, 8MB stack growth
+        stacker::maybe_grow(256 * 1024, 8 * 1024 * 1024, || {
+            if self.memo.contains_key(&node_id) {
+                return Ok(self.memo.get(&node_id).unwrap().clone());
+            }
+            let node = asg
+                .find_node(node_id)
+                .ok_or(ASGError::NodeNotFound(node_id))?
+                .clone();
+            self.eval_node(asg, &node)?;
+            if node.node_type.is_effectful() {
+                // Эффект не должен пропускаться при повторном вычислении
+                return Ok(self.memo.remove(&node_id).unwrap());
+            }
+            Ok(self.memo.get(&node_id).unwrap().clone())
+        })
+    }
+
+    /// Проверить соответствие паттерна значению.
+    /// Возвращает (matches: bool, bindings: Vec<(name, value)>)
+    fn match_pattern(
+        &mut self,
+        asg: &ASG,
+        pattern_node: &Node,
+        subject: &Value,
+    ) -> ASGResult<(bool, Vec<(String, Value)>)> {
+        match pattern_node.node_type {
+            // Wildcard pattern: always matches, no bindings
+            NodeType::VarRef => {
+                let name = pattern_node.get_name().unwrap_or_default();
+                if name == "_" {
+                    // Wildcard
+                    Ok((true, vec![]))
+                } else if matches!(self.enum_variants.get(&name), Some((_, 0))) {
+                    // Вариант без полей: сравниваем тег
+                    match subject {
+                        Value::Variant { tag, .. } => Ok((*tag == name, vec![])),
+                        _ => Ok((false, vec![])),
+                    }
+                } else {
+                    // Variable binding
+                    Ok((true, vec![(name, subject.clone())]))
+                }
+            }
+
+            // Literal patterns
+            NodeType::LiteralInt => {
+                if let Some(bytes) = &pattern_node.payload {
+                    if bytes.len() >= 8 {
+                        let pattern_val = i64::from_le_bytes(bytes[..8].try_into().unwrap());
+                        match subject {
+                            Value::Int(n) => Ok((*n == pattern_val, vec![])),
+                            _ => Ok((false, vec![])),
+                        }
+                    } else {
+                        Ok((false, vec![]))
+                    }
+                } else {
+                    Ok((false, vec![]))
+                }
+            }
+
+            NodeType::LiteralFloat => {
+                if let Some(bytes) = &pattern_node.payload {
+                    if bytes.len() >= 8 {
+                        let pattern_val = f64::from_le_bytes(bytes[..8].try_into().unwrap());
+                        match subject {
+                            Value::Float(f) => Ok((self.floats_equal(*f, pattern_val), vec![])),
+                            _ => Ok((false, vec![])),
+                        }
+                    } else {
+                        Ok((false, vec![]))
+                    }
+                } else {
+                    Ok((false, vec![]))
+                }
+            }
+
+            NodeType::LiteralBool => {
+                if let Some(bytes) = &pattern_node.payload {
+                    let pattern_val = bytes.first().map(|&b| b != 0).unwrap_or(false);
+                    match subject {
+                        Value::Bool(b) => Ok((*b == pattern_val, vec![])),
+                        _ => Ok((false, vec![])),
+                    }
+                } else {
+                    Ok((false, vec![]))
+                }
+            }
+
+            NodeType::LiteralString => {
+                if let Some(bytes) = &pattern_node.payload {
+                    let pattern_val = String::from_utf8_lossy(bytes).to_string();
+                    match subject {
+                        Value::String(s) => Ok((s == &pattern_val, vec![])),
+                        _ => Ok((false, vec![])),
+                    }
+                } else {
+                    Ok((false, vec![]))
+                }
+            }
+
+            NodeType::LiteralUnit => match subject {
+                Value::Unit => Ok((true, vec![])),
+                _ => Ok((false, vec![])),
+            },
+
+            // Option patterns: none совпадает только с none, (some p) — с (some v), где v подходит под p
+            NodeType::OptionNone => Ok((matches!(subject, Value::Option(None)), vec![])),
+
+            NodeType::OptionSome => match subject {
+                Value::Option(Some(inner)) => {
+                    let inner_pattern_id = pattern_node
+                        .edges
+                        .first()
+                        .ok_or(ASGError::MissingEdge(
+                            pattern_node.id,
+                            EdgeType::ApplicationArgument,
+                        ))?
+                        .target_node_id;
+                    let inner_pattern = asg
+                        .find_node(inner_pattern_id)
+                        .ok_or(ASGError::NodeNotFound(inner_pattern_id))?
+                        .clone();
+                    self.match_pattern(asg, &inner_pattern, inner)
+                }
+                _ => Ok((false, vec![])),
+            },
+
+            // Array pattern matching
+            NodeType::Array => match subject {
+                Value::Array(arr) => {
+                    let pattern_elements: Vec<_> = pattern_node
+                        .find_edges(EdgeType::ArrayElement)
+                        .into_iter()
+                        .map(|e| e.target_node_id)
+                        .collect();
+
+                    if pattern_elements.len() != arr.len() {
+                        return Ok((false, vec![]));
+                    }
+
+                    let mut all_bindings = vec![];
+                    for (i, elem_id) in pattern_elements.iter().enumerate() {
+                        let elem_node = asg
+                            .find_node(*elem_id)
+                            .ok_or(ASGError::NodeNotFound(*elem_id))?
+                            .clone();
+                        let (matches, bindings) = self.match_pattern(asg, &elem_node, &arr[i])?;
+                        if !matches || !self.merge_bindings(&mut all_bindings, bindings) {
+                            return Ok((false, vec![]));
+                        }
+                    }
+                    Ok((true, all_bindings))
+                }
+                _ => Ok((false, vec![])),
+            },
+
+            // Variant pattern: (Circle r) — тег совпадает, поля сопоставляются рекурсивно
+            NodeType::Call => {
+                let target_name = pattern_node
+                    .find_edge(EdgeType::CallTarget)
+                    .and_then(|e| asg.find_node(e.target_node_id))
+                    .and_then(|n| n.get_name())
+                    .unwrap_or_default();
+                let args = pattern_node.find_edges(EdgeType::CallArgument);
+
+                // Типовой паттерн: (Int n) — совпадает по типу значения и связывает его
+                if let [arg] = args.as_slice() {
+                    if RUNTIME_TYPE_NAMES.contains(&target_name.as_str())
+                        && !self.enum_variants.contains_key(&target_name)
+                    {
+                        if subject.type_name() != target_name {
+                            return Ok((false, vec![]));
+                        }
+                        let arg_node = asg
+                            .find_node(arg.target_node_id)
+                            .ok_or(ASGError::NodeNotFound(arg.target_node_id))?
+                            .clone();
+                        return self.match_pattern(asg, &arg_node, subject);
+                    }
+                }
+
+                if !self.enum_variants.contains_key(&target_name) {
+                    let pattern_val = self.ensure_evaluated(asg, pattern_node.id)?;
+                    return Ok((self.values_equal(&pattern_val, subject), vec![]));
+                }
+
+                let (tag, fields) = match subject {
+                    Value::Variant { tag, fields, .. } => (tag, fields),
+                    _ => return Ok((false, vec![])),
+                };
+                let field_patterns: Vec<_> = args.into_iter().map(|e| e.target_node_id).collect();
+                if *tag != target_name || field_patterns.len() != fields.len() {
+                    return Ok((false, vec![]));
+                }
+
+                let mut all_bindings = vec![];
+                for (field_pattern_id, field_val) in field_patterns.iter().zip(fields) {
+                    let field_node = asg
+                        .find_node(*field_pattern_id)
+                        .ok_or(ASGError::NodeNotFound(*field_pattern_id))?
+                        .clone();
+                    let (matches, bindings) = self.match_pattern(asg, &field_node, field_val)?;
+                    if !matches || !self.merge_bindings(&mut all_bindings, bindings) {
+                        return Ok((false, vec![]));
+                    }
+                }
+                Ok((true, all_bindings))
+            }
+
+            // Default: evaluate pattern and compare
+            _ => {
+                let pattern_val = self.ensure_evaluated(asg, pattern_node.id)?;
+                Ok((self.values_equal(&pattern_val, subject), vec![]))
+            }
+        }
+    }
+
+    /// Добавить связывания подпаттерна к уже собранным. Повторное имя (`(array x x)`)
+    /// — ограничение равенства: при несовпадении значений паттерн не подходит (`false`).
+    fn merge_bindings(
+        &self,
+        all_bindings: &mut Vec<(String, Value)>,
+        bindings: Vec<(String, Value)>,
+    ) -> bool {
+        for (name, value) in bindings {
+            match all_bindings.iter().find(|(bound, _)| *bound == name) {
+                Some((_, previous)) => {
+                    if !(self.values_equal(previous, &value) || *previous == value) {
+                        return false;
+                    }
+                }
+                None => all_bindings.push((name, value)),
+            }
+        }
+        true
+    }
+
+    /// Применить NanPolicy к результату арифметической или математической операции.
+    fn apply_nan_policy(&self, node_type: NodeType, value: Value) -> Value {
+        let is_float_op = matches!(
+            node_type,
+            NodeType::BinaryOperation
+                | NodeType::Sub
+                | NodeType::Mul
+                | NodeType::Div
+                | NodeType::Mod
+                | NodeType::Neg
+                | NodeType::MathSqrt
+                | NodeType::MathSin
+                | NodeType::MathCos
+                | NodeType::MathTan
+                | NodeType::MathAsin
+                | NodeType::MathAcos
+                | NodeType::MathAtan
+                | NodeType::MathExp
+                | NodeType::MathLn
+                | NodeType::MathLog10
+                | NodeType::MathPow
+        );
+        match value {
+            Value::Float(f) if is_float_op && self.nan_policy == NanPolicy::Error => {
+                if f.is_nan() {
+                    Value::Error("NaN result".to_string())
+                } else if f.is_infinite() {
+                    Value::Error("Infinite result".to_string())
+                } else {
+                    Value::Float(f)
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Проверить равенство двух значений.
+    fn values_equal(&self, a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::Int(x), Value::Int(y)) => x == y,
+            (Value::Float(x), Value::Float(y)) => self.floats_equal(*x, *y),
+            (Value::Bool(x), Value::Bool(y)) => x == y,
+            (Value::String(x), Value::String(y)) => x == y,
+            (Value::Char(x), Value::Char(y)) => x == y,
+            (Value::Option(Some(x)), Value::Option(Some(y))) => self.values_equal(x, y),
+            (Value::Option(None), Value::Option(None)) => true,
+            (Value::Unit, Value::Unit) => true,
+            (Value::Bytes(x), Value::Bytes(y)) => x == y,
+            (Value::Set(x), Value::Set(y)) => x == y,
+            (Value::Array(x), Value::Array(y)) => {
+                x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| self.values_equal(a, b))
+            }
+            (
+                Value::Variant {
+                    enum_name: e1,
+                    tag: t1,
+                    fields: f1,
+                },
+                Value::Variant {
+                    enum_name: e2,
+                    tag: t2,
+                    fields: f2,
+                },
+            ) => {
+                e1 == e2
+                    && t1 == t2
+                    && f1.len() == f2.len()
+                    && f1
+                        .iter()
+                        .zip(f2.iter())
+                        .all(|(a, b)| self.values_equal(a, b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Первое расхождение для assert-eq: путь — цепочка индексов и ключей (`[1]["k"]`,
+    /// пустой — различаются сами значения) и описание `expected X, got Y`.
+    fn first_difference(
+        &self,
+        expected: &Value,
+        actual: &Value,
+        path: String,
+    ) -> Option<(String, String)> {
+        match (expected, actual) {
+            (Value::Array(e), Value::Array(a)) => {
+                for (i, (x, y)) in e.iter().zip(a).enumerate() {
+                    let difference = self.first_difference(x, y, format!("{}[{}]", path, i));
+                    if difference.is_some() {
+                        return difference;
+                    }
+                }
+                (e.len() != a.len()).then(|| {
+                    let detail = format!("expected length {}, got {}", e.len(), a.len());
+                    (path, detail)
+                })
+            }
+            (Value::Dict(e), Value::Dict(a)) => {
+                let mut keys: Vec<_> = e.keys().chain(a.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                keys.into_iter().find_map(|key| {
+                    let key_path = format!("{}[{:?}]", path, key);
+                    let show =
+                        |v: Option<&Value>| v.map_or("nothing".to_string(), Value::format_display);
+                    match (e.get(key), a.get(key)) {
+                        (Some(x), Some(y)) => self.first_difference(x, y, key_path),
+                        (x, y) => {
+                            Some((key_path, format!("expected {}, got {}", show(x), show(y))))
+                        }
+                    }
+                })
+            }
+            (e, a) if self.values_equal(e, a) || e == a => None,
+            (e, a) => Some((
+                path,
+                format!(
+                    "expected {}, got {}",
+                    e.format_display(),
+                    a.format_display()
+                ),
+            )),
+        }
+    }
+
+    /// Получить два операнда для бинарной операции.
+    fn get_binary_operands(&mut self, asg: &ASG, node: &Node) -> ASGResult<(Value, Value)> {
+        let (lhs, rhs) = self.get_binary_operand_ids(node)?;
+
+        let val1 = self.ensure_evaluated(asg, lhs)?;
+        let val2 = self.ensure_evaluated(asg, rhs)?;
+
+        Ok((val1, val2))
+    }
+
+    /// Получить ID двух операндов бинарной операции без их вычисления.
+    fn get_binary_operand_ids(&self, node: &Node) -> ASGResult<(NodeID, NodeID)> {
+        // Порядок вычисления — слева направо: FirstOperand, затем SecondOperand,
+        // независимо от порядка рёбер в узле
+        if let (Some(first), Some(second)) = (
+            node.find_edge(EdgeType::FirstOperand),
+            node.find_edge(EdgeType::SecondOperand),
+        ) {
+            return Ok((first.target_node_id, second.target_node_id));
+        }
+
+        let edges: Vec<_> = node
+            .edges
+            .iter()
+            .filter(|e| {
+                e.edge_type == EdgeType::ApplicationArgument
+                    || e.edge_type == EdgeType::FirstOperand
+                    || e.edge_type == EdgeType::SecondOperand
+            })
+            .map(|e| e.target_node_id)
+            .collect();
+
+        if edges.len() < 2 {
+            return Err(ASGError::MissingEdge(
+                node.id,
+                EdgeType::ApplicationArgument,
+            ));
+        }
+
+        Ok((edges[0], edges[1]))
+    }
+
+    /// Получить единственный операнд.
+    fn get_single_operand(&mut self, asg: &ASG, node: &Node) -> ASGResult<Value> {
+        let edge = node.edges.first().ok_or(ASGError::MissingEdge(
+            node.id,
+            EdgeType::ApplicationArgument,
+        ))?;
+        self.ensure_evaluated(asg, edge.target_node_id)
+    }
+
+    /// Операнд для потребителя, который обходит последовательность поэлементно
+    /// (for, sum, product, reduce, collect): `(range a b)` становится ленивым
+    /// диапазоном по тем же границам, и массив не выделяется.
+    fn streamed_operand(&mut self, asg: &ASG, node_id: NodeID) -> ASGResult<Value> {
+        match asg.find_node(node_id) {
+            Some(range) if range.node_type == NodeType::Range => {
+                let (current, end, step) = self.range_bounds(asg, range)?;
+                Ok(Value::LazySeq(Box::new(LazySeqKind::Range {
+                    current,
+                    end,
+                    step,
+                })))
+            }
+            _ => self.ensure_evaluated(asg, node_id),
+        }
+    }
+
+    /// [`Self::streamed_operand`] для единственного операнда.
+    fn streamed_single_operand(&mut self, asg: &ASG, node: &Node) -> ASGResult<Value> {
+        let edge = node.edges.first().ok_or(ASGError::MissingEdge(
+            node.id,
+            EdgeType::ApplicationArgument,
+        ))?;
+        self.streamed_operand(asg, edge.target_node_id)
+    }
+
+    /// Получить первый операнд (FirstOperand edge).
+    fn get_first_operand(&mut self, asg: &ASG, node: &Node) -> ASGResult<Value> {
+        let edge = node
+            .find_edge(EdgeType::FirstOperand)
+            .ok_or(ASGError::MissingEdge(node.id, EdgeType::FirstOperand))?;
+        self.ensure_evaluated(asg, edge.target_node_id)
+    }
+
+    /// Получить второй операнд (SecondOperand edge).
+    fn get_second_operand(&mut self, asg: &ASG, node: &Node) -> ASGResult<Value> {
+        let edge = node
+            .find_edge(EdgeType::SecondOperand)
+            .ok_or(ASGError::MissingEdge(node.id, EdgeType::SecondOperand))?;
+        self.ensure_evaluated(asg, edge.target_node_id)
+    }
+
+    /// Convert Value to JSON string.
+    /// Nesting deeper than [`MAX_DISPLAY_DEPTH`] is written as the string `"..."`.
+    fn value_to_json(&self, val: &Value) -> String {
+        self.value_to_json_at(val, 0)
+    }
+
+    fn value_to_json_at(&self, val: &Value, depth: usize) -> String {
+        if depth > MAX_DISPLAY_DEPTH {
+            return "\"...\"".to_string();
+        }
+        match val {
+            Value::Int(n) => n.to_string(),
+            #[cfg(feature = "bigint")]
+            Value::BigInt(n) => n.to_string(),
+            #[cfg(feature = "decimal")]
+            Value::Decimal(d) => d.to_string(),
+            Value::Float(f) => f.to_string(),
+            Value::Bool(b) => b.to_string(),
+            Value::String(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
+            Value::Char(c) => self.value_to_json_at(&Value::String(c.to_string()), depth),
+            Value::Option(Some(inner)) => self.value_to_json_at(inner, depth),
+            Value::Unit | Value::Option(None) => "null".to_string(),
+            Value::Array(arr) => {
+                let items: Vec<String> = arr
+                    .iter()
+                    .map(|v| self.value_to_json_at(v, depth + 1))
+                    .collect();
+                format!("[{}]", items.join(","))
+            }
+            Value::Dict(d) => {
+                let items: Vec<String> = d
+                    .iter()
+                    .map(|(k, v)| format!("\"{}\":{}", k, self.value_to_json_at(v, depth + 1)))
+                    .collect();
+                format!("{{{}}}", items.join(","))
+            }
+            Value::Record(fields) => {
+                let items: Vec<String> = fields
+                    .iter()
+                    .map(|(k, v)| format!("\"{}\":{}", k, self.value_to_json_at(v, depth + 1)))
+                    .collect();
+                format!("{{{}}}", items.join(","))
+            }
+            Value::Set(items) => {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|k| self.value_to_json_at(&k.to_value(), depth + 1))
+                    .collect();
+                format!("[{}]", items.join(","))
+            }
+            Value::Error(msg) => format!("{{\"error\":\"{}\"}}", msg),
+            _ => "null".to_string(),
+        }
+    }
+
+    /// Convert JSON value to ASG Value.
+    fn json_to_value(&self, json: serde_json::Value) -> Value {
+        match json {
+            serde_json::Value::Null => Value::Unit,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    Value::Int(i)
+                } else if let Some(f) = n.as_f64() {
+                    Value::Float(f)
+                } else {
+                    Value::Unit
+                }
+            }
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(arr) => {
+                Value::Array(arr.into_iter().map(|v| self.json_to_value(v)).collect())
+            }
+            serde_json::Value::Object(map) => {
+                let mut dict = HashMap::new();
+                for (k, v) in map {
+                    dict.insert(k, self.json_to_value(v));
+                }
+                Value::Dict(dict)
+            }
+        }
+    }
+
+    /// Вызвать функцию (Function или ComposedFunction) с одним аргументом.
+    fn call_function_value(&mut self, asg: &ASG, fn_val: Value, arg: Value) -> ASGResult<Value> {
+        self.call(asg, &fn_val, &[arg])
+    }
+
+    /// Пройти конечную lazy sequence до конца, передавая элементы в `f`.
+    /// Бесконечные источники (iterate, repeat, cycle) — ошибка: их нужно ограничить `take-lazy`.
+    fn for_each_lazy(
+        &mut self,
+        asg: &ASG,
+        mut kind: LazySeqKind,
+        what: &str,
+        mut f: impl FnMut(&mut Self, Value) -> ASGResult<()>,
+    ) -> ASGResult<()> {
+        if !kind.is_bounded() {
+            return Err(ASGError::InvalidOperation(format!(
+                "{}: lazy sequence is unbounded, limit it with take-lazy",
+                what
+            )));
+        }
+        while let Some(item) = self.next_lazy_element(asg, &mut kind)? {
+            f(self, item)?;
+        }
+        Ok(())
+    }
+
+    /// Разобрать исход итерации цикла: true — цикл прерван `(break)`, и значение
+    /// `(break v)` записано в `result` (без значения остаётся результат последней
+    /// итерации). `(continue)` — false без изменения `result`; прочие ошибки
+    /// пробрасываются.
+    fn loop_broken(&mut self, step: ASGResult<()>, result: &mut Value) -> ASGResult<bool> {
+        match step {
+            Ok(()) => Ok(false),
+            Err(ASGError::LoopBreak) => {
+                if let Some(value) = self.break_value.take() {
+                    *result = value;
+                }
+                Ok(true)
+            }
+            Err(ASGError::LoopContinue) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Вычислить и проверить границы `(range start end [step])`.
+    fn range_bounds(&mut self, asg: &ASG, node: &Node) -> ASGResult<(i64, i64, i64)> {
+        let start_val = self.get_first_operand(asg, node)?;
+        let end_val = self.get_second_operand(asg, node)?;
+
+        let start = match start_val {
+            Value::Int(n) => n,
+            _ => {
+                return Err(ASGError::TypeError(
+                    "Expected integer for range start".to_string(),
+                ))
+            }
+        };
+        let end = match end_val {
+            Value::Int(n) => n,
+            _ => {
+                return Err(ASGError::TypeError(
+                    "Expected integer for range end".to_string(),
+                ))
+            }
+        };
+
+        let step = if let Some(step_edge) = node.find_edge(EdgeType::LoopStep) {
+            let step_val = self.ensure_evaluated(asg, step_edge.target_node_id)?;
+            match step_val {
+                Value::Int(n) => n,
+                _ => {
+                    return Err(ASGError::TypeError(
+                        "Expected integer for range step".to_string(),
+                    ))
+                }
+            }
+        } else {
+            1
+        };
+
+        if step == 0 {
+            return Err(ASGError::InvalidOperation(
+                "Range step cannot be zero".to_string(),
+            ));
+        }
+        Ok((start, end, step))
+    }
+
+    /// Одна итерация `for`: связать переменные цикла с элементом и вычислить тело.
+    fn run_for_body(
+        &mut self,
+        asg: &ASG,
+        var_names: &[String],
+        item: Value,
+        body_id: NodeID,
+    ) -> ASGResult<Value> {
+        let mut frame = CallFrame::default();
+        match (var_names, item) {
+            ([name], item) => {
+                frame.locals.insert(name.clone(), item);
+            }
+            ([first, second], Value::Array(pair)) if pair.len() == 2 => {
+                let mut pair = pair.into_iter();
+                frame.locals.extend([
+                    (first.clone(), pair.next().unwrap_or(Value::Unit)),
+                    (second.clone(), pair.next().unwrap_or(Value::Unit)),
+                ]);
+            }
+            (_, other) => {
+                return Err(ASGError::TypeError(format!(
+                    "for [{}] expects two-element arrays, got {}",
+                    var_names.join(" "),
+                    other.format_display()
+                )))
+            }
+        }
+        frame.memo = std::mem::take(&mut self.memo);
+        self.call_stack.push(frame);
+
+        let result = self.ensure_evaluated(asg, body_id);
 
-                // Try to evaluate the try body
-                match self.ensure_evaluated(asg, try_edge.target_node_id) {
-                    Ok(Value::Error(msg)) => {
-                        // Error was thrown, execute handler
-                        let saved_memo = std::mem::take(&mut self.memo);
-                        let mut frame = CallFrame::default();
-                        frame.locals.insert(error_var_name, Value::Error(msg));
-                        frame.memo = saved_memo;
-                        self.call_stack.push(frame);
+        if let Some(popped_frame) = self.call_stack.pop() {
+            self.memo = popped_frame.memo;
+        }
+        result
+    }
 
-                        let result = self.ensure_evaluated(asg, handler_edge.target_node_id)?;
+    /// Материализовать n элементов из lazy sequence.
+    fn take_from_lazy(
+        &mut self,
+        asg: &ASG,
+        mut kind: LazySeqKind,
+        n: usize,
+    ) -> ASGResult<Vec<Value>> {
+        let mut result = Vec::with_capacity(n.min(COLLECT_LIMIT));
 
-                        if let Some(popped_frame) = self.call_stack.pop() {
-                            self.memo = popped_frame.memo;
+        for _ in 0..n {
+            match self.next_lazy_element(asg, &mut kind)? {
+                Some(val) => {
+                    self.check_collection_size(result.len() + 1)?;
+                    result.push(val);
+                }
+                None => break,
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Получить следующий элемент из lazy sequence.
+    fn next_lazy_element(&mut self, asg: &ASG, kind: &mut LazySeqKind) -> ASGResult<Option<Value>> {
+        match kind {
+            LazySeqKind::Iterate { func, current } => {
+                let val = (**current).clone();
+                let next = self.call_function_value(asg, (**func).clone(), val.clone())?;
+                *current = Box::new(next);
+                Ok(Some(val))
+            }
+            LazySeqKind::Repeat(val) => Ok(Some((**val).clone())),
+            LazySeqKind::Cycle { arr, index } => {
+                if arr.is_empty() {
+                    return Ok(None);
+                }
+                let val = arr[*index].clone();
+                *index = (*index + 1) % arr.len();
+                Ok(Some(val))
+            }
+            LazySeqKind::Range { current, end, step } => {
+                if (*step > 0 && *current >= *end) || (*step < 0 && *current <= *end) || *step == 0
+                {
+                    return Ok(None);
+                }
+                let val = Value::Int(*current);
+                *current += *step;
+                Ok(Some(val))
+            }
+            LazySeqKind::Map { func, source } => match self.next_lazy_element(asg, source)? {
+                Some(val) => {
+                    let mapped = self.call_function_value(asg, (**func).clone(), val)?;
+                    Ok(Some(mapped))
+                }
+                None => Ok(None),
+            },
+            LazySeqKind::Filter { func, source } => {
+                // Keep getting elements until we find one that passes the filter
+                loop {
+                    match self.next_lazy_element(asg, source)? {
+                        Some(val) => {
+                            let result =
+                                self.call_function_value(asg, (**func).clone(), val.clone())?;
+                            match result {
+                                Value::Bool(true) => return Ok(Some(val)),
+                                Value::Bool(false) => continue,
+                                _ => return Ok(Some(val)), // Non-bool treated as true
+                            }
                         }
-                        result
+                        None => return Ok(None),
                     }
-                    Ok(val) => val, // No error, return value
-                    Err(e) => {
-                        // Runtime error, convert to Value::Error and execute handler
-                        let saved_memo = std::mem::take(&mut self.memo);
-                        let mut frame = CallFrame::default();
-                        frame
-                            .locals
-                            .insert(error_var_name, Value::Error(e.to_string()));
-                        frame.memo = saved_memo;
-                        self.call_stack.push(frame);
+                }
+            }
+        }
+    }
+
+    // === REPL Helper Methods ===
+
+    /// Получить все переменные для REPL команды :env
+    pub fn get_variables(&self) -> &HashMap<String, Value> {
+        &self.variables
+    }
+
+    /// Установить глобальную переменную (например, из привязанного поля GUI)
+    pub fn set_variable(&mut self, name: &str, value: Value) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    /// Получить все функции для REPL команды :funcs
+    /// Возвращает HashMap с именем функции и её Value представлением
+    pub fn get_functions(&self) -> HashMap<String, Value> {
+        self.functions
+            .iter()
+            .map(|(name, (params, body_id, _))| {
+                (
+                    name.clone(),
+                    Value::Function {
+                        params: params.clone(),
+                        body_id: *body_id,
+                        captured: HashMap::new(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asg::{Edge, Node, ASG};
+
+    /// Выполнить все формы программы в интерпретаторе и вернуть значение последней.
+    fn run_program_with(interp: &mut Interpreter, source: &str) -> ASGResult<Value> {
+        let (asg, roots) = crate::parser::parse(source).unwrap();
+        let mut result = Value::Unit;
+        for root in roots {
+            result = interp.execute(&asg, root)?;
+        }
+        Ok(result)
+    }
+
+    /// Выполнить программу в новом интерпретаторе.
+    fn run_program(source: &str) -> ASGResult<Value> {
+        run_program_with(&mut Interpreter::new(), source)
+    }
+
+    #[test]
+    fn test_interpret_add() {
+        let mut asg = ASG::new();
+
+        // Создаём 5 + 8
+        asg.add_node(Node::new(
+            1,
+            NodeType::LiteralInt,
+            Some(5i64.to_le_bytes().to_vec()),
+        ));
+        asg.add_node(Node::new(
+            2,
+            NodeType::LiteralInt,
+            Some(8i64.to_le_bytes().to_vec()),
+        ));
+        asg.add_node(Node::with_edges(
+            3,
+            NodeType::BinaryOperation,
+            None,
+            vec![
+                Edge::new(EdgeType::ApplicationArgument, 1),
+                Edge::new(EdgeType::ApplicationArgument, 2),
+            ],
+        ));
+
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, 3).unwrap();
+
+        match result {
+            Value::Int(val) => assert_eq!(val, 13),
+            _ => panic!("Expected Int"),
+        }
+    }
+
+    #[test]
+    fn test_interpret_comparison() {
+        let mut asg = ASG::new();
+
+        // 5 < 10
+        asg.add_node(Node::new(
+            1,
+            NodeType::LiteralInt,
+            Some(5i64.to_le_bytes().to_vec()),
+        ));
+        asg.add_node(Node::new(
+            2,
+            NodeType::LiteralInt,
+            Some(10i64.to_le_bytes().to_vec()),
+        ));
+        asg.add_node(Node::with_edges(
+            3,
+            NodeType::Lt,
+            None,
+            vec![
+                Edge::new(EdgeType::ApplicationArgument, 1),
+                Edge::new(EdgeType::ApplicationArgument, 2),
+            ],
+        ));
+
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, 3).unwrap();
+
+        match result {
+            Value::Bool(val) => assert!(val),
+            _ => panic!("Expected Bool"),
+        }
+    }
+
+    #[test]
+    fn test_json_encode() {
+        use crate::parser::parse_expr;
+
+        let (asg, root) = parse_expr(r#"(json-encode (dict "name" "John" "age" 30))"#).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+
+        match result {
+            Value::String(s) => {
+                assert!(s.contains("\"name\":\"John\"") || s.contains("\"age\":30"));
+            }
+            _ => panic!("Expected String"),
+        }
+    }
+
+    #[test]
+    fn test_json_decode() {
+        use crate::parser::parse_expr;
+
+        let (asg, root) = parse_expr(r#"(json-decode "{\"x\": 42}")"#).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+
+        match result {
+            Value::Dict(d) => {
+                assert_eq!(d.get("x"), Some(&Value::Int(42)));
+            }
+            _ => panic!("Expected Dict"),
+        }
+    }
+
+    #[test]
+    fn test_html_generation() {
+        use crate::parser::parse_expr;
+
+        let (asg, root) = parse_expr(r#"(div "Hello")"#).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+
+        match result {
+            Value::String(s) => {
+                assert!(s.contains("<div>"));
+                assert!(s.contains("Hello"));
+                assert!(s.contains("</div>"));
+            }
+            _ => panic!("Expected String"),
+        }
+    }
 
-                        let result = self.ensure_evaluated(asg, handler_edge.target_node_id)?;
+    #[test]
+    fn test_html_with_attributes() {
+        use crate::parser::parse_expr;
 
-                        if let Some(popped_frame) = self.call_stack.pop() {
-                            self.memo = popped_frame.memo;
-                        }
-                        result
-                    }
-                }
-            }
+        let (asg, root) = parse_expr(r#"(div "@class=container" "Content")"#).unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
 
-            NodeType::Throw => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(msg) => Value::Error(msg),
-                    _ => Value::Error(format!("{:?}", val)),
-                }
+        match result {
+            Value::String(s) => {
+                assert!(s.contains("class=\"container\""));
+                assert!(s.contains("Content"));
             }
+            _ => panic!("Expected String"),
+        }
+    }
 
-            NodeType::IsError => {
-                let val = self.get_single_operand(asg, node)?;
-                Value::Bool(matches!(val, Value::Error(_)))
-            }
+    #[test]
+    fn test_variadic_add() {
+        use crate::parser::parse_expr;
 
-            NodeType::ErrorMessage => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::Error(msg) => Value::String(msg),
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected error for error-message".to_string(),
-                        ))
-                    }
-                }
-            }
+        let (asg, root) = parse_expr("(+ 1 2 3 4 5)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
 
-            // === Record ===
-            NodeType::Record => {
-                let field_data: Vec<_> = node
-                    .find_edges(EdgeType::RecordFieldDef)
-                    .into_iter()
-                    .map(|e| {
-                        let field_node = asg.find_node(e.target_node_id);
-                        (e.target_node_id, field_node.and_then(|n| n.get_name()))
-                    })
-                    .collect();
-                let mut fields = HashMap::new();
-                for (field_id, field_name_opt) in field_data {
-                    let field_name = field_name_opt.unwrap_or_default();
-                    let field_val = self.ensure_evaluated(asg, field_id)?;
-                    fields.insert(field_name, field_val);
-                }
-                Value::Record(fields)
-            }
+        match result {
+            Value::Int(val) => assert_eq!(val, 15),
+            _ => panic!("Expected Int"),
+        }
+    }
 
-            NodeType::RecordField => {
-                let field_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
-                let record_edge = node
-                    .find_edge(EdgeType::RecordFieldAccess)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::RecordFieldAccess))?;
+    #[test]
+    fn test_variadic_mul() {
+        use crate::parser::parse_expr;
 
-                let record_val = self.ensure_evaluated(asg, record_edge.target_node_id)?;
-                match record_val {
-                    Value::Record(fields) => {
-                        fields
-                            .get(&field_name)
-                            .cloned()
-                            .ok_or(ASGError::InvalidOperation(format!(
-                                "Field {} not found",
-                                field_name
-                            )))?
-                    }
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected record for field access".to_string(),
-                        ))
-                    }
-                }
-            }
+        let (asg, root) = parse_expr("(* 2 3 4)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
 
-            // === Pattern Matching ===
-            NodeType::Match => {
-                let subject_edge = node
-                    .find_edge(EdgeType::MatchSubject)
-                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::MatchSubject))?;
+        match result {
+            Value::Int(val) => assert_eq!(val, 24),
+            _ => panic!("Expected Int"),
+        }
+    }
 
-                let subject_val = self.ensure_evaluated(asg, subject_edge.target_node_id)?;
+    #[test]
+    fn test_variadic_sub_left_assoc() {
+        use crate::parser::parse_expr;
 
-                // Get all match arms
-                let arm_edges: Vec<_> = node.find_edges(EdgeType::ApplicationArgument);
+        // (- 10 3 2) = (10 - 3) - 2
+        let (asg, root) = parse_expr("(- 10 3 2)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+        assert_eq!(result, Value::Int(5));
 
-                for arm_edge in arm_edges {
-                    let arm_node = asg
-                        .find_node(arm_edge.target_node_id)
-                        .ok_or(ASGError::NodeNotFound(arm_edge.target_node_id))?
-                        .clone();
+        let (asg, root) = parse_expr("(- 100 50 25 5)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+        assert_eq!(result, Value::Int(20));
 
-                    if arm_node.node_type != NodeType::MatchArm {
-                        continue;
-                    }
+        // Унарный минус не затронут
+        let (asg, root) = parse_expr("(- 7)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+        assert_eq!(result, Value::Int(-7));
+    }
 
-                    let pattern_edge = arm_node
-                        .find_edge(EdgeType::MatchPattern)
-                        .ok_or(ASGError::MissingEdge(arm_node.id, EdgeType::MatchPattern))?;
-                    let body_edge = arm_node
-                        .find_edge(EdgeType::MatchBody)
-                        .ok_or(ASGError::MissingEdge(arm_node.id, EdgeType::MatchBody))?;
+    #[test]
+    fn test_variadic_div_left_assoc() {
+        use crate::parser::parse_expr;
 
-                    // Check if pattern matches
-                    let pattern_node = asg
-                        .find_node(pattern_edge.target_node_id)
-                        .ok_or(ASGError::NodeNotFound(pattern_edge.target_node_id))?
-                        .clone();
+        // (/ 20 2 5) = (20 / 2) / 5
+        let (asg, root) = parse_expr("(/ 20 2 5)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+        assert_eq!(result, Value::Float(2.0));
 
-                    let (matches, bindings) =
-                        self.match_pattern(asg, &pattern_node, &subject_val)?;
+        // Правая ассоциативность дала бы 20 / (2 / 5) = 50
+        let (asg, root) = parse_expr("(/ 100 10 2)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root).unwrap();
+        assert_eq!(result, Value::Float(5.0));
+    }
 
-                    if matches {
-                        // Pattern matched! Evaluate body with bindings
-                        let result = if !bindings.is_empty() {
-                            let saved_memo = std::mem::take(&mut self.memo);
-                            let mut frame = CallFrame::default();
-                            for (name, val) in bindings {
-                                frame.locals.insert(name, val);
-                            }
-                            frame.memo = saved_memo;
-                            self.call_stack.push(frame);
+    #[test]
+    fn test_host_call_lambda() {
+        use crate::parser::parse_expr;
 
-                            let res = self.ensure_evaluated(asg, body_edge.target_node_id)?;
+        let (asg, root) = parse_expr("(lambda (a b) (+ (* a 10) b))").unwrap();
+        let mut interpreter = Interpreter::new();
+        let f = interpreter.execute(&asg, root).unwrap();
 
-                            if let Some(popped_frame) = self.call_stack.pop() {
-                                self.memo = popped_frame.memo;
-                            }
-                            res
-                        } else {
-                            self.ensure_evaluated(asg, body_edge.target_node_id)?
-                        };
-                        self.memo.insert(node.id, result);
-                        return Ok(());
-                    }
-                }
+        let result = interpreter
+            .call(&asg, &f, &[Value::Int(1), Value::Int(2)])
+            .unwrap();
+        assert_eq!(result, Value::Int(12));
 
-                return Err(ASGError::InvalidOperation(
-                    "No matching pattern found".to_string(),
-                ));
-            }
+        let result = interpreter
+            .call(&asg, &f, &[Value::Int(3), Value::Int(4)])
+            .unwrap();
+        assert_eq!(result, Value::Int(34));
+    }
 
-            NodeType::MatchArm => {
-                // MatchArm nodes are processed by Match, should not be evaluated directly
-                Value::Unit
-            }
+    #[test]
+    fn test_call_non_function() {
+        use crate::parser::parse_expr;
 
-            // === Modules ===
-            NodeType::Module => {
-                // (module name body...)
-                // Просто выполняем все body выражения
-                let content_edges: Vec<_> = node.find_edges(EdgeType::ModuleContent);
-                let mut last_value = Value::Unit;
-                for edge in content_edges {
-                    last_value = self.ensure_evaluated(asg, edge.target_node_id)?;
-                }
-                last_value
-            }
+        let (asg, root) = parse_expr("(5 1 2)").unwrap();
+        let mut interpreter = Interpreter::new();
+        let err = interpreter.execute(&asg, root).unwrap_err();
+        assert!(matches!(err, ASGError::TypeError(_)));
+        assert!(err.to_string().contains("cannot call value of type Int"));
 
-            NodeType::Import => {
-                // (import "path/to/file.asg") или (import "path" as alias)
-                let payload_str = node.get_name().unwrap_or_default();
+        let (asg, root) = parse_expr("(do (let x 1) (x 1))").unwrap();
+        let mut interpreter = Interpreter::new();
+        let err = interpreter.execute(&asg, root).unwrap_err();
+        assert!(err.to_string().contains("cannot call value of type Int"));
+    }
 
-                // Разбираем payload: path|alias или просто path
-                let parts: Vec<&str> = payload_str.split('|').collect();
-                let path = parts[0];
-                let _alias = parts.get(1).copied(); // alias пока не используем
+    #[test]
+    fn test_and_or_short_circuit() {
+        use crate::parser::parse_expr;
 
-                // Читаем и парсим файл
-                let source = match fs::read_to_string(path) {
-                    Ok(content) => content,
-                    Err(e) => {
-                        return Err(ASGError::InvalidOperation(format!(
-                            "Cannot import '{}': {}",
-                            path, e
-                        )));
-                    }
-                };
+        // Правый операнд обращается к неопределённой переменной и упал бы при вычислении
+        let (asg, root) = parse_expr("(or true undefined_var)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.execute(&asg, root).unwrap(), Value::Bool(true));
 
-                // Парсим файл
-                let (imported_asg, root_ids) = match parse(&source) {
-                    Ok((asg, ids)) => (asg, ids),
-                    Err(e) => {
-                        return Err(ASGError::InvalidOperation(format!(
-                            "Parse error in '{}': {:?}",
-                            path, e
-                        )));
-                    }
-                };
+        let (asg, root) = parse_expr("(and false undefined_var)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.execute(&asg, root).unwrap(), Value::Bool(false));
 
-                // Запоминаем какие функции были до импорта
-                let functions_before: std::collections::HashSet<String> =
-                    self.functions.keys().cloned().collect();
+        // Когда левый операнд не решает исход, правый вычисляется
+        let (asg, root) = parse_expr("(and true undefined_var)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.execute(&asg, root).is_err());
+    }
 
-                // Выполняем все top-level выражения
-                // Сохраняем текущее состояние memo
-                let saved_memo = std::mem::take(&mut self.memo);
+    #[test]
+    fn test_when_unless() {
+        use crate::parser::parse_expr;
 
-                for root_id in &root_ids {
-                    self.ensure_evaluated(&imported_asg, *root_id)?;
-                }
+        let cases = [
+            ("(when (> 2 1) (+ 1 1) 42)", Value::Int(42)),
+            ("(when (< 2 1) 42)", Value::Unit),
+            ("(unless (< 2 1) (+ 1 1) 7)", Value::Int(7)),
+            ("(unless (> 2 1) 7)", Value::Unit),
+        ];
+        for (src, expected) in cases {
+            let (asg, root) = parse_expr(src).unwrap();
+            let mut interpreter = Interpreter::new();
+            assert_eq!(
+                interpreter.execute(&asg, root).unwrap(),
+                expected,
+                "{}",
+                src
+            );
+        }
+    }
 
-                // Обновляем импортированные функции, добавляя ASG
-                let new_functions: Vec<String> = self
-                    .functions
-                    .keys()
-                    .filter(|k| !functions_before.contains(*k))
-                    .cloned()
-                    .collect();
+    #[test]
+    fn test_extension_handler() {
+        use crate::parser::parse_expr;
 
-                for name in new_functions {
-                    if let Some((params, body_id, _)) = self.functions.remove(&name) {
-                        self.functions
-                            .insert(name, (params, body_id, Some(imported_asg.clone())));
-                    }
-                }
+        let (asg, root) = parse_expr("(ext:double (+ 1 2))").unwrap();
+        let mut interpreter = Interpreter::new();
 
-                // Восстанавливаем memo (импортированные определения остаются в self.functions и self.variables)
-                self.memo = saved_memo;
+        // Без обработчика — ошибка
+        assert!(interpreter.execute(&asg, root).is_err());
+
+        interpreter
+            .register_extension("double", |args| match args {
+                [Value::Int(n)] => Ok(Value::Int(n * 2)),
+                _ => Err(ASGError::TypeError("double expects one Int".to_string())),
+            })
+            .unwrap();
+        assert_eq!(interpreter.execute(&asg, root).unwrap(), Value::Int(6));
+    }
+
+    #[test]
+    fn test_time_with_mock_clock() {
+        use crate::parser::parse_expr;
+        use std::cell::{Cell, RefCell};
+        use std::rc::Rc;
 
-                Value::Unit
-            }
+        let stderr = Rc::new(RefCell::new(Vec::new()));
 
-            NodeType::Export => {
-                // Export пока просто возвращает Unit (для полноценной модульной системы нужен отдельный механизм)
-                Value::Unit
-            }
+        // Каждое обращение к часам сдвигает время на 5 мс
+        let ticks = Rc::new(Cell::new(0u64));
+        let clock_ticks = Rc::clone(&ticks);
 
-            // === HTML Generation ===
-            NodeType::HtmlElement => {
-                let raw_tag = node.get_name().unwrap_or_else(|| "div".to_string());
-                // Преобразуем html-* теги в обычные HTML теги
-                let tag_name = if raw_tag.starts_with("html-") {
-                    raw_tag[5..].to_string()
-                } else {
-                    raw_tag
-                };
-                let mut html = format!("<{}", tag_name);
+        let (asg, root) = parse_expr("(time (* 6 7))").unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_clock(move || {
+            clock_ticks.set(clock_ticks.get() + 1);
+            Duration::from_millis(5 * clock_ticks.get())
+        });
+        let sink = Rc::clone(&stderr);
+        interpreter.set_debug_output(move |line| sink.borrow_mut().push(line.to_string()));
+        assert_eq!(interpreter.execute(&asg, root).unwrap(), Value::Int(42));
+        assert_eq!(ticks.get(), 2);
+        assert_eq!(*stderr.borrow(), vec!["Elapsed time: 5.000 ms"]);
+
+        let clock_ticks = Rc::clone(&ticks);
+        let (asg, root) = parse_expr("(bench 3 (+ 1 2))").unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_clock(move || {
+            clock_ticks.set(clock_ticks.get() + 1);
+            Duration::from_millis(5 * clock_ticks.get())
+        });
+        let sink = Rc::clone(&stderr);
+        interpreter.set_debug_output(move |line| sink.borrow_mut().push(line.to_string()));
+        let result = interpreter.execute(&asg, root).unwrap();
+        match result {
+            Value::Dict(stats) => {
+                assert_eq!(stats["runs"], Value::Int(3));
+                assert_eq!(stats["mean"], Value::Float(5.0));
+            }
+            other => panic!("expected stats dict, got {:?}", other),
+        }
+        assert_eq!(ticks.get(), 8);
+        assert_eq!(
+            stderr.borrow()[1],
+            "Bench (3 runs): mean 5.000 ms, min 5.000 ms, max 5.000 ms"
+        );
+    }
 
-                // Собираем children
-                let children: Vec<_> = node.find_edges(EdgeType::ApplicationArgument);
-                let mut content = String::new();
+    #[test]
+    fn test_loop_timeout_stops_at_deadline() {
+        use crate::parser::parse;
+        use std::cell::Cell;
+        use std::rc::Rc;
 
-                for child_edge in children {
-                    let child_val = self.ensure_evaluated(asg, child_edge.target_node_id)?;
-                    match child_val {
-                        Value::String(s) => {
-                            // Проверяем, атрибут ли это (начинается с @)
-                            if s.starts_with("@") {
-                                // @attr=value формат
-                                if let Some((attr, val)) = s[1..].split_once('=') {
-                                    html.push_str(&format!(" {}=\"{}\"", attr, val));
-                                }
-                            } else if s.starts_with('<') {
-                                // Вложенный HTML
-                                content.push_str(&s);
-                            } else {
-                                // Текстовый контент
-                                content.push_str(&s);
-                            }
-                        }
-                        _ => content.push_str(&child_val.format_display()),
-                    }
-                }
+        // Каждое обращение к часам сдвигает время на 10 мс
+        let ticks = Rc::new(Cell::new(0u64));
+        let clock_ticks = Rc::clone(&ticks);
 
-                // Самозакрывающиеся теги
-                let self_closing = matches!(
-                    tag_name.as_str(),
-                    "br" | "hr" | "img" | "input" | "meta" | "link"
-                );
-                if self_closing {
-                    html.push_str(" />");
-                } else {
-                    html.push('>');
-                    html.push_str(&content);
-                    html.push_str(&format!("</{}>", tag_name));
-                }
+        let (asg, roots) =
+            parse("(let n 0) (loop-timeout 35 true (do (set n (+ n 1)) n))").unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_clock(move || {
+            clock_ticks.set(clock_ticks.get() + 1);
+            Duration::from_millis(10 * clock_ticks.get())
+        });
+        let mut result = Value::Unit;
+        for root in roots {
+            result = interpreter.execute(&asg, root).unwrap();
+        }
 
-                Value::String(html)
-            }
+        // Дедлайн 10 + 35 = 45 мс: итерации на 20, 30, 40 мс, выход на 50 мс
+        assert_eq!(result, Value::Int(3));
+        assert_eq!(interpreter.get_variables()["n"], Value::Int(3));
+        assert_eq!(ticks.get(), 5);
+    }
 
-            NodeType::HtmlAttr => {
-                // Не должно напрямую вызываться
-                Value::Unit
-            }
+    #[test]
+    fn test_deep_nesting_is_capped_in_display_and_json() {
+        let mut value = Value::Int(1);
+        for _ in 0..10_000 {
+            value = Value::Array(vec![value]);
+        }
 
-            // === JSON ===
-            NodeType::JsonEncode => {
-                let val = self.get_single_operand(asg, node)?;
-                let json = self.value_to_json(&val);
-                Value::String(json)
-            }
+        let shown = value.format_display();
+        assert!(shown.starts_with(&"[".repeat(MAX_DISPLAY_DEPTH + 1)));
+        assert!(shown.contains("[...]"));
 
-            NodeType::JsonDecode => {
-                let val = self.get_single_operand(asg, node)?;
-                match val {
-                    Value::String(s) => match serde_json::from_str::<serde_json::Value>(&s) {
-                        Ok(json) => self.json_to_value(json),
-                        Err(e) => {
-                            return Err(ASGError::InvalidOperation(format!(
-                                "JSON parse error: {}",
-                                e
-                            )))
-                        }
-                    },
-                    _ => {
-                        return Err(ASGError::TypeError(
-                            "Expected string for json-decode".to_string(),
-                        ))
-                    }
-                }
-            }
+        let json = Interpreter::new().value_to_json(&value);
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let mut innermost = &parsed;
+        while let serde_json::Value::Array(items) = innermost {
+            innermost = &items[0];
+        }
+        assert_eq!(innermost, &serde_json::Value::String("...".to_string()));
 
-            // === HTTP Server (requires 'web' feature) ===
-            #[cfg(feature = "web")]
-            NodeType::HttpServe => {
-                let (port_val, handler_val) = self.get_binary_operands(asg, node)?;
-                match port_val {
-                    Value::Int(port) => {
-                        #[allow(unused_imports)]
-                        use std::io::Read;
-                        use tiny_http::{Method, Response, Server};
+        // Неглубокие значения выводятся как раньше
+        let small = Value::Array(vec![Value::Int(1), Value::Array(vec![Value::Int(2)])]);
+        assert_eq!(small.format_display(), "[1, [2]]");
+    }
 
-                        let server = Server::http(format!("0.0.0.0:{}", port)).map_err(|e| {
-                            ASGError::InvalidOperation(format!("Cannot start HTTP server: {}", e))
-                        })?;
+    #[test]
+    fn test_rec_lambda_anonymous_factorial() {
+        // Безымянная функция вызывается сразу
+        assert_eq!(
+            run_program("((rec-lambda self (n) (if (<= n 1) 1 (* n (self (- n 1))))) 10)").unwrap(),
+            Value::Int(3628800)
+        );
+
+        // Глобальная переменная с тем же именем не перекрывает самоссылку
+        assert_eq!(
+            run_program(
+                "(let self 0)\n\
+                 (let fact (rec-lambda self (n) (if (<= n 1) 1 (* n (self (- n 1))))))\n\
+                 (map (array 3 5) fact)"
+            )
+            .unwrap(),
+            Value::Array(vec![Value::Int(6), Value::Int(120)])
+        );
+    }
 
-                        println!("╔════════════════════════════════════════╗");
-                        println!("║   ASG HTTP Server                  ║");
-                        println!("║   http://localhost:{}                 ║", port);
-                        println!("║   Press Ctrl+C to stop                 ║");
-                        println!("╚════════════════════════════════════════╝");
+    #[test]
+    fn test_rec_lambda_self_survives_escape() {
+        // Возвращённая самоссылка вызывается уже вне исходного вызова
+        assert!(matches!(
+            run_program("((((rec-lambda self (n) self) 0) 1) 2)").unwrap(),
+            Value::Function { .. }
+        ));
+        assert!(matches!(
+            run_program(
+                "(do (let f (rec-lambda self (n) (if (== n 0) self (self (- n 1)))))\n\
+                     (let g (f 0))\n\
+                     (g 3))"
+            )
+            .unwrap(),
+            Value::Function { .. }
+        ));
+        // Самоссылка внутри лямбды, пережившей вызов
+        assert_eq!(
+            run_program("(let mk (rec-lambda self (n) (lambda (k) (if (== k 0) n ((self (+ n 1)) (- k 1))))))\n\
+                 ((mk 0) 5)").unwrap(),
+            Value::Int(5)
+        );
+    }
 
-                        for mut request in server.incoming_requests() {
-                            let method = match request.method() {
-                                Method::Get => "GET",
-                                Method::Post => "POST",
-                                Method::Put => "PUT",
-                                Method::Delete => "DELETE",
-                                _ => "OTHER",
-                            };
-                            let path = request.url().to_string();
+    #[test]
+    fn test_value_conversions_round_trip() {
+        let n: i64 = Value::from(42).try_into().unwrap();
+        assert_eq!(n, 42);
+        let f: f64 = Value::from(2.5).try_into().unwrap();
+        assert_eq!(f, 2.5);
+        let b: bool = Value::from(true).try_into().unwrap();
+        assert!(b);
+        let s: String = Value::from("text").try_into().unwrap();
+        assert_eq!(s, "text");
+
+        let array: Value = (1..=3).map(i64::from).collect();
+        assert_eq!(
+            array,
+            Value::Array(vec![Value::Int(1), Value::Int(2), Value::Int(3)])
+        );
+        let items: Vec<Value> = array.try_into().unwrap();
+        let sum: i64 = items
+            .into_iter()
+            .map(i64::try_from)
+            .sum::<ASGResult<i64>>()
+            .unwrap();
+        assert_eq!(sum, 6);
+
+        assert!(matches!(
+            i64::try_from(Value::from("7")),
+            Err(ASGError::TypeError(msg)) if msg == "Expected Int, got String"
+        ));
+    }
 
-                            // Read body for POST requests
-                            let mut body_content = String::new();
-                            request.as_reader().read_to_string(&mut body_content).ok();
+    #[test]
+    fn test_max_collection_size() {
+        let eval = |source: &str| {
+            run_program_with(
+                &mut Interpreter::new().with_max_collection_size(100),
+                source,
+            )
+        };
+        let limited = |result: ASGResult<Value>| {
+            matches!(result, Err(ASGError::InvalidOperation(msg))
+                if msg == "collection size limit exceeded")
+        };
 
-                            // Create request dict for handler
-                            let mut req_dict = HashMap::new();
-                            req_dict
-                                .insert("method".to_string(), Value::String(method.to_string()));
-                            req_dict.insert("path".to_string(), Value::String(path.clone()));
-                            req_dict.insert("body".to_string(), Value::String(body_content));
+        assert_eq!(
+            eval("(length (collect (range 0 100)))").unwrap(),
+            Value::Int(100)
+        );
+        // Большой диапазон не материализуется целиком до ошибки
+        assert!(limited(eval("(collect (range 0 1000000000))")));
+        assert!(limited(eval("(take-lazy 101 (repeat 0))")));
+        // gen-int проверяет размер до генерации элементов
+        assert!(limited(eval("(gen-int 0 9 1000000000000)")));
+        assert!(limited(eval(
+            "(array-concat (collect (range 0 60)) (collect (range 0 60)))"
+        )));
+        // По умолчанию ограничения нет
+        assert_eq!(
+            run_program("(length (collect (range 0 1000)))").unwrap(),
+            Value::Int(1000)
+        );
+    }
 
-                            // Parse query params
-                            let mut params = HashMap::new();
-                            if let Some(query) = path.split('?').nth(1) {
-                                for pair in query.split('&') {
-                                    if let Some((k, v)) = pair.split_once('=') {
-                                        params.insert(k.to_string(), Value::String(v.to_string()));
-                                    }
-                                }
-                            }
-                            req_dict.insert("params".to_string(), Value::Dict(params));
+    #[test]
+    fn test_loop_analysis_is_computed_once_per_loop() {
+        // Много определений и цикл в функции, которую вызывают тысячи раз:
+        // анализ цикла не должен повторяться при каждом входе в него
+        let mut source: String = (0..300)
+            .map(|i| format!("(fn f{} (x) (+ x {}))\n", i, i))
+            .collect();
+        source.push_str(
+            "(fn spin () (do (let i 0) (while (< i 3) (set i (+ i 1))) i))
+             (do (let total 0)
+                 (for k (range 0 1000) (set total (+ total (spin))))
+                 total)",
+        );
+        let (asg, roots) = parse(&source).unwrap();
+        let mut interp = Interpreter::new();
+        let started = Instant::now();
+        let mut result = Value::Unit;
+        for root in roots {
+            result = interp.execute(&asg, root).unwrap();
+        }
+        assert_eq!(result, Value::Int(3000));
+        assert_eq!(interp.loop_variants.len(), 1);
+        assert_eq!(interp.graph_facts.len(), 1);
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
 
-                            let req_value = Value::Dict(req_dict);
+    #[test]
+    fn test_while_keeps_memo_of_invariant_nodes() {
+        let (asg, root) = crate::parser::parse_expr(
+            "(do (let n 5) (let i 0) (let total 0)
+                 (while (< i n)
+                   (do (set total (+ total (sum (collect (range 0 1000)))))
+                       (set i (+ i 1))))
+                 total)",
+        )
+        .unwrap();
+        let mut interp = Interpreter::new();
+        assert_eq!(interp.execute(&asg, root).unwrap(), Value::Int(5 * 499500));
+
+        let node_of = |node_type| {
+            asg.nodes
+                .iter()
+                .find(|node| node.node_type == node_type)
+                .unwrap()
+                .id
+        };
+        let loop_id = node_of(NodeType::Loop);
+        let variant = interp.loop_variant_nodes(&asg, loop_id);
+        // Сумма константного диапазона вычисляется один раз, условие — каждую итерацию
+        assert!(!variant.contains(&node_of(NodeType::ArraySum)));
+        assert!(!variant.contains(&node_of(NodeType::Collect)));
+        assert!(variant.contains(&node_of(NodeType::Lt)));
+        assert!(variant.contains(&node_of(NodeType::Assign)));
+    }
 
-                            // Call handler with request
-                            let response_val =
-                                self.call_function_value(asg, handler_val.clone(), req_value)?;
+    #[test]
+    fn test_int_overflow_modes() {
+        let run = |mode: IntMode, source: &str| {
+            run_program_with(&mut Interpreter::new().with_int_mode(mode), source)
+        };
 
-                            // Process response
-                            let (status_code, content_type, body): (u32, String, String) =
-                                match response_val {
-                                    Value::String(s) => (200, "text/html".to_string(), s),
-                                    Value::Dict(d) => {
-                                        let status = match d.get("status") {
-                                            Some(Value::Int(s)) => *s as u32,
-                                            _ => 200,
-                                        };
-                                        let ctype = match d.get("content-type") {
-                                            Some(Value::String(s)) => s.clone(),
-                                            _ => "text/html".to_string(),
-                                        };
-                                        let body = match d.get("body") {
-                                            Some(Value::String(s)) => s.clone(),
-                                            Some(v) => v.format_display(),
-                                            _ => "".to_string(),
-                                        };
-                                        (status, ctype, body)
-                                    }
-                                    other => {
-                                        (200, "text/plain".to_string(), other.format_display())
-                                    }
-                                };
+        let overflow = "(* 9223372036854775807 2)";
+        assert!(matches!(
+            run(IntMode::Checked, overflow),
+            Err(ASGError::InvalidOperation(msg)) if msg.contains("overflow")
+        ));
+        assert_eq!(run(IntMode::Wrapping, overflow).unwrap(), Value::Int(-2));
+        assert!(run(IntMode::Checked, "(- 0 (- -9223372036854775807 1))").is_err());
+
+        let max_plus_one = "(+ 9223372036854775807 1)";
+        assert!(run(IntMode::Checked, max_plus_one).is_err());
+        assert_eq!(
+            run(IntMode::Wrapping, max_plus_one).unwrap(),
+            Value::Int(i64::MIN)
+        );
+
+        // pow не паникует, а следует тому же режиму
+        assert!(matches!(
+            run(IntMode::Checked, "(pow 10 30)"),
+            Err(ASGError::InvalidOperation(msg)) if msg == "integer overflow"
+        ));
+        assert_eq!(run(IntMode::Wrapping, "(pow 2 64)").unwrap(), Value::Int(0));
+        assert_eq!(run(IntMode::Checked, "(pow 3 4)").unwrap(), Value::Int(81));
+    }
 
-                            println!(
-                                "[{}] {} {} -> {}",
-                                method,
-                                path.split('?').next().unwrap_or(&path),
-                                status_code,
-                                body.len()
-                            );
+    #[test]
+    fn test_checked_arithmetic_flag() {
+        let max_plus_one = "(+ 9223372036854775807 1)";
+        // По умолчанию, как и раньше, — переполнение по модулю 2^64
+        assert_eq!(run_program(max_plus_one).unwrap(), Value::Int(i64::MIN));
+        assert_eq!(
+            run_program_with(
+                &mut Interpreter::new().with_checked_arithmetic(false),
+                max_plus_one
+            )
+            .unwrap(),
+            Value::Int(i64::MIN)
+        );
+        assert!(matches!(
+            run_program_with(
+                &mut Interpreter::new().with_checked_arithmetic(true),
+                max_plus_one
+            ),
+            Err(ASGError::InvalidOperation(msg)) if msg == "integer overflow"
+        ));
+    }
 
-                            let response = Response::from_string(body)
-                                .with_status_code(status_code as u16)
-                                .with_header(
-                                    format!("Content-Type: {}", content_type)
-                                        .parse::<tiny_http::Header>()
-                                        .unwrap(),
-                                );
-                            request.respond(response).ok();
-                        }
-                        Value::Unit
-                    }
-                    _ => return Err(ASGError::TypeError("Expected integer port".to_string())),
-                }
-            }
+    #[test]
+    fn test_pow_with_large_exponents() {
+        let run = |mode: IntMode, source: &str| {
+            run_program_with(&mut Interpreter::new().with_int_mode(mode), source)
+        };
 
-            #[cfg(not(feature = "web"))]
-            NodeType::HttpServe => {
-                return Err(ASGError::InvalidOperation(
-                    "HTTP server requires 'web' feature. Recompile with: cargo build --features web".to_string()
-                ));
-            }
+        // Основания 0, 1 и -1 не переполняются
+        for mode in [IntMode::Checked, IntMode::Wrapping] {
+            assert_eq!(run(mode, "(pow 1 5000000000)").unwrap(), Value::Int(1));
+            assert_eq!(run(mode, "(pow 0 5000000000)").unwrap(), Value::Int(0));
+            assert_eq!(run(mode, "(pow 0 0)").unwrap(), Value::Int(1));
+            assert_eq!(run(mode, "(pow -1 5000000001)").unwrap(), Value::Int(-1));
+            assert_eq!(run(mode, "(pow -1 5000000000)").unwrap(), Value::Int(1));
+        }
+        assert!(run(IntMode::Checked, "(pow 2 5000000000)").is_err());
+
+        // Показатель не усекается до u32
+        assert_eq!(
+            run(IntMode::Wrapping, "(pow 2 4294967296)").unwrap(),
+            Value::Int(0)
+        );
+        assert_eq!(
+            run(IntMode::Wrapping, "(pow 3 4294967296)").unwrap(),
+            Value::Int(wrapping_pow(3, 1 << 32))
+        );
+        assert_ne!(
+            run(IntMode::Wrapping, "(pow 3 4294967296)").unwrap(),
+            Value::Int(1)
+        );
+    }
 
-            NodeType::HttpResponse => {
-                // Создание HTTP response (используется внутри handler)
-                let edges: Vec<_> = node.find_edges(EdgeType::ApplicationArgument);
-                if edges.len() != 3 {
-                    return Err(ASGError::InvalidOperation(
-                        "http-response requires 3 arguments".to_string(),
-                    ));
-                }
-                let status = self.ensure_evaluated(asg, edges[0].target_node_id)?;
-                let _headers = self.ensure_evaluated(asg, edges[1].target_node_id)?;
-                let body = self.ensure_evaluated(asg, edges[2].target_node_id)?;
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_factorial_50() {
+        let run = |source: &str| {
+            run_program_with(
+                &mut Interpreter::new().with_int_mode(IntMode::Promote),
+                source,
+            )
+            .unwrap()
+        };
 
-                // Возвращаем как Dict
-                let mut response = HashMap::new();
-                response.insert("status".to_string(), status);
-                response.insert("body".to_string(), body);
-                Value::Dict(response)
-            }
+        assert_eq!(
+            run("(fn fact (n) (if (<= n 1) 1 (* n (fact (- n 1)))))\n\
+                 (to-string (fact 50))"),
+            Value::String(
+                "30414093201713378043612608166064768844377641568960512000000000000".to_string()
+            )
+        );
+
+        // Литерал вне i64; результат, помещающийся в i64, снова Int
+        assert_eq!(run("(- 9223372036854775808 1)"), Value::Int(i64::MAX));
+        assert_eq!(
+            run("(< 9223372036854775807 9223372036854775808)"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            run("(== (* 4294967296 4294967296) 18446744073709551616)"),
+            Value::Bool(true)
+        );
+    }
 
-            // === Native GUI (requires 'gui' feature) ===
-            #[cfg(feature = "gui")]
-            NodeType::GuiRun => {
-                let window_val = self.get_single_operand(asg, node)?;
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_pow_rejects_huge_exponents() {
+        let run = |source: &str| {
+            run_program_with(
+                &mut Interpreter::new().with_int_mode(IntMode::Promote),
+                source,
+            )
+        };
 
-                // Check if it's the special calculator command
-                let is_calculator = matches!(&window_val, Value::String(s) if s == "calculator");
+        assert_eq!(run("(pow 1 5000000000)").unwrap(), Value::Int(1));
+        assert_eq!(run("(pow -1 5000000001)").unwrap(), Value::Int(-1));
+        assert!(matches!(
+            run("(pow 2 5000000000)"),
+            Err(ASGError::InvalidOperation(msg)) if msg.contains("too large")
+        ));
+        assert_eq!(
+            run("(to-string (pow 2 100))").unwrap(),
+            Value::String("1267650600228229401496703205376".to_string())
+        );
+    }
 
-                if is_calculator {
-                    crate::gui::run_calculator()
-                        .map_err(|e| ASGError::InvalidOperation(format!("GUI error: {}", e)))?;
-                } else {
-                    // Convert Value to Widget tree
-                    let widgets = match crate::gui::ASGGuiApp::value_to_widget(&window_val) {
-                        Some(w) => vec![w],
-                        None => Vec::new(),
-                    };
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal_exact_arithmetic() {
+        let dec = |s: &str| Value::Decimal(s.parse().unwrap());
+
+        assert_eq!(run_program("(+ 0.1d 0.2d)").unwrap(), dec("0.3"));
+        assert_eq!(
+            run_program("(== (+ 0.1d 0.2d) 0.3d)").unwrap(),
+            Value::Bool(true)
+        );
+        // Для сравнения: float так не умеет
+        assert_ne!(
+            run_program("(- (+ 0.1 0.2) 0.3)").unwrap(),
+            Value::Float(0.0)
+        );
+
+        // Масштаб сохраняется при выводе; Int смешивается точно
+        assert_eq!(
+            run_program("(to-string (* 1.10d 3))").unwrap(),
+            Value::String("3.30".to_string())
+        );
+        assert_eq!(run_program("(/ 1d 4)").unwrap(), dec("0.25"));
+        assert_eq!(run_program("(< 2.5d 3)").unwrap(), Value::Bool(true));
+
+        // Явные преобразования
+        assert_eq!(run_program("(cast Float 2.5d)").unwrap(), Value::Float(2.5));
+        assert_eq!(run_program("(cast Int -2.75d)").unwrap(), Value::Int(-2));
+        assert_eq!(run_program("(cast Decimal 7)").unwrap(), dec("7"));
+        assert_eq!(run_program("(cast Decimal 0.5)").unwrap(), dec("0.5"));
+
+        // Float не смешивается с Decimal неявно; деление на ноль — ошибка
+        assert!(matches!(
+            run_program("(+ 1.0d 0.5)"),
+            Err(ASGError::TypeError(_))
+        ));
+        assert!(matches!(
+            run_program("(/ 1.0d 0)"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+    }
 
-                    let title = match &window_val {
-                        Value::Dict(d) => match d.get("title") {
-                            Some(Value::String(s)) => s.clone(),
-                            _ => "ASG App".to_string(),
-                        },
-                        _ => "ASG App".to_string(),
-                    };
+    #[test]
+    fn test_keyword_arguments() {
+        let defs = "(fn point3 (x y z) (array x y z))\n(let f (lambda (a b) (- a b)))\n";
+        let arr = |items: &[i64]| Value::Array(items.iter().map(|&n| Value::Int(n)).collect());
+
+        assert_eq!(
+            run_program(&format!("{}(point3 :z 3 :x 1 :y 2)", defs)).unwrap(),
+            arr(&[1, 2, 3])
+        );
+        // Позиционные первыми, остальные по имени в любом порядке
+        assert_eq!(
+            run_program(&format!("{}(point3 1 :z 3 :y 2)", defs)).unwrap(),
+            arr(&[1, 2, 3])
+        );
+        assert_eq!(
+            run_program(&format!("{}(f :b 10 :a 15)", defs)).unwrap(),
+            Value::Int(5)
+        );
+
+        assert!(matches!(
+            run_program(&format!("{}(point3 1 2 :w 3)", defs)),
+            Err(ASGError::TypeError(msg)) if msg.contains("unknown keyword argument :w")
+        ));
+        assert!(matches!(
+            run_program(&format!("{}(point3 :x 1 :y 2 :x 3)", defs)),
+            Err(ASGError::TypeError(msg)) if msg.contains("more than once")
+        ));
+        assert!(matches!(
+            run_program(&format!("{}(point3 1 2 :x 3)", defs)),
+            Err(ASGError::TypeError(msg)) if msg.contains("more than once")
+        ));
+        assert_eq!(
+            run_program("(defrecord Point (x y))\n(field (Point :y 2 :x 1) x)").unwrap(),
+            Value::Int(1)
+        );
+        // Позиционный аргумент после именованного — ошибка разбора
+        assert!(crate::parser::parse("(point3 :x 1 2)").is_err());
+    }
 
-                    crate::gui::run_gui(&title, widgets)
-                        .map_err(|e| ASGError::InvalidOperation(format!("GUI error: {}", e)))?;
-                }
+    #[test]
+    fn test_default_parameter_values() {
+        let defs = "(fn add (x (y 10)) (+ x y))\n\
+                    (fn scale (x (k (* x 2)) (b 1)) (+ (* x k) b))\n\
+                    (let f (lambda (a (b 3)) (* a b)))\n";
+
+        assert_eq!(
+            run_program(&format!("{}(add 1 2)", defs)).unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            run_program(&format!("{}(add 1)", defs)).unwrap(),
+            Value::Int(11)
+        );
+        // Умолчание видит предыдущие параметры; пропуск перед именованным аргументом
+        assert_eq!(
+            run_program(&format!("{}(scale 3)", defs)).unwrap(),
+            Value::Int(19)
+        );
+        assert_eq!(
+            run_program(&format!("{}(scale 3 :b 0)", defs)).unwrap(),
+            Value::Int(18)
+        );
+        assert_eq!(
+            run_program(&format!("{}(f 2)", defs)).unwrap(),
+            Value::Int(6)
+        );
+        assert_eq!(
+            run_program(&format!("{}(map (array 1 2) f)", defs)).unwrap(),
+            Value::Array(vec![Value::Int(3), Value::Int(6)])
+        );
+    }
 
-                Value::Unit
-            }
+    #[test]
+    fn test_match_error_shows_value() {
+        let (asg, root) =
+            crate::parser::parse_expr(r#"(match (array 1 "two") 0 "zero" (array 1 2) "pair")"#)
+                .unwrap();
+        let err = Interpreter::new().execute(&asg, root).unwrap_err();
+        assert!(matches!(
+            err,
+            ASGError::InvalidOperation(msg)
+                if msg == r#"No matching pattern for value [1, "two"]"#
+        ));
+    }
 
-            #[cfg(not(feature = "gui"))]
-            NodeType::GuiRun => {
-                return Err(ASGError::InvalidOperation(
-                    "Native GUI requires 'gui' feature. Recompile with: cargo build --features gui"
-                        .to_string(),
-                ));
-            }
+    #[test]
+    fn test_match_repeated_binding_requires_equality() {
+        let pattern = |subject: &str| {
+            format!(
+                r#"(match {} (array x x) x (array a b) "different")"#,
+                subject
+            )
+        };
 
-            NodeType::GuiWindow
-            | NodeType::GuiButton
-            | NodeType::GuiTextField
-            | NodeType::GuiLabel
-            | NodeType::GuiVBox
-            | NodeType::GuiHBox
-            | NodeType::GuiCanvas => {
-                // GUI widgets - возвращаем описание для gui-run
-                let mut widget = HashMap::new();
-                widget.insert(
-                    "type".to_string(),
-                    Value::String(format!("{:?}", node.node_type)),
-                );
+        assert_eq!(run_program(&pattern("(array 5 5)")).unwrap(), Value::Int(5));
+        assert_eq!(
+            run_program(&pattern("(array 5 6)")).unwrap(),
+            Value::String("different".to_string())
+        );
+        // Ограничение действует и между вложенными паттернами
+        assert_eq!(
+            run_program(r#"(match (array 1 (array 2 1)) (array x (array y x)) (+ x y) _ 0)"#)
+                .unwrap(),
+            Value::Int(3)
+        );
+        assert_eq!(
+            run_program(r#"(match (array 1 (array 2 3)) (array x (array y x)) (+ x y) _ 0)"#)
+                .unwrap(),
+            Value::Int(0)
+        );
+    }
 
-                let children: Vec<_> = node
-                    .find_edges(EdgeType::ApplicationArgument)
-                    .into_iter()
-                    .filter_map(|e| self.ensure_evaluated(asg, e.target_node_id).ok())
-                    .collect();
-                widget.insert("children".to_string(), Value::Array(children));
+    #[test]
+    fn test_match_on_runtime_type() {
+        let describe = "(fn describe (v)
+             (match v
+               (Int n) (+ n 1)
+               (String s) (str-length s)
+               _ 0))";
+
+        assert_eq!(
+            run_program(&format!("{} (describe 41)", describe)).unwrap(),
+            Value::Int(42)
+        );
+        assert_eq!(
+            run_program(&format!("{} (describe \"héllo\")", describe)).unwrap(),
+            Value::Int(5)
+        );
+        assert_eq!(
+            run_program(&format!("{} (describe 1.5)", describe)).unwrap(),
+            Value::Int(0)
+        );
+
+        // Одноимённый вариант enum важнее имени типа
+        assert_eq!(
+            run_program(
+                "(defenum Num (Int n) (Big n))
+                 (match (Int 3) (Int n) n _ 0)"
+            )
+            .unwrap(),
+            Value::Int(3)
+        );
+    }
 
-                Value::Dict(widget)
-            }
+    #[test]
+    fn test_block_local_functions() {
+        let defs = "(fn outer (x) (do (fn helper (y) (* y 10)) (helper x)))\n\
+                    (fn other (x) (helper x))\n";
+
+        assert_eq!(
+            run_program(&format!("{}(outer 2)", defs)).unwrap(),
+            Value::Int(20)
+        );
+        // helper локальна для outer: после выхода её не видно ни из другой функции, ни глобально
+        assert!(matches!(
+            run_program(&format!("{}(outer 2)\n(other 2)", defs)),
+            Err(ASGError::UnknownVariable(name)) if name == "helper"
+        ));
+        assert!(run_program(&format!("{}(outer 2)\n(helper 2)", defs)).is_err());
+
+        // Локальная функция видна во вложенных блоках и может быть рекурсивной
+        assert_eq!(
+            run_program("(do (fn fact (n) (if (<= n 1) 1 (* n (fact (- n 1))))) (do (fact 5)))")
+                .unwrap(),
+            Value::Int(120)
+        );
+        assert!(run_program("(do (fn tmp () 1) (tmp))\n(tmp)").is_err());
+
+        // Локальная функция, вернувшаяся из блока, остаётся рекурсивной
+        assert_eq!(
+            run_program(
+                "(do (fn make () (do (fn lp (n) (if (== n 0) 0 (lp (- n 1)))) lp)) ((make) 3))"
+            )
+            .unwrap(),
+            Value::Int(0)
+        );
+        // Лямбда, вернувшаяся из блока, видит его локальные функции
+        assert_eq!(
+            run_program(
+                "(do (fn make () (do (fn helper (x) (* x 2)) (lambda (y) (helper y)))) ((make) 4))"
+            )
+            .unwrap(),
+            Value::Int(8)
+        );
+        // Взаимная рекурсия соседних локальных функций
+        assert_eq!(
+            run_program(
+                "(do (fn make () (do (fn ev (n) (if (== n 0) true (od (- n 1))))
+                                     (fn od (n) (if (== n 0) false (ev (- n 1))))
+                                     ev))
+                     ((make) 7))"
+            )
+            .unwrap(),
+            Value::Bool(false)
+        );
+    }
 
-            // По умолчанию — Unit
-            _ => Value::Unit,
-        };
+    #[test]
+    fn test_generator_yields_finite_sequence() {
+        // let/set внутри генератора пишут его локальные переменные, они живут между next
+        let source = "(fn countdown (n)\n\
+                        (do (yield \"start\")\n\
+                            (let k n)\n\
+                            (while (> k 0) (do (yield k) (set k (- k 1))))))\n\
+                      (let g (countdown 2))\n\
+                      (array (next g) (next g) (next g) (next g) (next g))";
+        // После завершения next возвращает ()
+        assert_eq!(
+            run_program(source).unwrap(),
+            Value::Array(vec![
+                Value::String("start".to_string()),
+                Value::Int(2),
+                Value::Int(1),
+                Value::Unit,
+                Value::Unit,
+            ])
+        );
+
+        // yield внутри if; лишние next дают (), которые убирает compact
+        let source = "(fn evens (limit) (do (let i 0) (while (< i limit) (do \
+                        (if (== (% i 2) 0) (yield i)) (set i (+ i 1))))))\n\
+                      (let g (evens 7))\n\
+                      (compact (array (next g) (next g) (next g) (next g) (next g) (next g)))";
+        assert_eq!(
+            run_program(source).unwrap(),
+            Value::Array(vec![
+                Value::Int(0),
+                Value::Int(2),
+                Value::Int(4),
+                Value::Int(6)
+            ])
+        );
+
+        assert!(run_program("(yield 1)").is_err());
+        assert!(run_program("(fn bad () (print (yield 1)))\n(next (bad))").is_err());
+    }
 
-        self.memo.insert(node.id, result_value);
-        Ok(())
+    #[test]
+    fn test_generator_break_and_continue_in_while() {
+        let ints = |xs: &[i64]| Value::Array(xs.iter().map(|&x| Value::Int(x)).collect());
+
+        // continue пропускает остаток тела, цикл продолжается
+        let source = "(fn g () (do (let i 0) (while (< i 5) (do (set i (+ i 1))                         (if (== i 2) (continue) ()) (yield i)))))
+                      (let gen (g))
+                      (compact (array (next gen) (next gen) (next gen) (next gen) (next gen)))";
+        assert_eq!(run_program(source).unwrap(), ints(&[1, 3, 4, 5]));
+
+        // break выходит из цикла, выполнение продолжается после него
+        let source = "(fn g () (do (let i 0) (while true (do (set i (+ i 1))                         (if (== i 3) (break) ()) (yield i))) (yield 100)))
+                      (let gen (g))
+                      (compact (array (next gen) (next gen) (next gen) (next gen)))";
+        assert_eq!(run_program(source).unwrap(), ints(&[1, 2, 100]));
     }
 
-    /// Вычислить узел если не в кеше, и вернуть значение.
-    /// Использует stacker для автоматического расширения стека при глубокой рекурсии.
-    fn ensure_evaluated(&mut self, asg: &ASG, node_id: NodeID) -> ASGResult<Value> {
-        // Предотвращаем stack overflow при глубокой рекурсии
-        // 256KB red zone, 8MB stack growth
-        stacker::maybe_grow(256 * 1024, 8 * 1024 * 1024, || {
-            if self.memo.contains_key(&node_id) {
-                return Ok(self.memo.get(&node_id).unwrap().clone());
-            }
-            let node = asg
-                .find_node(node_id)
-                .ok_or(ASGError::NodeNotFound(node_id))?
-                .clone();
-            self.eval_node(asg, &node)?;
-            Ok(self.memo.get(&node_id).unwrap().clone())
-        })
+    #[test]
+    fn test_str_count_and_find_all() {
+        let ints = |items: &[i64]| Value::Array(items.iter().map(|&n| Value::Int(n)).collect());
+
+        // Вхождения не пересекаются: "aaaa" содержит "aa" дважды, а не трижды
+        assert_eq!(
+            run_program("(str-count \"aaaa\" \"aa\")").unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            run_program("(str-count \"abababa\" \"aba\")").unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            run_program("(str-count \"abc\" \"x\")").unwrap(),
+            Value::Int(0)
+        );
+        assert_eq!(
+            run_program("(str-find-all \"abababa\" \"aba\")").unwrap(),
+            ints(&[0, 4])
+        );
+
+        // Позиции — в символах, как в substring
+        assert_eq!(
+            run_program("(str-find-all \"éa😀a-a\" \"a\")").unwrap(),
+            ints(&[1, 3, 5])
+        );
+        assert_eq!(
+            run_program("(str-find-all \"abc\" \"x\")").unwrap(),
+            ints(&[])
+        );
+
+        assert!(matches!(
+            run_program("(str-count \"abc\" \"\")"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            run_program("(str-find-all \"abc\" 1)"),
+            Err(ASGError::TypeError(_))
+        ));
     }
 
-    /// Проверить соответствие паттерна значению.
-    /// Возвращает (matches: bool, bindings: Vec<(name, value)>)
-    fn match_pattern(
-        &mut self,
-        asg: &ASG,
-        pattern_node: &Node,
-        subject: &Value,
-    ) -> ASGResult<(bool, Vec<(String, Value)>)> {
-        match pattern_node.node_type {
-            // Wildcard pattern: always matches, no bindings
-            NodeType::VarRef => {
-                let name = pattern_node.get_name().unwrap_or_default();
-                if name == "_" {
-                    // Wildcard
-                    Ok((true, vec![]))
-                } else {
-                    // Variable binding
-                    Ok((true, vec![(name, subject.clone())]))
-                }
-            }
+    #[test]
+    fn test_str_replace_first_and_all() {
+        let string = |s: &str| Value::String(s.to_string());
+
+        assert_eq!(
+            run_program("(str-replace-first \"a-b-c\" \"-\" \"+\")").unwrap(),
+            string("a+b-c")
+        );
+        assert_eq!(
+            run_program("(str-replace-all \"a-b-c\" \"-\" \"+\")").unwrap(),
+            string("a+b+c")
+        );
+        // str-replace — алиас str-replace-all
+        assert_eq!(
+            run_program("(str-replace \"a-b-c\" \"-\" \"+\")").unwrap(),
+            string("a+b+c")
+        );
+        assert_eq!(
+            run_program("(str-replace-first \"abc\" \"x\" \"y\")").unwrap(),
+            string("abc")
+        );
+        assert!(matches!(
+            run_program("(str-replace-first \"abc\" 1 \"y\")"),
+            Err(ASGError::TypeError(_))
+        ));
+    }
 
-            // Literal patterns
-            NodeType::LiteralInt => {
-                if let Some(bytes) = &pattern_node.payload {
-                    if bytes.len() >= 8 {
-                        let pattern_val = i64::from_le_bytes(bytes[..8].try_into().unwrap());
-                        match subject {
-                            Value::Int(n) => Ok((*n == pattern_val, vec![])),
-                            _ => Ok((false, vec![])),
-                        }
-                    } else {
-                        Ok((false, vec![]))
-                    }
-                } else {
-                    Ok((false, vec![]))
-                }
-            }
+    #[test]
+    fn test_str_to_chars_round_trip() {
+        let strings = |items: &[&str]| {
+            Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect())
+        };
 
-            NodeType::LiteralFloat => {
-                if let Some(bytes) = &pattern_node.payload {
-                    if bytes.len() >= 8 {
-                        let pattern_val = f64::from_le_bytes(bytes[..8].try_into().unwrap());
-                        match subject {
-                            Value::Float(f) => {
-                                Ok(((*f - pattern_val).abs() < f64::EPSILON, vec![]))
-                            }
-                            _ => Ok((false, vec![])),
-                        }
-                    } else {
-                        Ok((false, vec![]))
-                    }
-                } else {
-                    Ok((false, vec![]))
-                }
-            }
+        // Символы — скаляры Unicode, как в str-length
+        assert_eq!(
+            run_program("(str-to-chars \"héй😀\")").unwrap(),
+            strings(&["h", "é", "й", "😀"])
+        );
+        assert_eq!(run_program("(str-to-chars \"\")").unwrap(), strings(&[]));
+        assert_eq!(
+            run_program("(chars-to-str (str-to-chars \"héй😀\"))").unwrap(),
+            Value::String("héй😀".to_string())
+        );
+        assert_eq!(
+            run_program("(chars-to-str (reverse (str-to-chars \"abc\")))").unwrap(),
+            Value::String("cba".to_string())
+        );
+        assert!(matches!(
+            run_program("(chars-to-str (array \"a\" 1))"),
+            Err(ASGError::TypeError(_))
+        ));
+    }
 
-            NodeType::LiteralBool => {
-                if let Some(bytes) = &pattern_node.payload {
-                    let pattern_val = bytes.first().map(|&b| b != 0).unwrap_or(false);
-                    match subject {
-                        Value::Bool(b) => Ok((*b == pattern_val, vec![])),
-                        _ => Ok((false, vec![])),
-                    }
-                } else {
-                    Ok((false, vec![]))
-                }
-            }
+    #[test]
+    fn test_array_set_operations() {
+        let ints = |items: &[i64]| Value::Array(items.iter().map(|&n| Value::Int(n)).collect());
+
+        // Дубликаты схлопываются, порядок — первого появления
+        assert_eq!(
+            run_program("(array-union (array 3 1 3 2) (array 2 4 1 4))").unwrap(),
+            ints(&[3, 1, 2, 4])
+        );
+        assert_eq!(
+            run_program("(array-intersect (array 3 1 3 2 5) (array 2 3 3 9))").unwrap(),
+            ints(&[3, 2])
+        );
+        assert_eq!(
+            run_program("(array-diff (array 3 1 3 2 1) (array 2 7))").unwrap(),
+            ints(&[3, 1])
+        );
+        assert_eq!(
+            run_program("(array-diff (array) (array 1))").unwrap(),
+            ints(&[])
+        );
+
+        // values_equal: float сравниваются с допуском
+        assert_eq!(
+            run_program("(array-intersect (array 0.3) (array (+ 0.1 0.2)))").unwrap(),
+            Value::Array(vec![Value::Float(0.3)])
+        );
+        assert!(matches!(
+            run_program("(array-union (array 1) 2)"),
+            Err(ASGError::TypeError(_))
+        ));
+    }
 
-            NodeType::LiteralString => {
-                if let Some(bytes) = &pattern_node.payload {
-                    let pattern_val = String::from_utf8_lossy(bytes).to_string();
-                    match subject {
-                        Value::String(s) => Ok((s == &pattern_val, vec![])),
-                        _ => Ok((false, vec![])),
-                    }
-                } else {
-                    Ok((false, vec![]))
-                }
+    #[test]
+    fn test_array_shuffle_seeded() {
+        let shuffle = |seed: u64| {
+            let (asg, root) = crate::parser::parse_expr("(shuffle (range 0 20))").unwrap();
+            let result = Interpreter::new()
+                .with_seed(seed)
+                .execute(&asg, root)
+                .unwrap();
+            match result {
+                Value::Array(items) => items
+                    .into_iter()
+                    .map(|v| match v {
+                        Value::Int(n) => n,
+                        other => panic!("expected Int, got {:?}", other),
+                    })
+                    .collect::<Vec<_>>(),
+                other => panic!("expected Array, got {:?}", other),
             }
+        };
 
-            NodeType::LiteralUnit => match subject {
-                Value::Unit => Ok((true, vec![])),
-                _ => Ok((false, vec![])),
-            },
+        // Одинаковый seed — одинаковая перестановка
+        assert_eq!(shuffle(7), shuffle(7));
+        assert_ne!(shuffle(7), (0..20).collect::<Vec<_>>());
 
-            // Array pattern matching
-            NodeType::Array => match subject {
-                Value::Array(arr) => {
-                    let pattern_elements: Vec<_> = pattern_node
-                        .find_edges(EdgeType::ArrayElement)
-                        .into_iter()
-                        .map(|e| e.target_node_id)
-                        .collect();
+        // Элементы те же, меняется только порядок
+        let mut sorted = shuffle(7);
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+    }
 
-                    if pattern_elements.len() != arr.len() {
-                        return Ok((false, vec![]));
-                    }
+    #[test]
+    fn test_array_sample_seeded() {
+        let run = |source: &str, seed: u64| {
+            run_program_with(&mut Interpreter::new().with_seed(seed), source)
+        };
 
-                    let mut all_bindings = vec![];
-                    for (i, elem_id) in pattern_elements.iter().enumerate() {
-                        let elem_node = asg
-                            .find_node(*elem_id)
-                            .ok_or(ASGError::NodeNotFound(*elem_id))?
-                            .clone();
-                        let (matches, bindings) = self.match_pattern(asg, &elem_node, &arr[i])?;
-                        if !matches {
-                            return Ok((false, vec![]));
-                        }
-                        all_bindings.extend(bindings);
-                    }
-                    Ok((true, all_bindings))
-                }
-                _ => Ok((false, vec![])),
-            },
+        // Одинаковый seed — одинаковая выборка
+        let picked = run("(sample (range 0 100) 5)", 3).unwrap();
+        assert_eq!(picked, run("(sample (range 0 100) 5)", 3).unwrap());
+        let Value::Array(items) = picked else {
+            panic!("expected Array, got {:?}", picked);
+        };
+        let mut ints: Vec<i64> = items
+            .iter()
+            .map(|v| match v {
+                Value::Int(n) => *n,
+                other => panic!("expected Int, got {:?}", other),
+            })
+            .collect();
+        ints.sort();
+        ints.dedup();
+        assert_eq!(ints.len(), 5);
+        assert!(ints.iter().all(|n| (0..100).contains(n)));
+
+        assert_eq!(
+            run("(sample-one (range 0 100))", 3).unwrap(),
+            run("(sample-one (range 0 100))", 3).unwrap()
+        );
+        assert_eq!(run("(sample-one (array 9))", 3).unwrap(), Value::Int(9));
+        assert_eq!(
+            run("(sample (array 1 2) 0)", 3).unwrap(),
+            Value::Array(vec![])
+        );
+
+        // Больше, чем элементов в массиве
+        assert!(matches!(
+            run("(sample (array 1 2 3) 4)", 3),
+            Err(ASGError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            run("(sample-one (array))", 3),
+            Err(ASGError::InvalidOperation(_))
+        ));
+    }
 
-            // Default: evaluate pattern and compare
-            _ => {
-                let pattern_val = self.ensure_evaluated(asg, pattern_node.id)?;
-                Ok((self.values_equal(&pattern_val, subject), vec![]))
-            }
-        }
+    #[test]
+    fn test_array_flatten_deep() {
+        let ints = |items: &[i64]| Value::Array(items.iter().map(|&n| Value::Int(n)).collect());
+        let nested = "(array 1 (array 2 (array 3 (array 4))) \"s\")";
+
+        assert_eq!(
+            run_program(&format!("(flatten-deep {})", nested)).unwrap(),
+            Value::Array(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Int(3),
+                Value::Int(4),
+                Value::String("s".to_string()),
+            ])
+        );
+        // Ограничение глубины: один уровень оставляет вложенность глубже
+        assert_eq!(
+            run_program(&format!("(flatten-n {} 1)", nested)).unwrap(),
+            Value::Array(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Array(vec![Value::Int(3), ints(&[4])]),
+                Value::String("s".to_string()),
+            ])
+        );
+        assert_eq!(
+            run_program(&format!("(flatten-n {} 2)", nested)).unwrap(),
+            Value::Array(vec![
+                Value::Int(1),
+                Value::Int(2),
+                Value::Int(3),
+                ints(&[4]),
+                Value::String("s".to_string()),
+            ])
+        );
+        assert_eq!(
+            run_program("(flatten-n (array (array 1)) 0)").unwrap(),
+            Value::Array(vec![ints(&[1])])
+        );
+        assert!(run_program("(flatten-n (array 1) -1)").is_err());
     }
 
-    /// Проверить равенство двух значений.
-    fn values_equal(&self, a: &Value, b: &Value) -> bool {
-        match (a, b) {
-            (Value::Int(x), Value::Int(y)) => x == y,
-            (Value::Float(x), Value::Float(y)) => (x - y).abs() < f64::EPSILON,
-            (Value::Bool(x), Value::Bool(y)) => x == y,
-            (Value::String(x), Value::String(y)) => x == y,
-            (Value::Unit, Value::Unit) => true,
-            (Value::Array(x), Value::Array(y)) => {
-                x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| self.values_equal(a, b))
-            }
-            _ => false,
+    #[test]
+    fn test_array_compact() {
+        let ints = |items: &[i64]| Value::Array(items.iter().map(|&n| Value::Int(n)).collect());
+
+        assert_eq!(
+            run_program("(compact (array 1 () 2 ()))").unwrap(),
+            ints(&[1, 2])
+        );
+        // Пустые массивы и false — не Unit, остаются
+        assert_eq!(
+            run_program("(compact (array (array) false ()))").unwrap(),
+            Value::Array(vec![Value::Array(vec![]), Value::Bool(false)])
+        );
+
+        let with_error = |source: &str| {
+            let mut interp = Interpreter::new();
+            interp.set_variable(
+                "xs",
+                Value::Array(vec![
+                    Value::Int(1),
+                    Value::Unit,
+                    Value::Error("bad".to_string()),
+                ]),
+            );
+            let (asg, root) = crate::parser::parse_expr(source).unwrap();
+            interp.execute(&asg, root).unwrap()
+        };
+        assert_eq!(
+            with_error("(compact xs)"),
+            Value::Array(vec![Value::Int(1), Value::Error("bad".to_string())])
+        );
+        assert_eq!(with_error("(compact xs true)"), ints(&[1]));
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            run_program(r#"(render "Hi {name}, you have {count}" (dict "name" "Ann" "count" 3))"#)
+                .unwrap(),
+            Value::String("Hi Ann, you have 3".to_string())
+        );
+        assert_eq!(
+            run_program(r#"(render "{{x}} = {x}}}" (dict "x" (array 1 2)))"#).unwrap(),
+            Value::String("{x} = [1, 2]}".to_string())
+        );
+
+        // Отсутствующий ключ: ошибка по умолчанию или подстановка
+        assert!(matches!(
+            run_program(r#"(render "Hi {name}" (dict))"#),
+            Err(ASGError::InvalidOperation(msg)) if msg.contains("name")
+        ));
+        assert_eq!(
+            run_program(r#"(render "Hi {name}!" (dict) "")"#).unwrap(),
+            Value::String("Hi !".to_string())
+        );
+        assert!(run_program(r#"(render "Hi {name" (dict "name" 1))"#).is_err());
+    }
+
+    #[test]
+    fn test_sort_desc_and_nth_largest() {
+        use crate::parser::parse_expr;
+
+        let (asg, root) = parse_expr("(sort-desc (array 3 9 1 7))").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.execute(&asg, root).unwrap(),
+            Value::Array(vec![
+                Value::Int(9),
+                Value::Int(7),
+                Value::Int(3),
+                Value::Int(1)
+            ])
+        );
+
+        // Дубликаты учитываются: 2-й по величине из [9, 9, 7, ...] — это 9
+        let (asg, root) = parse_expr("(nth-largest (array 3 9 7 9 1) 2)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.execute(&asg, root).unwrap(), Value::Int(9));
+
+        let (asg, root) = parse_expr("(nth-largest (array 3 9 7 9 1) 3)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.execute(&asg, root).unwrap(), Value::Int(7));
+
+        let (asg, root) = parse_expr("(nth-largest (array 1 2) 3)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.execute(&asg, root).is_err());
+    }
+
+    #[test]
+    fn test_ordering_rejects_incomparable_elements() {
+        for source in [
+            "(nth-largest (array 3 \"a\" 1) 1)",
+            "(sort (array 1 \"b\"))",
+            "(sort-desc (array \"a\" 2.5))",
+            "(sort (array true false))",
+            "(sort (array (array 1 \"a\") (array 1 2)))",
+            "(sort (array (array 1) 0))",
+        ] {
+            assert!(
+                matches!(run_program(source), Err(ASGError::TypeError(_))),
+                "{}",
+                source
+            );
         }
+
+        // Однородные массивы и смесь Int с Float по-прежнему упорядочиваются
+        assert_eq!(
+            run_program("(sort (array 2 1.5 1))").unwrap(),
+            Value::Array(vec![Value::Int(1), Value::Float(1.5), Value::Int(2)])
+        );
+        assert_eq!(
+            run_program("(sort (array \"b\" \"a\"))").unwrap(),
+            Value::Array(vec![
+                Value::String("a".to_string()),
+                Value::String("b".to_string())
+            ])
+        );
+        assert_eq!(run_program("(sort (array))").unwrap(), Value::Array(vec![]));
+
+        // Массивы — лексикографически (так сортирует пары sort-by из stdlib)
+        assert_eq!(
+            run_program("(sort (array (array 2 \"b\") (array 1 \"z\") (array 2 \"a\") (array 1)))")
+                .unwrap(),
+            run_program("(array (array 1) (array 1 \"z\") (array 2 \"a\") (array 2 \"b\"))")
+                .unwrap()
+        );
     }
 
-    /// Получить два операнда для бинарной операции.
-    fn get_binary_operands(&mut self, asg: &ASG, node: &Node) -> ASGResult<(Value, Value)> {
-        let edges: Vec<_> = node
-            .edges
+    #[test]
+    fn test_nth_largest_orders_floats_totally() {
+        // Int и Float сравниваются как числа
+        assert_eq!(
+            run_program("(nth-largest (array 1 2.5 3 0.5) 2)").unwrap(),
+            Value::Float(2.5)
+        );
+
+        // С NaN выборка согласована с sort-desc для каждого k
+        let bits = |v: &Value| match v {
+            Value::Float(f) => f.to_bits(),
+            other => panic!("expected Float, got {:?}", other),
+        };
+        let xs = "(array 1.5 (sqrt -1) 3.0 2.0 -4.0)";
+        let Value::Array(sorted) = run_program(&format!("(sort-desc {})", xs)).unwrap() else {
+            panic!("expected Array");
+        };
+        for (k, expected) in sorted.iter().enumerate() {
+            let nth = run_program(&format!("(nth-largest {} {})", xs, k + 1)).unwrap();
+            assert_eq!(bits(&nth), bits(expected));
+        }
+        let finite: Vec<f64> = sorted
             .iter()
-            .filter(|e| {
-                e.edge_type == EdgeType::ApplicationArgument
-                    || e.edge_type == EdgeType::FirstOperand
-                    || e.edge_type == EdgeType::SecondOperand
-            })
-            .map(|e| e.target_node_id)
+            .map(|v| f64::from_bits(bits(v)))
+            .filter(|f| !f.is_nan())
             .collect();
+        assert_eq!(finite, [3.0, 2.0, 1.5, -4.0]);
+    }
 
-        if edges.len() < 2 {
-            return Err(ASGError::MissingEdge(
-                node.id,
-                EdgeType::ApplicationArgument,
-            ));
-        }
+    #[test]
+    fn test_record_with_field() {
+        use crate::parser::parse_expr;
 
-        let val1 = self.ensure_evaluated(asg, edges[0])?;
-        let val2 = self.ensure_evaluated(asg, edges[1])?;
+        let src = r#"(do
+            (let p (record Point (x 1) (y 2)))
+            (let q (with-field p "x" 10))
+            (array (field p x) (field q x) (field q y)))"#;
+        let (asg, root) = parse_expr(src).unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.execute(&asg, root).unwrap(),
+            Value::Array(vec![Value::Int(1), Value::Int(10), Value::Int(2)])
+        );
 
-        Ok((val1, val2))
+        // Отсутствующее поле — ошибка
+        let (asg, root) = parse_expr("(with-field (record Point (x 1)) z 0)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.execute(&asg, root).is_err());
     }
 
-    /// Получить единственный операнд.
-    fn get_single_operand(&mut self, asg: &ASG, node: &Node) -> ASGResult<Value> {
-        let edge = node.edges.first().ok_or(ASGError::MissingEdge(
-            node.id,
-            EdgeType::ApplicationArgument,
-        ))?;
-        self.ensure_evaluated(asg, edge.target_node_id)
-    }
+    #[test]
+    fn test_defrecord_construction() {
+        use crate::parser::parse_expr;
 
-    /// Получить первый операнд (FirstOperand edge).
-    fn get_first_operand(&mut self, asg: &ASG, node: &Node) -> ASGResult<Value> {
-        let edge = node
-            .find_edge(EdgeType::FirstOperand)
-            .ok_or(ASGError::MissingEdge(node.id, EdgeType::FirstOperand))?;
-        self.ensure_evaluated(asg, edge.target_node_id)
-    }
+        let src = "(do (defrecord Point (x y)) (let p (Point 1 2)) (+ (field p x) (field p y)))";
+        let (asg, root) = parse_expr(src).unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.execute(&asg, root).unwrap(), Value::Int(3));
 
-    /// Получить второй операнд (SecondOperand edge).
-    fn get_second_operand(&mut self, asg: &ASG, node: &Node) -> ASGResult<Value> {
-        let edge = node
-            .find_edge(EdgeType::SecondOperand)
-            .ok_or(ASGError::MissingEdge(node.id, EdgeType::SecondOperand))?;
-        self.ensure_evaluated(asg, edge.target_node_id)
+        let (asg, root) = parse_expr("(do (defrecord Point (x y)) (Point 1))").unwrap();
+        let mut interpreter = Interpreter::new();
+        let err = interpreter.execute(&asg, root).unwrap_err();
+        assert!(err.to_string().contains("Record Point expects 2 fields"));
     }
 
-    /// Convert Value to JSON string.
-    fn value_to_json(&self, val: &Value) -> String {
-        match val {
-            Value::Int(n) => n.to_string(),
-            Value::Float(f) => f.to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::String(s) => format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")),
-            Value::Unit => "null".to_string(),
-            Value::Array(arr) => {
-                let items: Vec<String> = arr.iter().map(|v| self.value_to_json(v)).collect();
-                format!("[{}]", items.join(","))
-            }
-            Value::Dict(d) => {
-                let items: Vec<String> = d
-                    .iter()
-                    .map(|(k, v)| format!("\"{}\":{}", k, self.value_to_json(v)))
-                    .collect();
-                format!("{{{}}}", items.join(","))
-            }
-            Value::Record(fields) => {
-                let items: Vec<String> = fields
-                    .iter()
-                    .map(|(k, v)| format!("\"{}\":{}", k, self.value_to_json(v)))
-                    .collect();
-                format!("{{{}}}", items.join(","))
+    #[test]
+    fn test_defenum_construct_and_match() {
+        use crate::parser::parse_expr;
+
+        let area = |shape: &str| {
+            let src = format!(
+                "(do (defenum Shape (Circle r) (Rect w h) (Empty))
+                     (match {}
+                       (Circle r) (* 3 (* r r))
+                       (Rect w h) (* w h)
+                       Empty 0))",
+                shape
+            );
+            let (asg, root) = parse_expr(&src).unwrap();
+            Interpreter::new().execute(&asg, root).unwrap()
+        };
+
+        assert_eq!(area("(Circle 2)"), Value::Int(12));
+        assert_eq!(area("(Rect 3 5)"), Value::Int(15));
+        assert_eq!(area("Empty"), Value::Int(0));
+
+        let (asg, root) =
+            parse_expr("(do (defenum Shape (Circle r) (Rect w h)) (Rect 3 4))").unwrap();
+        assert_eq!(
+            Interpreter::new().execute(&asg, root).unwrap(),
+            Value::Variant {
+                enum_name: "Shape".to_string(),
+                tag: "Rect".to_string(),
+                fields: vec![Value::Int(3), Value::Int(4)],
             }
-            Value::Error(msg) => format!("{{\"error\":\"{}\"}}", msg),
-            _ => "null".to_string(),
-        }
+        );
     }
 
-    /// Convert JSON value to ASG Value.
-    fn json_to_value(&self, json: serde_json::Value) -> Value {
-        match json {
-            serde_json::Value::Null => Value::Unit,
-            serde_json::Value::Bool(b) => Value::Bool(b),
-            serde_json::Value::Number(n) => {
-                if let Some(i) = n.as_i64() {
-                    Value::Int(i)
-                } else if let Some(f) = n.as_f64() {
-                    Value::Float(f)
-                } else {
-                    Value::Unit
-                }
-            }
-            serde_json::Value::String(s) => Value::String(s),
-            serde_json::Value::Array(arr) => {
-                Value::Array(arr.into_iter().map(|v| self.json_to_value(v)).collect())
-            }
-            serde_json::Value::Object(map) => {
-                let mut dict = HashMap::new();
-                for (k, v) in map {
-                    dict.insert(k, self.json_to_value(v));
-                }
-                Value::Dict(dict)
-            }
+    #[test]
+    fn test_option_result_builtins() {
+        use crate::parser::parse_expr;
+
+        let cases = [
+            ("(unwrap (some 5) 0)", Value::Int(5)),
+            ("(unwrap (none) 0)", Value::Int(0)),
+            ("(is-some (some 1))", Value::Bool(true)),
+            ("(is-some (none))", Value::Bool(false)),
+            ("(is-ok (ok 1))", Value::Bool(true)),
+            ("(is-ok (err \"boom\"))", Value::Bool(false)),
+            ("(unwrap (ok 7) 0)", Value::Int(7)),
+            ("(unwrap (err \"boom\") 0)", Value::Int(0)),
+        ];
+        for (src, expected) in cases {
+            let (asg, root) = parse_expr(src).unwrap();
+            let mut interpreter = Interpreter::new();
+            assert_eq!(
+                interpreter.execute(&asg, root).unwrap(),
+                expected,
+                "{}",
+                src
+            );
         }
     }
 
-    /// Вызвать функцию (Function или ComposedFunction) с одним аргументом.
-    fn call_function_value(&mut self, asg: &ASG, fn_val: Value, arg: Value) -> ASGResult<Value> {
-        match fn_val {
+    #[test]
+    fn test_runtime_type_names_cover_every_value() {
+        let (asg, roots) =
+            crate::parser::parse("(fn g () (yield 1)) (g)\n(lazy-range 0 3)").unwrap();
+        let mut interp = Interpreter::new();
+        let mut computed = Vec::new();
+        for root in roots {
+            computed.push(interp.execute(&asg, root).unwrap());
+        }
+
+        let mut samples = vec![
+            Value::Int(1),
+            Value::Float(1.0),
+            Value::Bool(true),
+            Value::String(String::new()),
+            Value::Char('a'),
+            Value::Unit,
+            Value::Tensor(DifferentiableTensor::new(
+                ndarray::arr0(1.0).into_dyn(),
+                false,
+            )),
             Value::Function {
-                params,
-                body_id,
-                captured,
-            } => {
-                let saved_memo = std::mem::take(&mut self.memo);
-                let mut frame = CallFrame::default();
-                for (name, val) in &captured {
-                    frame.locals.insert(name.clone(), val.clone());
-                }
-                if !params.is_empty() {
-                    frame.locals.insert(params[0].clone(), arg);
-                }
-                frame.memo = saved_memo;
-                self.call_stack.push(frame);
+                params: vec![],
+                body_id: 0,
+                captured: HashMap::new(),
+            },
+            Value::Record(HashMap::new()),
+            Value::Array(vec![]),
+            Value::Error(String::new()),
+            Value::Dict(HashMap::new()),
+            Value::ComposedFunction(vec![]),
+            Value::Builtin("add"),
+            Value::Bytes(vec![]),
+            Value::Variant {
+                enum_name: "E".to_string(),
+                tag: "A".to_string(),
+                fields: vec![],
+            },
+            Value::Set(BTreeSet::new()),
+            Value::Option(None),
+            Value::Cell(CellRef(Rc::new(RefCell::new(Value::Int(1))))),
+        ];
+        #[cfg(feature = "bigint")]
+        samples.push(Value::BigInt(BigInt::from(1)));
+        #[cfg(feature = "decimal")]
+        samples.push(Value::Decimal(Decimal::ONE));
+        samples.extend(computed.into_iter().skip(1));
+
+        // Новый вариант Value ломает этот match: добавьте его пример в samples
+        for value in &samples {
+            match value {
+                Value::Int(_)
+                | Value::Float(_)
+                | Value::Bool(_)
+                | Value::String(_)
+                | Value::Char(_)
+                | Value::Unit
+                | Value::Tensor(_)
+                | Value::Function { .. }
+                | Value::Record(_)
+                | Value::Array(_)
+                | Value::Error(_)
+                | Value::Dict(_)
+                | Value::ComposedFunction(_)
+                | Value::Builtin(_)
+                | Value::LazySeq(_)
+                | Value::Bytes(_)
+                | Value::Variant { .. }
+                | Value::Generator(_)
+                | Value::Set(_)
+                | Value::Option(_)
+                | Value::Cell(_) => {}
+                #[cfg(feature = "bigint")]
+                Value::BigInt(_) => {}
+                #[cfg(feature = "decimal")]
+                Value::Decimal(_) => {}
+            }
+            assert!(
+                RUNTIME_TYPE_NAMES.contains(&value.type_name()),
+                "{} is missing from RUNTIME_TYPE_NAMES",
+                value.type_name()
+            );
+        }
+        assert!(samples.iter().any(|v| v.type_name() == "Generator"));
+        assert!(samples.iter().any(|v| v.type_name() == "LazySeq"));
+    }
 
-                let result = self.ensure_evaluated(asg, body_id)?;
+    #[test]
+    fn test_option_value_type() {
+        use crate::parser::parse_expr;
 
-                if let Some(popped_frame) = self.call_stack.pop() {
-                    self.memo = popped_frame.memo;
-                }
-                Ok(result)
-            }
-            Value::ComposedFunction(fns) => {
-                // Применяем все функции последовательно
-                let mut current = arg;
-                for f in fns {
-                    current = self.call_function_value(asg, f, current)?;
-                }
-                Ok(current)
-            }
-            _ => Err(ASGError::TypeError("Expected function".to_string())),
-        }
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+        let some = |v: Value| Value::Option(Some(Box::new(v)));
+
+        assert_eq!(eval("(some 5)").unwrap(), some(Value::Int(5)));
+        assert_eq!(eval("none").unwrap(), Value::Option(None));
+        assert_eq!(eval("(some 5)").unwrap().format_display(), "(some 5)");
+        assert_eq!(eval("none").unwrap().format_display(), "none");
+
+        assert_eq!(eval("(unwrap (some 5))").unwrap(), Value::Int(5));
+        assert!(matches!(
+            eval("(unwrap none)"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+        assert_eq!(eval("(unwrap-or none 7)").unwrap(), Value::Int(7));
+        assert_eq!(eval("(unwrap-or (some 1) 7)").unwrap(), Value::Int(1));
+        assert_eq!(eval("(is-some none)").unwrap(), Value::Bool(false));
+
+        assert_eq!(
+            eval("(map-option (some 5) (lambda (x) (* x 2)))").unwrap(),
+            some(Value::Int(10))
+        );
+        assert_eq!(
+            eval("(map-option none (lambda (x) (* x 2)))").unwrap(),
+            Value::Option(None)
+        );
+        assert!(matches!(
+            eval("(map-option 5 (lambda (x) x))"),
+            Err(ASGError::TypeError(_))
+        ));
+        assert_eq!(eval("(== (some 1) (some 1))").unwrap(), Value::Bool(true));
+        assert_eq!(eval("(== (some 1) none)").unwrap(), Value::Bool(false));
+
+        // (some x) связывает x; none совпадает только с none, но не с Unit
+        let describe = "(match {} (some x) (+ x 1) none 0 _ -1)";
+        assert_eq!(
+            eval(&describe.replace("{}", "(some 41)")).unwrap(),
+            Value::Int(42)
+        );
+        assert_eq!(
+            eval(&describe.replace("{}", "none")).unwrap(),
+            Value::Int(0)
+        );
+        assert_eq!(eval(&describe.replace("{}", "()")).unwrap(), Value::Int(-1));
+        assert_eq!(
+            eval("(match (some (some 2)) (some (some y)) y _ 0)").unwrap(),
+            Value::Int(2)
+        );
     }
 
-    /// Материализовать n элементов из lazy sequence.
-    fn take_from_lazy(
-        &mut self,
-        asg: &ASG,
-        mut kind: LazySeqKind,
-        n: usize,
-    ) -> ASGResult<Vec<Value>> {
-        let mut result = Vec::with_capacity(n);
+    #[test]
+    fn test_builtin_operators_as_values() {
+        use crate::parser::parse_expr;
 
-        for _ in 0..n {
-            match self.next_lazy_element(asg, &mut kind)? {
-                Some(val) => result.push(val),
-                None => break,
-            }
-        }
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
 
-        Ok(result)
+        assert_eq!(eval("(reduce (array 1 2 3) 0 add)").unwrap(), Value::Int(6));
+        assert_eq!(
+            eval("(map (array 1 2) neg)").unwrap(),
+            Value::Array(vec![Value::Int(-1), Value::Int(-2)])
+        );
+        assert_eq!(
+            eval("(filter (array true false) not)").unwrap(),
+            Value::Array(vec![Value::Bool(false)])
+        );
+        assert_eq!(eval("(lt 1 2)").unwrap(), Value::Bool(true));
+        assert_eq!(
+            eval("(reduce (array \"a\" \"b\") \"\" str-concat)").unwrap(),
+            Value::String("ab".to_string())
+        );
+        assert_eq!(eval("add").unwrap().format_display(), "<builtin add>");
+        assert!(matches!(
+            eval("(add 1)"),
+            Err(ASGError::TypeError(msg)) if msg == "add expects 2 arguments, got 1"
+        ));
+        // Пользовательское определение перекрывает встроенное имя
+        assert_eq!(
+            eval("(do (fn add (a b) (* a b)) (reduce (array 2 3) 1 add))").unwrap(),
+            Value::Int(6)
+        );
+        assert_eq!(eval("(do (let add 5) add)").unwrap(), Value::Int(5));
     }
 
-    /// Получить следующий элемент из lazy sequence.
-    fn next_lazy_element(&mut self, asg: &ASG, kind: &mut LazySeqKind) -> ASGResult<Option<Value>> {
-        match kind {
-            LazySeqKind::Iterate { func, current } => {
-                let val = (**current).clone();
-                let next = self.call_function_value(asg, (**func).clone(), val.clone())?;
-                *current = Box::new(next);
-                Ok(Some(val))
-            }
-            LazySeqKind::Repeat(val) => Ok(Some((**val).clone())),
-            LazySeqKind::Cycle { arr, index } => {
-                if arr.is_empty() {
-                    return Ok(None);
-                }
-                let val = arr[*index].clone();
-                *index = (*index + 1) % arr.len();
-                Ok(Some(val))
-            }
-            LazySeqKind::Range { current, end, step } => {
-                if (*step > 0 && *current >= *end) || (*step < 0 && *current <= *end) || *step == 0
-                {
-                    return Ok(None);
-                }
-                let val = Value::Int(*current);
-                *current += *step;
-                Ok(Some(val))
-            }
-            LazySeqKind::Map { func, source } => match self.next_lazy_element(asg, source)? {
-                Some(val) => {
-                    let mapped = self.call_function_value(asg, (**func).clone(), val)?;
-                    Ok(Some(mapped))
-                }
-                None => Ok(None),
-            },
-            LazySeqKind::Filter { func, source } => {
-                // Keep getting elements until we find one that passes the filter
-                loop {
-                    match self.next_lazy_element(asg, source)? {
-                        Some(val) => {
-                            let result =
-                                self.call_function_value(asg, (**func).clone(), val.clone())?;
-                            match result {
-                                Value::Bool(true) => return Ok(Some(val)),
-                                Value::Bool(false) => continue,
-                                _ => return Ok(Some(val)), // Non-bool treated as true
-                            }
-                        }
-                        None => return Ok(None),
-                    }
-                }
-            }
+    #[test]
+    fn test_binary_search() {
+        use crate::parser::parse_expr;
+
+        let cases = [
+            // Найден
+            ("(binary-search (array 1 3 5 7 9) 7)", Value::Int(3)),
+            // Не найден: -(точка вставки) - 1
+            ("(binary-search (array 1 3 5 7 9) 4)", Value::Int(-3)),
+            // Границы
+            ("(binary-search (array 1 3 5 7 9) 1)", Value::Int(0)),
+            ("(binary-search (array 1 3 5 7 9) 9)", Value::Int(4)),
+            ("(binary-search (array 1 3 5 7 9) 0)", Value::Int(-1)),
+            ("(binary-search (array 1 3 5 7 9) 10)", Value::Int(-6)),
+            ("(binary-search (array) 1)", Value::Int(-1)),
+        ];
+        for (src, expected) in cases {
+            let (asg, root) = parse_expr(src).unwrap();
+            let mut interpreter = Interpreter::new();
+            assert_eq!(
+                interpreter.execute(&asg, root).unwrap(),
+                expected,
+                "{}",
+                src
+            );
         }
+
+        let (asg, root) = parse_expr("(binary-search (array 1 \"a\" 3) 3)").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.execute(&asg, root).is_err());
     }
 
-    // === REPL Helper Methods ===
+    #[test]
+    fn test_debug_returns_value() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
 
-    /// Получить все переменные для REPL команды :env
-    pub fn get_variables(&self) -> &HashMap<String, Value> {
-        &self.variables
+        let stderr = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&stderr);
+        let mut interpreter = Interpreter::new();
+        interpreter.set_debug_output(move |line| sink.borrow_mut().push(line.to_string()));
+        let source = "(let a (+ 1 (debug \"x\" 2)))\n(array a (debug 7 (array 1 2)))";
+        assert_eq!(
+            run_program_with(&mut interpreter, source).unwrap(),
+            Value::Array(vec![
+                Value::Int(3),
+                Value::Array(vec![Value::Int(1), Value::Int(2)])
+            ])
+        );
+        assert_eq!(*stderr.borrow(), vec!["x = 2", "7 = [1, 2]"]);
+
+        assert_eq!(
+            debug_line(&Value::String("x".to_string()), &Value::Int(2)),
+            "x = 2"
+        );
     }
 
-    /// Получить все функции для REPL команды :funcs
-    /// Возвращает HashMap с именем функции и её Value представлением
-    pub fn get_functions(&self) -> HashMap<String, Value> {
-        self.functions
-            .iter()
-            .map(|(name, (params, body_id, _))| {
-                (
-                    name.clone(),
-                    Value::Function {
-                        params: params.clone(),
-                        body_id: *body_id,
-                        captured: HashMap::new(),
-                    },
-                )
-            })
-            .collect()
+    #[test]
+    fn test_str_split_limit_and_empty_delimiter() {
+        use crate::parser::parse_expr;
+
+        let strings = |items: &[&str]| {
+            Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect())
+        };
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+
+        assert_eq!(
+            eval("(str-split \"a,b,c,d\" \",\" 2)").unwrap(),
+            strings(&["a", "b", "c,d"])
+        );
+        assert_eq!(
+            eval("(str-split \"a,b\" \",\" 5)").unwrap(),
+            strings(&["a", "b"])
+        );
+        assert_eq!(
+            eval("(str-split \"a,b\" \",\" 0)").unwrap(),
+            strings(&["a,b"])
+        );
+        assert!(eval("(str-split \"a,b\" \",\" -1)").is_err());
+
+        // Пустой разделитель — по символам
+        assert_eq!(
+            eval("(str-split \"abc\" \"\")").unwrap(),
+            strings(&["a", "b", "c"])
+        );
+        assert_eq!(
+            eval("(str-split \"abcd\" \"\" 2)").unwrap(),
+            strings(&["a", "b", "cd"])
+        );
+    }
+
+    #[test]
+    fn test_print_multiple_arguments() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&lines);
+        let (asg, root) =
+            crate::parser::parse_expr("(do (print \"x =\" 5) (print \"xs:\" (array 1 \"a\") 2.5))")
+                .unwrap();
+        let mut interp = Interpreter::new();
+        interp.set_output(move |line| sink.borrow_mut().push(line.to_string()));
+        interp.execute(&asg, root).unwrap();
+        assert_eq!(*lines.borrow(), vec!["x = 5", "xs: [1, \"a\"] 2.5"]);
+    }
+
+    #[test]
+    fn test_pretty_print_and_print_json() {
+        let (asg, roots) = crate::parser::parse(
+            "(let v (dict \"name\" \"ada\" \"tags\" (array 1 (array) (dict \"k\" true))))
+             (pp v)
+             (pp 42)
+             (print-json (dict \"xs\" (array 1 2) \"s\" \"q\\\"\"))",
+        )
+        .unwrap();
+        let mut interp = Interpreter::new();
+        let mut text = String::new();
+        for root in roots {
+            text.push_str(&interp.run_capturing(&asg, root).unwrap().1);
+        }
+        let (pretty, json) = text.split_at(text.find("{\"").unwrap());
+
+        assert_eq!(
+            pretty,
+            "{\n  name: \"ada\",\n  tags: [\n    1,\n    [],\n    {\n      k: true\n    }\n  ]\n}\n42\n"
+        );
+        let parsed: serde_json::Value = serde_json::from_str(json.trim_end()).unwrap();
+        assert_eq!(parsed, serde_json::json!({"xs": [1, 2], "s": "q\""}));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::asg::{Edge, Node, ASG};
+    #[test]
+    fn test_run_capturing_collects_prints() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&lines);
+        let mut interp = Interpreter::new();
+        interp.set_output(move |line| sink.borrow_mut().push(line.to_string()));
+
+        let (asg, roots) = crate::parser::parse(
+            "(do (print \"start\") (print \"n =\" 2) (* 6 7))\n(print \"after\")",
+        )
+        .unwrap();
+        let (value, text) = interp.run_capturing(&asg, roots[0]).unwrap();
+        assert_eq!(value, Value::Int(42));
+        assert_eq!(text, "start\nn = 2\n");
+
+        // Прежний приёмник восстановлен и ничего не получил во время захвата
+        assert!(lines.borrow().is_empty());
+        interp.execute(&asg, roots[1]).unwrap();
+        assert_eq!(*lines.borrow(), vec!["after"]);
+    }
 
     #[test]
-    fn test_interpret_add() {
-        let mut asg = ASG::new();
+    fn test_side_effect_order() {
+        use crate::parser::parse_expr;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let run = |src: &str| {
+            let lines = Rc::new(RefCell::new(Vec::new()));
+            let sink = Rc::clone(&lines);
+            let (asg, root) = parse_expr(src).unwrap();
+            let mut interp = Interpreter::new();
+            interp.set_output(move |line| sink.borrow_mut().push(line.to_string()));
+            let result = interp.execute(&asg, root);
+            let lines = lines.borrow().clone();
+            (result, lines)
+        };
 
-        // Создаём 5 + 8
+        // Операнды — слева направо (сложение Unit затем падает, но оба print уже выполнены)
+        let (result, lines) = run("(+ (print \"a\") (print \"b\"))");
+        assert!(result.is_err());
+        assert_eq!(lines, vec!["a", "b"]);
+
+        // Аргументы вызова — слева направо
+        let (_, lines) = run("(do (fn f (x y) 0) (f (print \"1\") (print \"2\")))");
+        assert_eq!(lines, vec!["1", "2"]);
+
+        // Блок — по порядку
+        let (_, lines) = run("(do (print \"x\") (print \"y\") (print \"z\"))");
+        assert_eq!(lines, vec!["x", "y", "z"]);
+
+        // Эффект не пропускается из-за memo: один и тот же print дважды в блоке
+        let mut asg = ASG::new();
         asg.add_node(Node::new(
             1,
-            NodeType::LiteralInt,
-            Some(5i64.to_le_bytes().to_vec()),
+            NodeType::LiteralString,
+            Some(b"twice".to_vec()),
         ));
-        asg.add_node(Node::new(
+        asg.add_node(Node::with_edges(
             2,
-            NodeType::LiteralInt,
-            Some(8i64.to_le_bytes().to_vec()),
+            NodeType::Print,
+            None,
+            vec![Edge::new(EdgeType::ApplicationArgument, 1)],
         ));
         asg.add_node(Node::with_edges(
             3,
-            NodeType::BinaryOperation,
+            NodeType::Block,
             None,
             vec![
-                Edge::new(EdgeType::ApplicationArgument, 1),
-                Edge::new(EdgeType::ApplicationArgument, 2),
+                Edge::new(EdgeType::BlockStatement, 2),
+                Edge::new(EdgeType::BlockStatement, 2),
             ],
         ));
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let sink = Rc::clone(&lines);
+        let mut interp = Interpreter::new();
+        interp.set_output(move |line| sink.borrow_mut().push(line.to_string()));
+        interp.execute(&asg, 3).unwrap();
+        assert_eq!(*lines.borrow(), vec!["twice", "twice"]);
+    }
 
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, 3).unwrap();
+    #[test]
+    fn test_random_nodes_are_not_memoized() {
+        // Оба элемента массива указывают на один и тот же узел gen-int
+        let (mut asg, root) = crate::parser::parse_expr("(array (gen-int 0 1000000 1) 0)").unwrap();
+        let array = asg.find_node_mut(root).unwrap();
+        let first = array.edges[0].target_node_id;
+        array.edges[1].target_node_id = first;
+
+        let result = Interpreter::new().with_seed(7).execute(&asg, root).unwrap();
+        let Value::Array(items) = result else {
+            panic!("expected Array, got {:?}", result);
+        };
+        assert_ne!(items[0], items[1]);
+    }
 
-        match result {
-            Value::Int(val) => assert_eq!(val, 13),
-            _ => panic!("Expected Int"),
+    #[test]
+    fn test_for_all_property() {
+        use crate::parser::parse_expr;
+
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+
+        // Истинное свойство: выборка в пределах [lo, hi]
+        assert_eq!(
+            eval("(for-all (gen-int -10 10 200) (lambda (x) (&& (>= x -10) (<= x 10))))").unwrap(),
+            Value::Bool(true)
+        );
+
+        // Ложное свойство: первый контрпример в сообщении
+        match eval("(for-all (array 1 2 7 9) (lambda (x) (< x 5)))") {
+            Err(ASGError::InvalidOperation(msg)) => {
+                assert_eq!(msg, "Assertion failed: for-all counterexample: 7")
+            }
+            other => panic!("expected counterexample, got {:?}", other),
         }
+
+        // Выборка воспроизводима при одинаковом seed
+        let (asg, root) = parse_expr("(gen-int 0 1000 5)").unwrap();
+        let a = Interpreter::new()
+            .with_seed(42)
+            .execute(&asg, root)
+            .unwrap();
+        let b = Interpreter::new()
+            .with_seed(42)
+            .execute(&asg, root)
+            .unwrap();
+        assert_eq!(a, b);
+
+        assert!(eval("(assert (> 1 2) \"one is not greater\")").is_err());
+        assert_eq!(eval("(assert (> 2 1))").unwrap(), Value::Bool(true));
     }
 
     #[test]
-    fn test_interpret_comparison() {
-        let mut asg = ASG::new();
+    fn test_assert_eq_reports_difference() {
+        use crate::parser::parse_expr;
 
-        // 5 < 10
-        asg.add_node(Node::new(
-            1,
-            NodeType::LiteralInt,
-            Some(5i64.to_le_bytes().to_vec()),
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+        let message = |src: &str| match eval(src) {
+            Err(ASGError::InvalidOperation(msg)) => msg,
+            other => panic!("expected assertion failure, got {:?}", other),
+        };
+
+        assert_eq!(
+            eval("(assert-eq (array 1 (dict \"a\" 2.0)) (array 1 (dict \"a\" 2.0)))").unwrap(),
+            Value::Bool(true)
+        );
+
+        // Скаляры: только оба значения
+        assert_eq!(
+            message("(assert-eq 3 4)"),
+            "Assertion failed: expected 3, got 4"
+        );
+        // Массив: путь до различающегося элемента
+        assert_eq!(
+            message("(assert-eq (array 1 2 3) (array 1 5 3))"),
+            "Assertion failed: expected [1, 2, 3], got [1, 5, 3]\n  first difference at [1]: expected 2, got 5"
+        );
+        assert!(message("(assert-eq (array 1 2) (array 1 2 3))")
+            .ends_with("first difference: expected length 2, got 3"));
+        // Словарь: вложенный путь и отсутствующий ключ
+        assert!(
+            message("(assert-eq (dict \"xs\" (array 1 2)) (dict \"xs\" (array 1 9)))")
+                .ends_with("first difference at [\"xs\"][1]: expected 2, got 9")
+        );
+        assert!(message("(assert-eq (dict \"a\" 1 \"b\" 2) (dict \"a\" 1))")
+            .ends_with("first difference at [\"b\"]: expected 2, got nothing"));
+    }
+
+    #[test]
+    fn test_dict_update() {
+        use crate::parser::parse_expr;
+
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+
+        // Существующий ключ
+        assert_eq!(
+            eval(
+                "(dict-get (dict-update (dict \"hits\" 1) \"hits\" (lambda (n) (+ n 1))) \"hits\")"
+            )
+            .unwrap(),
+            Value::Int(2)
+        );
+        // Отсутствующий ключ — функция применяется к default
+        assert_eq!(
+            eval("(dict-get (dict-update (dict) \"hits\" (lambda (n) (+ n 1)) 0) \"hits\")")
+                .unwrap(),
+            Value::Int(1)
+        );
+        // Без default отсутствующий ключ — ошибка
+        assert!(eval("(dict-update (dict) \"hits\" (lambda (n) (+ n 1)))").is_err());
+    }
+
+    #[test]
+    fn test_let_is_local_to_block_and_call() {
+        // let в теле функции не перекрывает глобальную после вызова
+        assert_eq!(
+            run_program("(let x 1) (fn f () (let x 99)) (f) x").unwrap(),
+            Value::Int(1)
+        );
+        // Привязка блока не переживает блок
+        assert_eq!(
+            run_program("(do (let y 5) (+ y 1))").unwrap(),
+            Value::Int(6)
+        );
+        assert!(matches!(
+            run_program("(do (let y 5) y) y"),
+            Err(ASGError::UnknownVariable(name)) if name == "y"
         ));
-        asg.add_node(Node::new(
-            2,
-            NodeType::LiteralInt,
-            Some(10i64.to_le_bytes().to_vec()),
+        // let в теле цикла не затирает глобальную; set меняет ближайшую привязку
+        assert_eq!(
+            run_program(
+                "(let i 0) (let total 10)
+                 (while (< i 3) (do (let total i) (set i (+ i 1))))
+                 (array i total)"
+            )
+            .unwrap(),
+            Value::Array(vec![Value::Int(3), Value::Int(10)])
+        );
+        assert_eq!(
+            run_program(
+                "(fn sum-to (n) (do (let acc 0) (for k (range 1 (+ n 1)) (set acc (+ acc k))) acc))
+                 (sum-to 4)"
+            )
+            .unwrap(),
+            Value::Int(10)
+        );
+    }
+
+    #[test]
+    fn test_closure_mutates_captured_variable() {
+        // Изменение из closure видно создателю
+        assert_eq!(
+            run_program(
+                "(let count 0)
+                 (let bump (lambda () (set count (+ count 1))))
+                 (bump) (bump) (bump)
+                 count"
+            )
+            .unwrap(),
+            Value::Int(3)
+        );
+        // Возвращённый счётчик и геттер делят одну ячейку; счётчики независимы
+        assert_eq!(
+            run_program(
+                "(fn make-counter ()
+                   (do (let n 0)
+                       (array (lambda () (inc! n)) (lambda () n))))
+                 (let a (make-counter))
+                 (let b (make-counter))
+                 ((index a 0)) ((index a 0)) ((index b 0))
+                 (array ((index a 1)) ((index b 1)))"
+            )
+            .unwrap(),
+            Value::Array(vec![Value::Int(2), Value::Int(1)])
+        );
+        // Closure без set по-прежнему захватывает значение
+        assert_eq!(
+            run_program("(let x 1) (let f (lambda () x)) (set x 5) (f)").unwrap(),
+            Value::Int(1)
+        );
+    }
+
+    #[test]
+    fn test_break_with_value() {
+        // Поиск: цикл возвращает найденный элемент
+        assert_eq!(
+            run_program("(for x (array 3 8 12 5 20) (if (> x 10) (break x) ()))").unwrap(),
+            Value::Int(12)
+        );
+        assert_eq!(
+            run_program(
+                "(let i 0) (while (< i 100) (do (inc! i) (if (== (* i i) 49) (break i) i)))"
+            )
+            .unwrap(),
+            Value::Int(7)
+        );
+        assert_eq!(
+            run_program("(let n 0) (loop (do (inc! n) (if (>= n 5) (break (* n 10)) ())))")
+                .unwrap(),
+            Value::Int(50)
+        );
+        // Ленивый range прерывается без обхода до конца
+        assert_eq!(
+            run_program("(for x (range 0 100000000) (if (== x 3) (break \"found\") ()))").unwrap(),
+            Value::String("found".to_string())
+        );
+
+        // Простой break: результат последней завершённой итерации
+        assert_eq!(
+            run_program("(for x (array 1 2 3 4) (if (== x 3) (break) (* x 100)))").unwrap(),
+            Value::Int(200)
+        );
+        assert_eq!(
+            run_program("(let n 0) (loop (do (inc! n) (if (> n 2) (break) ())))").unwrap(),
+            Value::Unit
+        );
+
+        // break не пересекает границу функции и не работает вне цикла
+        assert!(matches!(
+            run_program("(fn stop () (break 1)) (for x (array 1 2) (stop))"),
+            Err(ASGError::InvalidOperation(_))
         ));
-        asg.add_node(Node::with_edges(
-            3,
-            NodeType::Lt,
-            None,
-            vec![
-                Edge::new(EdgeType::ApplicationArgument, 1),
-                Edge::new(EdgeType::ApplicationArgument, 2),
-            ],
+        assert!(matches!(
+            run_program("(break 1)"),
+            Err(ASGError::InvalidOperation(msg)) if msg == "break outside of a loop"
         ));
+    }
 
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, 3).unwrap();
+    #[test]
+    fn test_continue_skips_iteration() {
+        // Сумма нечётных: чётные пропускаются до set
+        assert_eq!(
+            run_program(
+                "(let s 0)
+                 (for x (range 1 10) (do (if (== (% x 2) 0) (continue) ()) (set s (+ s x))))
+                 s"
+            )
+            .unwrap(),
+            Value::Int(25)
+        );
+        assert_eq!(
+            run_program(
+                "(let i 0) (let s 0)
+                 (while (< i 6) (do (inc! i) (if (== i 3) (continue) ()) (set s (+ s i))))
+                 s"
+            )
+            .unwrap(),
+            Value::Int(18)
+        );
+        // Ленивая последовательность и break во вложенном if после continue
+        assert_eq!(
+            run_program(
+                "(for x (range 0 100000000)
+                   (if (< x 5) (continue) (if (== (% x 7) 0) (break x) ())))"
+            )
+            .unwrap(),
+            Value::Int(7)
+        );
+        // try не перехватывает сигналы цикла
+        assert_eq!(
+            run_program("(for x (array 1 2 3) (try (if (== x 2) (break \"stop\") x) (catch e 0)))")
+                .unwrap(),
+            Value::String("stop".to_string())
+        );
+
+        assert!(matches!(
+            run_program("(continue)"),
+            Err(ASGError::InvalidOperation(msg)) if msg == "continue outside of a loop"
+        ));
+        assert!(matches!(
+            run_program("(fn skip () (continue)) (for x (array 1 2) (skip))"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+    }
 
-        match result {
-            Value::Bool(val) => assert!(val),
-            _ => panic!("Expected Bool"),
+    #[test]
+    fn test_compound_assignment() {
+        // Счётчик цикла
+        assert_eq!(
+            run_program(
+                "(let i 0) (let n 0)
+                 (while (< i 5) (do (inc! i) (add-assign n 2)))
+                 (array i n)"
+            )
+            .unwrap(),
+            Value::Array(vec![Value::Int(5), Value::Int(10)])
+        );
+        // Сумма в for
+        assert_eq!(
+            run_program("(let sum 0) (for x (array 1 2 3 4) (add-assign sum x)) sum").unwrap(),
+            Value::Int(10)
+        );
+        // Возвращает новое значение; div-assign делит как `/`
+        assert_eq!(
+            run_program("(let x 10) (dec! x) (sub-assign x 3) (mul-assign x 4)").unwrap(),
+            Value::Int(24)
+        );
+        assert_eq!(
+            run_program("(let x 24) (div-assign x 2)").unwrap(),
+            Value::Float(12.0)
+        );
+        // Параметр меняется в своём кадре, глобальная с тем же именем — нет
+        assert_eq!(
+            run_program("(let k 100) (fn twice (k) (do (inc! k) (inc! k) k)) (array (twice 1) k)")
+                .unwrap(),
+            Value::Array(vec![Value::Int(3), Value::Int(100)])
+        );
+
+        assert!(matches!(
+            run_program("(inc! missing)"),
+            Err(ASGError::UnknownVariable(_))
+        ));
+        assert!(crate::parser::parse("(inc! x 1)").is_err());
+
+        // inc и dec без `!` остаются обычными именами: их определяет prelude
+        assert_eq!(
+            run_program("(do (fn inc (x) (+ x 1)) (inc 5))").unwrap(),
+            Value::Int(6)
+        );
+        assert_eq!(
+            run_program("(let x 3) (fn dec (n) (- n 1)) (array (dec x) x)").unwrap(),
+            Value::Array(vec![Value::Int(2), Value::Int(3)])
+        );
+    }
+
+    #[test]
+    fn test_for_over_dict_and_string() {
+        // Словарь: пары в порядке ключей, [k v] разбирает пару
+        assert_eq!(
+            run_program(
+                "(let keys \"\") (let total 0)
+                 (for [k v] (dict \"b\" 2 \"a\" 1 \"c\" 3)
+                   (do (set keys (concat keys k)) (set total (+ total v))))
+                 (array keys total)"
+            )
+            .unwrap(),
+            Value::Array(vec![Value::String("abc".to_string()), Value::Int(6)])
+        );
+        // Одна переменная получает пару целиком
+        assert_eq!(
+            run_program("(let last ()) (for e (dict \"k\" 7) (set last e)) last").unwrap(),
+            Value::Array(vec![Value::String("k".to_string()), Value::Int(7)])
+        );
+
+        // Строка: по символам Unicode
+        assert_eq!(
+            run_program("(let out \"\") (for c \"héй\" (set out (concat c out))) out").unwrap(),
+            Value::String("йéh".to_string())
+        );
+
+        assert!(matches!(
+            run_program("(for [a b] (array 1 2) a)"),
+            Err(ASGError::TypeError(_))
+        ));
+        assert!(matches!(
+            run_program("(for x 5 x)"),
+            Err(ASGError::TypeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_huge_range_is_an_error() {
+        // Без лимита коллекций массив на 4e18 элементов не выделить: ошибка, а не паника
+        assert!(matches!(
+            run_program("(length (range 0 4000000000000000000))"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+        // for по-прежнему обходит такой диапазон без массива
+        assert_eq!(
+            run_program(
+                "(let n 0) (for i (range 0 4000000000000000000) \
+                   (do (set n (+ n 1)) (if (== n 3) (break)))) n"
+            )
+            .unwrap(),
+            Value::Int(3)
+        );
+    }
+
+    #[test]
+    fn test_large_range_streams_into_consumers() {
+        // range — всегда массив, и любые операции с массивами к нему применимы
+        assert_eq!(
+            run_program("(range 0 3)").unwrap(),
+            Value::Array(vec![Value::Int(0), Value::Int(1), Value::Int(2)])
+        );
+        assert_eq!(
+            run_program("(length (range 0 20000))").unwrap(),
+            Value::Int(20000)
+        );
+        assert_eq!(
+            run_program("(length (filter (range 0 20000) (lambda (x) (< x 10))))").unwrap(),
+            Value::Int(10)
+        );
+        assert_eq!(
+            run_program("(index (range 0 20000) 12345)").unwrap(),
+            Value::Int(12345)
+        );
+        assert_eq!(
+            run_program("(first (reverse (range 0 20000)))").unwrap(),
+            Value::Int(19999)
+        );
+        assert_eq!(
+            run_program("(length (take (range 0 20000) 5))").unwrap(),
+            Value::Int(5)
+        );
+
+        // sum, product, reduce и collect обходят (range a b) без массива на 10 млн элементов
+        assert_eq!(
+            run_program("(sum (range 0 10000000))").unwrap(),
+            Value::Int(49_999_995_000_000)
+        );
+        assert_eq!(
+            run_program("(sum (range 100000 0 -2))").unwrap(),
+            Value::Int(2_500_050_000)
+        );
+        assert_eq!(
+            run_program("(reduce (range 0 1000000) 0 (lambda (acc x) (+ acc 1)))").unwrap(),
+            Value::Int(1_000_000)
+        );
+
+        // for и map принимают большой range
+        assert_eq!(
+            run_program("(let total 0) (for i (range 0 20000) (set total (+ total i))) total")
+                .unwrap(),
+            Value::Int(199_990_000)
+        );
+        assert_eq!(
+            run_program("(sum (map (range 0 20000) (lambda (x) (* x 2))))").unwrap(),
+            Value::Int(399_980_000)
+        );
+
+        // collect материализует конечную последовательность целиком
+        assert_eq!(
+            run_program("(length (collect (range 0 20000)))").unwrap(),
+            Value::Int(20000)
+        );
+        // Бесконечный источник нужно ограничить
+        assert!(matches!(
+            run_program("(sum (repeat 1))"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_expect_passes_values_and_fails_on_unit() {
+        let eval = |source: &str| {
+            let (asg, root) = crate::parser::parse_expr(source).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+
+        assert_eq!(
+            eval(r#"(expect (dict-get (dict "k" 5) "k") "no k")"#).unwrap(),
+            Value::Int(5)
+        );
+        assert_eq!(
+            eval(r#"(expect false "no flag")"#).unwrap(),
+            Value::Bool(false)
+        );
+        assert!(matches!(
+            eval(r#"(expect (dict-get (dict "k" 5) "x") "no x")"#),
+            Err(ASGError::InvalidOperation(msg)) if msg == "no x"
+        ));
+        assert!(matches!(
+            eval(r#"(expect (throw "boom") "failed")"#),
+            Err(ASGError::InvalidOperation(msg)) if msg == "failed"
+        ));
+        // Ошибку expect перехватывает try
+        assert_eq!(
+            eval(r#"(try (expect (none) "empty") (catch e (error-message e)))"#).unwrap(),
+            Value::String("Invalid operation: empty".to_string())
+        );
+    }
+
+    #[test]
+    fn test_char_operations() {
+        let eval = |source: &str| {
+            let (asg, root) = crate::parser::parse_expr(source).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+
+        // Индекс — по скалярам Unicode, а не по байтам
+        assert_eq!(eval(r#"(char-at "héllo" 2)"#).unwrap(), Value::Char('l'));
+        assert_eq!(
+            eval(r#"(char-at "héllo" 1)"#).unwrap().format_display(),
+            "'é'"
+        );
+        assert_eq!(
+            eval("(char->int (int->char 955))").unwrap(),
+            Value::Int(955)
+        );
+        assert_eq!(
+            eval(r#"(string->chars "aé")"#).unwrap(),
+            Value::Array(vec![Value::Char('a'), Value::Char('é')])
+        );
+        assert_eq!(
+            eval(r#"(== (char-at "ab" 0) (int->char 97))"#).unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval(r#"(!= (char-at "ab" 0) (char-at "ab" 1))"#).unwrap(),
+            Value::Bool(true)
+        );
+
+        assert!(matches!(
+            eval(r#"(char-at "héllo" 5)"#),
+            Err(ASGError::InvalidOperation(msg)) if msg.contains("out of bounds")
+        ));
+        assert!(matches!(
+            eval(r#"(char-at "abc" -1)"#),
+            Err(ASGError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            eval("(int->char 55296)"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let eval = |source: &str| {
+            let (asg, root) = crate::parser::parse_expr(source).unwrap();
+            Interpreter::new().execute(&asg, root)
+        };
+        let show = |source: &str| eval(source).unwrap().format_display();
+
+        // Повторы схлопываются, порядок — по возрастанию
+        assert_eq!(show("(set 3 1 2 1)"), "#{1, 2, 3}");
+        assert_eq!(show("(set)"), "#{}");
+        assert_eq!(show(r#"(set "b" 2 true "a")"#), r#"#{true, 2, "a", "b"}"#);
+        assert_eq!(eval("(set-size (set 3 1 2 1))").unwrap(), Value::Int(3));
+
+        assert_eq!(show("(set-add (set 1 2) 0)"), "#{0, 1, 2}");
+        assert_eq!(show("(set-union (set 1 2) (set 2 3))"), "#{1, 2, 3}");
+        assert_eq!(show("(set-intersect (set 1 2 3) (set 2 3 4))"), "#{2, 3}");
+        assert_eq!(show("(set-diff (set 1 2 3) (set 2))"), "#{1, 3}");
+        assert_eq!(
+            eval("(set-contains (set 1 2) 2)").unwrap(),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval("(set-contains (set 1 2) 1.5)").unwrap(),
+            Value::Bool(false)
+        );
+
+        // Обход: множество → массив → множество даёт то же множество
+        assert_eq!(eval("(for x (set 2 1) x)").unwrap(), Value::Int(2));
+        assert_eq!(
+            eval(
+                "(do (let s (set 5 1 3))
+                     (let xs (array))
+                     (for x s (set xs (append xs x)))
+                     (array xs (assert-eq s (reduce xs (set) (lambda (acc x) (set-add acc x))))))"
+            )
+            .unwrap(),
+            Value::Array(vec![
+                Value::Array(vec![Value::Int(1), Value::Int(3), Value::Int(5)]),
+                Value::Bool(true)
+            ])
+        );
+
+        // Элементом может быть только Int, String или Bool
+        for source in [
+            "(set (lambda (x) x))",
+            "(set 1.5)",
+            "(set-add (set) (array 1))",
+        ] {
+            assert!(
+                matches!(eval(source), Err(ASGError::TypeError(_))),
+                "{}",
+                source
+            );
         }
+        assert!(matches!(
+            eval("(set-size (array 1))"),
+            Err(ASGError::TypeError(_))
+        ));
     }
 
     #[test]
-    fn test_json_encode() {
+    fn test_for_over_range_skips_array() {
+        let sum_with = |iterable: &str| {
+            run_program(&format!(
+                "(let s 0) (for i {} (set s (+ s (* i 3)))) s",
+                iterable
+            ))
+            .unwrap()
+        };
+
+        // Миллион итераций по границам и по материализованному массиву
+        let fused = run_program("(for i (range 0 1000000) i)").unwrap();
+        assert_eq!(
+            fused,
+            run_program("(for i (collect (range 0 1000000)) i)").unwrap()
+        );
+        assert_eq!(fused, Value::Int(999_999));
+
+        // Шаг, обратный и пустой диапазоны совпадают с массивом
+        for range in ["(range 3 40 7)", "(range 10 0 -3)", "(range 5 5)"] {
+            let array = format!("(let xs {}) xs", range);
+            let materialized = match run_program(&array).unwrap() {
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.format_display())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                other => panic!("expected array, got {:?}", other),
+            };
+            assert_eq!(
+                sum_with(range),
+                sum_with(&format!("(array {})", materialized))
+            );
+        }
+        // Значение for — результат последней итерации
+        assert_eq!(
+            run_program("(for i (range 0 4) (* i i))").unwrap(),
+            Value::Int(9)
+        );
+    }
+
+    #[test]
+    fn test_reduce_over_lazy_sequence() {
+        // Больше лимита collect (10000): сворачивается без материализации
+        assert_eq!(
+            run_program("(reduce (lazy-range 0 100000) 0 (lambda (acc x) (+ acc x)))").unwrap(),
+            Value::Int(4_999_950_000)
+        );
+        assert_eq!(
+            run_program("(reduce (lazy-map (lambda (x) (* x x)) (lazy-range 1 4)) 0 (lambda (acc x) (+ acc x)))")
+                .unwrap(),
+            Value::Int(14)
+        );
+        assert_eq!(
+            run_program("(product (lazy-range 1 6))").unwrap(),
+            Value::Int(120)
+        );
+        assert_eq!(
+            run_program("(sum (lazy-filter (lambda (x) (== (% x 2) 0)) (lazy-range 0 100000)))")
+                .unwrap(),
+            Value::Int(2_499_950_000)
+        );
+
+        // Бесконечные источники требуют ограничения через take-lazy
+        assert!(matches!(
+            run_program("(reduce (iterate (lambda (x) (+ x 1)) 0) 0 (lambda (acc x) (+ acc x)))"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            run_program("(product (cycle (array 1 2)))"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_dict_spread_and_dict_with() {
+        let dict = |pairs: &[(&str, i64)]| {
+            Value::Dict(
+                pairs
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), Value::Int(*v)))
+                    .collect(),
+            )
+        };
+        let base = "(let base (dict \"a\" 1 \"b\" 2))\n";
+
+        // Ключи base сохраняются, повторные перекрываются более поздними
+        assert_eq!(
+            run_program(&format!("{}(dict ...base \"b\" 20 \"c\" 3)", base)).unwrap(),
+            dict(&[("a", 1), ("b", 20), ("c", 3)])
+        );
+        assert_eq!(
+            run_program(&format!("{}(dict-with base \"b\" 20 \"c\" 3)", base)).unwrap(),
+            dict(&[("a", 1), ("b", 20), ("c", 3)])
+        );
+        // Распаковка после ключа перекрывает его
+        assert_eq!(
+            run_program(&format!("{}(dict \"a\" 0 ...base)", base)).unwrap(),
+            dict(&[("a", 1), ("b", 2)])
+        );
+        // base не меняется
+        assert_eq!(
+            run_program(&format!("{}(dict-with base \"a\" 5) base", base)).unwrap(),
+            dict(&[("a", 1), ("b", 2)])
+        );
+
+        assert!(matches!(
+            run_program("(let n 1) (dict ...n)"),
+            Err(ASGError::TypeError(_))
+        ));
+        assert!(crate::parser::parse("(dict ...base \"a\")").is_err());
+    }
+
+    #[test]
+    fn test_call_method_passes_self() {
+        // Замыкание в словаре: видит захваченный offset и объект через self
+        let src = "(let offset 100)
+             (let obj (dict \"base\" 5 \"add\" (lambda (self x) (+ (+ (dict-get self \"base\") x) offset))))
+             (call-method obj \"add\" 10)";
+        assert_eq!(run_program(src).unwrap(), Value::Int(115));
+
+        // Метод без аргументов кроме self
+        let src =
+            "(let obj (dict \"name\" \"asg\" \"get\" (lambda (self) (dict-get self \"name\"))))
+             (call-method obj \"get\")";
+        assert_eq!(run_program(src).unwrap(), Value::String("asg".to_string()));
+
+        // Отсутствующий метод и поле-не-функция — ошибки
+        assert!(run_program("(call-method (dict) \"missing\")").is_err());
+        assert!(run_program("(call-method (dict \"x\" 1) \"x\")").is_err());
+    }
+
+    #[test]
+    fn test_set_place_mutates_in_place() {
+        let run = |src: &str| -> ASGResult<Interpreter> {
+            let mut interp = Interpreter::new();
+            run_program_with(&mut interp, src)?;
+            Ok(interp)
+        };
+
+        // Элемент словаря: существующий и новый ключ
+        let interp = run("(let d (dict \"a\" 1))
+             (set (dict-get d \"a\") 10)
+             (set (dict-get d \"b\") 20)")
+        .unwrap();
+        let Some(Value::Dict(d)) = interp.get_variables().get("d") else {
+            panic!("d must stay a dict");
+        };
+        assert_eq!(d.get("a"), Some(&Value::Int(10)));
+        assert_eq!(d.get("b"), Some(&Value::Int(20)));
+
+        // Вложенный элемент массива
+        let interp = run("(let m (array (array 1 2) (array 3 4)))
+             (set (index (index m 1) 0) 9)")
+        .unwrap();
+        assert_eq!(
+            interp.get_variables().get("m"),
+            Some(&Value::Array(vec![
+                Value::Array(vec![Value::Int(1), Value::Int(2)]),
+                Value::Array(vec![Value::Int(9), Value::Int(4)]),
+            ]))
+        );
+
+        // Индекс за пределами массива — ошибка, а не расширение
+        assert!(run("(let a (array 1)) (set (index a 5) 0)").is_err());
+    }
+
+    #[test]
+    fn test_str_lines_and_words() {
         use crate::parser::parse_expr;
 
-        let (asg, root) = parse_expr(r#"(json-encode (dict "name" "John" "age" 30))"#).unwrap();
+        let strings = |items: &[&str]| {
+            Value::Array(items.iter().map(|s| Value::String(s.to_string())).collect())
+        };
+
+        let (asg, root) = parse_expr("(str-lines \"one\\r\\ntwo\\nthree\")").unwrap();
         let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, root).unwrap();
+        assert_eq!(
+            interpreter.execute(&asg, root).unwrap(),
+            strings(&["one", "two", "three"])
+        );
 
-        match result {
-            Value::String(s) => {
-                assert!(s.contains("\"name\":\"John\"") || s.contains("\"age\":30"));
-            }
-            _ => panic!("Expected String"),
-        }
+        let (asg, root) = parse_expr("(str-words \"  hello \\t  big\\r\\nworld  \")").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.execute(&asg, root).unwrap(),
+            strings(&["hello", "big", "world"])
+        );
     }
 
     #[test]
-    fn test_json_decode() {
+    fn test_float_tolerance() {
         use crate::parser::parse_expr;
 
-        let (asg, root) = parse_expr(r#"(json-decode "{\"x\": 42}")"#).unwrap();
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, root).unwrap();
+        let eval = |interpreter: Interpreter, src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            let mut interpreter = interpreter;
+            interpreter.execute(&asg, root).unwrap()
+        };
 
-        match result {
-            Value::Dict(d) => {
-                assert_eq!(d.get("x"), Some(&Value::Int(42)));
-            }
-            _ => panic!("Expected Dict"),
+        // 0.1 + 0.2 != 0.3 точно, но в пределах допуска по умолчанию
+        assert_eq!(
+            eval(Interpreter::new(), "(== 0.3 (+ 0.1 0.2))"),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval(Interpreter::new(), "(!= 0.3 (+ 0.1 0.2))"),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            eval(Interpreter::new(), "(== 1.0 1.001)"),
+            Value::Bool(false)
+        );
+
+        // Более широкий допуск
+        let loose = Interpreter::new().with_float_tolerance(1e-2);
+        assert_eq!(eval(loose, "(== 1.0 1.001)"), Value::Bool(true));
+
+        // Относительный допуск для больших чисел
+        assert_eq!(
+            eval(Interpreter::new(), "(== 1000000000000.0 1000000000000.5)"),
+            Value::Bool(true)
+        );
+
+        // Бесконечности и NaN не попадают в допуск
+        for (src, expected) in [
+            ("(== (/ 1.0 0.0) 1.0)", false),
+            ("(!= (/ 1.0 0.0) 5.0)", true),
+            ("(== (/ 1.0 0.0) (/ -1.0 0.0))", false),
+            ("(== (/ 1.0 0.0) (/ 1.0 0.0))", true),
+            ("(== (/ 0.0 0.0) 1.0)", false),
+            ("(== (/ 0.0 0.0) (/ 0.0 0.0))", false),
+        ] {
+            assert_eq!(
+                eval(Interpreter::new(), src),
+                Value::Bool(expected),
+                "{}",
+                src
+            );
         }
+        assert_eq!(
+            eval(
+                Interpreter::new(),
+                "(match (array (/ 1.0 0.0) 3.0) (array x x) \"same\" _ \"diff\")"
+            ),
+            Value::String("diff".to_string())
+        );
     }
 
     #[test]
-    fn test_html_generation() {
+    fn test_nan_policy() {
         use crate::parser::parse_expr;
 
-        let (asg, root) = parse_expr(r#"(div "Hello")"#).unwrap();
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, root).unwrap();
+        let eval = |policy: NanPolicy, src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            let mut interpreter = Interpreter::new().with_nan_policy(policy);
+            interpreter.execute(&asg, root).unwrap()
+        };
 
-        match result {
-            Value::String(s) => {
-                assert!(s.contains("<div>"));
-                assert!(s.contains("Hello"));
-                assert!(s.contains("</div>"));
-            }
-            _ => panic!("Expected String"),
-        }
+        // Propagate: значения IEEE 754
+        assert!(matches!(eval(NanPolicy::Propagate, "(/ 0.0 0.0)"), Value::Float(f) if f.is_nan()));
+        assert!(matches!(eval(NanPolicy::Propagate, "(sqrt -1.0)"), Value::Float(f) if f.is_nan()));
+
+        // Error: NaN/inf превращаются в Value::Error
+        assert!(matches!(
+            eval(NanPolicy::Error, "(/ 0.0 0.0)"),
+            Value::Error(_)
+        ));
+        assert!(matches!(
+            eval(NanPolicy::Error, "(sqrt -1.0)"),
+            Value::Error(_)
+        ));
+        assert!(matches!(
+            eval(NanPolicy::Error, "(/ 1.0 0.0)"),
+            Value::Error(_)
+        ));
+        assert_eq!(eval(NanPolicy::Error, "(sqrt 4.0)"), Value::Float(2.0));
     }
 
     #[test]
-    fn test_html_with_attributes() {
+    fn test_scan() {
         use crate::parser::parse_expr;
 
-        let (asg, root) = parse_expr(r#"(div "@class=container" "Content")"#).unwrap();
+        let (asg, root) = parse_expr("(scan + 0 (array 1 2 3))").unwrap();
         let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, root).unwrap();
+        assert_eq!(
+            interpreter.execute(&asg, root).unwrap(),
+            Value::Array(vec![Value::Int(1), Value::Int(3), Value::Int(6)])
+        );
 
-        match result {
-            Value::String(s) => {
-                assert!(s.contains("class=\"container\""));
-                assert!(s.contains("Content"));
-            }
-            _ => panic!("Expected String"),
-        }
+        let (asg, root) = parse_expr("(scan (lambda (acc x) (* acc x)) 1 (array 2 3 4))").unwrap();
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter.execute(&asg, root).unwrap(),
+            Value::Array(vec![Value::Int(2), Value::Int(6), Value::Int(24)])
+        );
     }
 
     #[test]
-    fn test_variadic_add() {
+    fn test_base64_hex_round_trip() {
         use crate::parser::parse_expr;
 
-        let (asg, root) = parse_expr("(+ 1 2 3 4 5)").unwrap();
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, root).unwrap();
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root).unwrap()
+        };
+        let bytes = Value::Bytes(vec![0, 1, 127, 128, 255]);
+
+        assert_eq!(
+            eval("(base64-encode (array 0 1 127 128 255))"),
+            Value::String("AAF/gP8=".to_string())
+        );
+        assert_eq!(
+            eval("(base64-decode (base64-encode (array 0 1 127 128 255)))"),
+            bytes
+        );
+        assert_eq!(
+            eval("(hex-encode (array 0 1 127 128 255))"),
+            Value::String("00017f80ff".to_string())
+        );
+        assert_eq!(
+            eval("(hex-decode (hex-encode (array 0 1 127 128 255)))"),
+            bytes
+        );
+        assert_eq!(
+            eval("(base64-encode (hex-decode \"00017f80ff\"))"),
+            Value::String("AAF/gP8=".to_string())
+        );
+
+        // Неверный ввод — Value::Error
+        assert!(matches!(eval("(hex-decode \"xyz\")"), Value::Error(_)));
+        assert!(matches!(eval("(base64-decode \"!!\")"), Value::Error(_)));
+    }
 
-        match result {
-            Value::Int(val) => assert_eq!(val, 15),
-            _ => panic!("Expected Int"),
+    #[test]
+    fn test_loop_indexed_sums_indices() {
+        use crate::parser::parse;
+
+        let (asg, roots) = parse(
+            "(let total 0)
+             (loop-indexed i (< i 5) (set total (+ total i)))
+             total",
+        )
+        .unwrap();
+        let mut interp = Interpreter::new();
+        let mut last = Value::Unit;
+        for root in roots {
+            last = interp.execute(&asg, root).unwrap();
         }
+        // 0 + 1 + 2 + 3 + 4
+        assert_eq!(last, Value::Int(10));
+        // Счётчик не остаётся в глобальной области
+        assert!(!interp.get_variables().contains_key("i"));
     }
 
     #[test]
-    fn test_variadic_mul() {
+    fn test_multiple_value_return_destructure() {
+        let result = run_program(
+            "(fn divmod (x y) (array (// x y) (% x y)))
+             (let [q r] (divmod 17 5))
+             (+ (* q 10) r)",
+        );
+        assert_eq!(result.unwrap(), Value::Int(32));
+
+        // Функция вернула меньше значений, чем ожидается
+        let result = run_program(
+            "(fn one (x) (array x))
+             (let [a b] (one 1))",
+        );
+        assert!(matches!(result, Err(ASGError::InvalidOperation(_))));
+    }
+
+    #[test]
+    fn test_circular_import_detected() {
         use crate::parser::parse_expr;
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.asg");
+        let b = dir.path().join("b.asg");
+        fs::write(&a, format!("(import \"{}\")", b.display())).unwrap();
+        fs::write(&b, format!("(import \"{}\")", a.display())).unwrap();
+
+        let (asg, root) = parse_expr(&format!("(import \"{}\")", a.display())).unwrap();
+        let err = Interpreter::new().execute(&asg, root).unwrap_err();
+        match err {
+            ASGError::InvalidOperation(msg) => {
+                let a = fs::canonicalize(&a).unwrap();
+                let b = fs::canonicalize(&b).unwrap();
+                assert_eq!(
+                    msg,
+                    format!(
+                        "circular import: {} -> {} -> {}",
+                        a.display(),
+                        b.display(),
+                        a.display()
+                    )
+                );
+            }
+            other => panic!("expected InvalidOperation, got {:?}", other),
+        }
+    }
 
-        let (asg, root) = parse_expr("(* 2 3 4)").unwrap();
-        let mut interpreter = Interpreter::new();
-        let result = interpreter.execute(&asg, root).unwrap();
+    #[test]
+    fn test_relative_import_from_importing_file_dir() {
+        use crate::parser::parse_expr;
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        fs::create_dir(&sub).unwrap();
+        fs::write(
+            sub.join("a.asg"),
+            "(import \"./b.asg\")\n(fn quad (x) (double (double x)))",
+        )
+        .unwrap();
+        fs::write(sub.join("b.asg"), "(fn double (x) (* x 2))").unwrap();
+
+        // Путь к a.asg — относительно выполняемого файла, ./b.asg — относительно a.asg
+        let (asg, root) = parse_expr("(do (import \"sub/a.asg\") (quad 5))").unwrap();
+        let mut interp = Interpreter::new();
+        interp.set_current_file(dir.path().join("main.asg"));
+        assert_eq!(interp.execute(&asg, root).unwrap(), Value::Int(20));
+    }
 
-        match result {
-            Value::Int(val) => assert_eq!(val, 24),
-            _ => panic!("Expected Int"),
+    #[test]
+    fn test_import_installed_package() {
+        use crate::parser::parse;
+        use tempfile::tempdir;
+
+        // Раскладка как после `asg-pkg install`: .asg/packages/<name>/<version>/
+        let dir = tempdir().unwrap();
+        let package = dir.path().join(".asg/packages/math-utils/0.2.0");
+        fs::create_dir_all(package.join("src")).unwrap();
+        fs::write(
+            package.join("asg.toml"),
+            "[package]\nname = \"math-utils\"\n",
+        )
+        .unwrap();
+        fs::write(package.join("src/lib.syn"), "(fn square (x) (* x x))").unwrap();
+
+        let (asg, roots) = parse("(import \"pkg:math-utils\") (square 7)").unwrap();
+        let mut interp = Interpreter::new();
+        interp.set_current_file(dir.path().join("main.syn"));
+        let mut result = Value::Unit;
+        for root in roots {
+            result = interp.execute(&asg, root).unwrap();
         }
+        assert_eq!(result, Value::Int(49));
+
+        let (asg, root) = crate::parser::parse_expr("(import \"pkg:missing\")").unwrap();
+        let mut interp = Interpreter::new();
+        interp.set_current_file(dir.path().join("main.syn"));
+        assert!(interp.execute(&asg, root).is_err());
+    }
+
+    #[test]
+    fn test_import_only_and_as() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("math.asg"),
+            "(let PI 3)\n(fn square (x) (* x x))\n(fn cube (x) (* (square x) x))",
+        )
+        .unwrap();
+        let run = |source: &str| {
+            let mut interp = Interpreter::new();
+            interp.set_current_file(dir.path().join("main.asg"));
+            run_program_with(&mut interp, source)
+        };
+
+        // :only привязывает подмножество; cube по-прежнему видит square модуля
+        assert_eq!(
+            run("(do (import \"math.asg\" :only (cube)) (cube 2))").unwrap(),
+            Value::Int(8)
+        );
+        assert!(matches!(
+            run("(do (import \"math.asg\" :only (cube)) (square 2))"),
+            Err(ASGError::UnknownVariable(_))
+        ));
+        assert!(matches!(
+            run("(do (import \"math.asg\" :only (PI)) PI)"),
+            Ok(Value::Int(3))
+        ));
+        assert!(run("(import \"math.asg\" :only (tan))").is_err());
+
+        // :as добавляет префикс ко всем именам модуля
+        assert_eq!(
+            run("(do (import \"math.asg\" :as m) (+ (m.square 4) (m.cube 2) m.PI))").unwrap(),
+            Value::Int(27)
+        );
+        assert!(matches!(
+            run("(do (import \"math.asg\" :as m) (square 4))"),
+            Err(ASGError::UnknownVariable(_))
+        ));
+    }
+
+    #[test]
+    fn test_module_function_updates_module_state() {
+        use tempfile::tempdir;
+
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("m.asg"),
+            "(let count 0)\n(fn bump () (do (set count (+ count 1)) count))",
+        )
+        .unwrap();
+        let run = |source: &str| {
+            let mut interp = Interpreter::new();
+            interp.set_current_file(dir.path().join("main.asg"));
+            run_program_with(&mut interp, source)
+        };
+
+        assert_eq!(
+            run("(do (import \"m.asg\") (bump) (array (bump) count))").unwrap(),
+            Value::Array(vec![Value::Int(2), Value::Int(2)])
+        );
+        // Изменение видно и через имя с префиксом, и функциям модуля
+        assert_eq!(
+            run("(do (import \"m.asg\" :as m) (m.bump) (m.bump) m.count)").unwrap(),
+            Value::Int(2)
+        );
+        assert_eq!(
+            run("(do (import \"m.asg\" :as m) (set m.count 10) (m.bump))").unwrap(),
+            Value::Int(11)
+        );
+    }
+
+    #[cfg(feature = "hashing")]
+    #[test]
+    fn test_sha256_md5_vectors() {
+        use crate::parser::parse_expr;
+
+        let eval = |src: &str| {
+            let (asg, root) = parse_expr(src).unwrap();
+            Interpreter::new().execute(&asg, root).unwrap()
+        };
+
+        assert_eq!(
+            eval("(sha256 \"\")"),
+            Value::String(
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string()
+            )
+        );
+        assert_eq!(
+            eval("(sha256 \"abc\")"),
+            Value::String(
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string()
+            )
+        );
+        assert_eq!(
+            eval("(md5 \"\")"),
+            Value::String("d41d8cd98f00b204e9800998ecf8427e".to_string())
+        );
+        assert_eq!(
+            eval("(md5 \"abc\")"),
+            Value::String("900150983cd24fb0d6963f7d28e17f72".to_string())
+        );
+        // Байты и строка с одинаковым содержимым дают одинаковый хеш
+        assert_eq!(eval("(md5 (hex-decode \"616263\"))"), eval("(md5 \"abc\")"));
     }
 
     #[test]
@@ -10,6 +10,7 @@
 //! - [`interpreter`] - Интерпретатор ASG
 //! - [`type_checker`] - Проверка и вывод типов
 //! - [`types`] - Система типов ASG
+//! - [`graph_builder`] - Построение ASG из Rust-кода без парсера
 //! - [`llvm_backend`] - Компиляция в LLVM IR (требует feature `llvm_backend`)
 //!
 //! ## Features
@@ -50,8 +51,7 @@ pub mod llvm_backend;
 pub mod wasm; // WASM GC и runtime
 pub mod wasm_backend;
 
-// === GUI модуль (requires feature 'gui') ===
-#[cfg(feature = "gui")]
+// === GUI модуль (окна и egui — feature 'gui'; дерево виджетов и render_to_string — всегда) ===
 pub mod gui;
 
 // === Дополнительные модули ===
@@ -60,8 +60,10 @@ pub mod concurrency;
 pub mod concurrency_async;
 pub mod effects;
 pub mod ffi;
+pub mod graph_builder;
 pub mod macros;
 pub mod modules;
+pub mod optimize;
 
 // === Система доказательств ===
 pub mod proof;
@@ -74,5 +76,5 @@ pub use error::{ASGError, ASGResult};
 pub use interpreter::{Interpreter, Value};
 pub use nodecodes::{EdgeType, NodeType};
 pub use parser::{parse, parse_expr};
-pub use type_checker::{check_types, infer_types, TypeChecker};
+pub use type_checker::{check_match_wildcards, check_types, infer_types, TypeChecker};
 pub use types::SynType;
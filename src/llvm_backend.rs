@@ -8,7 +8,7 @@
 use std::collections::HashMap;
 
 #[cfg(feature = "llvm_backend")]
-use crate::asg::{Node, NodeID, ASG};
+use crate::asg::{Edge, Node, NodeID, ASG};
 #[cfg(feature = "llvm_backend")]
 use crate::error::{ASGError, ASGResult};
 #[cfg(feature = "llvm_backend")]
@@ -63,6 +63,8 @@ pub struct LLVMBackend<'ctx> {
     values: HashMap<NodeID, BasicValueEnum<'ctx>>,
     /// Кэш функций
     functions: HashMap<String, FunctionValue<'ctx>>,
+    /// Имена параметров функций: по ним раскладываются именованные аргументы
+    function_params: HashMap<String, Vec<String>>,
     /// Кэш переменных (alloca)
     variables: HashMap<String, PointerValue<'ctx>>,
     /// Кэш замыканий
@@ -86,6 +88,7 @@ impl<'ctx> LLVMBackend<'ctx> {
             builder,
             values: HashMap::new(),
             functions: HashMap::new(),
+            function_params: HashMap::new(),
             variables: HashMap::new(),
             closures: HashMap::new(),
             closure_counter: 0,
@@ -306,6 +309,9 @@ impl<'ctx> LLVMBackend<'ctx> {
                 }
             }
 
+            // === Cast (явное приведение типа) ===
+            NodeType::Cast => self.compile_cast(asg, node)?,
+
             // === IntDiv (целочисленное деление) ===
             NodeType::IntDiv => {
                 self.compile_binary_int_op(asg, node, "intdiv", |builder, a, b| {
@@ -567,6 +573,31 @@ impl<'ctx> LLVMBackend<'ctx> {
         self.compile_node(asg, target)
     }
 
+    /// Компиляция явного приведения типа: Int -> Float (sitofp), Float -> Int (fptosi).
+    fn compile_cast(&mut self, asg: &ASG, node: &Node) -> ASGResult<BasicValueEnum<'ctx>> {
+        let target = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+        let operand = self.get_single_operand(asg, node)?;
+        match (target.as_str(), operand) {
+            ("Float", BasicValueEnum::IntValue(v)) => {
+                let result = self
+                    .builder
+                    .build_signed_int_to_float(v, self.context.f64_type(), "sitofp")
+                    .map_err(|e| ASGError::CompilationError(e.to_string()))?;
+                Ok(BasicValueEnum::FloatValue(result))
+            }
+            ("Int", BasicValueEnum::FloatValue(v)) => {
+                let result = self
+                    .builder
+                    .build_float_to_signed_int(v, self.context.i64_type(), "fptosi")
+                    .map_err(|e| ASGError::CompilationError(e.to_string()))?;
+                Ok(BasicValueEnum::IntValue(result))
+            }
+            ("Float", v @ BasicValueEnum::FloatValue(_))
+            | ("Int", v @ BasicValueEnum::IntValue(_)) => Ok(v),
+            _ => Err(ASGError::TypeError(format!("Cannot cast to {}", target))),
+        }
+    }
+
     /// Компиляция унарной math intrinsic функции (sin, cos, sqrt, etc.).
     fn compile_math_intrinsic(
         &mut self,
@@ -839,7 +870,8 @@ impl<'ctx> LLVMBackend<'ctx> {
         let function = self.module.add_function(&func_name, fn_type, None);
 
         // Сохраняем функцию
-        self.functions.insert(func_name, function);
+        self.functions.insert(func_name.clone(), function);
+        self.function_params.insert(func_name, param_names.clone());
 
         // Сохраняем текущий builder position и scope
         let current_block = self.builder.get_insert_block();
@@ -1178,6 +1210,22 @@ impl<'ctx> LLVMBackend<'ctx> {
             }
         }
 
+        // Именованные аргументы (:name value) встают на место параметра с этим именем
+        let arg_edges = if arg_edges.iter().any(|e| e.payload.is_some()) {
+            let params = match (&closure, self.function_params.get(&func_name)) {
+                (None, Some(params)) => params,
+                _ => {
+                    return Err(ASGError::CompilationError(format!(
+                        "keyword arguments need a named function, got {}",
+                        func_name
+                    )))
+                }
+            };
+            order_keyword_args(&func_name, params, arg_edges)?
+        } else {
+            arg_edges
+        };
+
         // Добавляем обычные аргументы
         for edge in arg_edges {
             let arg_node = asg
@@ -1574,6 +1622,11 @@ impl<'ctx> LLVMBackend<'ctx> {
     /// Компиляция print (вызов printf).
     fn compile_print(&mut self, asg: &ASG, node: &Node) -> ASGResult<BasicValueEnum<'ctx>> {
         // Получаем аргумент
+        if node.edges.len() > 1 {
+            return Err(ASGError::CompilationError(
+                "LLVM backend supports print of a single argument".to_string(),
+            ));
+        }
         let arg_edge = node.edges.first().ok_or(ASGError::MissingEdge(
             node.id,
             EdgeType::ApplicationArgument,
@@ -1701,6 +1754,58 @@ impl<'ctx> LLVMBackend<'ctx> {
     }
 }
 
+/// Разложить аргументы вызова по параметрам: позиционные по порядку, именованные
+/// (имя в payload ребра) — по имени. Значений по умолчанию в LLVM нет, поэтому
+/// каждый параметр должен получить ровно один аргумент.
+#[cfg(feature = "llvm_backend")]
+fn order_keyword_args<'a>(
+    func_name: &str,
+    params: &[String],
+    arg_edges: Vec<&'a Edge>,
+) -> ASGResult<Vec<&'a Edge>> {
+    let mut slots: Vec<Option<&Edge>> = vec![None; params.len()];
+    let mut next = 0;
+    for edge in arg_edges {
+        let index = match &edge.payload {
+            Some(name) => {
+                let name = String::from_utf8_lossy(name);
+                params.iter().position(|p| *p == name).ok_or_else(|| {
+                    ASGError::CompilationError(format!("{} has no parameter {}", func_name, name))
+                })?
+            }
+            None => {
+                next += 1;
+                next - 1
+            }
+        };
+        match slots.get_mut(index) {
+            Some(slot @ None) => *slot = Some(edge),
+            Some(Some(_)) => {
+                return Err(ASGError::CompilationError(format!(
+                    "{}: parameter {} is given twice",
+                    func_name, params[index]
+                )))
+            }
+            None => {
+                return Err(ASGError::CompilationError(format!(
+                    "{} expects {} arguments",
+                    func_name,
+                    params.len()
+                )))
+            }
+        }
+    }
+    slots
+        .into_iter()
+        .zip(params)
+        .map(|(slot, param)| {
+            slot.ok_or_else(|| {
+                ASGError::CompilationError(format!("{}: missing argument {}", func_name, param))
+            })
+        })
+        .collect()
+}
+
 // === Заглушка для сборки без LLVM ===
 
 #[cfg(not(feature = "llvm_backend"))]
@@ -1742,6 +1847,7 @@ mod tests {
                 payload: Some(value.to_le_bytes().to_vec()),
                 edges: vec![],
                 span: None,
+                attributes: Default::default(),
             });
             asg
         }
@@ -1757,6 +1863,7 @@ mod tests {
                 payload: Some(a.to_le_bytes().to_vec()),
                 edges: vec![],
                 span: None,
+                attributes: Default::default(),
             });
 
             // Литерал b
@@ -1766,6 +1873,7 @@ mod tests {
                 payload: Some(b.to_le_bytes().to_vec()),
                 edges: vec![],
                 span: None,
+                attributes: Default::default(),
             });
 
             // Операция
@@ -1786,6 +1894,7 @@ mod tests {
                     },
                 ],
                 span: None,
+                attributes: Default::default(),
             });
 
             asg
@@ -1817,6 +1926,7 @@ mod tests {
                 payload: Some(3.14_f64.to_le_bytes().to_vec()),
                 edges: vec![],
                 span: None,
+                attributes: Default::default(),
             });
 
             let result = backend.compile(&asg);
@@ -1917,6 +2027,24 @@ mod tests {
             assert!(closure.env_ptr.is_none());
         }
 
+        #[test]
+        fn test_keyword_args_follow_parameter_order() {
+            let params = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+            let positional = Edge::new(EdgeType::CallArgument, 1);
+            let c = Edge::with_payload(EdgeType::CallArgument, 2, b"c".to_vec());
+            let b = Edge::with_payload(EdgeType::CallArgument, 3, b"b".to_vec());
+
+            let ordered = order_keyword_args("f", &params, vec![&positional, &c, &b]).unwrap();
+            let ids: Vec<NodeID> = ordered.iter().map(|e| e.target_node_id).collect();
+            assert_eq!(ids, vec![1, 3, 2]);
+
+            // Пропущенный, повторный и неизвестный параметры — ошибка компиляции
+            assert!(order_keyword_args("f", &params, vec![&positional, &b]).is_err());
+            assert!(order_keyword_args("f", &params, vec![&positional, &b, &b, &c]).is_err());
+            let unknown = Edge::with_payload(EdgeType::CallArgument, 4, b"x".to_vec());
+            assert!(order_keyword_args("f", &params, vec![&positional, &unknown]).is_err());
+        }
+
         #[test]
         fn test_mixed_int_float_arithmetic() {
             let context = Context::create();
@@ -1931,6 +2059,7 @@ mod tests {
                 payload: Some(10_i64.to_le_bytes().to_vec()),
                 edges: vec![],
                 span: None,
+                attributes: Default::default(),
             });
 
             // Float literal
@@ -1940,6 +2069,7 @@ mod tests {
                 payload: Some(3.5_f64.to_le_bytes().to_vec()),
                 edges: vec![],
                 span: None,
+                attributes: Default::default(),
             });
 
             // Addition (should auto-convert int to float)
@@ -1960,6 +2090,7 @@ mod tests {
                     },
                 ],
                 span: None,
+                attributes: Default::default(),
             });
 
             let result = backend.compile(&asg);
@@ -97,8 +97,9 @@ impl ModuleLoader {
             .map_err(|e| ASGError::IoError(format!("Failed to read {}: {}", path.display(), e)))?;
 
         // Парсим
-        let (asg, root_ids) = parser::parse(&source).map_err(|e| {
-            ASGError::ParseError(format!("Parse error in {}: {}", path.display(), e))
+        let (asg, root_ids) = parser::parse(&source).map_err(|e| ASGError::ModuleParseError {
+            path: path.display().to_string(),
+            source: Box::new(e),
         })?;
 
         // Создаём модуль
@@ -243,8 +244,9 @@ impl ModuleLoader {
             .map(|p| String::from_utf8_lossy(p).to_string())
             .ok_or_else(|| ASGError::ModuleError("Import missing module name".to_string()))?;
 
-        // Загружаем импортируемый модуль (рекурсивно)
-        self.load(&import_name)?;
+        // Загружаем импортируемый модуль (рекурсивно); :as / :only после '|' не влияют на загрузку
+        let module_path = import_name.split('|').next().unwrap_or_default();
+        self.load(module_path)?;
 
         Ok(())
     }
@@ -404,6 +406,24 @@ mod tests {
         assert!(loader.registry.is_loaded("simple"));
     }
 
+    #[test]
+    fn test_parse_error_keeps_source() {
+        use std::error::Error;
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("broken.asg"), "(fn f (x)\n  (+ x 1)").unwrap();
+
+        let mut loader = ModuleLoader::with_search_paths(vec![dir.path().to_path_buf()]);
+        let err = loader.load("broken").unwrap_err();
+        assert!(err.to_string().starts_with("Parse error in "));
+        assert!(err.source().is_some());
+        let ASGError::ModuleParseError { source, .. } = &err else {
+            panic!("expected ModuleParseError, got {:?}", err);
+        };
+        assert!(matches!(**source, ASGError::ParseError(_)));
+        assert!(source.span().is_some());
+    }
+
     #[test]
     fn test_circular_import_detection() {
         let mut loader = ModuleLoader::new();
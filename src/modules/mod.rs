@@ -24,7 +24,7 @@ mod resolver;
 
 pub use loader::ModuleLoader;
 pub use registry::{Module, ModuleRegistry};
-pub use resolver::{ModuleResolver, ResolveStrategy};
+pub use resolver::{ModuleResolver, ResolveStrategy, PACKAGES_DIR, PACKAGE_PREFIX};
 
 use std::path::PathBuf;
 
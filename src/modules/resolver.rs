@@ -7,6 +7,15 @@ use std::path::{Path, PathBuf};
 
 use crate::error::{ASGError, ASGResult};
 
+/// Каталог установленных пакетов относительно корня проекта (его заполняет `asg-pkg install`).
+pub const PACKAGES_DIR: &str = ".asg/packages";
+
+/// Префикс имени модуля, который разрешается среди установленных пакетов: `"pkg:math-utils"`.
+pub const PACKAGE_PREFIX: &str = "pkg:";
+
+/// Точка входа пакета, если в его asg.toml не указан `entry`.
+const DEFAULT_PACKAGE_ENTRY: &str = "src/lib.syn";
+
 /// Стратегия разрешения имён модулей.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResolveStrategy {
@@ -18,6 +27,8 @@ pub enum ResolveStrategy {
     LocalOnly,
     /// Только stdlib
     StdlibOnly,
+    /// Только установленные пакеты (каталог `.asg/packages`)
+    Registry,
 }
 
 impl Default for ResolveStrategy {
@@ -33,6 +44,8 @@ pub struct ModuleResolver {
     search_paths: Vec<PathBuf>,
     /// Путь к stdlib
     stdlib_path: Option<PathBuf>,
+    /// Каталог установленных пакетов
+    packages_dir: Option<PathBuf>,
     /// Стратегия разрешения
     strategy: ResolveStrategy,
     /// Кэш разрешённых путей
@@ -47,6 +60,7 @@ impl ModuleResolver {
         Self {
             search_paths: vec![PathBuf::from(".")],
             stdlib_path: None,
+            packages_dir: None,
             strategy: ResolveStrategy::default(),
             cache: HashMap::new(),
             extensions: vec!["syn".to_string(), "asg".to_string()],
@@ -58,6 +72,7 @@ impl ModuleResolver {
         Self {
             search_paths: paths,
             stdlib_path: None,
+            packages_dir: None,
             strategy: ResolveStrategy::default(),
             cache: HashMap::new(),
             extensions: vec!["syn".to_string(), "asg".to_string()],
@@ -69,6 +84,11 @@ impl ModuleResolver {
         self.stdlib_path = Some(path);
     }
 
+    /// Установить каталог установленных пакетов.
+    pub fn set_packages_dir(&mut self, path: PathBuf) {
+        self.packages_dir = Some(path);
+    }
+
     /// Добавить путь поиска.
     pub fn add_search_path(&mut self, path: PathBuf) {
         if !self.search_paths.contains(&path) {
@@ -93,16 +113,21 @@ impl ModuleResolver {
             return Ok(path.clone());
         }
 
-        // Разрешаем по стратегии
-        let result = match self.strategy {
-            ResolveStrategy::LocalFirst => self
-                .resolve_local(module_name)
-                .or_else(|_| self.resolve_stdlib(module_name)),
-            ResolveStrategy::StdlibFirst => self
-                .resolve_stdlib(module_name)
-                .or_else(|_| self.resolve_local(module_name)),
-            ResolveStrategy::LocalOnly => self.resolve_local(module_name),
-            ResolveStrategy::StdlibOnly => self.resolve_stdlib(module_name),
+        // Разрешаем по стратегии; "pkg:" всегда означает установленный пакет
+        let result = if let Some(package) = module_name.strip_prefix(PACKAGE_PREFIX) {
+            self.resolve_package(package)
+        } else {
+            match self.strategy {
+                ResolveStrategy::LocalFirst => self
+                    .resolve_local(module_name)
+                    .or_else(|_| self.resolve_stdlib(module_name)),
+                ResolveStrategy::StdlibFirst => self
+                    .resolve_stdlib(module_name)
+                    .or_else(|_| self.resolve_local(module_name)),
+                ResolveStrategy::LocalOnly => self.resolve_local(module_name),
+                ResolveStrategy::StdlibOnly => self.resolve_stdlib(module_name),
+                ResolveStrategy::Registry => self.resolve_package(module_name),
+            }
         };
 
         // Кэшируем успешный результат
@@ -141,6 +166,43 @@ impl ModuleResolver {
             .ok_or_else(|| ASGError::ModuleNotFound(module_name.to_string()))
     }
 
+    /// Разрешить установленный пакет: `<packages>/<name>/<version>/<entry>`.
+    ///
+    /// Берётся наибольшая установленная версия; точка входа — `entry` из asg.toml
+    /// пакета или `src/lib.syn`.
+    fn resolve_package(&self, package: &str) -> ASGResult<PathBuf> {
+        let packages_dir = self.packages_dir.as_ref().ok_or_else(|| {
+            ASGError::ModuleNotFound(format!("packages dir not configured for: {}", package))
+        })?;
+        let not_installed = || ASGError::ModuleNotFound(format!("{}{}", PACKAGE_PREFIX, package));
+
+        let version_dir = std::fs::read_dir(packages_dir.join(package))
+            .map_err(|_| not_installed())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_dir())
+            .max_by_key(|path| version_key(path))
+            .ok_or_else(not_installed)?;
+
+        let entry = std::fs::read_to_string(version_dir.join("asg.toml"))
+            .ok()
+            .and_then(|manifest| manifest_entry(&manifest))
+            .unwrap_or_else(|| DEFAULT_PACKAGE_ENTRY.to_string());
+        let path = version_dir.join(entry);
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(not_installed())
+        }
+    }
+
+    /// Найти каталог пакетов проекта: `.asg/packages` в `start` или в одном из его предков.
+    pub fn find_packages_dir(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .map(|dir| dir.join(PACKAGES_DIR))
+            .find(|dir| dir.is_dir())
+    }
+
     /// Найти модуль в директории.
     fn find_module_in_dir(&self, dir: &Path, module_name: &str) -> Option<PathBuf> {
         // Преобразуем путь модуля: "std/math" -> "std/math"
@@ -239,6 +301,26 @@ impl ModuleResolver {
     }
 }
 
+/// Ключ сортировки каталога версии: числовые компоненты "1.10.0" -> [1, 10, 0].
+fn version_key(path: &Path) -> Vec<u64> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| {
+            name.split(['.', '-'])
+                .map(|part| part.parse().unwrap_or(0))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Значение `entry = "..."` из asg.toml пакета.
+fn manifest_entry(manifest: &str) -> Option<String> {
+    manifest.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "entry").then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
 impl Default for ModuleResolver {
     fn default() -> Self {
         Self::new()
@@ -321,6 +403,41 @@ mod tests {
         assert!(!resolver.cache.contains_key("cached"));
     }
 
+    #[test]
+    fn test_resolve_installed_package() {
+        let dir = tempdir().unwrap();
+        let packages = dir.path().join(PACKAGES_DIR);
+        for version in ["1.9.0", "1.10.0"] {
+            let version_dir = packages.join("math-utils").join(version);
+            fs::create_dir_all(version_dir.join("lib")).unwrap();
+            fs::write(
+                version_dir.join("asg.toml"),
+                "[package]\nentry = \"lib/math.syn\"\n",
+            )
+            .unwrap();
+            fs::write(version_dir.join("lib/math.syn"), "(fn square (x) (* x x))").unwrap();
+        }
+
+        let nested = dir.path().join("src");
+        fs::create_dir(&nested).unwrap();
+        assert_eq!(
+            ModuleResolver::find_packages_dir(&nested),
+            Some(packages.clone())
+        );
+
+        let mut resolver = ModuleResolver::new();
+        resolver.set_packages_dir(packages.clone());
+        assert_eq!(
+            resolver.resolve("pkg:math-utils").unwrap(),
+            packages.join("math-utils/1.10.0/lib/math.syn")
+        );
+        assert!(resolver.resolve("pkg:missing").is_err());
+
+        // Со стратегией Registry имя без префикса тоже ищется среди пакетов
+        resolver.set_strategy(ResolveStrategy::Registry);
+        assert!(resolver.resolve("math-utils").is_ok());
+    }
+
     #[test]
     fn test_is_stdlib_module() {
         let resolver = ModuleResolver::new();
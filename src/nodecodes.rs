@@ -8,6 +8,10 @@ pub enum NodeType {
     // === Литералы ===
     /// Целочисленный литерал (i64, payload: 8 bytes little-endian)
     LiteralInt,
+    /// Целочисленный литерал вне диапазона i64 (payload: десятичная запись, feature `bigint`)
+    LiteralBigInt,
+    /// Десятичный литерал `1.10d` (payload: запись без суффикса, feature `decimal`)
+    LiteralDecimal,
     /// Литерал с плавающей точкой (f64, payload: 8 bytes little-endian)
     LiteralFloat,
     /// Булевый литерал (payload: 1 byte, 0=false, 1=true)
@@ -62,7 +66,8 @@ pub enum NodeType {
     If,
     /// Блок выражений (последовательное выполнение)
     Block,
-    /// Цикл
+    /// Цикл: (loop body), (while cond body), (loop-indexed i cond body) — имя счётчика в payload,
+    /// (loop-timeout ms cond body) — таймаут по ребру LoopTimeout
     Loop,
     /// Выход из цикла
     Break,
@@ -76,7 +81,10 @@ pub enum NodeType {
     Function,
     /// Вызов функции
     Call,
-    /// Лямбда-выражение
+    /// Вызов метода: (call-method obj "name" args...) — функция из поля словаря/записи,
+    /// obj передаётся первым аргументом (self)
+    CallMethod,
+    /// Лямбда-выражение; payload — имя самоссылки для rec-lambda
     Lambda,
     /// Параметр функции (payload: имя параметра UTF-8)
     Parameter,
@@ -88,6 +96,9 @@ pub enum NodeType {
     VarRef,
     /// Присваивание
     Assign,
+    /// Составное присваивание: (inc! x), (add-assign x n) ... — AssignValue вычисляет (op x n),
+    /// результат записывается в ближайшую область видимости, где определена x
+    CompoundAssign,
 
     // === Тензорные операции (ML) ===
     /// Сложение тензоров
@@ -104,6 +115,16 @@ pub enum NodeType {
     Record,
     /// Доступ к полю записи (payload: имя поля UTF-8)
     RecordField,
+    /// Иммутабельное обновление поля: (with-field rec "x" value) (payload: имя поля UTF-8)
+    RecordUpdate,
+    /// Объявление типа записи: (defrecord Point (x y)) (payload: имя типа UTF-8,
+    /// поля — узлы RecordField по рёбрам RecordFieldDef)
+    RecordDef,
+    /// Объявление суммы типов: (defenum Shape (Circle r) (Rect w h)) (payload: имя типа UTF-8,
+    /// варианты — узлы EnumVariant по рёбрам ApplicationArgument)
+    EnumDef,
+    /// Вариант enum (payload: имя варианта UTF-8, поля — узлы RecordField по рёбрам RecordFieldDef)
+    EnumVariant,
     /// Массив
     Array,
     /// Индексирование массива
@@ -120,6 +141,8 @@ pub enum NodeType {
     ArrayFilter,
     /// reduce по массиву: (reduce arr init fn)
     ArrayReduce,
+    /// Инклюзивный scan: (scan fn init arr) — все промежуточные аккумуляторы, без init
+    ArrayScan,
     /// Создание диапазона: (range start end) или (range start end step)
     Range,
     /// Цикл for: (for var iterable body)
@@ -128,6 +151,10 @@ pub enum NodeType {
     ArrayReverse,
     /// Сортировка массива: (sort arr)
     ArraySort,
+    /// Сортировка массива по убыванию: (sort-desc arr)
+    ArraySortDescending,
+    /// k-й по величине элемент (с 1, дубликаты учитываются): (nth-largest arr k)
+    ArrayNthLargest,
     /// Сумма элементов: (sum arr)
     ArraySum,
     /// Произведение элементов: (product arr)
@@ -136,6 +163,9 @@ pub enum NodeType {
     ArrayContains,
     /// Найти индекс: (index-of arr elem)
     ArrayIndexOf,
+    /// Бинарный поиск в отсортированном массиве: (binary-search arr x)
+    /// (индекс, либо -(точка вставки) - 1, если не найден)
+    ArrayBinarySearch,
     /// Взять первые n: (take arr n)
     ArrayTake,
     /// Пропустить первые n: (drop arr n)
@@ -144,16 +174,35 @@ pub enum NodeType {
     ArrayAppend,
     /// Объединить два массива: (array-concat arr1 arr2)
     ArrayConcat,
+    /// Объединение как множеств, в порядке первого появления: (array-union a b)
+    ArrayUnion,
+    /// Пересечение как множеств, в порядке a: (array-intersect a b)
+    ArrayIntersect,
+    /// Разность как множеств, в порядке a: (array-diff a b)
+    ArrayDifference,
     /// Срез массива: (slice arr start end)
     ArraySlice,
+    /// Удаление Unit-элементов (и Error при drop-errors = true): (compact arr [drop-errors])
+    ArrayCompact,
+    /// Рекурсивное раскрытие вложенных массивов: (flatten-deep arr), (flatten-n arr depth)
+    ArrayFlattenDeep,
+    /// Случайная перестановка (Fisher–Yates на генераторе с seed): (shuffle arr)
+    ArrayShuffle,
+    /// n различных случайных элементов (без возвращения): (sample arr n)
+    ArraySample,
+    /// Один случайный элемент: (sample-one arr)
+    ArraySampleOne,
 
     // === Словари (Dict) ===
-    /// Создание словаря: (dict k1 v1 k2 v2 ...)
+    /// Создание словаря: (dict k1 v1 k2 v2 ...); ребро ApplicationArgument —
+    /// распаковка (dict ...base k v) и (dict-with base k v)
     Dict,
     /// Получение значения: (dict-get d key)
     DictGet,
     /// Установка значения: (dict-set d key value)
     DictSet,
+    /// Обновление значения функцией: (dict-update d key f [default])
+    DictUpdate,
     /// Проверка наличия ключа: (dict-has d key)
     DictHas,
     /// Удаление ключа: (dict-remove d key)
@@ -167,6 +216,22 @@ pub enum NodeType {
     /// Размер словаря: (dict-size d)
     DictSize,
 
+    // === Множества (Set) ===
+    /// Создание множества: (set 1 2 3); элементы — рёбра ArrayElement
+    SetNew,
+    /// Множество с добавленным элементом: (set-add s x)
+    SetAdd,
+    /// Проверка принадлежности: (set-contains s x)
+    SetContains,
+    /// Объединение: (set-union a b)
+    SetUnion,
+    /// Пересечение: (set-intersect a b)
+    SetIntersect,
+    /// Разность: (set-diff a b)
+    SetDiff,
+    /// Число элементов: (set-size s)
+    SetSize,
+
     // === Pipe и Composition ===
     /// Pipe operator: (|> value fn1 fn2 ...)
     Pipe,
@@ -198,6 +263,12 @@ pub enum NodeType {
     LazyFilter,
     /// Collect lazy to array: (collect seq)
     Collect,
+    /// Тело функции с yield: вызов возвращает генератор вместо выполнения тела
+    Generator,
+    /// Выдать значение из генератора: (yield v)
+    Yield,
+    /// Следующее значение генератора или () после завершения: (next gen)
+    GeneratorNext,
 
     // === Строковые операции ===
     /// Конкатенация строк: (concat s1 s2)
@@ -208,12 +279,34 @@ pub enum NodeType {
     StringSubstring,
     /// Разбиение строки: (str-split s delimiter)
     StringSplit,
+    /// Разбиение на строки по \n и \r\n: (str-lines s)
+    StringLines,
+    /// Разбиение на слова по пробельным символам, без пустых: (str-words s)
+    StringWords,
+    /// Массив символов (скаляров Unicode) как односимвольных строк: (str-to-chars s)
+    StringToChars,
+    /// Склейка массива строк или символов без разделителя: (chars-to-str arr)
+    CharsToString,
+    /// Символ строки по индексу скаляра Unicode: (char-at s i) -> Char
+    StringCharAt,
+    /// Код символа: (char->int c)
+    CharToInt,
+    /// Символ по коду: (int->char n)
+    IntToChar,
+    /// Массив символов строки: (string->chars s) -> Array of Char
+    StringChars,
     /// Объединение массива строк: (str-join arr delimiter)
     StringJoin,
     /// Содержит ли подстроку: (str-contains s substr)
     StringContains,
-    /// Замена подстроки: (str-replace s from to)
+    /// Число непересекающихся вхождений подстроки: (str-count s needle)
+    StringCount,
+    /// Позиции (в символах) непересекающихся вхождений: (str-find-all s needle)
+    StringFindAll,
+    /// Замена всех вхождений подстроки: (str-replace-all s from to), алиас str-replace
     StringReplace,
+    /// Замена только первого вхождения: (str-replace-first s from to)
+    StringReplaceFirst,
     /// Преобразование в строку: (to-string value)
     ToString,
     /// Преобразование в число: (parse-int s), (parse-float s)
@@ -224,6 +317,22 @@ pub enum NodeType {
     /// Uppercase/lowercase: (str-upper s), (str-lower s)
     StringUpper,
     StringLower,
+    /// Шаблон с именованными полями: (render template ctx), (render template ctx missing)
+    StringRender,
+
+    // === Кодирование ===
+    /// Base64: (base64-encode bytes-or-string) -> строка
+    Base64Encode,
+    /// Base64: (base64-decode s) -> bytes (Value::Error при неверном вводе)
+    Base64Decode,
+    /// Hex: (hex-encode bytes-or-string) -> строка
+    HexEncode,
+    /// Hex: (hex-decode s) -> bytes (Value::Error при неверном вводе)
+    HexDecode,
+    /// SHA-256: (sha256 bytes-or-string) -> hex-строка (feature "hashing")
+    Sha256,
+    /// MD5: (md5 bytes-or-string) -> hex-строка (feature "hashing")
+    Md5,
 
     // === Математические функции ===
     MathSqrt,
@@ -255,6 +364,25 @@ pub enum NodeType {
     IsError,
     /// Получение сообщения ошибки: (error-message err)
     ErrorMessage,
+    /// Отсутствующее значение: none или (none)
+    OptionNone,
+    /// Присутствующее значение: (some v)
+    OptionSome,
+    /// Проверка наличия значения: (is-some o) — false для none и Unit
+    OptionIsSome,
+    /// Извлечение значения: (unwrap o) — ошибка для none, Unit и ошибок
+    OptionUnwrap,
+    /// Значение или запасное: (unwrap-or o default), (unwrap o default) — default для
+    /// none, Unit и ошибок
+    OptionUnwrapOr,
+    /// Применить функцию к значению внутри some: (map-option o f), none остаётся none
+    OptionMap,
+    /// Значение или ошибка с сообщением для Unit и ошибок: (expect v "message")
+    Expect,
+    /// Успешный результат: (ok v) — вариант Result/Ok (ошибка — (err msg), как throw)
+    ResultOk,
+    /// Проверка успеха: (is-ok r) — false только для ошибок
+    IsOk,
 
     // === Алгебраические типы данных ===
     /// Конструктор варианта ADT (payload: имя варианта UTF-8)
@@ -267,6 +395,12 @@ pub enum NodeType {
     // === Ввод/вывод ===
     /// Печать значения
     Print,
+    /// Многострочная печать вложенных значений с отступами: (pp v)
+    PrettyPrint,
+    /// Печать значения в виде JSON: (print-json v)
+    PrintJson,
+    /// Отладочный вывод: (debug label expr) — печатает "label = value" в stderr, возвращает value
+    Debug,
     /// Чтение строки с консоли: (input) или (input prompt)
     Input,
     /// Чтение целого числа: (input-int) или (input-int prompt)
@@ -284,6 +418,28 @@ pub enum NodeType {
     /// Проверка существования файла: (file-exists path)
     FileExists,
 
+    // === Профилирование ===
+    /// Замер времени: (time expr) — печатает время в stderr, возвращает значение expr
+    Time,
+    /// Бенчмарк: (bench n expr) — выполняет expr n раз, возвращает {runs, mean, min, max} в мс
+    Bench,
+
+    // === Тестирование ===
+    /// Проверка условия: (assert cond) или (assert cond msg) — ошибка, если cond ложно
+    Assert,
+    /// Проверка равенства: (assert-eq expected actual) — при расхождении ошибка с обоими
+    /// значениями и путём до первого различающегося элемента массива/словаря
+    AssertEq,
+    /// Предусловие функции: (requires cond) — ошибка, если cond ложно
+    Requires,
+    /// Постусловие функции: (ensures cond) — проверяется после тела, результат
+    /// вызова виден в cond как `result`
+    Ensures,
+    /// Генератор выборки: (gen-int lo hi count) — массив из count случайных Int в [lo, hi]
+    GenInt,
+    /// Проверка свойства: (for-all samples pred) — ошибка с первым контрпримером
+    ForAll,
+
     // === Эффекты ===
     /// Выполнение эффекта
     EffectPerform,
@@ -301,6 +457,8 @@ pub enum NodeType {
     // === Аннотации ===
     /// Явная аннотация типа
     TypeAnnotation,
+    /// Явное приведение типа: (cast Float x) (payload: имя целевого типа UTF-8)
+    Cast,
 
     // === Web/HTTP ===
     /// HTTP сервер: (http-serve port handler)
@@ -331,8 +489,109 @@ pub enum NodeType {
     GuiHBox,
     /// Canvas для рисования: (canvas width height ondraw)
     GuiCanvas,
+    /// Текстовое поле, привязанное к переменной: (gui-bind var)
+    GuiBind,
+    /// Отступ между детьми vbox/hbox: (gui-spacing n)
+    GuiSpacing,
     /// Запуск GUI приложения: (gui-run window)
     GuiRun,
+
+    // === Расширения ===
+    /// Узел расширения: (ext:name args...) (payload: имя расширения UTF-8).
+    /// Код вычисляется через `extension_code`, семантику задаёт обработчик,
+    /// зарегистрированный в интерпретаторе.
+    Extension(u16),
+}
+
+impl NodeType {
+    /// Узел с побочным эффектом (ввод/вывод, время, случайность, расширения): его
+    /// результат не кэшируется в memo, и каждое вычисление выполняет эффект заново.
+    pub fn is_effectful(&self) -> bool {
+        matches!(
+            self,
+            NodeType::Print
+                | NodeType::PrettyPrint
+                | NodeType::PrintJson
+                | NodeType::Debug
+                | NodeType::Input
+                | NodeType::InputInt
+                | NodeType::InputFloat
+                | NodeType::ClearScreen
+                | NodeType::ReadFile
+                | NodeType::WriteFile
+                | NodeType::AppendFile
+                | NodeType::FileExists
+                | NodeType::Time
+                | NodeType::GeneratorNext
+                | NodeType::GenInt
+                | NodeType::ArrayShuffle
+                | NodeType::ArraySample
+                | NodeType::ArraySampleOne
+                | NodeType::Extension(_)
+        )
+    }
+
+    /// Значение зависит только от операндов: нет эффектов, случайности и вызовов
+    /// функций. Такой узел над неизменными операндами цикл не пересчитывает.
+    pub fn is_pure(&self) -> bool {
+        matches!(
+            self,
+            NodeType::LiteralInt
+                | NodeType::LiteralFloat
+                | NodeType::LiteralBool
+                | NodeType::LiteralString
+                | NodeType::LiteralUnit
+                | NodeType::BinaryOperation
+                | NodeType::Sub
+                | NodeType::Mul
+                | NodeType::Div
+                | NodeType::IntDiv
+                | NodeType::Mod
+                | NodeType::Neg
+                | NodeType::MathPow
+                | NodeType::MathAbs
+                | NodeType::MathFloor
+                | NodeType::MathCeil
+                | NodeType::MathSqrt
+                | NodeType::MathMin
+                | NodeType::MathMax
+                | NodeType::Eq
+                | NodeType::Ne
+                | NodeType::Lt
+                | NodeType::Le
+                | NodeType::Gt
+                | NodeType::Ge
+                | NodeType::And
+                | NodeType::Or
+                | NodeType::Not
+                | NodeType::If
+                | NodeType::Array
+                | NodeType::ArrayIndex
+                | NodeType::ArrayLength
+                | NodeType::ArraySum
+                | NodeType::ArrayProduct
+                | NodeType::ArrayReverse
+                | NodeType::ArrayContains
+                | NodeType::Range
+                | NodeType::Collect
+                | NodeType::StringConcat
+                | NodeType::StringLength
+                | NodeType::ToString
+                | NodeType::Dict
+                | NodeType::DictGet
+        )
+    }
+}
+
+/// Код расширения по его имени (FNV-1a, свёрнутый до 16 бит).
+/// Стабилен между запусками, поэтому builder и интерпретатор согласуются без общего реестра.
+pub fn extension_code(name: &str) -> u16 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    ((hash >> 16) ^ (hash & 0xffff)) as u16
 }
 
 /// Типы рёбер ASG
@@ -359,6 +618,8 @@ pub enum EdgeType {
     LoopInit,
     /// Шаг цикла
     LoopStep,
+    /// Таймаут цикла в миллисекундах (для loop-timeout)
+    LoopTimeout,
     /// Выражение в блоке
     BlockStatement,
     /// Try-выражение
@@ -379,6 +640,8 @@ pub enum EdgeType {
     CallArgument,
     /// Возвращаемое значение
     ReturnValue,
+    /// Значение параметра по умолчанию: (fn f (x (y 10)) ...)
+    ParameterDefault,
 
     // === Переменные ===
     /// Объявление переменной
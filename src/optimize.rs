@@ -0,0 +1,959 @@
+//! Оптимизирующие преобразования ASG перед компиляцией и интерпретацией.
+//!
+//! - [`inline`] — подстановка тел маленьких чистых функций на место вызовов.
+//! - [`licm`] — вынос инвариантных подвыражений из тел циклов.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::asg::{Edge, Node, NodeID, ASG};
+use crate::nodecodes::{EdgeType, NodeType};
+
+/// Узлы, из которых может состоять тело инлайнящейся функции и её аргументы:
+/// без побочных эффектов, без привязок имён и без управляющих переходов
+/// (`return`, `break`). `let` сюда не входит: он связывает имя в текущей области.
+fn is_pure_node(node_type: NodeType) -> bool {
+    matches!(
+        node_type,
+        NodeType::LiteralInt
+            | NodeType::LiteralBigInt
+            | NodeType::LiteralDecimal
+            | NodeType::LiteralFloat
+            | NodeType::LiteralBool
+            | NodeType::LiteralString
+            | NodeType::LiteralUnit
+            | NodeType::BinaryOperation
+            | NodeType::Sub
+            | NodeType::Mul
+            | NodeType::Div
+            | NodeType::IntDiv
+            | NodeType::Mod
+            | NodeType::Neg
+            | NodeType::Eq
+            | NodeType::Ne
+            | NodeType::Lt
+            | NodeType::Le
+            | NodeType::Gt
+            | NodeType::Ge
+            | NodeType::And
+            | NodeType::Or
+            | NodeType::Not
+            | NodeType::If
+            | NodeType::Block
+            | NodeType::Cast
+            | NodeType::Array
+            | NodeType::ArrayIndex
+            | NodeType::ArrayLength
+            | NodeType::Record
+            | NodeType::RecordField
+            | NodeType::VarRef
+            | NodeType::Call
+    )
+}
+
+/// Функция верхнего уровня: параметры и тело.
+struct FunctionDef {
+    params: Vec<String>,
+    body: NodeID,
+}
+
+/// Подставить тела маленьких функций на место их вызовов; возвращает число подстановок.
+///
+/// Инлайнится вызов `(f args...)`, если `f` определена через `fn` ровно один раз
+/// и её имя больше нигде не связывается, тело занимает не больше `threshold`
+/// узлов, состоит только из чистых узлов, ссылается лишь на свои параметры и
+/// вызывает только такие же функции, а `f` не рекурсивна (в том числе через
+/// другие функции). Аргументы тоже должны быть чистыми. Аргумент, который не
+/// может упасть (литерал, ссылка на переменную), подставляется на место ссылок
+/// на параметр общим узлом; остальные, как при вызове, вычисляются заранее:
+/// вызов становится `(do (let __inline_<call>_<n> arg) ... body)`, иначе
+/// `(f false (/ 1 0))` перестал бы падать. Так как тело не связывает имён и не
+/// ссылается на внешние переменные, захват имён при подстановке невозможен.
+///
+/// Определения функций остаются в графе для оставшихся вызовов.
+pub fn inline(asg: &mut ASG, threshold: usize) -> usize {
+    let functions = inlineable_functions(asg, threshold);
+    let mut inlined = 0;
+
+    // Подставленные тела могут содержать вызовы других инлайнящихся функций;
+    // граф вызовов между ними ацикличен, так что цикл завершается.
+    loop {
+        let candidates: Vec<(NodeID, String, Vec<NodeID>)> = asg
+            .nodes
+            .iter()
+            .filter(|n| n.node_type == NodeType::Call)
+            .filter_map(|n| {
+                let name = call_target_name(asg, n)?;
+                let def = functions.get(&name)?;
+                let arg_edges = n.find_edges(EdgeType::CallArgument);
+                // Именованные аргументы не подставляются: связывание по имени — при выполнении
+                if arg_edges.iter().any(|e| e.payload.is_some()) {
+                    return None;
+                }
+                let args: Vec<NodeID> = arg_edges.iter().map(|e| e.target_node_id).collect();
+                let pure_args = args
+                    .iter()
+                    .all(|&arg| pure_expr(asg, arg, &functions, None).is_some());
+                (args.len() == def.params.len() && pure_args).then_some((n.id, name, args))
+            })
+            .collect();
+        if candidates.is_empty() {
+            return inlined;
+        }
+
+        for (call_id, name, args) in candidates {
+            let def = &functions[&name];
+            let mut next_id = asg.next_id();
+            let mut substitutions: HashMap<&str, NodeID> = HashMap::new();
+            let mut bindings = Vec::new();
+            for (n, (param, &arg)) in def.params.iter().zip(&args).enumerate() {
+                if cannot_fail(asg, arg) {
+                    substitutions.insert(param, arg);
+                    continue;
+                }
+                let name = format!("__inline_{}_{}", call_id, n);
+                let (binding, var_ref) = (next_id, next_id + 1);
+                next_id += 2;
+                asg.add_node(Node::with_edges(
+                    binding,
+                    NodeType::Variable,
+                    Some(name.as_bytes().to_vec()),
+                    vec![Edge::new(EdgeType::VarValue, arg)],
+                ));
+                asg.add_node(Node::new(
+                    var_ref,
+                    NodeType::VarRef,
+                    Some(name.into_bytes()),
+                ));
+                substitutions.insert(param, var_ref);
+                bindings.push(binding);
+            }
+            replace_call(asg, call_id, def.body, &substitutions, bindings);
+            inlined += 1;
+        }
+    }
+}
+
+/// Выражение, вычисление которого не может завершиться ошибкой: литералы и
+/// ссылки на переменные, в том числе внутри литералов массивов и записей.
+fn cannot_fail(asg: &ASG, root: NodeID) -> bool {
+    asg.reachable_from(root).into_iter().all(|id| {
+        asg.find_node(id).is_some_and(|n| {
+            matches!(
+                n.node_type,
+                NodeType::LiteralInt
+                    | NodeType::LiteralBigInt
+                    | NodeType::LiteralDecimal
+                    | NodeType::LiteralFloat
+                    | NodeType::LiteralBool
+                    | NodeType::LiteralString
+                    | NodeType::LiteralUnit
+                    | NodeType::VarRef
+                    | NodeType::Array
+                    | NodeType::Record
+            )
+        })
+    })
+}
+
+/// Имя вызываемой функции, если цель вызова — ссылка по имени.
+fn call_target_name(asg: &ASG, call: &Node) -> Option<String> {
+    let target = asg.find_node(call.find_edge(EdgeType::CallTarget)?.target_node_id)?;
+    (target.node_type == NodeType::VarRef)
+        .then(|| target.get_name())
+        .flatten()
+}
+
+/// Функции, вызовы которых можно инлайнить (см. [`inline`]).
+fn inlineable_functions(asg: &ASG, threshold: usize) -> HashMap<String, FunctionDef> {
+    let mut defined: HashMap<String, Vec<&Node>> = HashMap::new();
+    let mut rebound: HashSet<String> = HashSet::new();
+    for node in &asg.nodes {
+        match node.node_type {
+            NodeType::Function => {
+                if let Some(name) = node.get_name() {
+                    defined.entry(name).or_default().push(node);
+                }
+            }
+            NodeType::Variable
+            | NodeType::Parameter
+            | NodeType::Assign
+            | NodeType::For
+            | NodeType::Loop
+            | NodeType::Lambda => {
+                if let Some(name) = node.get_name() {
+                    rebound.insert(name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut functions: HashMap<String, FunctionDef> = defined
+        .into_iter()
+        .filter(|(name, nodes)| nodes.len() == 1 && !rebound.contains(name))
+        .filter_map(|(name, nodes)| {
+            let node = nodes[0];
+            let params = node
+                .find_edges(EdgeType::FunctionParameter)
+                .iter()
+                .filter_map(|e| asg.find_node(e.target_node_id)?.get_name())
+                .collect();
+            let body = node.find_edge(EdgeType::FunctionBody)?.target_node_id;
+            Some((name, FunctionDef { params, body }))
+        })
+        .collect();
+
+    // Отбрасываем функции, тело которых велико, нечисто или вызывает
+    // отброшенные функции; повторяем, пока множество меняется.
+    let mut callees: HashMap<String, HashSet<String>> = HashMap::new();
+    loop {
+        let rejected: Vec<String> = functions
+            .iter()
+            .filter_map(|(name, def)| {
+                match pure_expr(asg, def.body, &functions, Some(&def.params)) {
+                    Some((size, called)) if size <= threshold => {
+                        callees.insert(name.clone(), called);
+                        None
+                    }
+                    _ => Some(name.clone()),
+                }
+            })
+            .collect();
+        if rejected.is_empty() {
+            break;
+        }
+        for name in rejected {
+            functions.remove(&name);
+        }
+    }
+
+    // Рекурсивные функции (прямо или через другие) не инлайнятся
+    let recursive: Vec<String> = functions
+        .keys()
+        .filter(|name| reaches(name, name, &callees))
+        .cloned()
+        .collect();
+    for name in &recursive {
+        functions.remove(name);
+    }
+    // Функции, вызывающие рекурсивные, теперь вызывают неинлайнящиеся
+    loop {
+        let rejected: Vec<String> = functions
+            .keys()
+            .filter(|name| callees[*name].iter().any(|c| !functions.contains_key(c)))
+            .cloned()
+            .collect();
+        if rejected.is_empty() {
+            return functions;
+        }
+        for name in rejected {
+            functions.remove(&name);
+        }
+    }
+}
+
+/// Достижима ли `target` из `from` по графу вызовов (хотя бы одним ребром).
+fn reaches(from: &str, target: &str, callees: &HashMap<String, HashSet<String>>) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack: Vec<&str> = callees
+        .get(from)
+        .map(|c| c.iter().map(String::as_str).collect())
+        .unwrap_or_default();
+    while let Some(name) = stack.pop() {
+        if name == target {
+            return true;
+        }
+        if visited.insert(name) {
+            if let Some(next) = callees.get(name) {
+                stack.extend(next.iter().map(String::as_str));
+            }
+        }
+    }
+    false
+}
+
+/// Проверить, что выражение чистое; вернуть число его узлов и имена вызываемых функций.
+///
+/// Вызовы допускаются только к функциям из `functions`. Если заданы `params`
+/// (проверка тела функции), прочие ссылки на переменные могут указывать лишь на них.
+fn pure_expr(
+    asg: &ASG,
+    root: NodeID,
+    functions: &HashMap<String, FunctionDef>,
+    params: Option<&[String]>,
+) -> Option<(usize, HashSet<String>)> {
+    let mut called = HashSet::new();
+    let mut call_targets = HashSet::new();
+    let reachable = asg.reachable_from(root);
+
+    for &id in &reachable {
+        let node = asg.find_node(id)?;
+        if !is_pure_node(node.node_type) {
+            return None;
+        }
+        if node.node_type == NodeType::Call {
+            let name = call_target_name(asg, node)?;
+            if !functions.contains_key(&name) {
+                return None;
+            }
+            call_targets.insert(node.find_edge(EdgeType::CallTarget)?.target_node_id);
+            called.insert(name);
+        }
+    }
+
+    if let Some(params) = params {
+        for &id in &reachable {
+            let node = asg.find_node(id)?;
+            if node.node_type == NodeType::VarRef && !call_targets.contains(&id) {
+                let name = node.get_name()?;
+                if !params.contains(&name) {
+                    return None;
+                }
+            }
+        }
+    }
+
+    Some((reachable.len(), called))
+}
+
+/// Заменить узел вызова копией тела, где ссылки на параметры указывают на аргументы.
+/// ID вызова сохраняется, поэтому рёбра родителей остаются верными. Если есть
+/// привязки аргументов (`bindings`), вызов становится блоком: привязки, затем тело.
+fn replace_call(
+    asg: &mut ASG,
+    call_id: NodeID,
+    body: NodeID,
+    substitutions: &HashMap<&str, NodeID>,
+    bindings: Vec<NodeID>,
+) {
+    let mut copies = HashMap::new();
+    let mut next_id = asg.next_id();
+    let copied_root = copy_subgraph(asg, body, substitutions, &mut copies, &mut next_id);
+
+    let Some(root) = asg.find_node(copied_root).cloned() else {
+        return;
+    };
+    let old_target = asg
+        .find_node(call_id)
+        .and_then(|n| n.find_edge(EdgeType::CallTarget))
+        .map(|e| e.target_node_id);
+
+    let keep_root = !bindings.is_empty();
+    if let Some(call) = asg.find_node_mut(call_id) {
+        if keep_root {
+            call.node_type = NodeType::Block;
+            call.payload = None;
+            call.edges = bindings
+                .into_iter()
+                .chain([copied_root])
+                .map(|id| Edge::new(EdgeType::BlockStatement, id))
+                .collect();
+        } else {
+            call.node_type = root.node_type;
+            call.payload = root.payload;
+            call.edges = root.edges;
+        }
+    }
+
+    // Убираем ставшие недостижимыми копию корня и ссылку на имя функции
+    let orphans: Vec<NodeID> = copies
+        .get(&body)
+        .copied()
+        .filter(|_| !keep_root)
+        .into_iter()
+        .chain(old_target)
+        .collect();
+    for orphan in orphans {
+        let referenced = asg
+            .nodes
+            .iter()
+            .any(|n| n.edges.iter().any(|e| e.target_node_id == orphan));
+        if !referenced {
+            asg.nodes.retain(|n| n.id != orphan);
+        }
+    }
+}
+
+/// Скопировать подграф с новыми ID; ссылки на параметры заменяются аргументами.
+/// Общие узлы копируются один раз.
+fn copy_subgraph(
+    asg: &mut ASG,
+    id: NodeID,
+    substitutions: &HashMap<&str, NodeID>,
+    copies: &mut HashMap<NodeID, NodeID>,
+    next_id: &mut NodeID,
+) -> NodeID {
+    if let Some(&copy) = copies.get(&id) {
+        return copy;
+    }
+    let Some(node) = asg.find_node(id).cloned() else {
+        return id;
+    };
+    if node.node_type == NodeType::VarRef {
+        if let Some(&arg) = node
+            .get_name()
+            .as_deref()
+            .and_then(|name| substitutions.get(name))
+        {
+            return arg;
+        }
+    }
+
+    let edges = node
+        .edges
+        .iter()
+        .map(|e| Edge {
+            target_node_id: copy_subgraph(asg, e.target_node_id, substitutions, copies, next_id),
+            ..e.clone()
+        })
+        .collect();
+    let copy_id = *next_id;
+    *next_id += 1;
+    asg.add_node(Node {
+        id: copy_id,
+        edges,
+        ..node
+    });
+    copies.insert(id, copy_id);
+    copy_id
+}
+
+/// Вынести инвариантные подвыражения из тел циклов `NodeType::Loop`;
+/// возвращает число вынесенных выражений.
+///
+/// Интерпретатор очищает memo на каждой итерации, поэтому чистое выражение,
+/// не зависящее от изменяемых в цикле переменных, вычисляется заново каждый
+/// проход. Такое выражение связывается через `let` со сгенерированным именем
+/// `__licm_<loop>_<n>` перед циклом, а в теле заменяется ссылкой на него.
+/// У цикла с условием привязка охраняется им: `(let __licm_.. (if cond expr ()))`,
+/// так что при нуле итераций выражение не вычисляется и не может упасть
+/// (например, делением на ноль). Поэтому пропускаются циклы, условие которых
+/// нельзя вычислить до цикла: `loop-indexed`, `loop-timeout` и условия с эффектами.
+/// Привязка попадает в блок, содержащий цикл (например, в тело функции), и
+/// видна только в нём; если цикл — не инструкция блока, его узел становится
+/// `(do (let __licm_.. expr) ... loop)`. Такой цикл пропускается, когда `let`
+/// из него (вне вложенных блоков) оказался бы заперт в новом блоке.
+///
+/// Изменяемыми считаются имена из `set`, `let` и счётчиков `loop-indexed`
+/// внутри цикла. Выносятся только выражения, вычисляемые на каждой итерации
+/// (не из ветвей `if`, правых операндов `and`/`or` и не после `break`/`continue`).
+/// Цикл пропускается, если в нём есть узлы вне белого списка: в частности,
+/// вызовы, через которые рекурсия могла бы заново войти в цикл и перезаписать
+/// привязку. Узлы с эффектами ([`NodeType::is_effectful`]) не выносятся никогда.
+pub fn licm(asg: &mut ASG) -> usize {
+    let loops: Vec<NodeID> = asg
+        .nodes
+        .iter()
+        .filter(|n| n.node_type == NodeType::Loop)
+        .map(|n| n.id)
+        .collect();
+    loops.into_iter().map(|id| hoist_invariants(asg, id)).sum()
+}
+
+/// Узлы, допустимые внутри цикла для [`licm`].
+fn is_licm_safe(node_type: NodeType) -> bool {
+    node_type != NodeType::Call
+        && (is_pure_node(node_type)
+            || matches!(
+                node_type,
+                NodeType::Block
+                    | NodeType::Assign
+                    | NodeType::CompoundAssign
+                    | NodeType::Variable
+                    | NodeType::Print
+                    | NodeType::Loop
+                    | NodeType::Break
+                    | NodeType::Continue
+            ))
+}
+
+fn hoist_invariants(asg: &mut ASG, loop_id: NodeID) -> usize {
+    let Some(loop_node) = asg.find_node(loop_id).cloned() else {
+        return 0;
+    };
+    let Some(body) = loop_node
+        .find_edge(EdgeType::LoopBody)
+        .map(|e| e.target_node_id)
+    else {
+        return 0;
+    };
+    // Охрана привязок: условие цикла, вычисленное до него. Цикл без условия
+    // выполняет тело хотя бы раз, и охрана не нужна.
+    let guard = match loop_node.find_edge(EdgeType::Condition) {
+        None => None,
+        Some(_) if loop_node.payload.is_some() => return 0,
+        Some(_) if loop_node.find_edge(EdgeType::LoopTimeout).is_some() => return 0,
+        Some(cond) => {
+            let pure_cond = asg
+                .reachable_from(cond.target_node_id)
+                .into_iter()
+                .all(|id| {
+                    asg.find_node(id)
+                        .is_some_and(|n| is_pure_node(n.node_type) && !n.node_type.is_effectful())
+                });
+            if !pure_cond {
+                return 0;
+            }
+            Some(cond.target_node_id)
+        }
+    };
+    let in_loop = asg.reachable_from(loop_id);
+    let safe = in_loop
+        .iter()
+        .all(|&id| asg.find_node(id).is_some_and(|n| is_licm_safe(n.node_type)));
+    if !safe {
+        return 0;
+    }
+
+    let mutated = mutated_names(asg, &in_loop);
+    let mut invariants = Vec::new();
+    collect_invariants(asg, body, &mutated, &mut invariants);
+    if invariants.is_empty() {
+        return 0;
+    }
+
+    // Цикл — инструкция блока: привязки встают в этот блок прямо перед ним
+    let parent_block = asg
+        .nodes
+        .iter()
+        .find(|n| {
+            n.node_type == NodeType::Block
+                && n.edges
+                    .iter()
+                    .any(|e| e.edge_type == EdgeType::BlockStatement && e.target_node_id == loop_id)
+        })
+        .map(|n| n.id);
+    if parent_block.is_none() && binds_outside_blocks(asg, loop_id) {
+        return 0;
+    }
+
+    let mut next_id = asg.next_id();
+    let mut alloc = || {
+        let id = next_id;
+        next_id += 1;
+        id
+    };
+    let mut statements = Vec::new();
+    for (n, &expr) in invariants.iter().enumerate() {
+        let name = format!("__licm_{}_{}", loop_id, n);
+        let var_ref = alloc();
+        let binding = alloc();
+        asg.add_node(Node::new(
+            var_ref,
+            NodeType::VarRef,
+            Some(name.as_bytes().to_vec()),
+        ));
+        let value = match guard {
+            Some(cond) => {
+                let unit = alloc();
+                let guarded = alloc();
+                asg.add_node(Node::new(unit, NodeType::LiteralUnit, None));
+                asg.add_node(Node::with_edges(
+                    guarded,
+                    NodeType::If,
+                    None,
+                    vec![
+                        Edge::new(EdgeType::Condition, cond),
+                        Edge::new(EdgeType::ThenBranch, expr),
+                        Edge::new(EdgeType::ElseBranch, unit),
+                    ],
+                ));
+                guarded
+            }
+            None => expr,
+        };
+        asg.add_node(Node::with_edges(
+            binding,
+            NodeType::Variable,
+            Some(name.into_bytes()),
+            vec![Edge::new(EdgeType::VarValue, value)],
+        ));
+        for node in asg.nodes.iter_mut().filter(|n| in_loop.contains(&n.id)) {
+            for edge in node.edges.iter_mut().filter(|e| e.target_node_id == expr) {
+                edge.target_node_id = var_ref;
+            }
+        }
+        statements.push(Edge::new(EdgeType::BlockStatement, binding));
+    }
+
+    if let Some(block) = parent_block.and_then(|id| asg.find_node_mut(id)) {
+        let at = block
+            .edges
+            .iter()
+            .position(|e| e.edge_type == EdgeType::BlockStatement && e.target_node_id == loop_id)
+            .unwrap_or(block.edges.len());
+        block.edges.splice(at..at, statements);
+        return invariants.len();
+    }
+
+    // Цикл переезжает на новый ID, а его прежний ID занимает блок с привязками,
+    // чтобы рёбра родителей остались верными
+    let moved_id = alloc();
+    let Some(node) = asg.find_node_mut(loop_id) else {
+        return 0;
+    };
+    let moved = Node {
+        id: moved_id,
+        ..node.clone()
+    };
+    node.node_type = NodeType::Block;
+    node.payload = None;
+    statements.push(Edge::new(EdgeType::BlockStatement, moved_id));
+    node.edges = statements;
+    asg.add_node(moved);
+
+    invariants.len()
+}
+
+/// Есть ли в цикле `let`, не вложенный в блок: такая привязка видна вокруг цикла
+/// и оказалась бы заперта в блоке-обёртке.
+fn binds_outside_blocks(asg: &ASG, loop_id: NodeID) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![loop_id];
+    while let Some(id) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let Some(node) = asg.find_node(id) else {
+            continue;
+        };
+        match node.node_type {
+            NodeType::Variable => return true,
+            NodeType::Block => {}
+            _ => stack.extend(node.edges.iter().map(|e| e.target_node_id)),
+        }
+    }
+    false
+}
+
+/// Имена, которые могут меняться между итерациями цикла.
+fn mutated_names(asg: &ASG, in_loop: &HashSet<NodeID>) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for node in in_loop.iter().filter_map(|&id| asg.find_node(id)) {
+        match node.node_type {
+            NodeType::Variable | NodeType::Loop => names.extend(node.get_name()),
+            NodeType::Assign | NodeType::CompoundAssign => {
+                if let Some(target) = node.find_edge(EdgeType::AssignTarget) {
+                    names.extend(
+                        asg.reachable_from(target.target_node_id)
+                            .into_iter()
+                            .filter_map(|id| asg.find_node(id))
+                            .filter(|n| n.node_type == NodeType::VarRef)
+                            .filter_map(|n| n.get_name()),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Найти максимальные инвариантные подвыражения, вычисляемые на каждой итерации.
+fn collect_invariants(asg: &ASG, id: NodeID, mutated: &HashSet<String>, found: &mut Vec<NodeID>) {
+    let Some(node) = asg.find_node(id) else {
+        return;
+    };
+    // Литералы и ссылки на переменные выносить незачем
+    if !node.edges.is_empty() && is_invariant(asg, id, mutated) {
+        if !found.contains(&id) {
+            found.push(id);
+        }
+        return;
+    }
+
+    let children: Vec<NodeID> = match node.node_type {
+        NodeType::If => node
+            .find_edges(EdgeType::Condition)
+            .iter()
+            .map(|e| e.target_node_id)
+            .collect(),
+        NodeType::And | NodeType::Or => node
+            .find_edges(EdgeType::FirstOperand)
+            .iter()
+            .map(|e| e.target_node_id)
+            .collect(),
+        NodeType::Assign => node
+            .find_edges(EdgeType::AssignValue)
+            .iter()
+            .map(|e| e.target_node_id)
+            .collect(),
+        NodeType::Loop => Vec::new(),
+        _ => node.edges.iter().map(|e| e.target_node_id).collect(),
+    };
+    for child in children {
+        collect_invariants(asg, child, mutated, found);
+        // После возможного break/continue остальное вычисляется не на каждой итерации
+        if exits_iteration(asg, child) {
+            break;
+        }
+    }
+}
+
+/// Может ли вычисление узла прервать итерацию через `break` или `continue`.
+fn exits_iteration(asg: &ASG, id: NodeID) -> bool {
+    asg.reachable_from(id).into_iter().any(|id| {
+        asg.find_node(id)
+            .is_some_and(|n| matches!(n.node_type, NodeType::Break | NodeType::Continue))
+    })
+}
+
+/// Чистое выражение без вызовов, не читающее изменяемых в цикле имён.
+fn is_invariant(asg: &ASG, id: NodeID, mutated: &HashSet<String>) -> bool {
+    asg.reachable_from(id).into_iter().all(|id| {
+        asg.find_node(id).is_some_and(|n| {
+            is_pure_node(n.node_type)
+                && !n.node_type.is_effectful()
+                && n.node_type != NodeType::Call
+                && !(n.node_type == NodeType::VarRef
+                    && n.get_name().is_some_and(|name| mutated.contains(&name)))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, Value};
+    use crate::parser::parse;
+
+    fn calls_to(asg: &ASG, name: &str) -> usize {
+        asg.nodes
+            .iter()
+            .filter(|n| n.node_type == NodeType::Call)
+            .filter(|n| call_target_name(asg, n).as_deref() == Some(name))
+            .count()
+    }
+
+    fn run(asg: &ASG, roots: &[NodeID]) -> Value {
+        let mut interp = Interpreter::new();
+        let mut result = Value::Unit;
+        for &root in roots {
+            result = interp.execute(asg, root).unwrap();
+        }
+        result
+    }
+
+    #[test]
+    fn test_inline_one_line_helper() {
+        let source = "(fn square (x) (* x x))\n\
+                      (fn area (w h) (+ (square w) (* w h)))\n\
+                      (area (+ 2 1) 4)";
+        let (mut asg, roots) = parse(source).unwrap();
+        let expected = run(&asg, &roots);
+
+        assert_eq!(inline(&mut asg, 16), 2);
+        assert_eq!(calls_to(&asg, "square"), 0);
+        assert_eq!(calls_to(&asg, "area"), 0);
+        assert_eq!(run(&asg, &roots), expected);
+        assert_eq!(expected, Value::Int(21));
+    }
+
+    #[test]
+    fn test_inline_skips_recursive_impure_and_large() {
+        let source = "(fn fact (n) (if (<= n 1) 1 (* n (fact (- n 1)))))\n\
+                      (fn loud (x) (do (print x) x))\n\
+                      (fn square (x) (* x x))\n\
+                      (fact 5) (loud 1) (square 3)";
+        let (mut asg, _) = parse(source).unwrap();
+
+        // Тело square — 3 узла: порог 2 его не пропускает
+        assert_eq!(inline(&mut asg, 2), 0);
+        assert_eq!(inline(&mut asg, 64), 1);
+        assert_eq!(calls_to(&asg, "fact"), 2);
+        assert_eq!(calls_to(&asg, "loud"), 1);
+        assert_eq!(calls_to(&asg, "square"), 0);
+
+        // Нечистый аргумент не подставляется
+        let (mut asg, _) = parse("(fn square (x) (* x x))\n(square (print 2))").unwrap();
+        assert_eq!(inline(&mut asg, 16), 0);
+    }
+
+    #[test]
+    fn test_inline_keeps_arguments_eager() {
+        // Аргумент вычисляется до тела, даже если тело его не использует
+        let source = "(fn f (c x) (if c x 0))\n(f false (/ 1 0))";
+        let (mut asg, roots) = parse(source).unwrap();
+        let eval = |asg: &ASG| {
+            let mut interp = Interpreter::new();
+            roots
+                .iter()
+                .try_fold(Value::Unit, |_, &root| interp.execute(asg, root))
+        };
+        assert!(eval(&asg).is_err());
+
+        assert_eq!(inline(&mut asg, 16), 1);
+        assert_eq!(calls_to(&asg, "f"), 0);
+        assert!(eval(&asg).is_err());
+
+        // Литералы подставляются напрямую, без привязок
+        let (mut asg, roots) = parse("(fn f (c x) (if c x 0))\n(f true 5)").unwrap();
+        assert_eq!(inline(&mut asg, 16), 1);
+        assert!(!asg.nodes.iter().any(|n| n
+            .get_name()
+            .is_some_and(|name| name.starts_with("__inline"))));
+        assert_eq!(run(&asg, &roots), Value::Int(5));
+    }
+
+    #[test]
+    fn test_licm_hoists_invariant_product() {
+        let source = "(let a 3) (let b 4) (let i 0) (let acc 0)\n\
+                      (while (< i 5) (do (set acc (+ acc (* a b))) (set i (+ (* i 2) 1))))\n\
+                      acc";
+        let (mut asg, roots) = parse(source).unwrap();
+        let expected = run(&asg, &roots);
+
+        assert_eq!(licm(&mut asg), 1);
+
+        // (* a b) вычисляется в привязке до цикла, а не в его теле
+        let loop_node = asg
+            .nodes
+            .iter()
+            .find(|n| n.node_type == NodeType::Loop)
+            .unwrap();
+        let muls_in_loop = asg
+            .reachable_from(loop_node.id)
+            .into_iter()
+            .filter(|&id| asg.find_node(id).unwrap().node_type == NodeType::Mul)
+            .count();
+        assert_eq!(muls_in_loop, 1); // (* i 2) зависит от i и остаётся
+        let hoisted = asg
+            .nodes
+            .iter()
+            .find(|n| {
+                n.node_type == NodeType::Variable && n.get_name().unwrap().starts_with("__licm")
+            })
+            .unwrap();
+        // Привязка охраняется условием цикла: (if (< i 5) (* a b) ())
+        let guard = asg
+            .find_node(
+                hoisted
+                    .find_edge(EdgeType::VarValue)
+                    .unwrap()
+                    .target_node_id,
+            )
+            .unwrap();
+        assert_eq!(guard.node_type, NodeType::If);
+        let value = guard
+            .find_edge(EdgeType::ThenBranch)
+            .unwrap()
+            .target_node_id;
+        assert_eq!(asg.find_node(value).unwrap().node_type, NodeType::Mul);
+
+        assert_eq!(run(&asg, &roots), expected);
+        assert_eq!(expected, Value::Int(36));
+    }
+
+    #[test]
+    fn test_licm_binds_in_enclosing_block() {
+        let source = "(fn total (a b) (do (let i 0) (let acc 0)\n\
+                        (while (< i 4) (do (set acc (+ acc (* a b))) (set i (+ i 1))))\n\
+                        acc))\n\
+                      (total 2 5)";
+        let (mut asg, roots) = parse(source).unwrap();
+
+        assert_eq!(licm(&mut asg), 1);
+        // Привязка — инструкция тела функции прямо перед циклом
+        let body = asg
+            .nodes
+            .iter()
+            .find(|n| n.node_type == NodeType::Function)
+            .and_then(|f| f.find_edge(EdgeType::FunctionBody))
+            .map(|e| e.target_node_id)
+            .unwrap();
+        let kinds: Vec<NodeType> = asg
+            .find_node(body)
+            .unwrap()
+            .find_edges(EdgeType::BlockStatement)
+            .iter()
+            .map(|e| asg.find_node(e.target_node_id).unwrap().node_type)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                NodeType::Variable,
+                NodeType::Variable,
+                NodeType::Variable,
+                NodeType::Loop,
+                NodeType::VarRef
+            ]
+        );
+
+        let mut interp = Interpreter::new();
+        let mut result = Value::Unit;
+        for &root in &roots {
+            result = interp.execute(&asg, root).unwrap();
+        }
+        assert_eq!(result, Value::Int(40));
+        assert!(!interp
+            .get_variables()
+            .keys()
+            .any(|k| k.starts_with("__licm")));
+    }
+
+    #[test]
+    fn test_licm_keeps_let_visible_after_loop() {
+        // let прямо в теле цикла виден после него: обёртка его бы спрятала
+        let source = "(let a 2) (let i 0)\n\
+                      (while (< i 1) (let i (+ i (* a 3))))\n\
+                      i";
+        let (mut asg, roots) = parse(source).unwrap();
+        assert_eq!(licm(&mut asg), 0);
+        assert_eq!(run(&asg, &roots), Value::Int(6));
+    }
+
+    #[test]
+    fn test_licm_zero_iterations_do_not_evaluate_hoisted() {
+        // Цикл не выполняется ни разу: деление на ноль не должно случиться
+        let source = "(let d 0) (let i 0)\n\
+                      (while (< i 0) (do (print (/ 10 d)) (set i (+ i 1))))\n\
+                      i";
+        let (mut asg, roots) = parse(source).unwrap();
+        assert_eq!(licm(&mut asg), 1);
+        assert_eq!(run(&asg, &roots), Value::Int(0));
+    }
+
+    #[test]
+    fn test_licm_stops_at_break() {
+        // (/ 10 d) после break вычисляется не на каждой итерации
+        let source = "(let d 0) (let i 0)\n\
+                      (while (< i 3) (do (if (== d 0) (break)) (print (/ 10 d)) (set i (+ i 1))))\n\
+                      i";
+        let (mut asg, roots) = parse(source).unwrap();
+        // Выносится только (== d 0) до break
+        assert_eq!(licm(&mut asg), 1);
+        let loop_id = asg
+            .nodes
+            .iter()
+            .find(|n| n.node_type == NodeType::Loop)
+            .unwrap()
+            .id;
+        assert!(asg.reachable_from(loop_id).into_iter().any(|id| asg
+            .find_node(id)
+            .unwrap()
+            .node_type
+            == NodeType::Div));
+        assert_eq!(run(&asg, &roots), Value::Int(0));
+    }
+
+    #[test]
+    fn test_licm_skips_indexed_and_timed_loops() {
+        // Условие loop-indexed читает счётчик, которого до цикла нет
+        for source in [
+            "(let a 2) (let s 0) (loop-indexed k (< k 3) (set s (+ s (* a 5)))) s",
+            "(let a 2) (let s 0) (loop-timeout 1000 (< s 30) (set s (+ s (* a 5)))) s",
+        ] {
+            let (mut asg, roots) = parse(source).unwrap();
+            assert_eq!(licm(&mut asg), 0, "{}", source);
+            assert_eq!(run(&asg, &roots), Value::Int(30), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_licm_skips_loops_with_calls() {
+        let source = "(fn f (x) x) (let a 3) (let i 0)\n\
+                      (while (< i 3) (set i (+ i (f (* a 2)))))";
+        let (mut asg, _) = parse(source).unwrap();
+        assert_eq!(licm(&mut asg), 0);
+    }
+}
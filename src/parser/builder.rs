@@ -2,14 +2,17 @@
 
 use super::error::ParseError;
 use super::parser::{Atom, SExpr};
+use super::printer::format_sexpr;
 use super::token::Spanned;
 use crate::asg::{Edge, Node, NodeID, ASG};
-use crate::nodecodes::{EdgeType, NodeType};
+use crate::nodecodes::{extension_code, EdgeType, NodeType};
 
 /// Построитель ASG из S-Expression.
 pub struct AsgBuilder {
     asg: ASG,
     next_id: NodeID,
+    /// Встречен ли yield в теле строящейся функции
+    yield_seen: bool,
 }
 
 impl AsgBuilder {
@@ -18,6 +21,7 @@ impl AsgBuilder {
         Self {
             asg: ASG::new(),
             next_id: 1,
+            yield_seen: false,
         }
     }
 
@@ -65,6 +69,35 @@ impl AsgBuilder {
                 Some(n.to_le_bytes().to_vec()),
                 span,
             ),
+            #[cfg(feature = "bigint")]
+            Atom::BigInt(s) => Node::with_span(
+                id,
+                NodeType::LiteralBigInt,
+                Some(s.as_bytes().to_vec()),
+                span,
+            ),
+            #[cfg(not(feature = "bigint"))]
+            Atom::BigInt(_) => {
+                return Err(ParseError::InvalidLiteral {
+                    span,
+                    message: "Integer literal out of i64 range (enable the `bigint` feature)"
+                        .to_string(),
+                })
+            }
+            #[cfg(feature = "decimal")]
+            Atom::Decimal(s) => Node::with_span(
+                id,
+                NodeType::LiteralDecimal,
+                Some(s.as_bytes().to_vec()),
+                span,
+            ),
+            #[cfg(not(feature = "decimal"))]
+            Atom::Decimal(_) => {
+                return Err(ParseError::InvalidLiteral {
+                    span,
+                    message: "Decimal literals require the `decimal` feature".to_string(),
+                })
+            }
             Atom::Float(f) => Node::with_span(
                 id,
                 NodeType::LiteralFloat,
@@ -82,6 +115,7 @@ impl AsgBuilder {
                 match s.as_str() {
                     "true" => Node::with_span(id, NodeType::LiteralBool, Some(vec![1]), span),
                     "false" => Node::with_span(id, NodeType::LiteralBool, Some(vec![0]), span),
+                    "none" => Node::with_span(id, NodeType::OptionNone, None, span),
                     _ => {
                         // Ссылка на переменную
                         Node::with_span(id, NodeType::VarRef, Some(s.as_bytes().to_vec()), span)
@@ -112,15 +146,20 @@ impl AsgBuilder {
             return Ok(id);
         }
 
-        // Получаем имя формы
+        // Получаем имя формы; если первый элемент не имя — это вызов значения: (expr args...)
         let first = &elements[0];
-        let form_name = first
-            .as_ident()
-            .or_else(|| first.as_symbol())
-            .ok_or_else(|| ParseError::InvalidLiteral {
+        // () в голове списка — не вызов: Unit вызвать нельзя
+        if matches!(first, SExpr::List(head) if head.value.is_empty()) {
+            return Err(ParseError::UnexpectedToken {
                 span: first.span(),
-                message: "Expected identifier or symbol as first element".to_string(),
-            })?;
+                expected: "function or form name".to_string(),
+                found: "()".to_string(),
+            });
+        }
+        let form_name = match first.as_ident().or_else(|| first.as_symbol()) {
+            Some(name) => name,
+            None => return self.build_call(elements, list.span),
+        };
 
         // Диспетчеризация по форме
         match form_name {
@@ -128,10 +167,11 @@ impl AsgBuilder {
             "+" => self.build_variadic_add(elements, list.span),
             "-" => self.build_binop_or_unary(elements, NodeType::Sub, NodeType::Neg, list.span),
             "*" => self.build_variadic_mul(elements, list.span),
-            "/" => self.build_binop(elements, NodeType::Div, list.span),
+            "/" => self.build_left_assoc(elements, NodeType::Div, "/", list.span),
             "//" => self.build_binop(elements, NodeType::IntDiv, list.span),
             "%" => self.build_binop(elements, NodeType::Mod, list.span),
             "neg" => self.build_unop(elements, NodeType::Neg, list.span),
+            "cast" => self.build_cast(elements, list.span),
 
             // Сравнение
             "==" => self.build_binop(elements, NodeType::Eq, list.span),
@@ -149,12 +189,24 @@ impl AsgBuilder {
             // Переменные
             "let" => self.build_let(elements, list.span),
             "set" => self.build_set(elements, list.span),
+            "inc!" => self.build_compound_assign(elements, NodeType::BinaryOperation, list.span),
+            "dec!" => self.build_compound_assign(elements, NodeType::Sub, list.span),
+            "add-assign" => {
+                self.build_compound_assign(elements, NodeType::BinaryOperation, list.span)
+            }
+            "sub-assign" => self.build_compound_assign(elements, NodeType::Sub, list.span),
+            "mul-assign" => self.build_compound_assign(elements, NodeType::Mul, list.span),
+            "div-assign" => self.build_compound_assign(elements, NodeType::Div, list.span),
 
             // Управление
             "if" => self.build_if(elements, list.span),
+            "when" => self.build_when(elements, false, list.span),
+            "unless" => self.build_when(elements, true, list.span),
             "do" => self.build_do(elements, list.span),
             "loop" => self.build_loop(elements, list.span),
             "while" => self.build_while(elements, list.span),
+            "loop-indexed" => self.build_loop_indexed(elements, list.span),
+            "loop-timeout" => self.build_loop_timeout(elements, list.span),
             "break" => self.build_break(elements, list.span),
             "continue" => self.build_continue(list.span),
             "return" => self.build_return(elements, list.span),
@@ -162,6 +214,7 @@ impl AsgBuilder {
             // Функции
             "fn" => self.build_fn(elements, list.span),
             "lambda" => self.build_lambda(elements, list.span),
+            "rec-lambda" => self.build_rec_lambda(elements, list.span),
 
             // Структуры данных
             "array" => self.build_array(elements, list.span),
@@ -176,11 +229,18 @@ impl AsgBuilder {
             "map" => self.build_map(elements, list.span),
             "filter" => self.build_filter(elements, list.span),
             "reduce" => self.build_reduce(elements, list.span),
+            "scan" => self.build_scan(elements, list.span),
             "record" => self.build_record(elements, list.span),
             "field" => self.build_field(elements, list.span),
+            "with-field" => self.build_with_field(elements, list.span),
+            "defrecord" => self.build_defrecord(elements, list.span),
+            "defenum" => self.build_defenum(elements, list.span),
 
             // I/O
             "print" => self.build_print(elements, list.span),
+            "pp" => self.build_unary(elements, NodeType::PrettyPrint, list.span),
+            "print-json" => self.build_unary(elements, NodeType::PrintJson, list.span),
+            "debug" => self.build_binop(elements, NodeType::Debug, list.span),
             "input" => self.build_input(elements, NodeType::Input, list.span),
             "input-int" => self.build_input(elements, NodeType::InputInt, list.span),
             "input-float" => self.build_input(elements, NodeType::InputFloat, list.span),
@@ -190,20 +250,65 @@ impl AsgBuilder {
             "append-file" => self.build_binop(elements, NodeType::AppendFile, list.span),
             "file-exists" => self.build_unary(elements, NodeType::FileExists, list.span),
 
+            // Профилирование
+            "time" => self.build_unop(elements, NodeType::Time, list.span),
+            "bench" => self.build_binop(elements, NodeType::Bench, list.span),
+
+            // Тестирование
+            "assert" if elements.len() == 3 => {
+                self.build_binop(elements, NodeType::Assert, list.span)
+            }
+            "assert" => self.build_unary(elements, NodeType::Assert, list.span),
+            "assert-eq" => self.build_binop(elements, NodeType::AssertEq, list.span),
+            "requires" => self.build_unary(elements, NodeType::Requires, list.span),
+            "ensures" => self.build_unary(elements, NodeType::Ensures, list.span),
+            "gen-int" => self.build_ternary(elements, NodeType::GenInt, list.span),
+            "for-all" => self.build_binop(elements, NodeType::ForAll, list.span),
+
             // Строковые операции
             "concat" => self.build_binop(elements, NodeType::StringConcat, list.span),
             "str-length" => self.build_unary(elements, NodeType::StringLength, list.span),
             "substring" => self.build_substring(elements, list.span),
+            "str-split" if elements.len() == 4 => {
+                self.build_ternary(elements, NodeType::StringSplit, list.span)
+            }
             "str-split" => self.build_binop(elements, NodeType::StringSplit, list.span),
+            "str-lines" => self.build_unary(elements, NodeType::StringLines, list.span),
+            "str-words" => self.build_unary(elements, NodeType::StringWords, list.span),
+            "str-to-chars" => self.build_unary(elements, NodeType::StringToChars, list.span),
+            "chars-to-str" => self.build_unary(elements, NodeType::CharsToString, list.span),
+            "char-at" => self.build_binop(elements, NodeType::StringCharAt, list.span),
+            "char->int" => self.build_unary(elements, NodeType::CharToInt, list.span),
+            "int->char" => self.build_unary(elements, NodeType::IntToChar, list.span),
+            "string->chars" => self.build_unary(elements, NodeType::StringChars, list.span),
             "str-join" => self.build_binop(elements, NodeType::StringJoin, list.span),
             "str-contains" => self.build_binop(elements, NodeType::StringContains, list.span),
-            "str-replace" => self.build_str_replace(elements, list.span),
+            "str-count" => self.build_binop(elements, NodeType::StringCount, list.span),
+            "str-find-all" => self.build_binop(elements, NodeType::StringFindAll, list.span),
+            "str-replace-all" | "str-replace" => {
+                self.build_str_replace(elements, NodeType::StringReplace, form_name, list.span)
+            }
+            "str-replace-first" => {
+                self.build_str_replace(elements, NodeType::StringReplaceFirst, form_name, list.span)
+            }
             "to-string" | "str" => self.build_unary(elements, NodeType::ToString, list.span),
             "parse-int" => self.build_unary(elements, NodeType::ParseInt, list.span),
             "parse-float" => self.build_unary(elements, NodeType::ParseFloat, list.span),
             "str-trim" => self.build_unary(elements, NodeType::StringTrim, list.span),
             "str-upper" => self.build_unary(elements, NodeType::StringUpper, list.span),
             "str-lower" => self.build_unary(elements, NodeType::StringLower, list.span),
+            "render" if elements.len() == 4 => {
+                self.build_ternary(elements, NodeType::StringRender, list.span)
+            }
+            "render" => self.build_binop(elements, NodeType::StringRender, list.span),
+
+            // Кодирование
+            "base64-encode" => self.build_unary(elements, NodeType::Base64Encode, list.span),
+            "base64-decode" => self.build_unary(elements, NodeType::Base64Decode, list.span),
+            "hex-encode" => self.build_unary(elements, NodeType::HexEncode, list.span),
+            "hex-decode" => self.build_unary(elements, NodeType::HexDecode, list.span),
+            "sha256" => self.build_unary(elements, NodeType::Sha256, list.span),
+            "md5" => self.build_unary(elements, NodeType::Md5, list.span),
 
             // Math functions
             "sqrt" => self.build_unary(elements, NodeType::MathSqrt, list.span),
@@ -232,6 +337,22 @@ impl AsgBuilder {
             "is-error" => self.build_unary(elements, NodeType::IsError, list.span),
             "error-message" => self.build_unary(elements, NodeType::ErrorMessage, list.span),
 
+            // Option/Result
+            "none" => self.build_constant(NodeType::OptionNone),
+            "some" => self.build_unary(elements, NodeType::OptionSome, list.span),
+            "is-some" => self.build_unary(elements, NodeType::OptionIsSome, list.span),
+            "unwrap" if elements.len() == 2 => {
+                self.build_unary(elements, NodeType::OptionUnwrap, list.span)
+            }
+            "unwrap" | "unwrap-or" => {
+                self.build_binop(elements, NodeType::OptionUnwrapOr, list.span)
+            }
+            "map-option" => self.build_binop(elements, NodeType::OptionMap, list.span),
+            "expect" => self.build_binop(elements, NodeType::Expect, list.span),
+            "ok" => self.build_unary(elements, NodeType::ResultOk, list.span),
+            "err" => self.build_unary(elements, NodeType::Throw, list.span),
+            "is-ok" => self.build_unary(elements, NodeType::IsOk, list.span),
+
             // Pattern matching
             "match" => self.build_match(elements, list.span),
 
@@ -249,23 +370,45 @@ impl AsgBuilder {
             "lazy-map" => self.build_binop(elements, NodeType::LazyMap, list.span),
             "lazy-filter" => self.build_binop(elements, NodeType::LazyFilter, list.span),
             "collect" => self.build_unary(elements, NodeType::Collect, list.span),
+            "yield" => {
+                self.yield_seen = true;
+                self.build_unary(elements, NodeType::Yield, list.span)
+            }
+            "next" => self.build_unary(elements, NodeType::GeneratorNext, list.span),
 
             "reverse" => self.build_unary(elements, NodeType::ArrayReverse, list.span),
             "sort" => self.build_unary(elements, NodeType::ArraySort, list.span),
+            "sort-desc" => self.build_unary(elements, NodeType::ArraySortDescending, list.span),
+            "nth-largest" => self.build_binop(elements, NodeType::ArrayNthLargest, list.span),
             "sum" => self.build_unary(elements, NodeType::ArraySum, list.span),
             "product" => self.build_unary(elements, NodeType::ArrayProduct, list.span),
             "contains" => self.build_binop(elements, NodeType::ArrayContains, list.span),
             "index-of" => self.build_binop(elements, NodeType::ArrayIndexOf, list.span),
+            "binary-search" => self.build_binop(elements, NodeType::ArrayBinarySearch, list.span),
             "take" => self.build_binop(elements, NodeType::ArrayTake, list.span),
             "drop" => self.build_binop(elements, NodeType::ArrayDrop, list.span),
             "append" => self.build_binop(elements, NodeType::ArrayAppend, list.span),
             "array-concat" => self.build_binop(elements, NodeType::ArrayConcat, list.span),
+            "array-union" => self.build_binop(elements, NodeType::ArrayUnion, list.span),
+            "array-intersect" => self.build_binop(elements, NodeType::ArrayIntersect, list.span),
+            "array-diff" => self.build_binop(elements, NodeType::ArrayDifference, list.span),
             "slice" => self.build_ternary(elements, NodeType::ArraySlice, list.span),
+            "compact" if elements.len() == 3 => {
+                self.build_binop(elements, NodeType::ArrayCompact, list.span)
+            }
+            "compact" => self.build_unary(elements, NodeType::ArrayCompact, list.span),
+            "flatten-deep" => self.build_unary(elements, NodeType::ArrayFlattenDeep, list.span),
+            "flatten-n" => self.build_binop(elements, NodeType::ArrayFlattenDeep, list.span),
+            "shuffle" => self.build_unary(elements, NodeType::ArrayShuffle, list.span),
+            "sample" => self.build_binop(elements, NodeType::ArraySample, list.span),
+            "sample-one" => self.build_unary(elements, NodeType::ArraySampleOne, list.span),
 
             // Dict operations
-            "dict" => self.build_dict(elements, list.span),
+            "dict" | "dict-with" => self.build_dict(elements, list.span),
             "dict-get" => self.build_binop(elements, NodeType::DictGet, list.span),
             "dict-set" => self.build_ternary(elements, NodeType::DictSet, list.span),
+            "dict-update" => self.build_dict_update(elements, list.span),
+            "call-method" => self.build_call_method(elements, list.span),
             "dict-has" => self.build_binop(elements, NodeType::DictHas, list.span),
             "dict-remove" => self.build_binop(elements, NodeType::DictRemove, list.span),
             "dict-keys" => self.build_unary(elements, NodeType::DictKeys, list.span),
@@ -273,6 +416,14 @@ impl AsgBuilder {
             "dict-merge" => self.build_binop(elements, NodeType::DictMerge, list.span),
             "dict-size" => self.build_unary(elements, NodeType::DictSize, list.span),
 
+            // Set operations ((set 1 2 3) разбирает build_set)
+            "set-add" => self.build_binop(elements, NodeType::SetAdd, list.span),
+            "set-contains" => self.build_binop(elements, NodeType::SetContains, list.span),
+            "set-union" => self.build_binop(elements, NodeType::SetUnion, list.span),
+            "set-intersect" => self.build_binop(elements, NodeType::SetIntersect, list.span),
+            "set-diff" => self.build_binop(elements, NodeType::SetDiff, list.span),
+            "set-size" => self.build_unary(elements, NodeType::SetSize, list.span),
+
             // Pipe and composition
             "|>" => self.build_pipe(elements, list.span),
             "pipe" => self.build_pipe(elements, list.span),
@@ -308,11 +459,18 @@ impl AsgBuilder {
             "gui-button" => self.build_gui_button(elements, list.span),
             "text-field" => self.build_gui_text_field(elements, list.span),
             "gui-label" => self.build_unary(elements, NodeType::GuiLabel, list.span),
+            "gui-bind" => self.build_gui_bind(elements, list.span),
+            "gui-spacing" => self.build_unary(elements, NodeType::GuiSpacing, list.span),
             "vbox" => self.build_gui_container(elements, NodeType::GuiVBox, list.span),
             "hbox" => self.build_gui_container(elements, NodeType::GuiHBox, list.span),
             "canvas" => self.build_gui_canvas(elements, list.span),
             "gui-run" => self.build_unary(elements, NodeType::GuiRun, list.span),
 
+            // Расширения: (ext:name args...)
+            name if name.starts_with("ext:") => {
+                self.build_extension(&name["ext:".len()..], elements, list.span)
+            }
+
             // По умолчанию — вызов функции
             _ => self.build_call(elements, list.span),
         }
@@ -324,31 +482,7 @@ impl AsgBuilder {
         elements: &[SExpr],
         span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
-        if elements.len() < 3 {
-            return Err(ParseError::wrong_arity(
-                span,
-                "+",
-                "at least 2",
-                elements.len() - 1,
-            ));
-        }
-
-        let mut result = self.build_expr(&elements[1])?;
-        for elem in &elements[2..] {
-            let right = self.build_expr(elem)?;
-            let id = self.alloc_id();
-            self.asg.add_node(Node::with_edges(
-                id,
-                NodeType::BinaryOperation,
-                None,
-                vec![
-                    Edge::new(EdgeType::FirstOperand, result),
-                    Edge::new(EdgeType::SecondOperand, right),
-                ],
-            ));
-            result = id;
-        }
-        Ok(result)
+        self.build_left_assoc(elements, NodeType::BinaryOperation, "+", span)
     }
 
     /// Построить variadic умножение: (* a b c ...) = (* (* a b) c) ...
@@ -356,11 +490,26 @@ impl AsgBuilder {
         &mut self,
         elements: &[SExpr],
         span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        self.build_left_assoc(elements, NodeType::Mul, "*", span)
+    }
+
+    /// Построить левоассоциативную цепочку бинарных операций:
+    /// (op a b c d) = (op (op (op a b) c) d).
+    ///
+    /// Для неассоциативных операций это определяет порядок вычисления:
+    /// (- 10 3 2) = (10 - 3) - 2 = 5, (/ 20 2 5) = (20 / 2) / 5 = 2.
+    fn build_left_assoc(
+        &mut self,
+        elements: &[SExpr],
+        node_type: NodeType,
+        name: &str,
+        span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
         if elements.len() < 3 {
             return Err(ParseError::wrong_arity(
                 span,
-                "*",
+                name,
                 "at least 2",
                 elements.len() - 1,
             ));
@@ -370,14 +519,15 @@ impl AsgBuilder {
         for elem in &elements[2..] {
             let right = self.build_expr(elem)?;
             let id = self.alloc_id();
-            self.asg.add_node(Node::with_edges(
+            self.asg.add_node(Node::with_edges_and_span(
                 id,
-                NodeType::Mul,
+                node_type,
                 None,
                 vec![
                     Edge::new(EdgeType::FirstOperand, result),
                     Edge::new(EdgeType::SecondOperand, right),
                 ],
+                span,
             ));
             result = id;
         }
@@ -484,6 +634,7 @@ impl AsgBuilder {
     }
 
     /// Построить бинарную или унарную операцию (для -).
+    /// (- a) — унарная, (- a b c ...) — левоассоциативная цепочка.
     fn build_binop_or_unary(
         &mut self,
         elements: &[SExpr],
@@ -493,16 +644,52 @@ impl AsgBuilder {
     ) -> Result<NodeID, ParseError> {
         match elements.len() {
             2 => self.build_unop(elements, unop_type, span),
-            3 => self.build_binop(elements, binop_type, span),
+            n if n >= 3 => self.build_left_assoc(elements, binop_type, "-", span),
             _ => Err(ParseError::wrong_arity(
                 span,
                 "-",
-                "1 or 2",
+                "at least 1",
                 elements.len() - 1,
             )),
         }
     }
 
+    /// Построить явное приведение типа: (cast Float x) или (cast Int x)
+    fn build_cast(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        if elements.len() != 3 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "cast",
+                "2",
+                elements.len() - 1,
+            ));
+        }
+
+        let target = elements[1]
+            .as_ident()
+            .filter(|t| matches!(*t, "Int" | "Float" | "Decimal"))
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: elements[1].span(),
+                message: "Expected cast target type (Int, Float or Decimal)".to_string(),
+            })?;
+
+        let operand_id = self.build_expr(&elements[2])?;
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::Cast,
+            Some(target.as_bytes().to_vec()),
+            vec![Edge::new(EdgeType::ApplicationArgument, operand_id)],
+            span,
+        ));
+        Ok(id)
+    }
+
     /// Построить let.
     fn build_let(
         &mut self,
@@ -541,11 +728,12 @@ impl AsgBuilder {
         let value_id = self.build_expr(value_expr)?;
 
         let id = self.alloc_id();
-        let node = Node::with_edges(
+        let node = Node::with_edges_and_span(
             id,
             NodeType::Variable,
             Some(name.as_bytes().to_vec()),
             vec![Edge::new(EdgeType::VarValue, value_id)],
+            span,
         );
         self.asg.add_node(node);
         Ok(id)
@@ -571,32 +759,32 @@ impl AsgBuilder {
         // Вычисляем выражение-источник
         let value_id = self.build_expr(value_expr)?;
 
-        // Кодируем имена: количество + имена через нуль-байт
-        let mut payload = Vec::new();
-        payload.extend_from_slice(&(names.len() as u32).to_le_bytes());
-        for name in &names {
-            payload.extend_from_slice(name.as_bytes());
-            payload.push(0); // разделитель
-        }
-
         let id = self.alloc_id();
         let node = Node::with_edges(
             id,
             NodeType::LetDestructure,
-            Some(payload),
+            Some(destructure_payload(&names)),
             vec![Edge::new(EdgeType::VarValue, value_id)],
         );
         self.asg.add_node(node);
         Ok(id)
     }
 
-    /// Построить set (присваивание).
+    /// Построить set: присваивание `(set place value)` или, если первый аргумент не
+    /// имя и не место, литерал множества `(set 1 2 3)`.
     fn build_set(
         &mut self,
         elements: &[SExpr],
         span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
-        if elements.len() != 3 {
+        let is_assignment = match elements.get(1) {
+            Some(SExpr::List(place)) => self.is_place(place),
+            Some(other) => other
+                .as_ident()
+                .is_some_and(|name| !matches!(name, "true" | "false")),
+            None => false,
+        };
+        if is_assignment && elements.len() != 3 {
             return Err(ParseError::wrong_arity(
                 span,
                 "set",
@@ -604,38 +792,154 @@ impl AsgBuilder {
                 elements.len() - 1,
             ));
         }
+        if !is_assignment {
+            let mut edges = Vec::new();
+            for elem_expr in &elements[1..] {
+                let elem_id = self.build_expr(elem_expr)?;
+                edges.push(Edge::new(EdgeType::ArrayElement, elem_id));
+            }
+            let id = self.alloc_id();
+            self.asg.add_node(Node::with_edges_and_span(
+                id,
+                NodeType::SetNew,
+                None,
+                edges,
+                span,
+            ));
+            return Ok(id);
+        }
 
-        let name = elements[1]
+        // Цель: имя переменной или место внутри неё:
+        //   place ::= name | (index place i) | (dict-get place key) | (field place name)
+        let target_id = match &elements[1] {
+            SExpr::List(place) if self.is_place(place) => self.build_expr(&elements[1])?,
+            _ => {
+                let name = elements[1]
+                    .as_ident()
+                    .ok_or_else(|| ParseError::InvalidLiteral {
+                        span: elements[1].span(),
+                        message: "Expected identifier or (index/dict-get/field ...) place"
+                            .to_string(),
+                    })?;
+
+                // Создаем VarRef для цели
+                let target_id = self.alloc_id();
+                self.asg.add_node(Node::new(
+                    target_id,
+                    NodeType::VarRef,
+                    Some(name.as_bytes().to_vec()),
+                ));
+                target_id
+            }
+        };
+
+        let value_id = self.build_expr(&elements[2])?;
+
+        let id = self.alloc_id();
+        let node = Node::with_edges(
+            id,
+            NodeType::Assign,
+            None,
+            vec![
+                Edge::new(EdgeType::AssignTarget, target_id),
+                Edge::new(EdgeType::AssignValue, value_id),
+            ],
+        );
+        self.asg.add_node(node);
+        Ok(id)
+    }
+
+    /// Построить составное присваивание: (inc! x), (dec! x) или (add-assign x n) и т.п.
+    /// Значение — узел `op` над (x, n); для inc!/dec! n = 1.
+    fn build_compound_assign(
+        &mut self,
+        elements: &[SExpr],
+        op: NodeType,
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        let name = elements[0].as_ident().unwrap_or_default();
+        let step = matches!(name, "inc!" | "dec!");
+        let expected = if step { 2 } else { 3 };
+        if elements.len() != expected {
+            return Err(ParseError::wrong_arity(
+                span,
+                name,
+                (expected - 1).to_string(),
+                elements.len() - 1,
+            ));
+        }
+
+        let var_name = elements[1]
             .as_ident()
             .ok_or_else(|| ParseError::InvalidLiteral {
                 span: elements[1].span(),
-                message: "Expected identifier for variable name".to_string(),
+                message: "Expected variable name".to_string(),
             })?;
 
-        // Создаем VarRef для цели
         let target_id = self.alloc_id();
         self.asg.add_node(Node::new(
             target_id,
             NodeType::VarRef,
-            Some(name.as_bytes().to_vec()),
+            Some(var_name.as_bytes().to_vec()),
+        ));
+        let current_id = self.alloc_id();
+        self.asg.add_node(Node::new(
+            current_id,
+            NodeType::VarRef,
+            Some(var_name.as_bytes().to_vec()),
         ));
+        let amount_id = if step {
+            let one_id = self.alloc_id();
+            self.asg.add_node(Node::new(
+                one_id,
+                NodeType::LiteralInt,
+                Some(1i64.to_le_bytes().to_vec()),
+            ));
+            one_id
+        } else {
+            self.build_expr(&elements[2])?
+        };
 
-        let value_id = self.build_expr(&elements[2])?;
+        let value_id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            value_id,
+            op,
+            None,
+            vec![
+                Edge::new(EdgeType::FirstOperand, current_id),
+                Edge::new(EdgeType::SecondOperand, amount_id),
+            ],
+            span,
+        ));
 
         let id = self.alloc_id();
-        let node = Node::with_edges(
+        self.asg.add_node(Node::with_edges_and_span(
             id,
-            NodeType::Assign,
+            NodeType::CompoundAssign,
             None,
             vec![
                 Edge::new(EdgeType::AssignTarget, target_id),
                 Edge::new(EdgeType::AssignValue, value_id),
             ],
-        );
-        self.asg.add_node(node);
+            span,
+        ));
         Ok(id)
     }
 
+    /// Является ли список допустимым местом для set: (index|nth|dict-get|field place k),
+    /// где place — имя или снова место.
+    fn is_place(&self, list: &Spanned<Vec<SExpr>>) -> bool {
+        let items = &list.value;
+        let head = items.first().and_then(|e| e.as_ident());
+        if !matches!(head, Some("index" | "nth" | "dict-get" | "field")) || items.len() != 3 {
+            return false;
+        }
+        match &items[1] {
+            SExpr::List(inner) => self.is_place(inner),
+            other => other.as_ident().is_some(),
+        }
+    }
+
     /// Построить if.
     fn build_if(
         &mut self,
@@ -670,6 +974,55 @@ impl AsgBuilder {
         Ok(id)
     }
 
+    /// Построить when/unless — условие с одной ветвью, сахар над If.
+    /// (when cond body...) выполняет тело при true, (unless cond body...) — при false;
+    /// в остальных случаях результат Unit.
+    fn build_when(
+        &mut self,
+        elements: &[SExpr],
+        negate: bool,
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        if elements.len() < 3 {
+            return Err(ParseError::wrong_arity(
+                span,
+                if negate { "unless" } else { "when" },
+                "2+",
+                elements.len() - 1,
+            ));
+        }
+
+        let cond_id = self.build_expr(&elements[1])?;
+        // elements[1..] — условие играет роль "головы", которую build_do пропускает
+        let body_id = self.build_do(&elements[1..], span)?;
+
+        let edges = if negate {
+            let unit_id = self.alloc_id();
+            self.asg
+                .add_node(Node::with_span(unit_id, NodeType::LiteralUnit, None, span));
+            vec![
+                Edge::new(EdgeType::Condition, cond_id),
+                Edge::new(EdgeType::ThenBranch, unit_id),
+                Edge::new(EdgeType::ElseBranch, body_id),
+            ]
+        } else {
+            vec![
+                Edge::new(EdgeType::Condition, cond_id),
+                Edge::new(EdgeType::ThenBranch, body_id),
+            ]
+        };
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::If,
+            None,
+            edges,
+            span,
+        ));
+        Ok(id)
+    }
+
     /// Построить do (sequence of expressions).
     fn build_do(
         &mut self,
@@ -768,6 +1121,76 @@ impl AsgBuilder {
         Ok(id)
     }
 
+    /// Построить loop-indexed: (loop-indexed i cond body), i — номер итерации с нуля.
+    fn build_loop_indexed(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        if elements.len() != 4 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "loop-indexed",
+                "3",
+                elements.len() - 1,
+            ));
+        }
+
+        let index_name = elements[1]
+            .as_ident()
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: elements[1].span(),
+                message: "Expected identifier for loop index".to_string(),
+            })?;
+        let cond_id = self.build_expr(&elements[2])?;
+        let body_id = self.build_expr(&elements[3])?;
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges(
+            id,
+            NodeType::Loop,
+            Some(index_name.as_bytes().to_vec()),
+            vec![
+                Edge::new(EdgeType::Condition, cond_id),
+                Edge::new(EdgeType::LoopBody, body_id),
+            ],
+        ));
+        Ok(id)
+    }
+
+    /// Построить loop-timeout: (loop-timeout ms cond body) — while с ограничением по времени.
+    fn build_loop_timeout(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        if elements.len() != 4 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "loop-timeout",
+                "3",
+                elements.len() - 1,
+            ));
+        }
+
+        let timeout_id = self.build_expr(&elements[1])?;
+        let cond_id = self.build_expr(&elements[2])?;
+        let body_id = self.build_expr(&elements[3])?;
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges(
+            id,
+            NodeType::Loop,
+            None,
+            vec![
+                Edge::new(EdgeType::LoopTimeout, timeout_id),
+                Edge::new(EdgeType::Condition, cond_id),
+                Edge::new(EdgeType::LoopBody, body_id),
+            ],
+        ));
+        Ok(id)
+    }
+
     /// Построить break.
     fn build_break(
         &mut self,
@@ -862,36 +1285,77 @@ impl AsgBuilder {
 
         // Создаем узлы параметров
         for param_expr in params_list {
-            let param_name = param_expr
-                .as_ident()
-                .ok_or_else(|| ParseError::InvalidLiteral {
-                    span: param_expr.span(),
-                    message: "Expected identifier for parameter name".to_string(),
-                })?;
-
-            let param_id = self.alloc_id();
-            self.asg.add_node(Node::new(
-                param_id,
-                NodeType::Parameter,
-                Some(param_name.as_bytes().to_vec()),
-            ));
+            let param_id = self.build_param(param_expr)?;
             edges.push(Edge::new(EdgeType::FunctionParameter, param_id));
         }
 
         // Строим тело функции
-        let body_id = self.build_expr(&elements[3])?;
+        let body_id = self.build_function_body(&elements[3])?;
         edges.push(Edge::new(EdgeType::FunctionBody, body_id));
 
         let id = self.alloc_id();
-        self.asg.add_node(Node::with_edges(
+        self.asg.add_node(Node::with_edges_and_span(
             id,
             NodeType::Function,
             Some(name.as_bytes().to_vec()),
             edges,
+            span,
+        ));
+        Ok(id)
+    }
+
+    /// Построить тело функции. Если в нём (не считая вложенных функций) есть
+    /// yield, тело оборачивается в узел Generator.
+    fn build_function_body(&mut self, body: &SExpr) -> Result<NodeID, ParseError> {
+        let outer = std::mem::replace(&mut self.yield_seen, false);
+        let body_id = self.build_expr(body);
+        let is_generator = std::mem::replace(&mut self.yield_seen, outer);
+        let body_id = body_id?;
+        if !is_generator {
+            return Ok(body_id);
+        }
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges(
+            id,
+            NodeType::Generator,
+            None,
+            vec![Edge::new(EdgeType::FunctionBody, body_id)],
         ));
         Ok(id)
     }
 
+    /// Построить параметр: `name` или `(name default)` со значением по умолчанию.
+    fn build_param(&mut self, param_expr: &SExpr) -> Result<NodeID, ParseError> {
+        let (name_expr, default) = match param_expr.as_list() {
+            Some([name, default]) => (name, Some(default)),
+            _ => (param_expr, None),
+        };
+        let param_name = name_expr
+            .as_ident()
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: param_expr.span(),
+                message: "Expected identifier or (name default) for parameter".to_string(),
+            })?;
+
+        let edges = match default {
+            Some(default) => vec![Edge::new(
+                EdgeType::ParameterDefault,
+                self.build_expr(default)?,
+            )],
+            None => Vec::new(),
+        };
+
+        let param_id = self.alloc_id();
+        self.asg.add_node(Node::with_edges(
+            param_id,
+            NodeType::Parameter,
+            Some(param_name.as_bytes().to_vec()),
+            edges,
+        ));
+        Ok(param_id)
+    }
+
     /// Построить lambda.
     fn build_lambda(
         &mut self,
@@ -908,38 +1372,64 @@ impl AsgBuilder {
             ));
         }
 
-        let params_list = elements[1]
-            .as_list()
+        self.build_lambda_node(&elements[1], &elements[2], None)
+    }
+
+    /// Построить lambda, видящую себя под именем `self_name`.
+    fn build_rec_lambda(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        // (rec-lambda self (params...) body)
+        if elements.len() != 4 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "rec-lambda",
+                "3",
+                elements.len() - 1,
+            ));
+        }
+
+        let self_name = elements[1]
+            .as_ident()
             .ok_or_else(|| ParseError::InvalidLiteral {
                 span: elements[1].span(),
-                message: "Expected parameter list".to_string(),
+                message: "Expected identifier for self-reference name".to_string(),
             })?;
 
+        self.build_lambda_node(
+            &elements[2],
+            &elements[3],
+            Some(self_name.as_bytes().to_vec()),
+        )
+    }
+
+    /// Узел Lambda из списка параметров и тела; payload — имя самоссылки (rec-lambda).
+    fn build_lambda_node(
+        &mut self,
+        params: &SExpr,
+        body: &SExpr,
+        payload: Option<Vec<u8>>,
+    ) -> Result<NodeID, ParseError> {
+        let params_list = params.as_list().ok_or_else(|| ParseError::InvalidLiteral {
+            span: params.span(),
+            message: "Expected parameter list".to_string(),
+        })?;
+
         let mut edges = Vec::new();
 
         for param_expr in params_list {
-            let param_name = param_expr
-                .as_ident()
-                .ok_or_else(|| ParseError::InvalidLiteral {
-                    span: param_expr.span(),
-                    message: "Expected identifier for parameter name".to_string(),
-                })?;
-
-            let param_id = self.alloc_id();
-            self.asg.add_node(Node::new(
-                param_id,
-                NodeType::Parameter,
-                Some(param_name.as_bytes().to_vec()),
-            ));
+            let param_id = self.build_param(param_expr)?;
             edges.push(Edge::new(EdgeType::FunctionParameter, param_id));
         }
 
-        let body_id = self.build_expr(&elements[2])?;
+        let body_id = self.build_function_body(body)?;
         edges.push(Edge::new(EdgeType::FunctionBody, body_id));
 
         let id = self.alloc_id();
         self.asg
-            .add_node(Node::with_edges(id, NodeType::Lambda, None, edges));
+            .add_node(Node::with_edges(id, NodeType::Lambda, payload, edges));
         Ok(id)
     }
 
@@ -957,27 +1447,52 @@ impl AsgBuilder {
             return Ok(id);
         }
 
-        // Создаем VarRef для имени функции
-        let func_name = elements[0]
-            .as_ident()
-            .ok_or_else(|| ParseError::InvalidLiteral {
-                span: elements[0].span(),
-                message: "Expected function name".to_string(),
-            })?;
-
-        let target_id = self.alloc_id();
-        self.asg.add_node(Node::new(
-            target_id,
-            NodeType::VarRef,
-            Some(func_name.as_bytes().to_vec()),
-        ));
+        // Создаем VarRef для имени функции, иначе вычисляем цель как выражение
+        let target_id = if let Some(func_name) = elements[0].as_ident() {
+            let target_id = self.alloc_id();
+            self.asg.add_node(Node::new(
+                target_id,
+                NodeType::VarRef,
+                Some(func_name.as_bytes().to_vec()),
+            ));
+            target_id
+        } else {
+            self.build_expr(&elements[0])?
+        };
 
         let mut edges = vec![Edge::new(EdgeType::CallTarget, target_id)];
 
-        // Строим аргументы
-        for arg_expr in &elements[1..] {
+        // Строим аргументы: сначала позиционные, затем именованные `:name value`
+        let mut args = &elements[1..];
+        let mut seen_keyword = false;
+        while let Some(arg_expr) = args.first() {
+            if let Some(name) = keyword_name(args) {
+                let Some(value_expr) = args.get(2) else {
+                    return Err(ParseError::UnexpectedEof {
+                        span: args[1].span(),
+                        message: format!("expected value after keyword :{}", name),
+                    });
+                };
+                let arg_id = self.build_expr(value_expr)?;
+                edges.push(Edge::with_payload(
+                    EdgeType::CallArgument,
+                    arg_id,
+                    name.as_bytes().to_vec(),
+                ));
+                seen_keyword = true;
+                args = &args[3..];
+                continue;
+            }
+            if seen_keyword {
+                return Err(ParseError::UnexpectedToken {
+                    span: arg_expr.span(),
+                    expected: "keyword argument".to_string(),
+                    found: format_sexpr(arg_expr),
+                });
+            }
             let arg_id = self.build_expr(arg_expr)?;
             edges.push(Edge::new(EdgeType::CallArgument, arg_id));
+            args = &args[1..];
         }
 
         let id = self.alloc_id();
@@ -986,6 +1501,30 @@ impl AsgBuilder {
         Ok(id)
     }
 
+    /// Построить узел расширения: (ext:name arg1 arg2 ...)
+    fn build_extension(
+        &mut self,
+        name: &str,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        let mut edges = Vec::new();
+        for arg_expr in &elements[1..] {
+            let arg_id = self.build_expr(arg_expr)?;
+            edges.push(Edge::new(EdgeType::ApplicationArgument, arg_id));
+        }
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::Extension(extension_code(name)),
+            Some(name.as_bytes().to_vec()),
+            edges,
+            span,
+        ));
+        Ok(id)
+    }
+
     /// Построить array.
     fn build_array(
         &mut self,
@@ -1218,52 +1757,182 @@ impl AsgBuilder {
         let pred_id = self.build_expr(&elements[2])?;
 
         let id = self.alloc_id();
-        self.asg.add_node(Node::with_edges(
+        self.asg.add_node(Node::with_edges(
+            id,
+            NodeType::ArrayFilter,
+            None,
+            vec![
+                Edge::new(EdgeType::SourceArray, array_id),
+                Edge::new(EdgeType::FilterPredicate, pred_id),
+            ],
+        ));
+        Ok(id)
+    }
+
+    /// Построить reduce: (reduce array init fn)
+    fn build_reduce(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        // (reduce array init fn)
+        if elements.len() != 4 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "reduce",
+                "3",
+                elements.len() - 1,
+            ));
+        }
+
+        let array_id = self.build_expr(&elements[1])?;
+        let init_id = self.build_expr(&elements[2])?;
+        let fn_id = self.build_expr(&elements[3])?;
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges(
+            id,
+            NodeType::ArrayReduce,
+            None,
+            vec![
+                Edge::new(EdgeType::SourceArray, array_id),
+                Edge::new(EdgeType::ReduceInit, init_id),
+                Edge::new(EdgeType::ReduceFunction, fn_id),
+            ],
+        ));
+        Ok(id)
+    }
+
+    /// Построить dict-update: (dict-update d key f) или (dict-update d key f default)
+    fn build_dict_update(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        if elements.len() != 4 && elements.len() != 5 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "dict-update",
+                "3 or 4",
+                elements.len() - 1,
+            ));
+        }
+
+        let mut edges = Vec::new();
+        for arg in &elements[1..] {
+            let arg_id = self.build_expr(arg)?;
+            edges.push(Edge::new(EdgeType::ApplicationArgument, arg_id));
+        }
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::DictUpdate,
+            None,
+            edges,
+            span,
+        ));
+        Ok(id)
+    }
+
+    /// Построить call-method: (call-method obj "name" args...)
+    fn build_call_method(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        if elements.len() < 3 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "call-method",
+                "at least 2",
+                elements.len() - 1,
+            ));
+        }
+
+        let mut edges = Vec::new();
+        for arg in &elements[1..] {
+            let arg_id = self.build_expr(arg)?;
+            edges.push(Edge::new(EdgeType::ApplicationArgument, arg_id));
+        }
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
             id,
-            NodeType::ArrayFilter,
+            NodeType::CallMethod,
             None,
-            vec![
-                Edge::new(EdgeType::SourceArray, array_id),
-                Edge::new(EdgeType::FilterPredicate, pred_id),
-            ],
+            edges,
+            span,
         ));
         Ok(id)
     }
 
-    /// Построить reduce: (reduce array init fn)
-    fn build_reduce(
+    /// Построить scan: (scan fn init array)
+    fn build_scan(
         &mut self,
         elements: &[SExpr],
         span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
-        // (reduce array init fn)
         if elements.len() != 4 {
             return Err(ParseError::wrong_arity(
                 span,
-                "reduce",
+                "scan",
                 "3",
                 elements.len() - 1,
             ));
         }
 
-        let array_id = self.build_expr(&elements[1])?;
+        let fn_id = self.build_function_arg(&elements[1])?;
         let init_id = self.build_expr(&elements[2])?;
-        let fn_id = self.build_expr(&elements[3])?;
+        let array_id = self.build_expr(&elements[3])?;
 
         let id = self.alloc_id();
-        self.asg.add_node(Node::with_edges(
+        self.asg.add_node(Node::with_edges_and_span(
             id,
-            NodeType::ArrayReduce,
+            NodeType::ArrayScan,
             None,
             vec![
                 Edge::new(EdgeType::SourceArray, array_id),
                 Edge::new(EdgeType::ReduceInit, init_id),
                 Edge::new(EdgeType::ReduceFunction, fn_id),
             ],
+            span,
         ));
         Ok(id)
     }
 
+    /// Построить аргумент-функцию. Символ бинарного оператора (`+`, `*`, ...)
+    /// разворачивается в (lambda (a b) (op a b)).
+    fn build_function_arg(&mut self, expr: &SExpr) -> Result<NodeID, ParseError> {
+        let op = match expr.as_symbol() {
+            Some(op) => op,
+            None => return self.build_expr(expr),
+        };
+
+        let span = expr.span();
+        let ident = |name: &str| {
+            SExpr::Atom(Spanned {
+                value: Atom::Ident(name.to_string()),
+                span,
+            })
+        };
+        let list = |items: Vec<SExpr>| SExpr::List(Spanned { value: items, span });
+
+        let lambda = list(vec![
+            ident("lambda"),
+            list(vec![ident("__a"), ident("__b")]),
+            list(vec![
+                SExpr::Atom(Spanned {
+                    value: Atom::Symbol(op.to_string()),
+                    span,
+                }),
+                ident("__a"),
+                ident("__b"),
+            ]),
+        ]);
+        self.build_expr(&lambda)
+    }
+
     /// Построить унарную операцию (один аргумент)
     fn build_unary(
         &mut self,
@@ -1326,19 +1995,16 @@ impl AsgBuilder {
         Ok(id)
     }
 
-    /// Построить str-replace: (str-replace s from to)
+    /// Построить замену подстроки: (str-replace-all s from to), (str-replace-first s from to)
     fn build_str_replace(
         &mut self,
         elements: &[SExpr],
+        node_type: NodeType,
+        name: &str,
         span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
         if elements.len() != 4 {
-            return Err(ParseError::wrong_arity(
-                span,
-                "str-replace",
-                "3",
-                elements.len() - 1,
-            ));
+            return Err(ParseError::wrong_arity(span, name, "3", elements.len() - 1));
         }
 
         let str_id = self.build_expr(&elements[1])?;
@@ -1348,7 +2014,7 @@ impl AsgBuilder {
         let id = self.alloc_id();
         self.asg.add_node(Node::with_edges(
             id,
-            NodeType::StringReplace,
+            node_type,
             None,
             vec![
                 Edge::new(EdgeType::ApplicationArgument, str_id),
@@ -1521,35 +2187,43 @@ impl AsgBuilder {
             ));
         }
 
-        let var_name = elements[1]
-            .as_ident()
-            .ok_or_else(|| ParseError::InvalidLiteral {
-                span: elements[1].span(),
-                message: "Expected variable name".to_string(),
-            })?;
+        // (for x iterable body) или (for [k v] iterable body) — разбор пары
+        let var_names = match &elements[1] {
+            SExpr::List(list) if list.value.len() == 2 => list
+                .value
+                .iter()
+                .map(SExpr::as_ident)
+                .collect::<Option<Vec<_>>>(),
+            var => var.as_ident().map(|name| vec![name]),
+        }
+        .ok_or_else(|| ParseError::InvalidLiteral {
+            span: elements[1].span(),
+            message: "Expected variable name or [a b]".to_string(),
+        })?;
 
         let iterable_id = self.build_expr(&elements[2])?;
         let body_id = self.build_expr(&elements[3])?;
 
-        // Create variable node for loop variable
+        // Одно ребро LoopInit: переменная цикла или паттерн пары
         let var_id = self.alloc_id();
-        self.asg.add_node(Node::new(
-            var_id,
-            NodeType::Variable,
-            Some(var_name.as_bytes().to_vec()),
-        ));
+        let var_node = match var_names.as_slice() {
+            [name] => Node::new(var_id, NodeType::Variable, Some(name.as_bytes().to_vec())),
+            names => Node::new(
+                var_id,
+                NodeType::LetDestructure,
+                Some(destructure_payload(names)),
+            ),
+        };
+        self.asg.add_node(var_node);
+        let edges = vec![
+            Edge::new(EdgeType::LoopInit, var_id),
+            Edge::new(EdgeType::Condition, iterable_id),
+            Edge::new(EdgeType::LoopBody, body_id),
+        ];
 
         let id = self.alloc_id();
-        self.asg.add_node(Node::with_edges(
-            id,
-            NodeType::For,
-            None,
-            vec![
-                Edge::new(EdgeType::LoopInit, var_id),
-                Edge::new(EdgeType::Condition, iterable_id),
-                Edge::new(EdgeType::LoopBody, body_id),
-            ],
-        ));
+        self.asg
+            .add_node(Node::with_edges(id, NodeType::For, None, edges));
         Ok(id)
     }
 
@@ -1612,17 +2286,31 @@ impl AsgBuilder {
         elements: &[SExpr],
         span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
-        // (dict) or (dict k1 v1 k2 v2 ...)
-        if (elements.len() - 1) % 2 != 0 {
-            return Err(ParseError::InvalidLiteral {
-                span,
-                message: "Dict requires even number of arguments (key-value pairs)".to_string(),
-            });
-        }
-
+        // (dict) or (dict k1 v1 k2 v2 ...), с распаковкой: (dict ...base k v)
+        // (dict-with base k v ...) — то же, что (dict ...base k v ...)
         let mut edges = Vec::new();
         let mut i = 1;
-        while i + 1 < elements.len() {
+        if elements[0].as_ident() == Some("dict-with") {
+            let base = elements.get(1).ok_or_else(|| {
+                ParseError::wrong_arity(span, "dict-with", "at least 1", elements.len() - 1)
+            })?;
+            let base_id = self.build_expr(base)?;
+            edges.push(Edge::new(EdgeType::ApplicationArgument, base_id));
+            i = 2;
+        }
+        while i < elements.len() {
+            if let Some(base) = spread_target(&elements[i..]) {
+                let base_id = self.build_expr(base)?;
+                edges.push(Edge::new(EdgeType::ApplicationArgument, base_id));
+                i += 2;
+                continue;
+            }
+            if i + 1 >= elements.len() {
+                return Err(ParseError::InvalidLiteral {
+                    span,
+                    message: "Dict requires even number of arguments (key-value pairs)".to_string(),
+                });
+            }
             let key_id = self.build_expr(&elements[i])?;
             let val_id = self.build_expr(&elements[i + 1])?;
             edges.push(Edge::new(EdgeType::FirstOperand, key_id));
@@ -1631,8 +2319,13 @@ impl AsgBuilder {
         }
 
         let id = self.alloc_id();
-        self.asg
-            .add_node(Node::with_edges(id, NodeType::Dict, None, edges));
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::Dict,
+            None,
+            edges,
+            span,
+        ));
         Ok(id)
     }
 
@@ -1696,25 +2389,27 @@ impl AsgBuilder {
         elements: &[SExpr],
         span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
-        // (print expr)
-        if elements.len() != 2 {
+        // (print expr...)
+        if elements.len() < 2 {
             return Err(ParseError::wrong_arity(
                 span,
                 "print",
-                "1",
+                "at least 1",
                 elements.len() - 1,
             ));
         }
 
-        let expr_id = self.build_expr(&elements[1])?;
+        let edges = elements[1..]
+            .iter()
+            .map(|expr| {
+                self.build_expr(expr)
+                    .map(|id| Edge::new(EdgeType::ApplicationArgument, id))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         let id = self.alloc_id();
-        self.asg.add_node(Node::with_edges(
-            id,
-            NodeType::Print,
-            None,
-            vec![Edge::new(EdgeType::ApplicationArgument, expr_id)],
-        ));
+        self.asg
+            .add_node(Node::with_edges(id, NodeType::Print, None, edges));
         Ok(id)
     }
 
@@ -1904,6 +2599,38 @@ impl AsgBuilder {
         Ok(id)
     }
 
+    /// Построить привязанное поле: (gui-bind var)
+    fn build_gui_bind(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        if elements.len() != 2 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "gui-bind",
+                "1",
+                elements.len() - 1,
+            ));
+        }
+
+        let var_name = elements[1]
+            .as_ident()
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: elements[1].span(),
+                message: "Expected variable name for gui-bind".to_string(),
+            })?;
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_span(
+            id,
+            NodeType::GuiBind,
+            Some(var_name.as_bytes().to_vec()),
+            span,
+        ));
+        Ok(id)
+    }
+
     /// Построить GUI container (vbox, hbox): (vbox child1 child2 ...)
     fn build_gui_container(
         &mut self,
@@ -2063,6 +2790,183 @@ impl AsgBuilder {
         Ok(id)
     }
 
+    /// Построить defrecord (объявление схемы записи).
+    fn build_defrecord(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        // (defrecord Name (field1 field2 ...))
+        if elements.len() != 3 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "defrecord",
+                "2",
+                elements.len() - 1,
+            ));
+        }
+
+        let name = elements[1]
+            .as_ident()
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: elements[1].span(),
+                message: "Expected record name".to_string(),
+            })?;
+
+        let field_list = elements[2]
+            .as_list()
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: elements[2].span(),
+                message: "Expected (field1 field2 ...) list".to_string(),
+            })?;
+
+        let mut edges = Vec::new();
+        for field_expr in field_list {
+            let field_name = field_expr
+                .as_ident()
+                .ok_or_else(|| ParseError::InvalidLiteral {
+                    span: field_expr.span(),
+                    message: "Expected field name".to_string(),
+                })?;
+
+            let field_id = self.alloc_id();
+            self.asg.add_node(Node::new(
+                field_id,
+                NodeType::RecordField,
+                Some(field_name.as_bytes().to_vec()),
+            ));
+            edges.push(Edge::new(EdgeType::RecordFieldDef, field_id));
+        }
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::RecordDef,
+            Some(name.as_bytes().to_vec()),
+            edges,
+            span,
+        ));
+        Ok(id)
+    }
+
+    /// Построить defenum (объявление суммы типов).
+    fn build_defenum(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        // (defenum Name (Variant1 field ...) (Variant2 ...) ...)
+        if elements.len() < 3 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "defenum",
+                "at least 2",
+                elements.len() - 1,
+            ));
+        }
+
+        let name = elements[1]
+            .as_ident()
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: elements[1].span(),
+                message: "Expected enum name".to_string(),
+            })?;
+
+        let mut edges = Vec::new();
+        for variant_expr in &elements[2..] {
+            let variant = variant_expr
+                .as_list()
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| ParseError::InvalidLiteral {
+                    span: variant_expr.span(),
+                    message: "Expected (Variant field ...) list".to_string(),
+                })?;
+
+            let mut names = Vec::with_capacity(variant.len());
+            for part in variant {
+                let part_name = part.as_ident().ok_or_else(|| ParseError::InvalidLiteral {
+                    span: part.span(),
+                    message: "Expected variant or field name".to_string(),
+                })?;
+                names.push(part_name);
+            }
+
+            let mut field_edges = Vec::new();
+            for field_name in &names[1..] {
+                let field_id = self.alloc_id();
+                self.asg.add_node(Node::new(
+                    field_id,
+                    NodeType::RecordField,
+                    Some(field_name.as_bytes().to_vec()),
+                ));
+                field_edges.push(Edge::new(EdgeType::RecordFieldDef, field_id));
+            }
+
+            let variant_id = self.alloc_id();
+            self.asg.add_node(Node::with_edges_and_span(
+                variant_id,
+                NodeType::EnumVariant,
+                Some(names[0].as_bytes().to_vec()),
+                field_edges,
+                variant_expr.span(),
+            ));
+            edges.push(Edge::new(EdgeType::ApplicationArgument, variant_id));
+        }
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::EnumDef,
+            Some(name.as_bytes().to_vec()),
+            edges,
+            span,
+        ));
+        Ok(id)
+    }
+
+    /// Построить with-field (копия записи с заменённым полем).
+    fn build_with_field(
+        &mut self,
+        elements: &[SExpr],
+        span: super::token::Span,
+    ) -> Result<NodeID, ParseError> {
+        // (with-field record field-name value), имя поля — идентификатор или строка
+        if elements.len() != 4 {
+            return Err(ParseError::wrong_arity(
+                span,
+                "with-field",
+                "3",
+                elements.len() - 1,
+            ));
+        }
+
+        let record_id = self.build_expr(&elements[1])?;
+
+        let field_name = elements[2]
+            .as_ident()
+            .or_else(|| elements[2].as_string())
+            .ok_or_else(|| ParseError::InvalidLiteral {
+                span: elements[2].span(),
+                message: "Expected field name".to_string(),
+            })?
+            .to_string();
+
+        let value_id = self.build_expr(&elements[3])?;
+
+        let id = self.alloc_id();
+        self.asg.add_node(Node::with_edges_and_span(
+            id,
+            NodeType::RecordUpdate,
+            Some(field_name.into_bytes()),
+            vec![
+                Edge::new(EdgeType::RecordFieldAccess, record_id),
+                Edge::new(EdgeType::VarValue, value_id),
+            ],
+            span,
+        ));
+        Ok(id)
+    }
+
     /// Построить match.
     fn build_match(
         &mut self,
@@ -2175,11 +3079,12 @@ impl AsgBuilder {
         }
 
         let id = self.alloc_id();
-        self.asg.add_node(Node::with_edges(
+        self.asg.add_node(Node::with_edges_and_span(
             id,
             NodeType::Module,
             Some(name.as_bytes().to_vec()),
             edges,
+            span,
         ));
         Ok(id)
     }
@@ -2191,11 +3096,11 @@ impl AsgBuilder {
         span: super::token::Span,
     ) -> Result<NodeID, ParseError> {
         // (import "path/to/file.asg") or (import module-name)
-        if elements.len() < 2 || elements.len() > 4 {
+        if elements.len() < 2 {
             return Err(ParseError::wrong_arity(
                 span,
                 "import",
-                "1-3",
+                "at least 1",
                 elements.len() - 1,
             ));
         }
@@ -2212,32 +3117,42 @@ impl AsgBuilder {
             });
         };
 
-        // Опционально: (import "path" as alias)
-        let alias = if elements.len() == 4 {
-            let as_keyword = elements[2].as_ident().unwrap_or_default();
-            if as_keyword != "as" {
+        // Форма импорта в payload: path (всё), path|alias (:as) или path|only:a,b (:only)
+        let rest = &elements[2..];
+        let payload = match (keyword_name(rest), rest) {
+            (_, []) => path.to_string(),
+            // Старая форма без двоеточия: (import "path" as alias)
+            (None, [keyword, alias]) if keyword.as_ident() == Some("as") => {
+                format!("{}|{}", path, import_alias(alias)?)
+            }
+            (Some("as"), [_, _, alias]) => format!("{}|{}", path, import_alias(alias)?),
+            (Some("only"), [_, _, names]) => {
+                let names = match names {
+                    SExpr::List(list) => list
+                        .value
+                        .iter()
+                        .map(|name| {
+                            name.as_ident().ok_or_else(|| ParseError::InvalidLiteral {
+                                span: name.span(),
+                                message: "Expected name to import".to_string(),
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    other => {
+                        return Err(ParseError::InvalidLiteral {
+                            span: other.span(),
+                            message: "Expected list of names after :only".to_string(),
+                        })
+                    }
+                };
+                format!("{}|only:{}", path, names.join(","))
+            }
+            _ => {
                 return Err(ParseError::InvalidLiteral {
-                    span: elements[2].span(),
-                    message: "Expected 'as' keyword".to_string(),
-                });
+                    span: rest[0].span(),
+                    message: "Expected ':as alias' or ':only (names...)'".to_string(),
+                })
             }
-            Some(
-                elements[3]
-                    .as_ident()
-                    .ok_or_else(|| ParseError::InvalidLiteral {
-                        span: elements[3].span(),
-                        message: "Expected alias name".to_string(),
-                    })?,
-            )
-        } else {
-            None
-        };
-
-        // Сохраняем путь и опционально alias в payload
-        let payload = if let Some(a) = alias {
-            format!("{}|{}", path, a)
-        } else {
-            path.to_string()
         };
 
         let id = self.alloc_id();
@@ -2255,3 +3170,42 @@ impl Default for AsgBuilder {
         Self::new()
     }
 }
+
+/// Payload паттерна деструктуризации: 4 байта — число имён, затем имена через \0.
+fn destructure_payload(names: &[&str]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&(names.len() as u32).to_le_bytes());
+    for name in names {
+        payload.extend_from_slice(name.as_bytes());
+        payload.push(0); // разделитель
+    }
+    payload
+}
+
+/// Распаковываемое выражение, если список начинается с `...name` (многоточие вплотную к имени).
+fn spread_target(args: &[SExpr]) -> Option<&SExpr> {
+    let [ellipsis, target, ..] = args else {
+        return None;
+    };
+    ellipsis.as_symbol().filter(|s| *s == "...")?;
+    (ellipsis.span().end == target.span().start && target.as_ident().is_some()).then_some(target)
+}
+
+/// Псевдоним модуля из `(import "path" :as alias)`.
+fn import_alias(alias: &SExpr) -> Result<&str, ParseError> {
+    alias.as_ident().ok_or_else(|| ParseError::InvalidLiteral {
+        span: alias.span(),
+        message: "Expected alias name".to_string(),
+    })
+}
+
+/// Имя ключевого аргумента, если список начинается с `:name` (двоеточие вплотную к имени).
+fn keyword_name(args: &[SExpr]) -> Option<&str> {
+    let [colon, name, ..] = args else {
+        return None;
+    };
+    colon.as_symbol().filter(|s| *s == ":")?;
+    (colon.span().end == name.span().start)
+        .then(|| name.as_ident())
+        .flatten()
+}
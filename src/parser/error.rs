@@ -65,6 +65,7 @@ const KNOWN_FORMS: &[&str] = &[
     "//",
     "%",
     "neg",
+    "cast",
     // Comparison
     "==",
     "!=",
@@ -82,10 +83,20 @@ const KNOWN_FORMS: &[&str] = &[
     // Variables
     "let",
     "set",
+    "inc!",
+    "dec!",
+    "add-assign",
+    "sub-assign",
+    "mul-assign",
+    "div-assign",
     // Control
     "if",
+    "when",
+    "unless",
     "do",
     "while",
+    "loop-indexed",
+    "loop-timeout",
     "loop",
     "for",
     "break",
@@ -95,6 +106,8 @@ const KNOWN_FORMS: &[&str] = &[
     // Functions
     "fn",
     "lambda",
+    "rec-lambda",
+    "call-method",
     // Data
     "array",
     "index",
@@ -107,20 +120,70 @@ const KNOWN_FORMS: &[&str] = &[
     "map",
     "filter",
     "reduce",
+    "scan",
+    "compact",
+    "flatten-deep",
+    "flatten-n",
+    "shuffle",
+    "sample",
+    "sample-one",
+    "array-union",
+    "array-intersect",
+    "array-diff",
     "dict",
+    "dict-with",
+    "set-add",
+    "set-contains",
+    "set-union",
+    "set-intersect",
+    "set-diff",
+    "set-size",
     "record",
     "field",
+    "defrecord",
+    "defenum",
     // I/O
     "print",
+    "pp",
+    "print-json",
+    "debug",
     "input",
     "read-file",
     "write-file",
+    "time",
+    "bench",
+    "assert",
+    "assert-eq",
+    "requires",
+    "ensures",
+    "gen-int",
+    "for-all",
     // Strings
     "concat",
     "str-length",
     "substring",
     "str-split",
+    "str-lines",
+    "str-words",
+    "str-to-chars",
+    "chars-to-str",
+    "char-at",
+    "char->int",
+    "int->char",
+    "string->chars",
+    "base64-encode",
+    "base64-decode",
+    "hex-encode",
+    "hex-decode",
+    "sha256",
+    "md5",
     "str-join",
+    "str-count",
+    "str-find-all",
+    "str-replace",
+    "str-replace-all",
+    "str-replace-first",
+    "render",
     // Math
     "sqrt",
     "sin",
@@ -138,6 +201,16 @@ const KNOWN_FORMS: &[&str] = &[
     "throw",
     "is-error",
     "error-message",
+    "some",
+    "none",
+    "unwrap",
+    "unwrap-or",
+    "map-option",
+    "expect",
+    "is-some",
+    "ok",
+    "err",
+    "is-ok",
     // Pipeline
     "|>",
     "pipe",
@@ -148,6 +221,8 @@ const KNOWN_FORMS: &[&str] = &[
     "cycle",
     "take-lazy",
     "collect",
+    "yield",
+    "next",
     // Modules
     "module",
     "import",
@@ -0,0 +1,223 @@
+//! Необязательный инфиксный слой: `1 + 2 * 3` -> `(+ 1 (* 2 3))`.
+//!
+//! Исходник разбирается обычным [`Parser`](super::Parser), после чего
+//! цепочки `операнд оператор операнд ...` переписываются в префиксные списки
+//! по приоритетам; все бинарные операторы левоассоциативны. Инфиксными
+//! считаются верхний уровень и списки, второй элемент которых — бинарный
+//! оператор (`(a + b)`, `(- a * b)`), если весь список — одна цепочка.
+//! Остальные списки остаются формами, в которых переписываются элементы:
+//! `(print (a + b))`, `(reduce + 0 xs)`.
+//!
+//! Вычитание пишется с пробелами: `a - 1`, так как `a -1` — это `a` и литерал `-1`.
+
+use super::error::ParseError;
+use super::parser::SExpr;
+use super::printer::format_sexpr;
+use super::token::{Span, Spanned};
+
+/// Приоритет унарных `-` и `!`/`not`: выше любого бинарного.
+const UNARY_PRECEDENCE: u8 = 8;
+
+/// Приоритет бинарного оператора (больше — связывает сильнее).
+fn precedence(op: &str) -> Option<u8> {
+    match op {
+        "|>" => Some(1),
+        "||" | "or" => Some(2),
+        "&&" | "and" => Some(3),
+        "==" | "!=" => Some(4),
+        "<" | "<=" | ">" | ">=" => Some(5),
+        "+" | "-" => Some(6),
+        "*" | "/" | "//" | "%" => Some(7),
+        _ => None,
+    }
+}
+
+/// Имя оператора, если выражение — символ или ключевое слово `and`/`or`/`not`.
+fn operator_name(expr: &SExpr) -> Option<&str> {
+    expr.as_symbol().or_else(|| {
+        expr.as_ident()
+            .filter(|name| matches!(*name, "and" | "or" | "not"))
+    })
+}
+
+fn is_binary(expr: &SExpr) -> bool {
+    operator_name(expr).and_then(precedence).is_some()
+}
+
+fn is_unary(expr: &SExpr) -> bool {
+    matches!(operator_name(expr), Some("-" | "!" | "not"))
+}
+
+/// Переписать верхний уровень программы: каждая инфиксная цепочка — одно выражение.
+pub fn desugar(exprs: Vec<SExpr>) -> Result<Vec<SExpr>, ParseError> {
+    let mut chain = Chain::new(&exprs);
+    let mut out = Vec::new();
+    while !chain.at_end() {
+        out.push(chain.expr(0)?);
+    }
+    Ok(out)
+}
+
+/// Переписать одно выражение (атом или список) рекурсивно.
+fn rewrite(expr: &SExpr) -> Result<SExpr, ParseError> {
+    let SExpr::List(list) = expr else {
+        return Ok(expr.clone());
+    };
+    let items = &list.value;
+    let infix = match items.as_slice() {
+        [_, op, ..] if is_binary(op) => true,
+        [first, _, op, ..] => is_unary(first) && is_binary(op),
+        _ => false,
+    };
+
+    if infix {
+        let mut chain = Chain::new(items);
+        let result = chain.expr(0)?;
+        if chain.at_end() {
+            return Ok(result);
+        }
+    }
+
+    let items = items.iter().map(rewrite).collect::<Result<_, _>>()?;
+    Ok(SExpr::List(Spanned::new(items, list.span)))
+}
+
+/// Разбор цепочки операндов и операторов методом подъёма приоритетов.
+struct Chain<'a> {
+    items: &'a [SExpr],
+    pos: usize,
+}
+
+impl<'a> Chain<'a> {
+    fn new(items: &'a [SExpr]) -> Self {
+        Self { items, pos: 0 }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.items.len()
+    }
+
+    fn peek(&self) -> Option<&'a SExpr> {
+        self.items.get(self.pos)
+    }
+
+    /// Выражение из операторов с приоритетом не ниже `min_precedence`.
+    fn expr(&mut self, min_precedence: u8) -> Result<SExpr, ParseError> {
+        let mut lhs = self.operand()?;
+        while let Some(op) = self.peek() {
+            let Some(prec) = operator_name(op).and_then(precedence) else {
+                break;
+            };
+            if prec < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            // +1: оператор того же приоритета справа не поглощается (левая ассоциативность)
+            let rhs = self.expr(prec + 1)?;
+            lhs = apply(op, vec![lhs, rhs]);
+        }
+        Ok(lhs)
+    }
+
+    fn operand(&mut self) -> Result<SExpr, ParseError> {
+        let Some(item) = self.peek() else {
+            let end = self.items.last().map_or(0, |e| e.span().end);
+            return Err(ParseError::UnexpectedEof {
+                span: Span::new(end, end),
+                message: "expected operand after operator".to_string(),
+            });
+        };
+        self.pos += 1;
+
+        if is_unary(item) && !self.at_end() {
+            let operand = self.expr(UNARY_PRECEDENCE)?;
+            return Ok(apply(item, vec![operand]));
+        }
+        if is_binary(item) {
+            return Err(ParseError::UnexpectedToken {
+                span: item.span(),
+                expected: "operand".to_string(),
+                found: format_sexpr(item),
+            });
+        }
+        rewrite(item)
+    }
+}
+
+/// Префиксный список `(op args...)`; span охватывает оператор и аргументы.
+fn apply(op: &SExpr, args: Vec<SExpr>) -> SExpr {
+    let first = args.first().map_or(op.span(), SExpr::span);
+    let start = first.start.min(op.span().start);
+    let end = args.last().map_or(op.span().end, |e| e.span().end);
+    let mut items = vec![op.clone()];
+    items.extend(args);
+    SExpr::List(Spanned::new(items, Span::new(start, end)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, Value};
+    use crate::parser::{parse_infix, Parser};
+
+    fn desugared(source: &str) -> Vec<String> {
+        let exprs = Parser::new(source).parse_all().unwrap();
+        desugar(exprs).unwrap().iter().map(format_sexpr).collect()
+    }
+
+    fn run(source: &str) -> Value {
+        let (asg, roots) = parse_infix(source).unwrap();
+        let mut interp = Interpreter::new();
+        let mut result = Value::Unit;
+        for root in roots {
+            result = interp.execute(&asg, root).unwrap();
+        }
+        result
+    }
+
+    #[test]
+    fn test_infix_precedence() {
+        assert_eq!(desugared("1 + 2 * 3"), vec!["(+ 1 (* 2 3))"]);
+        assert_eq!(run("1 + 2 * 3"), Value::Int(7));
+
+        // Левая ассоциативность и скобки
+        assert_eq!(desugared("10 - 3 - 2"), vec!["(- (- 10 3) 2)"]);
+        assert_eq!(desugared("(1 + 2) * 3"), vec!["(* (+ 1 2) 3)"]);
+        assert_eq!(
+            desugared("a < b && b <= c || not d"),
+            vec!["(|| (&& (< a b) (<= b c)) (not d))"]
+        );
+        assert_eq!(desugared("(- x * 2)"), vec!["(* (- x) 2)"]);
+    }
+
+    #[test]
+    fn test_infix_inside_prefix_forms() {
+        // Префиксные формы остаются как есть, инфикс внутри переписывается
+        assert_eq!(
+            desugared("(let x (2 * 3 + 1)) x * x"),
+            vec!["(let x (+ (* 2 3) 1))", "(* x x)"]
+        );
+        assert_eq!(desugared("(reduce + 0 xs)"), vec!["(reduce + 0 xs)"]);
+        assert_eq!(
+            run("(let x (2 * 3 + 1))\n(if (x > 5) (x * 10) 0)"),
+            Value::Int(70)
+        );
+    }
+
+    #[test]
+    fn test_infix_errors() {
+        let parse = |source: &str| desugar(Parser::new(source).parse_all().unwrap());
+        assert!(matches!(
+            parse("1 +"),
+            Err(ParseError::UnexpectedEof { .. })
+        ));
+        assert!(matches!(
+            parse("1 + * 2"),
+            Err(ParseError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            parse("(x * (1 +))"),
+            Err(ParseError::UnexpectedEof { .. })
+        ));
+    }
+}
@@ -16,6 +16,13 @@ enum LogosToken {
     #[token(")")]
     RParen,
 
+    // [ ] — синоним ( ) для паттернов: (let [a b] expr); пары должны совпадать
+    #[token("[")]
+    LBracket,
+
+    #[token("]")]
+    RBracket,
+
     // Булевы литералы (до идентификаторов!)
     #[token("true")]
     True,
@@ -27,9 +34,16 @@ enum LogosToken {
     #[regex(r"-?[0-9]+\.[0-9]+([eE][+-]?[0-9]+)?", |lex| lex.slice().parse::<f64>().ok())]
     Float(f64),
 
+    // Decimal: 1.10d (суффикс отбрасывается)
+    #[regex(r"-?[0-9]+(\.[0-9]+)?d", |lex| {
+        let s = lex.slice();
+        s[..s.len() - 1].to_string()
+    })]
+    Decimal(String),
+
     // Integer
-    #[regex(r"-?[0-9]+", |lex| lex.slice().parse::<i64>().ok())]
-    Int(i64),
+    #[regex(r"-?[0-9]+", |lex| lex.slice().to_string())]
+    Int(String),
 
     // Hex integer
     #[regex(r"0[xX][0-9a-fA-F]+", |lex| i64::from_str_radix(&lex.slice()[2..], 16).ok())]
@@ -86,9 +100,18 @@ enum LogosToken {
     Bang,
     #[token(":")]
     Colon,
-
-    // Идентификатор (включая ключевые слова с дефисом: tensor-add)
-    #[regex(r"[a-zA-Z_][a-zA-Z0-9_-]*", |lex| lex.slice().to_string())]
+    /// Распаковка словаря: (dict ...base k v)
+    #[token("...")]
+    Ellipsis,
+
+    // Идентификатор (включая ключевые слова с дефисом: tensor-add,
+    // преобразования со стрелкой: char->int,
+    // имена расширений с пространством имён: ext:double
+    // имена из модуля, импортированного через :as: m.square,
+    // и мутирующие формы с `!` в конце: inc!)
+    #[regex(r"[a-zA-Z_]([a-zA-Z0-9_-]|->)*!?", |lex| lex.slice().to_string())]
+    #[regex(r"[a-zA-Z_][a-zA-Z0-9_-]*(\.[a-zA-Z_][a-zA-Z0-9_-]*)+", |lex| lex.slice().to_string())]
+    #[regex(r"ext:[a-zA-Z_][a-zA-Z0-9_-]*", |lex| lex.slice().to_string())]
     Ident(String),
 }
 
@@ -178,12 +201,16 @@ impl<'a> Lexer<'a> {
         match logos_token {
             LogosToken::LParen => Token::LParen,
             LogosToken::RParen => Token::RParen,
+            LogosToken::LBracket => Token::LBracket,
+            LogosToken::RBracket => Token::RBracket,
             LogosToken::True => Token::Ident("true".to_string()),
             LogosToken::False => Token::Ident("false".to_string()),
-            LogosToken::Int(n) => Token::Int(n),
+            // Не помещается в i64 — BigInt
+            LogosToken::Int(s) => s.parse().map_or(Token::BigInt(s), Token::Int),
             LogosToken::HexInt(n) => Token::Int(n),
             LogosToken::BinInt(n) => Token::Int(n),
             LogosToken::Float(f) => Token::Float(f),
+            LogosToken::Decimal(s) => Token::Decimal(s),
             LogosToken::String(s) => Token::String(s),
             LogosToken::Ident(s) => Token::Ident(s),
             // Операторы
@@ -204,6 +231,7 @@ impl<'a> Lexer<'a> {
             LogosToken::Pipe => Token::Symbol("|>".to_string()),
             LogosToken::Bang => Token::Symbol("!".to_string()),
             LogosToken::Colon => Token::Symbol(":".to_string()),
+            LogosToken::Ellipsis => Token::Symbol("...".to_string()),
         }
     }
 
@@ -232,6 +260,31 @@ mod tests {
         assert!(matches!(lexer.next_token().unwrap().value, Token::Eof));
     }
 
+    #[test]
+    fn test_lexer_brackets() {
+        let mut lexer = Lexer::new("[a]");
+        assert!(matches!(lexer.next_token().unwrap().value, Token::LBracket));
+        assert!(matches!(
+            lexer.next_token().unwrap().value,
+            Token::Ident(s) if s == "a"
+        ));
+        assert!(matches!(lexer.next_token().unwrap().value, Token::RBracket));
+    }
+
+    #[test]
+    fn test_lexer_arrow_identifier() {
+        let mut lexer = Lexer::new("(char->int c)");
+        assert!(matches!(lexer.next_token().unwrap().value, Token::LParen));
+        assert!(matches!(
+            lexer.next_token().unwrap().value,
+            Token::Ident(s) if s == "char->int"
+        ));
+        assert!(matches!(
+            lexer.next_token().unwrap().value,
+            Token::Ident(s) if s == "c"
+        ));
+    }
+
     #[test]
     fn test_lexer_string() {
         let mut lexer = Lexer::new(r#""hello\nworld""#);
@@ -255,4 +308,22 @@ mod tests {
             _ => panic!("Expected float"),
         }
     }
+
+    #[test]
+    fn test_lexer_big_and_decimal_numbers() {
+        let mut lexer = Lexer::new("9223372036854775808 1.10d -3d 1.5");
+        assert!(matches!(
+            lexer.next_token().unwrap().value,
+            Token::BigInt(s) if s == "9223372036854775808"
+        ));
+        assert!(matches!(
+            lexer.next_token().unwrap().value,
+            Token::Decimal(s) if s == "1.10"
+        ));
+        assert!(matches!(
+            lexer.next_token().unwrap().value,
+            Token::Decimal(s) if s == "-3"
+        ));
+        assert!(matches!(lexer.next_token().unwrap().value, Token::Float(_)));
+    }
 }
@@ -44,14 +44,17 @@
 
 pub mod builder;
 pub mod error;
+pub mod infix;
 pub mod lexer;
 pub mod parser;
+pub mod printer;
 pub mod token;
 
 pub use builder::AsgBuilder;
 pub use error::ParseError;
 pub use lexer::Lexer;
 pub use parser::{Atom, Parser, SExpr};
+pub use printer::{format_source, FormatError};
 pub use token::{Span, Spanned, Token};
 
 use crate::asg::{NodeID, ASG};
@@ -76,14 +79,28 @@ use crate::error::ASGResult;
 /// ```
 pub fn parse(source: &str) -> ASGResult<(ASG, Vec<NodeID>)> {
     let mut parser = Parser::new(source);
-    let exprs = parser
-        .parse_all()
-        .map_err(|e| crate::error::ASGError::ParseError(e.to_string()))?;
+    let exprs = parser.parse_all()?;
 
     let builder = AsgBuilder::new();
-    builder
-        .build(exprs)
-        .map_err(|e| crate::error::ASGError::ParseError(e.to_string()))
+    Ok(builder.build(exprs)?)
+}
+
+/// Парсит исходный код с инфиксными операторами (`1 + 2 * 3`) в тот же ASG,
+/// что и эквивалентная префиксная запись. Правила — в модуле [`infix`].
+///
+/// # Пример
+///
+/// ```rust,ignore
+/// use asg_lang::parser::parse_infix;
+///
+/// let (asg, root_ids) = parse_infix("(let x 2) x * x + 1").unwrap();
+/// ```
+pub fn parse_infix(source: &str) -> ASGResult<(ASG, Vec<NodeID>)> {
+    let mut parser = Parser::new(source);
+    let exprs = infix::desugar(parser.parse_all()?)?;
+
+    let builder = AsgBuilder::new();
+    Ok(builder.build(exprs)?)
 }
 
 /// Парсит одно выражение и возвращает ASG с ID корневого узла.
@@ -97,14 +114,10 @@ pub fn parse(source: &str) -> ASGResult<(ASG, Vec<NodeID>)> {
 /// Кортеж (ASG, root_id) или ошибку.
 pub fn parse_expr(source: &str) -> ASGResult<(ASG, NodeID)> {
     let mut parser = Parser::new(source);
-    let expr = parser
-        .parse_sexpr()
-        .map_err(|e| crate::error::ASGError::ParseError(e.to_string()))?;
+    let expr = parser.parse_sexpr()?;
 
     let builder = AsgBuilder::new();
-    builder
-        .build_single(&expr)
-        .map_err(|e| crate::error::ASGError::ParseError(e.to_string()))
+    Ok(builder.build_single(&expr)?)
 }
 
 #[cfg(test)]
@@ -151,6 +164,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_literal_versus_assignment() {
+        use crate::nodecodes::{EdgeType, NodeType};
+
+        let (asg, root_id) = parse_expr("(set 1 2 3)").unwrap();
+        let node = asg.find_node(root_id).unwrap();
+        assert_eq!(node.node_type, NodeType::SetNew);
+        assert_eq!(node.find_edges(EdgeType::ArrayElement).len(), 3);
+
+        // Два аргумента с переменной или местом первым — по-прежнему присваивание
+        for source in ["(set x 1)", "(set (index xs 0) 1)"] {
+            let (asg, root_id) = parse_expr(source).unwrap();
+            assert_eq!(asg.find_node(root_id).unwrap().node_type, NodeType::Assign);
+        }
+        let (asg, root_id) = parse_expr("(set 1 2)").unwrap();
+        assert_eq!(asg.find_node(root_id).unwrap().node_type, NodeType::SetNew);
+
+        // С именем или местом первым это присваивание с неверным числом аргументов
+        for source in ["(set x 2 3)", "(set x)", "(set (index xs 0) 1 2)"] {
+            assert!(
+                matches!(
+                    parse_expr(source),
+                    Err(crate::error::ASGError::ParseError(
+                        ParseError::WrongArity { .. }
+                    ))
+                ),
+                "{}",
+                source
+            );
+        }
+        let (asg, root_id) = parse_expr("(set true false)").unwrap();
+        assert_eq!(asg.find_node(root_id).unwrap().node_type, NodeType::SetNew);
+    }
+
+    #[test]
+    fn test_for_has_single_loop_init() {
+        use crate::nodecodes::{EdgeType, NodeType};
+
+        // И переменная, и пара [k v] — одно ребро LoopInit
+        for (source, var_type) in [
+            ("(for x (array 1) x)", NodeType::Variable),
+            ("(for [k v] (dict \"a\" 1) k)", NodeType::LetDestructure),
+        ] {
+            let (asg, root_id) = parse_expr(source).unwrap();
+            let edges = asg
+                .find_node(root_id)
+                .unwrap()
+                .find_edges(EdgeType::LoopInit);
+            assert_eq!(edges.len(), 1, "{}", source);
+            let var = asg.find_node(edges[0].target_node_id).unwrap();
+            assert_eq!(var.node_type, var_type, "{}", source);
+        }
+    }
+
     #[test]
     fn test_parse_string() {
         let (asg, root_id) = parse_expr(r#""hello world""#).unwrap();
@@ -187,6 +254,40 @@ mod tests {
         assert!(matches!(result, crate::interpreter::Value::Unit));
     }
 
+    #[test]
+    fn test_parse_unit_as_value() {
+        // () как значение: аргумент, элемент массива, тело ветки
+        let (asg, root_id) = parse_expr("(array () (if true () 1))").unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(&asg, root_id).unwrap();
+
+        assert_eq!(
+            result,
+            crate::interpreter::Value::Array(vec![
+                crate::interpreter::Value::Unit,
+                crate::interpreter::Value::Unit
+            ])
+        );
+    }
+
+    #[test]
+    fn test_empty_list_in_head_position_is_error() {
+        for source in ["(() 1)", "(())", "(do (() 1 2))"] {
+            match parse_expr(source).unwrap_err() {
+                crate::error::ASGError::ParseError(ParseError::UnexpectedToken {
+                    span,
+                    found,
+                    ..
+                }) => {
+                    assert_eq!(found, "()");
+                    assert_eq!(&source[span.start..span.end], "()");
+                }
+                other => panic!("Expected UnexpectedToken for {}, got {:?}", source, other),
+            }
+        }
+    }
+
     #[test]
     fn test_parse_if() {
         let (asg, root_id) = parse_expr("(if true 42 0)").unwrap();
@@ -217,4 +318,29 @@ mod tests {
             _ => panic!("Expected Int"),
         }
     }
+
+    #[test]
+    fn test_parse_error_keeps_span() {
+        let source = "(let x 1)\n(do (import))";
+        let err = parse(source).unwrap_err();
+
+        match &err {
+            crate::error::ASGError::ParseError(e) => {
+                assert!(matches!(e, ParseError::WrongArity { .. }));
+                assert_eq!(e.span().start, source.find("(import").unwrap());
+            }
+            other => panic!("Expected ParseError, got {:?}", other),
+        }
+        // Сообщение не изменилось
+        assert!(err
+            .to_string()
+            .starts_with("Parse error: Wrong number of arguments for 'import'"));
+
+        let err = parse_expr("(+ 1").unwrap_err();
+        assert!(matches!(
+            err,
+            crate::error::ASGError::ParseError(ParseError::UnclosedParen { .. })
+                | crate::error::ASGError::ParseError(ParseError::UnexpectedEof { .. })
+        ));
+    }
 }
@@ -18,6 +18,10 @@ pub enum SExpr {
 pub enum Atom {
     /// Целое число.
     Int(i64),
+    /// Целое число вне диапазона i64 (десятичная запись).
+    BigInt(String),
+    /// Десятичное число `1.10d` (запись без суффикса).
+    Decimal(String),
     /// Число с плавающей точкой.
     Float(f64),
     /// Строка.
@@ -151,16 +155,19 @@ impl<'a> Parser<'a> {
         let token = self.lexer.next_token()?;
 
         match token.value {
-            Token::LParen => self.parse_list(token.span),
+            Token::LParen => self.parse_list(token.span, Token::RParen),
+            Token::LBracket => self.parse_list(token.span, Token::RBracket),
             Token::Int(n) => Ok(SExpr::Atom(Spanned::new(Atom::Int(n), token.span))),
+            Token::BigInt(s) => Ok(SExpr::Atom(Spanned::new(Atom::BigInt(s), token.span))),
+            Token::Decimal(s) => Ok(SExpr::Atom(Spanned::new(Atom::Decimal(s), token.span))),
             Token::Float(f) => Ok(SExpr::Atom(Spanned::new(Atom::Float(f), token.span))),
             Token::String(s) => Ok(SExpr::Atom(Spanned::new(Atom::String(s), token.span))),
             Token::Ident(s) => Ok(SExpr::Atom(Spanned::new(Atom::Ident(s), token.span))),
             Token::Symbol(s) => Ok(SExpr::Atom(Spanned::new(Atom::Symbol(s), token.span))),
-            Token::RParen => Err(ParseError::unexpected_token(
+            close @ (Token::RParen | Token::RBracket) => Err(ParseError::unexpected_token(
                 token.span,
                 "expression",
-                &Token::RParen,
+                &close,
             )),
             Token::Eof => Err(ParseError::unexpected_eof(
                 token.span,
@@ -169,16 +176,23 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Распарсить список (после открывающей скобки).
-    fn parse_list(&mut self, start_span: Span) -> Result<SExpr, ParseError> {
+    /// Распарсить список (после открывающей скобки) до парной закрывающей `close`.
+    fn parse_list(&mut self, start_span: Span, close: Token) -> Result<SExpr, ParseError> {
         let mut elements = Vec::new();
 
         loop {
             let token = self.lexer.peek_token()?;
 
             match &token.value {
-                Token::RParen => {
+                Token::RParen | Token::RBracket => {
                     let end_token = self.lexer.next_token()?;
+                    if end_token.value != close {
+                        return Err(ParseError::unexpected_token(
+                            end_token.span,
+                            format!("'{}'", close),
+                            &end_token.value,
+                        ));
+                    }
                     let span = start_span.merge(end_token.span);
                     return Ok(SExpr::List(Spanned::new(elements, span)));
                 }
@@ -232,6 +246,21 @@ mod tests {
         assert_eq!(expr.form_name(), Some("fn"));
     }
 
+    #[test]
+    fn test_parse_brackets_must_match() {
+        let mut parser = Parser::new("[a b]");
+        assert_eq!(parser.parse_sexpr().unwrap().as_list().unwrap().len(), 2);
+
+        assert!(matches!(
+            Parser::new("(+ 1 2]").parse_sexpr(),
+            Err(ParseError::UnexpectedToken { found, .. }) if found == "]"
+        ));
+        assert!(matches!(
+            Parser::new("[a b)").parse_sexpr(),
+            Err(ParseError::UnexpectedToken { found, .. }) if found == ")"
+        ));
+    }
+
     #[test]
     fn test_parse_empty_list() {
         let mut parser = Parser::new("()");
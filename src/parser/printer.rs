@@ -0,0 +1,257 @@
+//! Каноничный вывод S-выражений (форматтер).
+//!
+//! Форма помещается в одну строку, если укладывается в [`MAX_WIDTH`]; иначе
+//! заголовок формы (`fn name (params)`, `let name`, `if cond`, ...) остаётся на
+//! первой строке, а остальные элементы переносятся с отступом в 2 пробела.
+//! Результат снова парсится в тот же граф.
+
+use super::error::ParseError;
+use super::parser::{Atom, Parser, SExpr};
+use super::token::Span;
+
+/// Максимальная ширина строки.
+pub const MAX_WIDTH: usize = 80;
+
+/// Отступ вложенных строк.
+const INDENT: usize = 2;
+
+/// Ошибка форматирования.
+#[derive(Debug, thiserror::Error)]
+pub enum FormatError {
+    /// Исходный код не парсится.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    /// Комментарии не сохраняются парсером, поэтому такой код не форматируется.
+    #[error("Cannot format source with comments (comment at position {})", span.start)]
+    HasComments { span: Span },
+}
+
+/// Отформатировать исходный код в каноничный вид.
+pub fn format_source(source: &str) -> Result<String, FormatError> {
+    if let Some(start) = find_comment(source) {
+        return Err(FormatError::HasComments {
+            span: Span::new(start, start + 1),
+        });
+    }
+
+    let exprs = Parser::new(source).parse_all()?;
+    let mut out = String::new();
+    let mut prev_multiline = false;
+    for (i, expr) in exprs.iter().enumerate() {
+        let text = format_sexpr(expr);
+        let multiline = text.contains('\n');
+        // Многострочные формы отделяются пустой строкой
+        if i > 0 && (multiline || prev_multiline) {
+            out.push('\n');
+        }
+        out.push_str(&text);
+        out.push('\n');
+        prev_multiline = multiline;
+    }
+    Ok(out)
+}
+
+/// Отформатировать одно выражение, начиная с нулевого отступа.
+pub fn format_sexpr(expr: &SExpr) -> String {
+    let mut out = String::new();
+    print(expr, 0, &mut out);
+    out
+}
+
+/// Позиция первого `;` вне строкового литерала.
+fn find_comment(source: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in source.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Число элементов заголовка (включая голову), которые остаются на первой строке.
+fn header_len(head: &str) -> usize {
+    match head {
+        "fn" | "rec-lambda" | "for" | "loop-indexed" | "defrecord" | "defenum" => 3,
+        "let" | "set" | "lambda" | "if" | "when" | "unless" | "while" | "match" | "module"
+        | "loop-timeout" => 2,
+        _ => 1,
+    }
+}
+
+/// Вывести выражение с переносами; `column` — текущий столбец.
+fn print(expr: &SExpr, column: usize, out: &mut String) {
+    let flat = flat(expr);
+    let items = match expr {
+        SExpr::List(list) if column + flat.len() > MAX_WIDTH && !list.value.is_empty() => {
+            &list.value
+        }
+        _ => {
+            out.push_str(&flat);
+            return;
+        }
+    };
+
+    let groups = group_adjacent(items);
+    let head = items[0]
+        .as_ident()
+        .or_else(|| items[0].as_symbol())
+        .unwrap_or("");
+    let header = header_len(head).min(groups.len());
+
+    out.push('(');
+    let mut col = column + 1;
+    for (i, group) in groups[..header].iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+            col += 1;
+        }
+        col = print_group(group, col, out);
+    }
+
+    let body_column = column + INDENT;
+    for group in &groups[header..] {
+        out.push('\n');
+        out.push_str(&" ".repeat(body_column));
+        print_group(group, body_column, out);
+    }
+    out.push(')');
+}
+
+/// Вывести группу выражений без пробелов между ними; возвращает новый столбец.
+fn print_group(group: &[&SExpr], column: usize, out: &mut String) -> usize {
+    let mut col = column;
+    for expr in group {
+        let start = out.len();
+        print(expr, col, out);
+        let printed = &out[start..];
+        col = match printed.rfind('\n') {
+            Some(nl) => printed.len() - nl - 1,
+            None => col + printed.len(),
+        };
+    }
+    col
+}
+
+/// Однострочный вывод.
+fn flat(expr: &SExpr) -> String {
+    match expr {
+        SExpr::Atom(atom) => atom_text(&atom.value),
+        SExpr::List(list) => {
+            let parts: Vec<String> = group_adjacent(&list.value)
+                .iter()
+                .map(|group| group.iter().map(|e| flat(e)).collect())
+                .collect();
+            format!("({})", parts.join(" "))
+        }
+    }
+}
+
+/// Соседние атомы без пробела в исходнике (например, `:` и имя) остаются слитными.
+fn group_adjacent(items: &[SExpr]) -> Vec<Vec<&SExpr>> {
+    let mut groups: Vec<Vec<&SExpr>> = Vec::new();
+    for item in items {
+        let joins = match (groups.last().and_then(|g| g.last()), item) {
+            (Some(prev @ SExpr::Atom(_)), SExpr::Atom(_)) => prev.span().end == item.span().start,
+            _ => false,
+        };
+        match groups.last_mut() {
+            Some(group) if joins => group.push(item),
+            _ => groups.push(vec![item]),
+        }
+    }
+    groups
+}
+
+/// Текст атома в каноничном виде.
+fn atom_text(atom: &Atom) -> String {
+    match atom {
+        Atom::Int(n) => n.to_string(),
+        Atom::Float(f) => {
+            // Лексеру нужна точка: 1e300 -> 1.0e300, 2 -> 2.0
+            let s = format!("{:?}", f);
+            if s.contains('.') || !f.is_finite() {
+                s
+            } else if let Some(e) = s.find('e') {
+                format!("{}.0{}", &s[..e], &s[e..])
+            } else {
+                format!("{}.0", s)
+            }
+        }
+        Atom::String(s) => {
+            let mut escaped = String::with_capacity(s.len() + 2);
+            escaped.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => escaped.push_str("\\\""),
+                    '\\' => escaped.push_str("\\\\"),
+                    '\n' => escaped.push_str("\\n"),
+                    '\t' => escaped.push_str("\\t"),
+                    '\r' => escaped.push_str("\\r"),
+                    '\0' => escaped.push_str("\\0"),
+                    c => escaped.push(c),
+                }
+            }
+            escaped.push('"');
+            escaped
+        }
+        Atom::Decimal(s) => format!("{}d", s),
+        Atom::BigInt(s) | Atom::Ident(s) | Atom::Symbol(s) => s.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_format_poorly_indented_source() {
+        let source =
+            "(fn   square (x)\n      (* x x))\n\n\n(let y    (square   3))\n(print \"y = \\\"\" y)";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(
+            formatted,
+            "(fn square (x) (* x x))\n(let y (square 3))\n(print \"y = \\\"\" y)\n"
+        );
+
+        // Длинная форма переносится: заголовок на первой строке, тело с отступом
+        let long = "(fn describe (value) (if (> value 100) (print \"a rather large value was given\") (print \"a small value\")))";
+        assert_eq!(
+            format_source(long).unwrap(),
+            "(fn describe (value)\n  (if (> value 100)\n    (print \"a rather large value was given\")\n    (print \"a small value\")))\n"
+        );
+    }
+
+    #[test]
+    fn test_format_is_idempotent_and_preserves_graph() {
+        let source = "(module m (fn f (a b) (+ a b)) (let big (array 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20)))\n(import \"m\" as mm)\n(let z 1.0e300)";
+        let once = format_source(source).unwrap();
+        assert_eq!(format_source(&once).unwrap(), once);
+        assert!(once.contains("1.0e300"));
+
+        let (original, _) = parse(source).unwrap();
+        let (reformatted, _) = parse(&once).unwrap();
+        assert_eq!(original.structural_hash(), reformatted.structural_hash());
+    }
+
+    #[test]
+    fn test_format_rejects_comments_and_invalid_source() {
+        assert!(matches!(
+            format_source("(let x 1) ; answer"),
+            Err(FormatError::HasComments { .. })
+        ));
+        // ';' внутри строки — не комментарий
+        assert!(format_source("(print \"a;b\")").is_ok());
+        assert!(matches!(
+            format_source("(let x"),
+            Err(FormatError::Parse(_))
+        ));
+    }
+}
@@ -46,9 +46,17 @@ pub enum Token {
     LParen,
     /// Закрывающая скобка `)`
     RParen,
+    /// Открывающая квадратная скобка `[`
+    LBracket,
+    /// Закрывающая квадратная скобка `]`
+    RBracket,
 
     /// Целое число
     Int(i64),
+    /// Целое число вне диапазона i64 (десятичная запись)
+    BigInt(String),
+    /// Десятичное число с суффиксом `d` (запись без суффикса)
+    Decimal(String),
     /// Число с плавающей точкой
     Float(f64),
     /// Строковый литерал
@@ -66,19 +74,25 @@ pub enum Token {
 impl Token {
     /// Проверить, является ли токен открывающей скобкой.
     pub fn is_lparen(&self) -> bool {
-        matches!(self, Token::LParen)
+        matches!(self, Token::LParen | Token::LBracket)
     }
 
     /// Проверить, является ли токен закрывающей скобкой.
     pub fn is_rparen(&self) -> bool {
-        matches!(self, Token::RParen)
+        matches!(self, Token::RParen | Token::RBracket)
     }
 
     /// Проверить, является ли токен атомом (не скобкой).
     pub fn is_atom(&self) -> bool {
         matches!(
             self,
-            Token::Int(_) | Token::Float(_) | Token::String(_) | Token::Ident(_) | Token::Symbol(_)
+            Token::Int(_)
+                | Token::BigInt(_)
+                | Token::Decimal(_)
+                | Token::Float(_)
+                | Token::String(_)
+                | Token::Ident(_)
+                | Token::Symbol(_)
         )
     }
 }
@@ -88,7 +102,11 @@ impl std::fmt::Display for Token {
         match self {
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
             Token::Int(n) => write!(f, "{}", n),
+            Token::BigInt(s) => write!(f, "{}", s),
+            Token::Decimal(s) => write!(f, "{}d", s),
             Token::Float(n) => write!(f, "{}", n),
             Token::String(s) => write!(f, "\"{}\"", s),
             Token::Ident(s) => write!(f, "{}", s),
@@ -1,8 +1,13 @@
 //! Модуль проверки доказательств.
 //!
-//! Требует feature `proofs` для использования Z3.
+//! Проверка через Z3 требует feature `proofs`; экспорт условий в SMT-LIB
+//! ([`to_smtlib`]) и перечисление путей ([`symbolic_paths`]) доступны всегда.
 
-use crate::{asg::ASG, ASGResult};
+use std::collections::HashMap;
+
+use crate::asg::{Node, NodeID, ASG};
+use crate::nodecodes::{EdgeType, NodeType};
+use crate::{ASGError, ASGResult};
 
 #[cfg(feature = "proofs")]
 use crate::proof_dsl::ProofDSL;
@@ -36,3 +41,638 @@ pub fn check_proofs(_asg: &ASG) -> ASGResult<bool> {
     println!("Proof checking requires feature 'proofs'. Returning Ok(true).");
     Ok(true)
 }
+
+/// Условия `(requires c)` / `(ensures c)` (по `kind`) среди инструкций тела функции.
+pub(crate) fn contract_conditions(asg: &ASG, body_id: NodeID, kind: NodeType) -> Vec<NodeID> {
+    body_statements(asg, body_id)
+        .into_iter()
+        .filter_map(|id| asg.find_node(id))
+        .filter(|node| node.node_type == kind)
+        .filter_map(|node| node.edges.first().map(|e| e.target_node_id))
+        .collect()
+}
+
+/// Инструкции тела: дочерние узлы блока или само тело.
+fn body_statements(asg: &ASG, body_id: NodeID) -> Vec<NodeID> {
+    match asg.find_node(body_id) {
+        Some(body) if body.node_type == NodeType::Block => body
+            .find_edges(EdgeType::BlockStatement)
+            .into_iter()
+            .map(|e| e.target_node_id)
+            .collect(),
+        _ => vec![body_id],
+    }
+}
+
+/// Условия проверки функции `goal` в формате SMT-LIB2 для внешнего решателя.
+///
+/// Параметры объявляются как `Int`, `let` в теле и результат — через
+/// `define-fun` (ветвления становятся `ite`, так что условия путей входят в
+/// терм), `(requires c)` — утверждения, `(ensures c)` — отрицание их
+/// конъюнкции. Ответ `unsat` означает, что постусловия следуют из предусловий;
+/// без `ensures` проверяется совместность предусловий.
+///
+/// Целочисленное деление `//` переводится в `div`/`mod` SMT-LIB (евклидовы): для
+/// отрицательных операндов они могут расходиться с интерпретатором. `/` даёт Float
+/// и не поддерживается.
+pub fn to_smtlib(asg: &ASG, goal: &str) -> ASGResult<String> {
+    let function = asg
+        .nodes
+        .iter()
+        .find(|n| n.node_type == NodeType::Function && n.get_name().as_deref() == Some(goal))
+        .ok_or_else(|| ASGError::UnknownFunction(goal.to_string()))?;
+    let body_id = function
+        .find_edge(EdgeType::FunctionBody)
+        .ok_or(ASGError::MissingEdge(function.id, EdgeType::FunctionBody))?
+        .target_node_id;
+
+    let mut sorts: HashMap<String, &'static str> = HashMap::new();
+    let mut out = format!("; verification conditions for {}\n(set-logic ALL)\n", goal);
+    for edge in function.find_edges(EdgeType::FunctionParameter) {
+        let name = asg
+            .find_node(edge.target_node_id)
+            .and_then(|param| param.get_name())
+            .ok_or(ASGError::MissingPayload(edge.target_node_id))?;
+        out.push_str(&format!("(declare-const {} Int)\n", name));
+        sorts.insert(name, "Int");
+    }
+
+    let mut requires = Vec::new();
+    let mut ensures = Vec::new();
+    let mut result = None;
+    let statements = body_statements(asg, body_id);
+    for (i, &id) in statements.iter().enumerate() {
+        let node = asg.find_node(id).ok_or(ASGError::NodeNotFound(id))?;
+        let operand = || {
+            node.edges
+                .first()
+                .map(|e| e.target_node_id)
+                .ok_or(ASGError::MissingEdge(
+                    node.id,
+                    EdgeType::ApplicationArgument,
+                ))
+        };
+        match node.node_type {
+            NodeType::Requires => requires.push(smt_term(asg, operand()?)?),
+            NodeType::Ensures => ensures.push(smt_term(asg, operand()?)?),
+            NodeType::Variable => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let value = node
+                    .find_edge(EdgeType::VarValue)
+                    .ok_or(ASGError::MissingEdge(node.id, EdgeType::VarValue))?
+                    .target_node_id;
+                let sort = smt_sort(asg, value, &sorts);
+                out.push_str(&format!(
+                    "(define-fun {} () {} {})\n",
+                    name,
+                    sort,
+                    smt_term(asg, value)?
+                ));
+                sorts.insert(name, sort);
+            }
+            _ if i + 1 == statements.len() => result = Some(id),
+            other => {
+                return Err(ASGError::InvalidOperation(format!(
+                    "to_smtlib: statement {:?} is not supported",
+                    other
+                )))
+            }
+        }
+    }
+
+    if let Some(id) = result {
+        out.push_str(&format!(
+            "(define-fun result () {} {})\n",
+            smt_sort(asg, id, &sorts),
+            smt_term(asg, id)?
+        ));
+    }
+    for condition in &requires {
+        out.push_str(&format!("(assert {})\n", condition));
+    }
+    match ensures.as_slice() {
+        [] => {}
+        [single] => out.push_str(&format!("(assert (not {}))\n", single)),
+        all => out.push_str(&format!("(assert (not (and {})))\n", all.join(" "))),
+    }
+    out.push_str("(check-sat)\n");
+    Ok(out)
+}
+
+/// Сорт SMT-LIB выражения: логические операции — `Bool`, остальное — `Int`.
+fn smt_sort(asg: &ASG, id: NodeID, sorts: &HashMap<String, &'static str>) -> &'static str {
+    let Some(node) = asg.find_node(id) else {
+        return "Int";
+    };
+    match node.node_type {
+        NodeType::LiteralBool
+        | NodeType::Eq
+        | NodeType::Ne
+        | NodeType::Lt
+        | NodeType::Le
+        | NodeType::Gt
+        | NodeType::Ge
+        | NodeType::And
+        | NodeType::Or
+        | NodeType::Not => "Bool",
+        NodeType::VarRef => node
+            .get_name()
+            .and_then(|name| sorts.get(&name).copied())
+            .unwrap_or("Int"),
+        NodeType::If => node
+            .find_edge(EdgeType::ThenBranch)
+            .map_or("Int", |e| smt_sort(asg, e.target_node_id, sorts)),
+        _ => "Int",
+    }
+}
+
+/// Терм SMT-LIB для чистого целочисленного или логического выражения.
+fn smt_term(asg: &ASG, id: NodeID) -> ASGResult<String> {
+    let node = asg.find_node(id).ok_or(ASGError::NodeNotFound(id))?;
+    let op = match node.node_type {
+        NodeType::LiteralInt => {
+            let bytes = node
+                .payload
+                .as_deref()
+                .ok_or(ASGError::MissingPayload(id))?;
+            let n = i64::from_le_bytes(bytes.try_into().map_err(|_| ASGError::InvalidPayload(id))?);
+            return Ok(if n < 0 {
+                format!("(- {})", n.unsigned_abs())
+            } else {
+                n.to_string()
+            });
+        }
+        NodeType::LiteralBool => {
+            let value = node
+                .payload
+                .as_deref()
+                .is_some_and(|b| b.first() == Some(&1));
+            return Ok(value.to_string());
+        }
+        NodeType::VarRef => return node.get_name().ok_or(ASGError::MissingPayload(id)),
+        NodeType::If => {
+            let branch = |edge_type| {
+                node.find_edge(edge_type)
+                    .ok_or(ASGError::MissingEdge(id, edge_type))
+                    .and_then(|e| smt_term(asg, e.target_node_id))
+            };
+            return Ok(format!(
+                "(ite {} {} {})",
+                branch(EdgeType::Condition)?,
+                branch(EdgeType::ThenBranch)?,
+                branch(EdgeType::ElseBranch)?
+            ));
+        }
+        other => smt_op(other).ok_or_else(|| {
+            ASGError::InvalidOperation(format!("to_smtlib: {:?} is not supported", other))
+        })?,
+    };
+    let operands = node
+        .edges
+        .iter()
+        .map(|e| smt_term(asg, e.target_node_id))
+        .collect::<ASGResult<Vec<_>>>()?;
+    Ok(format!("({} {})", op, operands.join(" ")))
+}
+
+/// Оператор SMT-LIB для арифметического, сравнительного или логического узла.
+fn smt_op(node_type: NodeType) -> Option<&'static str> {
+    Some(match node_type {
+        NodeType::BinaryOperation => "+",
+        NodeType::Sub | NodeType::Neg => "-",
+        NodeType::Mul => "*",
+        NodeType::IntDiv => "div",
+        NodeType::Mod => "mod",
+        NodeType::Eq => "=",
+        NodeType::Ne => "distinct",
+        NodeType::Lt => "<",
+        NodeType::Le => "<=",
+        NodeType::Gt => ">",
+        NodeType::Ge => ">=",
+        NodeType::And => "and",
+        NodeType::Or => "or",
+        NodeType::Not => "not",
+        _ => return None,
+    })
+}
+
+/// Путь исполнения, найденный [`symbolic_paths`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolicPath {
+    /// Условия выбранных ветвей (термы SMT-LIB над входами) в порядке ветвления.
+    pub conditions: Vec<String>,
+    /// Результат на этом пути (терм SMT-LIB); `unit` — если ветвь не дала значения.
+    pub result: String,
+}
+
+/// Результат пути, на котором ветвь не дала значения.
+const UNIT: &str = "unit";
+
+/// Состояние пути во время обхода: условия, `let`-привязки и значение последнего узла.
+#[derive(Debug, Clone, Default)]
+struct PathState {
+    conditions: Vec<String>,
+    env: HashMap<String, String>,
+    value: String,
+}
+
+impl PathState {
+    fn assume(mut self, condition: String) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    fn with_value(mut self, value: String) -> Self {
+        self.value = value;
+        self
+    }
+}
+
+/// Символьное исполнение `root`: пути через `if`/`match` с условиями и результатами.
+///
+/// Переменные из `let` подставляются, поэтому условия выражены через входы
+/// (параметры функции, если `root` — функция, или свободные переменные) и годятся
+/// для поиска входа, ведущего в ветвь. `(requires c)` добавляет `c` к условиям.
+/// Выполнимость условий не проверяется, кроме литералов `true`/`false`; `match`
+/// без подходящей ветви (ошибка времени выполнения) путём не считается.
+///
+/// Обход останавливается на первых `max_paths` путях.
+pub fn symbolic_paths(asg: &ASG, root: NodeID, max_paths: usize) -> ASGResult<Vec<SymbolicPath>> {
+    let mut root = root;
+    if let Some(node) = asg.find_node(root) {
+        if node.node_type == NodeType::Function {
+            root = node
+                .find_edge(EdgeType::FunctionBody)
+                .ok_or(ASGError::MissingEdge(node.id, EdgeType::FunctionBody))?
+                .target_node_id;
+        }
+    }
+
+    let start = if max_paths == 0 {
+        vec![]
+    } else {
+        vec![PathState::default()]
+    };
+    let explorer = PathExplorer { asg, max_paths };
+    Ok(explorer
+        .explore(root, start)?
+        .into_iter()
+        .map(|state| SymbolicPath {
+            conditions: state.conditions,
+            result: state.value,
+        })
+        .collect())
+}
+
+/// Обход ASG для [`symbolic_paths`].
+struct PathExplorer<'a> {
+    asg: &'a ASG,
+    max_paths: usize,
+}
+
+impl PathExplorer<'_> {
+    /// Продолжает каждый путь из `states` вычислением узла `id`.
+    fn explore(&self, id: NodeID, states: Vec<PathState>) -> ASGResult<Vec<PathState>> {
+        let node = self.asg.find_node(id).ok_or(ASGError::NodeNotFound(id))?;
+        let child = |edge_type| {
+            node.find_edge(edge_type)
+                .map(|e| e.target_node_id)
+                .ok_or(ASGError::MissingEdge(id, edge_type))
+        };
+
+        let mut result = match node.node_type {
+            NodeType::LiteralInt | NodeType::LiteralBool => {
+                let term = smt_term(self.asg, id)?;
+                states
+                    .into_iter()
+                    .map(|s| s.with_value(term.clone()))
+                    .collect()
+            }
+            NodeType::LiteralUnit => states
+                .into_iter()
+                .map(|s| s.with_value(UNIT.to_string()))
+                .collect(),
+            NodeType::VarRef => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(id))?;
+                states
+                    .into_iter()
+                    .map(|s| {
+                        let term = s.env.get(&name).cloned().unwrap_or_else(|| name.clone());
+                        s.with_value(term)
+                    })
+                    .collect()
+            }
+            NodeType::Block => {
+                let mut states = states
+                    .into_iter()
+                    .map(|s| s.with_value(UNIT.to_string()))
+                    .collect();
+                for statement in body_statements(self.asg, id) {
+                    states = self.explore(statement, states)?;
+                }
+                states
+            }
+            NodeType::Variable => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(id))?;
+                let mut states = self.explore(child(EdgeType::VarValue)?, states)?;
+                for s in &mut states {
+                    s.env.insert(name.clone(), s.value.clone());
+                }
+                states
+            }
+            NodeType::Requires => {
+                let condition = node
+                    .edges
+                    .first()
+                    .map(|e| e.target_node_id)
+                    .ok_or(ASGError::MissingEdge(id, EdgeType::ApplicationArgument))?;
+                let mut paths = Vec::new();
+                for mut s in self.explore(condition, states)? {
+                    let condition = std::mem::replace(&mut s.value, UNIT.to_string());
+                    match condition.as_str() {
+                        "false" => {}
+                        "true" => paths.push(s),
+                        _ => paths.push(s.assume(condition)),
+                    }
+                }
+                paths
+            }
+            NodeType::Ensures => states
+                .into_iter()
+                .map(|s| s.with_value(UNIT.to_string()))
+                .collect(),
+            NodeType::If => {
+                let else_branch = node
+                    .find_edge(EdgeType::ElseBranch)
+                    .map(|e| e.target_node_id);
+                let mut paths = Vec::new();
+                for s in self.explore(child(EdgeType::Condition)?, states)? {
+                    let condition = s.value.clone();
+                    if condition != "false" {
+                        let then = s.clone().assume(condition.clone());
+                        paths.extend(self.explore(child(EdgeType::ThenBranch)?, vec![then])?);
+                    }
+                    if condition != "true" {
+                        let otherwise = s.assume(format!("(not {})", condition));
+                        match else_branch {
+                            Some(branch) => paths.extend(self.explore(branch, vec![otherwise])?),
+                            None => paths.push(otherwise.with_value(UNIT.to_string())),
+                        }
+                    }
+                    if paths.len() >= self.max_paths {
+                        break;
+                    }
+                }
+                paths
+            }
+            NodeType::Match => self.explore_match(node, states)?,
+            other => {
+                let op = smt_op(other).ok_or_else(|| {
+                    ASGError::InvalidOperation(format!(
+                        "symbolic_paths: {:?} is not supported",
+                        other
+                    ))
+                })?;
+                // Операнды тоже могут ветвиться: пути перемножаются
+                let mut partial: Vec<(PathState, Vec<String>)> =
+                    states.into_iter().map(|s| (s, vec![])).collect();
+                for edge in &node.edges {
+                    let mut next = Vec::new();
+                    for (s, operands) in partial {
+                        for s in self.explore(edge.target_node_id, vec![s])? {
+                            let mut operands = operands.clone();
+                            operands.push(s.value.clone());
+                            next.push((s, operands));
+                        }
+                    }
+                    next.truncate(self.max_paths);
+                    partial = next;
+                }
+                partial
+                    .into_iter()
+                    .map(|(s, operands)| s.with_value(format!("({} {})", op, operands.join(" "))))
+                    .collect()
+            }
+        };
+        result.truncate(self.max_paths);
+        Ok(result)
+    }
+
+    /// Ветви `match`: ветвь выбирается, если её образец подходит, а предыдущие — нет.
+    fn explore_match(&self, node: &Node, states: Vec<PathState>) -> ASGResult<Vec<PathState>> {
+        let subject = node
+            .find_edge(EdgeType::MatchSubject)
+            .ok_or(ASGError::MissingEdge(node.id, EdgeType::MatchSubject))?
+            .target_node_id;
+        let mut arms = Vec::new();
+        for edge in node.find_edges(EdgeType::ApplicationArgument) {
+            let arm = self
+                .asg
+                .find_node(edge.target_node_id)
+                .ok_or(ASGError::NodeNotFound(edge.target_node_id))?;
+            let target = |edge_type| {
+                arm.find_edge(edge_type)
+                    .map(|e| e.target_node_id)
+                    .ok_or(ASGError::MissingEdge(arm.id, edge_type))
+            };
+            arms.push((
+                target(EdgeType::MatchPattern)?,
+                target(EdgeType::MatchBody)?,
+            ));
+        }
+
+        let mut paths = Vec::new();
+        for s in self.explore(subject, states)? {
+            let subject = s.value.clone();
+            let mut earlier = Vec::new();
+            for &(pattern, body) in &arms {
+                let (condition, binding) = self.pattern_condition(pattern, &subject)?;
+                let mut arm = s.clone();
+                arm.conditions
+                    .extend(earlier.iter().map(|c| format!("(not {})", c)));
+                if let Some(condition) = &condition {
+                    arm.conditions.push(condition.clone());
+                }
+                if let Some(name) = binding {
+                    arm.env.insert(name, subject.clone());
+                }
+                paths.extend(self.explore(body, vec![arm])?);
+                match condition {
+                    Some(condition) => earlier.push(condition),
+                    None => break,
+                }
+            }
+            if paths.len() >= self.max_paths {
+                break;
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Условие совпадения образца (`None` — совпадает всегда) и связываемое имя.
+    fn pattern_condition(
+        &self,
+        pattern: NodeID,
+        subject: &str,
+    ) -> ASGResult<(Option<String>, Option<String>)> {
+        let node = self
+            .asg
+            .find_node(pattern)
+            .ok_or(ASGError::NodeNotFound(pattern))?;
+        match node.node_type {
+            NodeType::LiteralInt | NodeType::LiteralBool => Ok((
+                Some(format!("(= {} {})", subject, smt_term(self.asg, pattern)?)),
+                None,
+            )),
+            NodeType::VarRef => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(pattern))?;
+                Ok((None, (name != "_").then_some(name)))
+            }
+            other => Err(ASGError::InvalidOperation(format!(
+                "symbolic_paths: pattern {:?} is not supported",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, Value};
+
+    const SAFE_DIV: &str = "(fn safe-div (x y)
+         (do (requires (> y 0))
+             (requires (>= x 0))
+             (ensures (>= result 0))
+             (let q (// x y))
+             (if (< q 0) 0 q)))";
+
+    #[test]
+    fn test_to_smtlib_exports_contracts() {
+        let (asg, _) = crate::parser::parse(SAFE_DIV).unwrap();
+        let smt = to_smtlib(&asg, "safe-div").unwrap();
+
+        assert_eq!(
+            smt,
+            "; verification conditions for safe-div\n\
+             (set-logic ALL)\n\
+             (declare-const x Int)\n\
+             (declare-const y Int)\n\
+             (define-fun q () Int (div x y))\n\
+             (define-fun result () Int (ite (< q 0) 0 q))\n\
+             (assert (> y 0))\n\
+             (assert (>= x 0))\n\
+             (assert (not (>= result 0)))\n\
+             (check-sat)\n"
+        );
+
+        assert!(matches!(
+            to_smtlib(&asg, "missing"),
+            Err(ASGError::UnknownFunction(_))
+        ));
+        // `/` даёт Float, а условия строятся над Int
+        let (asg, _) = crate::parser::parse("(fn half (x) (/ x 2))").unwrap();
+        assert!(matches!(
+            to_smtlib(&asg, "half"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+        let (asg, _) = crate::parser::parse(r#"(fn greet (x) (concat "hi " x))"#).unwrap();
+        assert!(matches!(
+            to_smtlib(&asg, "greet"),
+            Err(ASGError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_symbolic_paths_of_if() {
+        let (asg, roots) =
+            crate::parser::parse("(fn clamp (x) (do (let y (* x 2)) (if (> y 10) 10 y)))").unwrap();
+        let paths = symbolic_paths(&asg, roots[0], 8).unwrap();
+
+        assert_eq!(
+            paths,
+            vec![
+                SymbolicPath {
+                    conditions: vec!["(> (* x 2) 10)".to_string()],
+                    result: "10".to_string(),
+                },
+                SymbolicPath {
+                    conditions: vec!["(not (> (* x 2) 10))".to_string()],
+                    result: "(* x 2)".to_string(),
+                },
+            ]
+        );
+        assert_eq!(symbolic_paths(&asg, roots[0], 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_symbolic_paths_of_match() {
+        let (asg, roots) =
+            crate::parser::parse("(match (+ n 1) 0 100 1 (if b 1 2) k (* k 3))").unwrap();
+        let paths = symbolic_paths(&asg, roots[0], 8).unwrap();
+        let conditions: Vec<_> = paths.iter().map(|p| p.conditions.join(" ")).collect();
+
+        assert_eq!(
+            conditions,
+            vec![
+                "(= (+ n 1) 0)",
+                "(not (= (+ n 1) 0)) (= (+ n 1) 1) b",
+                "(not (= (+ n 1) 0)) (= (+ n 1) 1) (not b)",
+                "(not (= (+ n 1) 0)) (not (= (+ n 1) 1))",
+            ]
+        );
+        assert_eq!(paths[3].result, "(* (+ n 1) 3)");
+    }
+
+    #[test]
+    fn test_contracts_are_checked_at_runtime() {
+        let run = |call: &str| {
+            let (asg, roots) = crate::parser::parse(&format!(
+                "{} (fn bad (x) (do (ensures (> result x)) (- x 1))) {}",
+                SAFE_DIV, call
+            ))
+            .unwrap();
+            let mut interp = Interpreter::new();
+            let mut result = Ok(Value::Unit);
+            for root in roots {
+                result = interp.execute(&asg, root);
+            }
+            result
+        };
+
+        assert_eq!(run("(safe-div 7 2)").unwrap(), Value::Int(3));
+        assert!(matches!(
+            run("(safe-div 7 0)"),
+            Err(ASGError::InvalidOperation(msg)) if msg == "Precondition failed"
+        ));
+        assert!(matches!(
+            run("(bad 5)"),
+            Err(ASGError::InvalidOperation(msg)) if msg == "Postcondition failed"
+        ));
+    }
+
+    #[test]
+    fn test_postconditions_see_body_locals() {
+        let run = |source: &str| {
+            let (asg, roots) = crate::parser::parse(source).unwrap();
+            let mut interp = Interpreter::new();
+            let mut result = Ok(Value::Unit);
+            for root in roots {
+                result = interp.execute(&asg, root);
+            }
+            result
+        };
+
+        assert_eq!(
+            run("(fn f (x) (do (let q (* x 2)) (ensures (== result q)) q)) (f 3)").unwrap(),
+            Value::Int(6)
+        );
+        assert!(matches!(
+            run("(fn f (x) (do (let q (* x 2)) (ensures (== result q)) x)) (f 3)"),
+            Err(ASGError::InvalidOperation(msg)) if msg == "Postcondition failed"
+        ));
+        // Локальные переменные тела не остаются видны после вызова
+        assert!(run("(fn f (x) (do (let q x) (ensures (== result q)) q)) (f 3) q").is_err());
+    }
+}
@@ -7,7 +7,7 @@
 //! - Вывод типов на основе Hindley-Milner
 //! - Проверку корректности типов в ASG
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use crate::asg::{Node, NodeID, ASG};
@@ -307,10 +307,20 @@ impl TypeContext {
 
 // === Type Checker ===
 
+/// Предупреждение проверки типов (не прерывает проверку).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeWarning {
+    pub node_id: NodeID,
+    pub message: String,
+}
+
 /// Type Checker для ASG.
 pub struct TypeChecker {
     context: TypeContext,
     substitution: Substitution,
+    warnings: Vec<TypeWarning>,
+    /// Схемы записей из defrecord: имя типа -> имена полей
+    record_schemas: HashMap<String, Vec<String>>,
 }
 
 impl TypeChecker {
@@ -318,9 +328,16 @@ impl TypeChecker {
         Self {
             context: TypeContext::new(),
             substitution: Substitution::new(),
+            warnings: Vec::new(),
+            record_schemas: HashMap::new(),
         }
     }
 
+    /// Предупреждения, собранные во время проверки.
+    pub fn warnings(&self) -> &[TypeWarning] {
+        &self.warnings
+    }
+
     /// Основная функция проверки типов.
     pub fn check(&mut self, asg: &ASG) -> ASGResult<()> {
         // Вывод типов для всех узлов
@@ -348,7 +365,7 @@ impl TypeChecker {
 
         let ty = match node.node_type {
             // === Литералы ===
-            NodeType::LiteralInt => SynType::Int,
+            NodeType::LiteralInt | NodeType::LiteralBigInt => SynType::Int,
             NodeType::LiteralFloat => SynType::Float,
             NodeType::LiteralBool => SynType::Bool,
             NodeType::LiteralString => SynType::String,
@@ -362,6 +379,21 @@ impl TypeChecker {
             | NodeType::Mod => {
                 let (t1, t2) = self.get_binary_operand_types(asg, node)?;
 
+                // Неявное расширение Int -> Float допускается, но с предупреждением;
+                // явная точка преобразования — (cast Float x)
+                match (self.substitution.apply(&t1), self.substitution.apply(&t2)) {
+                    (SynType::Int, SynType::Float) | (SynType::Float, SynType::Int) => {
+                        self.warnings.push(TypeWarning {
+                            node_id: node.id,
+                            message: "Implicit promotion of Int to Float; use (cast Float x)"
+                                .to_string(),
+                        });
+                        self.context.insert_node_type(node.id, SynType::Float);
+                        return Ok(SynType::Float);
+                    }
+                    _ => {}
+                }
+
                 // Создаём переменную типа для результата
                 let result_type = fresh_type_var();
 
@@ -388,6 +420,33 @@ impl TypeChecker {
                 operand_type
             }
 
+            // Явное приведение типа — авторитетная точка преобразования
+            NodeType::Cast => {
+                let operand_type = self.get_unary_operand_type(asg, node)?;
+                let operand_type = self.substitution.apply(&operand_type);
+                if !matches!(
+                    operand_type,
+                    SynType::Int | SynType::Float | SynType::TypeVariable(_)
+                ) {
+                    return Err(ASGError::TypeError(format!(
+                        "Cannot cast {:?} to numeric type",
+                        operand_type
+                    )));
+                }
+                match node.get_name().as_deref() {
+                    Some("Int") => SynType::Int,
+                    Some("Float") => SynType::Float,
+                    // Decimal в системе типов не представлен
+                    Some("Decimal") => fresh_type_var(),
+                    other => {
+                        return Err(ASGError::TypeError(format!(
+                            "Unknown cast target: {}",
+                            other.unwrap_or("<none>")
+                        )))
+                    }
+                }
+            }
+
             // === Операции сравнения ===
             NodeType::Eq
             | NodeType::Ne
@@ -486,7 +545,88 @@ impl TypeChecker {
                 func_type
             }
 
+            // === Объявление записи ===
+            NodeType::RecordDef => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let fields = node
+                    .find_edges(EdgeType::RecordFieldDef)
+                    .into_iter()
+                    .filter_map(|e| asg.find_node(e.target_node_id))
+                    .filter_map(|n| n.get_name())
+                    .collect();
+                self.record_schemas.insert(name, fields);
+                SynType::Unit
+            }
+
+            // === Объявление enum: варианты регистрируются как конструкторы ===
+            NodeType::EnumDef => {
+                let name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
+                let mut variants = Vec::new();
+                for edge in node.find_edges(EdgeType::ApplicationArgument) {
+                    let variant = asg
+                        .find_node(edge.target_node_id)
+                        .ok_or(ASGError::NodeNotFound(edge.target_node_id))?;
+                    let tag = variant
+                        .get_name()
+                        .ok_or(ASGError::MissingPayload(variant.id))?;
+                    let field_types: Vec<_> = variant
+                        .find_edges(EdgeType::RecordFieldDef)
+                        .iter()
+                        .map(|_| fresh_type_var())
+                        .collect();
+                    variants.push((tag, field_types));
+                }
+
+                let adt = SynType::ADT {
+                    name,
+                    variants: variants.clone(),
+                };
+                for (tag, field_types) in variants {
+                    let constructor = if field_types.is_empty() {
+                        adt.clone()
+                    } else {
+                        SynType::Function {
+                            parameters: field_types,
+                            return_type: Box::new(adt.clone()),
+                        }
+                    };
+                    self.context.insert_var(tag, constructor);
+                }
+                SynType::Unit
+            }
+
             // === Вызов функции ===
+            // Именованные аргументы сопоставляются с параметрами по имени только при
+            // выполнении: тип функции хранит лишь порядок параметров
+            NodeType::Call if node.edges.iter().any(|e| e.payload.is_some()) => {
+                self.get_call_arguments(asg, node)?;
+                fresh_type_var()
+            }
+
+            NodeType::Call if self.record_constructor_name(asg, node).is_some() => {
+                let name = self.record_constructor_name(asg, node).unwrap_or_default();
+                let fields = self.record_schemas[&name].clone();
+                let arg_types = self.get_call_arguments(asg, node)?;
+
+                if arg_types.len() < fields.len() {
+                    return Err(ASGError::TypeError(format!(
+                        "Record {} is missing field(s): {}",
+                        name,
+                        fields[arg_types.len()..].join(", ")
+                    )));
+                }
+                if arg_types.len() > fields.len() {
+                    return Err(ASGError::TypeError(format!(
+                        "Record {} has {} field(s), got {} extra value(s)",
+                        name,
+                        fields.len(),
+                        arg_types.len() - fields.len()
+                    )));
+                }
+
+                SynType::Record(fields.into_iter().zip(arg_types).collect())
+            }
+
             NodeType::Call => {
                 let func_type = self.get_edge_target_type(asg, node, EdgeType::CallTarget)?;
                 let arg_types = self.get_call_arguments(asg, node)?;
@@ -555,10 +695,19 @@ impl TypeChecker {
             NodeType::VarRef => {
                 let var_name = node.get_name().ok_or(ASGError::MissingPayload(node.id))?;
 
-                self.context
-                    .get_var(&var_name)
-                    .cloned()
-                    .ok_or_else(|| ASGError::UnknownVariable(var_name))?
+                if let Some(ty) = self.context.get_var(&var_name) {
+                    ty.clone()
+                } else if let Some(fields) = self.record_schemas.get(&var_name) {
+                    // Имя записи используется как конструктор
+                    let parameters: Vec<_> = fields.iter().map(|_| fresh_type_var()).collect();
+                    let record = fields.iter().cloned().zip(parameters.clone()).collect();
+                    SynType::Function {
+                        parameters,
+                        return_type: Box::new(SynType::Record(record)),
+                    }
+                } else {
+                    return Err(ASGError::UnknownVariable(var_name));
+                }
             }
 
             // === Присваивание ===
@@ -574,6 +723,16 @@ impl TypeChecker {
                 SynType::Unit
             }
 
+            // Составное присваивание: значение — результат (op x n)
+            NodeType::CompoundAssign => {
+                let target_type = self.get_edge_target_type(asg, node, EdgeType::AssignTarget)?;
+                let value_type = self.get_edge_target_type(asg, node, EdgeType::AssignValue)?;
+                let s = unify(&target_type, &value_type)
+                    .map_err(|e| ASGError::TypeError(e.to_string()))?;
+                self.substitution = self.substitution.compose(&s);
+                s.apply(&value_type)
+            }
+
             // === Тензоры ===
             NodeType::LiteralTensor
             | NodeType::TensorAdd
@@ -695,6 +854,20 @@ impl TypeChecker {
         Ok((t1, t2))
     }
 
+    /// Имя записи, если узел Call вызывает конструктор из defrecord.
+    fn record_constructor_name(&self, asg: &ASG, node: &Node) -> Option<String> {
+        let target = asg.find_node(node.find_edge(EdgeType::CallTarget)?.target_node_id)?;
+        if target.node_type != NodeType::VarRef {
+            return None;
+        }
+        let name = target.get_name()?;
+        if self.context.get_var(&name).is_none() && self.record_schemas.contains_key(&name) {
+            Some(name)
+        } else {
+            None
+        }
+    }
+
     /// Получить тип единственного операнда.
     fn get_unary_operand_type(&mut self, asg: &ASG, node: &Node) -> ASGResult<SynType> {
         let edge = node.edges.first().ok_or(ASGError::MissingEdge(
@@ -782,6 +955,57 @@ pub fn infer_types(asg: &ASG) -> ASGResult<HashMap<NodeID, SynType>> {
     Ok(checker.get_node_types())
 }
 
+/// Проверка типов с возвращением предупреждений (например, неявного расширения Int -> Float).
+pub fn check_types_with_warnings(asg: &ASG) -> ASGResult<Vec<TypeWarning>> {
+    let mut checker = TypeChecker::new();
+    checker.check(asg)?;
+    Ok(checker.warnings)
+}
+
+/// Необязательная проверка `match`: предупреждение для каждого `match` без
+/// ветки, совпадающей с любым значением (`_` или переменная). Такой `match`
+/// завершается ошибкой выполнения, если ни один образец не подошёл.
+pub fn check_match_wildcards(asg: &ASG) -> Vec<TypeWarning> {
+    // Вариант без полей в образце сравнивает тег, а не связывает имя
+    let unit_variants: HashSet<String> = asg
+        .nodes
+        .iter()
+        .filter(|n| n.node_type == NodeType::EnumDef)
+        .flat_map(|n| n.find_edges(EdgeType::ApplicationArgument))
+        .filter_map(|e| asg.find_node(e.target_node_id))
+        .filter(|v| v.find_edge(EdgeType::RecordFieldDef).is_none())
+        .filter_map(|v| v.get_name())
+        .collect();
+
+    let catch_all = |arm_id: NodeID| {
+        asg.find_node(arm_id)
+            .and_then(|arm| arm.find_edge(EdgeType::MatchPattern))
+            .and_then(|e| asg.find_node(e.target_node_id))
+            .is_some_and(|pattern| {
+                pattern.node_type == NodeType::VarRef
+                    && pattern
+                        .get_name()
+                        .is_some_and(|name| !unit_variants.contains(&name))
+            })
+    };
+
+    asg.nodes
+        .iter()
+        .filter(|n| n.node_type == NodeType::Match)
+        .filter(|n| {
+            !n.find_edges(EdgeType::ApplicationArgument)
+                .iter()
+                .any(|e| catch_all(e.target_node_id))
+        })
+        .map(|n| TypeWarning {
+            node_id: n.id,
+            message:
+                "match has no catch-all arm (_ or a variable); unmatched values fail at runtime"
+                    .to_string(),
+        })
+        .collect()
+}
+
 // === Тесты ===
 
 #[cfg(test)]
@@ -827,4 +1051,80 @@ mod tests {
         assert!(!occurs_check("a", &SynType::TypeVariable("b".to_string())));
         assert!(!occurs_check("a", &SynType::Int));
     }
+
+    #[test]
+    fn test_explicit_cast_checks_cleanly() {
+        let (asg, root) = crate::parser::parse_expr("(+ (cast Float 1) 2.5)").unwrap();
+        let warnings = check_types_with_warnings(&asg).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(infer_types(&asg).unwrap()[&root], SynType::Float);
+    }
+
+    #[test]
+    fn test_defrecord_valid_construction() {
+        let (asg, _) =
+            crate::parser::parse_expr("(do (defrecord Point (x y)) (Point 1 2.5))").unwrap();
+        let types = infer_types(&asg).unwrap();
+        let call = asg
+            .nodes
+            .iter()
+            .find(|n| n.node_type == NodeType::Call)
+            .unwrap();
+        assert_eq!(
+            types[&call.id],
+            SynType::Record(vec![
+                ("x".to_string(), SynType::Int),
+                ("y".to_string(), SynType::Float),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_defrecord_missing_field() {
+        let (asg, _) = crate::parser::parse_expr("(do (defrecord Point (x y)) (Point 1))").unwrap();
+        let err = check_types(&asg).unwrap_err();
+        assert!(err.to_string().contains("missing field(s): y"));
+    }
+
+    #[test]
+    fn test_defenum_constructor_type() {
+        let (asg, _) =
+            crate::parser::parse_expr("(do (defenum Shape (Circle r) (Rect w h)) (Circle 5))")
+                .unwrap();
+        let types = infer_types(&asg).unwrap();
+        let call = asg
+            .nodes
+            .iter()
+            .find(|n| n.node_type == NodeType::Call)
+            .unwrap();
+        assert!(matches!(&types[&call.id], SynType::ADT { name, .. } if name == "Shape"));
+    }
+
+    #[test]
+    fn test_match_without_wildcard_warns() {
+        let (asg, root) = crate::parser::parse_expr("(match 2 1 \"one\" 2 \"two\")").unwrap();
+        let warnings = check_match_wildcards(&asg);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].node_id, root);
+
+        let (asg, _) = crate::parser::parse_expr("(match 2 1 \"one\" _ \"other\")").unwrap();
+        assert!(check_match_wildcards(&asg).is_empty());
+        let (asg, _) = crate::parser::parse_expr("(match 2 1 \"one\" n n)").unwrap();
+        assert!(check_match_wildcards(&asg).is_empty());
+
+        // Вариант без полей — не переменная
+        let (asg, _) = crate::parser::parse_expr(
+            "(do (defenum Color (Red) (Green)) (match Red Red 1 Green 2))",
+        )
+        .unwrap();
+        assert_eq!(check_match_wildcards(&asg).len(), 1);
+    }
+
+    #[test]
+    fn test_implicit_promotion_warns() {
+        let (asg, root) = crate::parser::parse_expr("(+ 1 2.5)").unwrap();
+        let warnings = check_types_with_warnings(&asg).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].node_id, root);
+    }
 }
@@ -3,14 +3,28 @@
 //! Компиляция ASG в WebAssembly (WASM).
 //!
 //! Включается feature-флагом `wasm_backend`.
+//!
+//! # Платформа WASI
+//!
+//! С [`Target::Wasi`] модуль импортирует `wasi_snapshot_preview1.fd_write`,
+//! экспортирует `_start` и `memory` и запускается напрямую (`wasmtime app.wasm`).
+//! `print` пишет в stdout (fd 1) строку и `\n`, как интерпретатор:
+//! - целые — десятичная запись со знаком `-` (`-42`);
+//! - булевы (литералы, сравнения, `and`/`or`/`not`) — `true` / `false`;
+//! - строковые литералы — байты UTF-8 как есть.
+//!
+//! Печать чисел с плавающей точкой и строк из переменных не поддерживается
+//! (ошибка компиляции).
 
 use crate::asg::ASG;
+#[cfg(feature = "wasm_backend")]
+use crate::compiler::Target;
 use crate::error::ASGResult;
 
 // === Реализация с wasm-encoder (когда feature включен) ===
 
 #[cfg(feature = "wasm_backend")]
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[cfg(feature = "wasm_backend")]
 use crate::asg::{Node, NodeID};
@@ -21,11 +35,42 @@ use crate::nodecodes::{EdgeType, NodeType};
 
 #[cfg(feature = "wasm_backend")]
 use wasm_encoder::{
-    CodeSection, DataSection, ExportKind, ExportSection, Function, FunctionSection, GlobalSection,
-    GlobalType, ImportSection, Instruction, MemorySection, MemoryType, Module, TypeSection,
-    ValType,
+    BlockType, CodeSection, DataSection, ExportKind, ExportSection, Function, FunctionSection,
+    GlobalSection, GlobalType, ImportSection, Instruction, MemArg, MemorySection, MemoryType,
+    Module, TypeSection, ValType,
 };
 
+/// Индексы функций WASI-модуля: импорт `fd_write`, `_start` и вспомогательные функции печати.
+#[cfg(feature = "wasm_backend")]
+const WASI_FD_WRITE: u32 = 0;
+#[cfg(feature = "wasm_backend")]
+const WASI_START: u32 = 1;
+#[cfg(feature = "wasm_backend")]
+const WASI_WRITE_BYTES: u32 = 2;
+#[cfg(feature = "wasm_backend")]
+const WASI_PRINT_I64: u32 = 3;
+#[cfg(feature = "wasm_backend")]
+const WASI_PRINT_BOOL: u32 = 4;
+
+/// Раскладка памяти WASI: iovec (ptr, len) по адресу 0, nwritten — 8,
+/// буфер десятичной записи числа заканчивается на 48 (20 цифр, знак и `\n`).
+#[cfg(feature = "wasm_backend")]
+const WASI_IOVEC: i32 = 0;
+#[cfg(feature = "wasm_backend")]
+const WASI_NWRITTEN: i32 = 8;
+#[cfg(feature = "wasm_backend")]
+const WASI_DIGITS_END: i32 = 48;
+
+/// Как значение аргумента `print` переводится в байты.
+#[cfg(feature = "wasm_backend")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PrintKind {
+    Int,
+    Bool,
+    Float,
+    String,
+}
+
 /// WASM Backend для компиляции ASG.
 #[cfg(feature = "wasm_backend")]
 pub struct WasmBackend {
@@ -45,6 +90,12 @@ pub struct WasmBackend {
     string_offset: u32,
     /// Включить GC
     gc_enabled: bool,
+    /// Целевая платформа
+    target: Target,
+    /// Вид значения переменных (для печати)
+    variable_kinds: HashMap<String, PrintKind>,
+    /// Адреса строк "true\n" / "false\n" (WASI)
+    bool_strings: (u32, u32),
 }
 
 #[cfg(feature = "wasm_backend")]
@@ -60,6 +111,17 @@ impl WasmBackend {
             string_data: Vec::new(),
             string_offset: 0x1000, // Строки начинаются после GC metadata
             gc_enabled: true,
+            target: Target::Wasm,
+            variable_kinds: HashMap::new(),
+            bool_strings: (0, 0),
+        }
+    }
+
+    /// Создать WASM backend для заданной платформы.
+    pub fn with_target(target: Target) -> Self {
+        Self {
+            target,
+            ..Self::new()
         }
     }
 
@@ -74,6 +136,9 @@ impl WasmBackend {
             string_data: Vec::new(),
             string_offset: 1024,
             gc_enabled: false,
+            target: Target::Wasm,
+            variable_kinds: HashMap::new(),
+            bool_strings: (0, 0),
         }
     }
 
@@ -84,6 +149,10 @@ impl WasmBackend {
 
     /// Компиляция ASG в WASM байткод.
     pub fn compile(&mut self, asg: &ASG) -> ASGResult<Vec<u8>> {
+        if self.target == Target::Wasi {
+            return self.compile_wasi(asg);
+        }
+
         let mut module = Module::new();
 
         // === Type Section ===
@@ -172,6 +241,13 @@ impl WasmBackend {
         module.section(&codes);
 
         // === Data Section (для строковых литералов) ===
+        self.emit_data(&mut module);
+
+        Ok(module.finish())
+    }
+
+    /// Добавить секцию Data со строковыми данными.
+    fn emit_data(&self, module: &mut Module) {
         if !self.string_data.is_empty() {
             let mut data = DataSection::new();
             for (offset, bytes) in &self.string_data {
@@ -183,25 +259,314 @@ impl WasmBackend {
             }
             module.section(&data);
         }
+    }
+
+    /// Разместить байты в секции Data; возвращает адрес.
+    fn add_data(&mut self, bytes: Vec<u8>) -> u32 {
+        let offset = self.string_offset;
+        self.string_offset += bytes.len() as u32;
+        self.string_data.push((offset, bytes));
+        offset
+    }
+
+    /// Компиляция в WASI-модуль с экспортом `_start`.
+    fn compile_wasi(&mut self, asg: &ASG) -> ASGResult<Vec<u8>> {
+        let mut module = Module::new();
+
+        // === Type Section ===
+        let mut types = TypeSection::new();
+        // Type 0: (i32, i32, i32, i32) -> i32 (fd_write)
+        types.ty().function(
+            vec![ValType::I32, ValType::I32, ValType::I32, ValType::I32],
+            vec![ValType::I32],
+        );
+        // Type 1: () -> () (_start)
+        types.ty().function(vec![], vec![]);
+        // Type 2: (i32, i32) -> () (write_bytes)
+        types
+            .ty()
+            .function(vec![ValType::I32, ValType::I32], vec![]);
+        // Type 3: (i64) -> () (print_i64, print_bool)
+        types.ty().function(vec![ValType::I64], vec![]);
+        module.section(&types);
+
+        // === Import Section ===
+        let mut imports = ImportSection::new();
+        imports.import(
+            "wasi_snapshot_preview1",
+            "fd_write",
+            wasm_encoder::EntityType::Function(0),
+        );
+        module.section(&imports);
+
+        // === Function Section ===
+        let mut functions = FunctionSection::new();
+        functions.function(1); // _start
+        functions.function(2); // write_bytes
+        functions.function(3); // print_i64
+        functions.function(3); // print_bool
+        module.section(&functions);
+
+        // === Memory Section ===
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: 1,
+            maximum: Some(16),
+            memory64: false,
+            shared: false,
+            page_size_log2: None,
+        });
+        module.section(&memories);
+
+        // === Export Section ===
+        let mut exports = ExportSection::new();
+        exports.export("_start", ExportKind::Func, WASI_START);
+        exports.export("memory", ExportKind::Memory, 0);
+        module.section(&exports);
+
+        // === Code Section ===
+        self.bool_strings = (
+            self.add_data(b"true\n".to_vec()),
+            self.add_data(b"false\n".to_vec()),
+        );
+        let start = self.compile_start(asg)?;
+
+        let mut codes = CodeSection::new();
+        codes.function(&start);
+        codes.function(&Self::wasi_write_bytes());
+        codes.function(&Self::wasi_print_i64());
+        codes.function(&self.wasi_print_bool());
+        module.section(&codes);
+
+        self.emit_data(&mut module);
 
         Ok(module.finish())
     }
 
+    /// Тело `_start`: корневые узлы по порядку, результаты отбрасываются.
+    fn compile_start(&mut self, asg: &ASG) -> ASGResult<Function> {
+        let (roots, variables) = roots_and_variables(asg);
+        let mut func = Function::new(vec![(variables, ValType::I64)]);
+
+        for node in roots {
+            self.compile_node(asg, node, &mut func)?;
+            func.instruction(&Instruction::Drop);
+        }
+
+        func.instruction(&Instruction::End);
+        Ok(func)
+    }
+
+    /// `write_bytes(ptr, len)`: записать байты в stdout через `fd_write`.
+    fn wasi_write_bytes() -> Function {
+        let mut func = Function::new(vec![]);
+        let word = |offset| MemArg {
+            offset,
+            align: 2,
+            memory_index: 0,
+        };
+
+        // iovec = { ptr, len }
+        func.instruction(&Instruction::I32Const(WASI_IOVEC));
+        func.instruction(&Instruction::LocalGet(0));
+        func.instruction(&Instruction::I32Store(word(0)));
+        func.instruction(&Instruction::I32Const(WASI_IOVEC));
+        func.instruction(&Instruction::LocalGet(1));
+        func.instruction(&Instruction::I32Store(word(4)));
+
+        // fd_write(stdout, &iovec, 1, &nwritten)
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Const(WASI_IOVEC));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Const(WASI_NWRITTEN));
+        func.instruction(&Instruction::Call(WASI_FD_WRITE));
+        func.instruction(&Instruction::Drop);
+        func.instruction(&Instruction::End);
+        func
+    }
+
+    /// `print_i64(value)`: десятичная запись числа и `\n`, цифры пишутся с конца буфера.
+    fn wasi_print_i64() -> Function {
+        // Локальные: 1 — позиция в буфере, 2 — число отрицательное, 3 — текущая цифра
+        let mut func = Function::new(vec![(2, ValType::I32), (1, ValType::I64)]);
+        let byte = MemArg {
+            offset: 0,
+            align: 0,
+            memory_index: 0,
+        };
+
+        // Последний байт — перевод строки
+        func.instruction(&Instruction::I32Const(WASI_DIGITS_END - 1));
+        func.instruction(&Instruction::LocalTee(1));
+        func.instruction(&Instruction::I32Const(b'\n' as i32));
+        func.instruction(&Instruction::I32Store8(byte));
+        func.instruction(&Instruction::LocalGet(0));
+        func.instruction(&Instruction::I64Const(0));
+        func.instruction(&Instruction::I64LtS);
+        func.instruction(&Instruction::LocalSet(2));
+
+        // Цифры младшими вперёд; остаток берётся по модулю, чтобы i64::MIN не переполнялся
+        func.instruction(&Instruction::Loop(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(1));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::LocalSet(1));
+        func.instruction(&Instruction::LocalGet(0));
+        func.instruction(&Instruction::I64Const(10));
+        func.instruction(&Instruction::I64RemS);
+        func.instruction(&Instruction::LocalSet(3));
+        func.instruction(&Instruction::LocalGet(2));
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::I64Const(0));
+        func.instruction(&Instruction::LocalGet(3));
+        func.instruction(&Instruction::I64Sub);
+        func.instruction(&Instruction::LocalSet(3));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::LocalGet(1));
+        func.instruction(&Instruction::LocalGet(3));
+        func.instruction(&Instruction::I32WrapI64);
+        func.instruction(&Instruction::I32Const(b'0' as i32));
+        func.instruction(&Instruction::I32Add);
+        func.instruction(&Instruction::I32Store8(byte));
+        func.instruction(&Instruction::LocalGet(0));
+        func.instruction(&Instruction::I64Const(10));
+        func.instruction(&Instruction::I64DivS);
+        func.instruction(&Instruction::LocalTee(0));
+        func.instruction(&Instruction::I64Const(0));
+        func.instruction(&Instruction::I64Ne);
+        func.instruction(&Instruction::BrIf(0));
+        func.instruction(&Instruction::End);
+
+        // Знак
+        func.instruction(&Instruction::LocalGet(2));
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::LocalGet(1));
+        func.instruction(&Instruction::I32Const(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::LocalTee(1));
+        func.instruction(&Instruction::I32Const(b'-' as i32));
+        func.instruction(&Instruction::I32Store8(byte));
+        func.instruction(&Instruction::End);
+
+        func.instruction(&Instruction::LocalGet(1));
+        func.instruction(&Instruction::I32Const(WASI_DIGITS_END));
+        func.instruction(&Instruction::LocalGet(1));
+        func.instruction(&Instruction::I32Sub);
+        func.instruction(&Instruction::Call(WASI_WRITE_BYTES));
+        func.instruction(&Instruction::End);
+        func
+    }
+
+    /// `print_bool(value)`: `true\n` или `false\n`.
+    fn wasi_print_bool(&self) -> Function {
+        let (true_str, false_str) = self.bool_strings;
+        let mut func = Function::new(vec![]);
+        func.instruction(&Instruction::LocalGet(0));
+        func.instruction(&Instruction::I64Eqz);
+        func.instruction(&Instruction::If(BlockType::Empty));
+        func.instruction(&Instruction::I32Const(false_str as i32));
+        func.instruction(&Instruction::I32Const(6));
+        func.instruction(&Instruction::Call(WASI_WRITE_BYTES));
+        func.instruction(&Instruction::Else);
+        func.instruction(&Instruction::I32Const(true_str as i32));
+        func.instruction(&Instruction::I32Const(5));
+        func.instruction(&Instruction::Call(WASI_WRITE_BYTES));
+        func.instruction(&Instruction::End);
+        func.instruction(&Instruction::End);
+        func
+    }
+
+    /// Печать через WASI: значение переводится в байты по виду аргумента.
+    fn compile_wasi_print(
+        &mut self,
+        asg: &ASG,
+        arg_node: &Node,
+        func: &mut Function,
+    ) -> ASGResult<()> {
+        match self.print_kind(asg, arg_node) {
+            PrintKind::String => {
+                let mut bytes = match (arg_node.node_type, &arg_node.payload) {
+                    (NodeType::LiteralString, Some(payload)) => payload.clone(),
+                    _ => {
+                        return Err(ASGError::CompilationError(
+                            "WASI target can only print string literals".to_string(),
+                        ))
+                    }
+                };
+                bytes.push(b'\n');
+                let len = bytes.len() as i32;
+                let offset = self.add_data(bytes);
+                func.instruction(&Instruction::I32Const(offset as i32));
+                func.instruction(&Instruction::I32Const(len));
+                func.instruction(&Instruction::Call(WASI_WRITE_BYTES));
+            }
+            PrintKind::Bool => {
+                self.compile_node(asg, arg_node, func)?;
+                func.instruction(&Instruction::Call(WASI_PRINT_BOOL));
+            }
+            PrintKind::Int => {
+                self.compile_node(asg, arg_node, func)?;
+                func.instruction(&Instruction::Call(WASI_PRINT_I64));
+            }
+            PrintKind::Float => {
+                return Err(ASGError::CompilationError(
+                    "WASI target cannot print floating-point values".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Вид значения выражения.
+    fn print_kind(&self, asg: &ASG, node: &Node) -> PrintKind {
+        match node.node_type {
+            NodeType::LiteralString => PrintKind::String,
+            NodeType::LiteralBool
+            | NodeType::Eq
+            | NodeType::Ne
+            | NodeType::Lt
+            | NodeType::Le
+            | NodeType::Gt
+            | NodeType::Ge
+            | NodeType::And
+            | NodeType::Or
+            | NodeType::Not => PrintKind::Bool,
+            NodeType::LiteralFloat
+            | NodeType::MathPi
+            | NodeType::MathE
+            | NodeType::MathSqrt
+            | NodeType::MathAbs
+            | NodeType::MathFloor
+            | NodeType::MathCeil
+            | NodeType::MathMin
+            | NodeType::MathMax => PrintKind::Float,
+            NodeType::VarRef => node
+                .get_name()
+                .and_then(|name| self.variable_kinds.get(&name).copied())
+                .unwrap_or(PrintKind::Int),
+            NodeType::If => node
+                .find_edge(EdgeType::ThenBranch)
+                .and_then(|edge| asg.find_node(edge.target_node_id))
+                .map_or(PrintKind::Int, |then| self.print_kind(asg, then)),
+            _ => PrintKind::Int,
+        }
+    }
+
     /// Компиляция main функции.
     fn compile_main(&mut self, asg: &ASG) -> ASGResult<Function> {
-        let mut func = Function::new(vec![(1, ValType::I64)]); // 1 локальная переменная
+        let (roots, variables) = roots_and_variables(asg);
+        let mut func = Function::new(vec![(variables, ValType::I64)]);
 
-        // Компилируем все корневые узлы
-        let mut last_node_id = None;
-        for node in &asg.nodes {
+        // Компилируем все корневые узлы; подвыражения компилируются из них
+        for (i, node) in roots.iter().enumerate() {
+            if i > 0 {
+                func.instruction(&Instruction::Drop);
+            }
             self.compile_node(asg, node, &mut func)?;
-            last_node_id = Some(node.id);
         }
 
         // Возвращаем результат последнего узла или 0
-        if last_node_id.is_some() {
-            // Значение уже на стеке
-        } else {
+        if roots.is_empty() {
             func.instruction(&Instruction::I64Const(0));
         }
         func.instruction(&Instruction::End);
@@ -291,29 +656,35 @@ impl WasmBackend {
                 self.compile_binary_op(asg, node, func, Instruction::I64RemS)?;
             }
 
-            // === Операции сравнения ===
+            // === Операции сравнения (результат i32 расширяется до i64) ===
             NodeType::Eq => {
                 self.compile_binary_op(asg, node, func, Instruction::I64Eq)?;
+                func.instruction(&Instruction::I64ExtendI32U);
             }
 
             NodeType::Ne => {
                 self.compile_binary_op(asg, node, func, Instruction::I64Ne)?;
+                func.instruction(&Instruction::I64ExtendI32U);
             }
 
             NodeType::Lt => {
                 self.compile_binary_op(asg, node, func, Instruction::I64LtS)?;
+                func.instruction(&Instruction::I64ExtendI32U);
             }
 
             NodeType::Le => {
                 self.compile_binary_op(asg, node, func, Instruction::I64LeS)?;
+                func.instruction(&Instruction::I64ExtendI32U);
             }
 
             NodeType::Gt => {
                 self.compile_binary_op(asg, node, func, Instruction::I64GtS)?;
+                func.instruction(&Instruction::I64ExtendI32U);
             }
 
             NodeType::Ge => {
                 self.compile_binary_op(asg, node, func, Instruction::I64GeS)?;
+                func.instruction(&Instruction::I64ExtendI32U);
             }
 
             // === Логические операции ===
@@ -335,6 +706,7 @@ impl WasmBackend {
                     .ok_or(ASGError::NodeNotFound(operand.target_node_id))?;
                 self.compile_node(asg, operand_node, func)?;
                 func.instruction(&Instruction::I64Eqz); // not = eqz
+                func.instruction(&Instruction::I64ExtendI32U);
             }
 
             // === Neg (унарный минус) ===
@@ -360,6 +732,11 @@ impl WasmBackend {
 
             // === Print ===
             NodeType::Print => {
+                if node.edges.len() > 1 {
+                    return Err(ASGError::CompilationError(
+                        "WASM backend supports print of a single argument".to_string(),
+                    ));
+                }
                 let arg = node.edges.first().ok_or(ASGError::MissingEdge(
                     node.id,
                     EdgeType::ApplicationArgument,
@@ -368,9 +745,13 @@ impl WasmBackend {
                     .find_node(arg.target_node_id)
                     .ok_or(ASGError::NodeNotFound(arg.target_node_id))?;
 
-                self.compile_node(asg, arg_node, func)?;
-                // Вызываем print_int (import index 0)
-                func.instruction(&Instruction::Call(0));
+                if self.target == Target::Wasi {
+                    self.compile_wasi_print(asg, arg_node, func)?;
+                } else {
+                    self.compile_node(asg, arg_node, func)?;
+                    // Вызываем print_int (import index 0)
+                    func.instruction(&Instruction::Call(0));
+                }
                 func.instruction(&Instruction::I64Const(0)); // return unit
             }
 
@@ -400,6 +781,8 @@ impl WasmBackend {
                         let value_node = asg
                             .find_node(value_edge.target_node_id)
                             .ok_or(ASGError::NodeNotFound(value_edge.target_node_id))?;
+                        let kind = self.print_kind(asg, value_node);
+                        self.variable_kinds.insert(name.clone(), kind);
                         self.compile_node(asg, value_node, func)?;
                     } else {
                         func.instruction(&Instruction::I64Const(0));
@@ -606,6 +989,34 @@ impl Default for WasmBackend {
     }
 }
 
+/// Корни графа (узлы, на которые не ссылается ни одно ребро) в порядке `nodes`
+/// и число `let` среди достижимых из них узлов — столько нужно локальных переменных.
+/// Недостижимые узлы не компилируются и локальных не занимают.
+#[cfg(feature = "wasm_backend")]
+fn roots_and_variables(asg: &ASG) -> (Vec<&Node>, u32) {
+    let targets: HashSet<NodeID> = asg
+        .nodes
+        .iter()
+        .flat_map(|n| n.edges.iter().map(|e| e.target_node_id))
+        .collect();
+    let roots: Vec<&Node> = asg
+        .nodes
+        .iter()
+        .filter(|n| !targets.contains(&n.id))
+        .collect();
+
+    let reachable: HashSet<NodeID> = roots
+        .iter()
+        .flat_map(|root| asg.reachable_from(root.id))
+        .collect();
+    let variables = asg
+        .nodes
+        .iter()
+        .filter(|n| n.node_type == NodeType::Variable && reachable.contains(&n.id))
+        .count() as u32;
+    (roots, variables)
+}
+
 // === Заглушка для сборки без wasm_backend ===
 
 #[cfg(not(feature = "wasm_backend"))]
@@ -652,4 +1063,143 @@ mod tests {
         // Check WASM magic number
         assert_eq!(&bytes[0..4], &[0x00, 0x61, 0x73, 0x6D]);
     }
+
+    #[cfg(feature = "wasm_backend")]
+    #[test]
+    fn test_wasm_main_compiles_roots_only() {
+        use crate::compiler::{compile, Target};
+        use wasmi::{Engine, Linker, Module, Store};
+
+        let run_main = |source: &str| {
+            let (asg, _) = crate::parser::parse(source).unwrap();
+            let bytes = compile(&asg, Target::Wasm).unwrap();
+            wasmparser::Validator::new().validate_all(&bytes).unwrap();
+
+            let engine = Engine::default();
+            let module = Module::new(&engine, &bytes[..]).unwrap();
+            let mut store = Store::new(&engine, ());
+            let mut linker = Linker::new(&engine);
+            linker.func_wrap("env", "print_int", |_: i64| {}).unwrap();
+            linker.func_wrap("env", "print_float", |_: f64| {}).unwrap();
+            let instance = linker
+                .instantiate(&mut store, &module)
+                .unwrap()
+                .start(&mut store)
+                .unwrap();
+            instance
+                .get_typed_func::<(), i64>(&store, "main")
+                .unwrap()
+                .call(&mut store, ())
+                .unwrap()
+        };
+
+        // Подвыражения не компилируются повторно как отдельные инструкции main
+        assert_eq!(run_main("(+ 1 2)"), 3);
+        assert_eq!(run_main("(let x 2)\n(let y 4)\n(* (+ x 3) y)"), 20);
+    }
+
+    /// Выполнить `_start` WASI-модуля под wasmi; fd_write собирает записанное в stdout.
+    #[cfg(feature = "wasm_backend")]
+    fn run_wasi(bytes: &[u8]) -> String {
+        use wasmi::{Caller, Engine, Extern, Linker, Module, Store};
+
+        let engine = Engine::default();
+        let module = Module::new(&engine, bytes).unwrap();
+        let mut store = Store::new(&engine, Vec::<u8>::new());
+        let mut linker = Linker::new(&engine);
+        linker
+            .func_wrap(
+                "wasi_snapshot_preview1",
+                "fd_write",
+                |mut caller: Caller<'_, Vec<u8>>,
+                 fd: i32,
+                 iovs: i32,
+                 iovs_len: i32,
+                 nwritten: i32|
+                 -> i32 {
+                    assert_eq!(fd, 1);
+                    let memory = caller
+                        .get_export("memory")
+                        .and_then(Extern::into_memory)
+                        .unwrap();
+                    let data = memory.data(&caller);
+                    let word = |at: usize| {
+                        u32::from_le_bytes(data[at..at + 4].try_into().unwrap()) as usize
+                    };
+                    let mut written = Vec::new();
+                    for i in 0..iovs_len as usize {
+                        let iov = iovs as usize + i * 8;
+                        let (ptr, len) = (word(iov), word(iov + 4));
+                        written.extend_from_slice(&data[ptr..ptr + len]);
+                    }
+                    let count = (written.len() as u32).to_le_bytes();
+                    let at = nwritten as usize;
+                    memory.data_mut(&mut caller)[at..at + 4].copy_from_slice(&count);
+                    caller.data_mut().extend(written);
+                    0
+                },
+            )
+            .unwrap();
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        instance
+            .get_typed_func::<(), ()>(&store, "_start")
+            .unwrap()
+            .call(&mut store, ())
+            .unwrap();
+        String::from_utf8(store.into_data()).unwrap()
+    }
+
+    #[cfg(feature = "wasm_backend")]
+    #[test]
+    fn test_wasi_print_module() {
+        use crate::compiler::{compile, Target};
+        use wasmparser::{Payload, Validator};
+
+        let (asg, _) = crate::parser::parse("(print 42)").unwrap();
+        let bytes = compile(&asg, Target::Wasi).unwrap();
+        Validator::new().validate_all(&bytes).unwrap();
+
+        let mut imports = Vec::new();
+        let mut exports = Vec::new();
+        let mut data = Vec::new();
+        for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+            match payload.unwrap() {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let import = import.unwrap();
+                        imports.push(format!("{}.{}", import.module, import.name));
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        exports.push(export.unwrap().name.to_string());
+                    }
+                }
+                Payload::DataSection(reader) => {
+                    for segment in reader {
+                        data.extend_from_slice(segment.unwrap().data);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(imports, vec!["wasi_snapshot_preview1.fd_write"]);
+        assert_eq!(exports, vec!["_start", "memory"]);
+        assert_eq!(data, b"true\nfalse\n");
+
+        // Модуль действительно печатает: _start под wasmi со своим fd_write
+        assert_eq!(run_wasi(&bytes), "42\n");
+        let (asg, _) = crate::parser::parse("(print -7) (print (< 1 2)) (print \"hi\")").unwrap();
+        let bytes = compile(&asg, Target::Wasi).unwrap();
+        assert_eq!(run_wasi(&bytes), "-7\ntrue\nhi\n");
+
+        // Печать float не поддерживается на WASI
+        let (asg, _) = crate::parser::parse("(print 1.5)").unwrap();
+        assert!(compile(&asg, Target::Wasi).is_err());
+    }
 }